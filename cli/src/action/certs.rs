@@ -16,6 +16,7 @@ use std::env;
 use std::fs::{self, metadata, OpenOptions};
 use std::io;
 use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
 
@@ -31,8 +32,14 @@ use openssl::error::ErrorStack;
 use openssl::hash::MessageDigest;
 use openssl::pkey::{PKey, PKeyRef, Private};
 use openssl::rsa::Rsa;
-use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, KeyUsage};
-use openssl::x509::{X509NameBuilder, X509Ref, X509};
+use openssl::stack::Stack;
+use openssl::x509::extension::{
+    BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName,
+};
+use openssl::x509::{
+    GeneralName, X509NameBuilder, X509NameRef, X509Ref, X509Req, X509ReqBuilder, X509ReqRef, X509,
+};
+use splinter::transport::tls::cert_days_until_expiry;
 
 use crate::error::CliError;
 
@@ -50,6 +57,9 @@ const SERVER_KEY: &str = "server.key";
 const CA_CERT: &str = "generated_ca.pem";
 const CA_KEY: &str = "generated_ca.key";
 
+const DEFAULT_REQUEST_NAME: &str = "node";
+const DEFAULT_CERT_DAYS: u32 = 365;
+
 impl Action for CertGenAction {
     fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
         let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
@@ -203,6 +213,168 @@ impl Action for CertGenAction {
     }
 }
 
+pub struct CertRequestAction;
+
+impl Action for CertRequestAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let common_name = args
+            .value_of("common_name")
+            .unwrap_or("localhost")
+            .to_string();
+        let name = args.value_of("name").unwrap_or(DEFAULT_REQUEST_NAME);
+
+        let cert_dir_string = args
+            .value_of("cert_dir")
+            .map(ToOwned::to_owned)
+            .or_else(|| env::var(CERT_DIR_ENV).ok())
+            .unwrap_or_else(|| DEFAULT_CERT_DIR.to_string());
+
+        let cert_dir = Path::new(&cert_dir_string);
+        if !cert_dir.is_dir() {
+            return Err(CliError::ActionError(format!(
+                "Cert directory does not exist: {}",
+                cert_dir.display()
+            )));
+        }
+
+        let private_cert_path = cert_dir.join("private/");
+        if !private_cert_path.is_dir() {
+            fs::create_dir_all(private_cert_path.clone()).map_err(|err| {
+                CliError::ActionError(format!("Unable to create private directory: {}", err))
+            })?
+        }
+
+        let key_file = format!("{}.key", name);
+        let csr_file = format!("{}.csr", name);
+        let key_path = private_cert_path.join(&key_file);
+        let csr_path = cert_dir.join(&csr_file);
+
+        if !args.is_present("force") {
+            let mut errored = false;
+            if key_path.exists() {
+                error!("Key already exists: {}", absolute_path(&key_path)?);
+                errored = true;
+            }
+
+            if csr_path.exists() {
+                error!(
+                    "Certificate signing request already exists: {}",
+                    absolute_path(&csr_path)?
+                );
+                errored = true;
+            }
+
+            if errored {
+                return Err(CliError::ActionError(
+                    "Refusing to overwrite files, exiting".into(),
+                ));
+            }
+        }
+
+        let (key, request) = make_cert_request(&common_name)?;
+
+        // the key is written first, so a request is never left without its key
+        write_file(
+            private_cert_path.clone(),
+            &key_file,
+            &key.private_key_to_pem_pkcs8()?,
+        )?;
+        info!(
+            "Wrote file: {}/{}",
+            absolute_path(&private_cert_path)?,
+            key_file
+        );
+
+        write_file(cert_dir.to_path_buf(), &csr_file, &request.to_pem()?)?;
+        info!("Wrote file: {}/{}", absolute_path(cert_dir)?, csr_file);
+
+        Ok(())
+    }
+}
+
+pub struct CertSignAction;
+
+impl Action for CertSignAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let csr_path = Path::new(args.value_of("csr").ok_or_else(|| CliError::RequiresArgs)?);
+        let ca_cert_path = Path::new(
+            args.value_of("ca_cert")
+                .ok_or_else(|| CliError::RequiresArgs)?,
+        );
+        let ca_key_path = Path::new(
+            args.value_of("ca_key")
+                .ok_or_else(|| CliError::RequiresArgs)?,
+        );
+
+        let days = match args.value_of("days") {
+            Some(days) => days
+                .parse::<u32>()
+                .map_err(|_| CliError::ActionError(format!("Invalid number of days: {}", days)))?,
+            None => DEFAULT_CERT_DAYS,
+        };
+
+        let cert_path = args
+            .value_of("output")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| csr_path.with_extension("crt"));
+
+        if cert_path.exists() && !args.is_present("force") {
+            return Err(CliError::ActionError(format!(
+                "Certificate already exists, refusing to overwrite: {}",
+                absolute_path(&cert_path)?
+            )));
+        }
+
+        let request = X509Req::from_pem(&fs::read(csr_path)?)?;
+        let ca_cert = get_ca_cert(ca_cert_path)?;
+        let ca_key = PKey::private_key_from_pem(&fs::read(ca_key_path)?)?;
+
+        let cert = sign_cert_request(&request, &ca_cert, &ca_key, days)?;
+
+        let (dir, file_name) = split_file_path(&cert_path)?;
+        write_file(dir, &file_name, &cert.to_pem()?)?;
+        info!("Wrote file: {}", absolute_path(&cert_path)?);
+
+        Ok(())
+    }
+}
+
+pub struct CertShowAction;
+
+impl Action for CertShowAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let cert_path = Path::new(
+            args.value_of("cert")
+                .ok_or_else(|| CliError::RequiresArgs)?,
+        );
+        let cert = X509::from_pem(&fs::read(cert_path)?)?;
+
+        let serial_number = cert.serial_number().to_bn()?.to_hex_str()?;
+
+        println!("Certificate: {}", absolute_path(cert_path)?);
+        println!("    Subject: {}", format_name(cert.subject_name()));
+        println!("    Issuer: {}", format_name(cert.issuer_name()));
+        println!("    Serial number: {}", serial_number);
+        println!("    Not before: {}", cert.not_before());
+        println!("    Not after: {}", cert.not_after());
+
+        let days = cert_days_until_expiry(&cert)?;
+        if days < 0 {
+            println!("    Expired {} days ago", -days);
+        } else {
+            println!("    Expires in {} days", days);
+        }
+
+        Ok(())
+    }
+}
+
 // if skip, check each pair of certificate/key to see if it exists. If not generate the
 // the missing files. If only one of the two files exists, this is an error.
 fn handle_skip(
@@ -576,6 +748,169 @@ fn make_ca_signed_cert(
     Ok((privkey, cert))
 }
 
+// Make a private key and a certificate signing request for it, to be signed by a CA
+fn make_cert_request(common_name: &str) -> Result<(PKey<Private>, X509Req), CliError> {
+    // generate private key
+    let rsa = Rsa::generate(2048)?;
+    let privkey = PKey::from_rsa(rsa)?;
+
+    // build x509_name
+    let mut x509_name = X509NameBuilder::new()?;
+    x509_name.append_entry_by_text("CN", &common_name)?;
+    let x509_name = x509_name.build();
+
+    let mut req_builder = X509ReqBuilder::new()?;
+    req_builder.set_version(0)?;
+    req_builder.set_subject_name(&x509_name)?;
+    req_builder.set_pubkey(&privkey)?;
+    req_builder.sign(&privkey, MessageDigest::sha256())?;
+
+    Ok((privkey, req_builder.build()))
+}
+
+// Make a certificate from a certificate signing request, signed by the given CA cert and private
+// key. Like the generated certs, the cert could act like both server or client
+fn sign_cert_request(
+    request: &X509ReqRef,
+    ca_cert: &X509Ref,
+    ca_privkey: &PKeyRef<Private>,
+    days: u32,
+) -> Result<X509, CliError> {
+    let pubkey = request.public_key()?;
+    if !request.verify(&pubkey)? {
+        return Err(CliError::ActionError(
+            "Certificate signing request has an invalid signature".into(),
+        ));
+    }
+    if !ca_cert.public_key()?.public_eq(ca_privkey) {
+        return Err(CliError::ActionError(
+            "CA private key does not match the CA certificate".into(),
+        ));
+    }
+
+    // build x509 cert
+    let mut cert_builder = X509::builder()?;
+    cert_builder.set_version(2)?;
+    let serial_number = {
+        let mut serial = BigNum::new()?;
+        serial.rand(159, MsbOption::MAYBE_ZERO, false)?;
+        serial.to_asn1_integer()?
+    };
+    cert_builder.set_serial_number(&serial_number)?;
+    cert_builder.set_subject_name(request.subject_name())?;
+    cert_builder.set_issuer_name(ca_cert.subject_name())?;
+    cert_builder.set_pubkey(&pubkey)?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    cert_builder.set_not_before(&not_before)?;
+    let not_after = Asn1Time::days_from_now(days)?;
+    cert_builder.set_not_after(&not_after)?;
+
+    // the cert may not be used to sign other certs
+    cert_builder.append_extension(BasicConstraints::new().critical().build()?)?;
+
+    // allow keys to be used for both server and client authorization
+    cert_builder.append_extension(
+        ExtendedKeyUsage::new()
+            .server_auth()
+            .client_auth()
+            .build()?,
+    )?;
+
+    // keep the host names and addresses the cert was requested for
+    if let Some(names) = requested_subject_alt_names(request)? {
+        let mut subject_alt_names = SubjectAlternativeName::new();
+        for name in names.iter() {
+            if let Some(dns) = name.dnsname() {
+                subject_alt_names.dns(dns);
+            } else if let Some(ip) = name.ipaddress() {
+                subject_alt_names.ip(&format_ip_address(ip)?);
+            } else if let Some(email) = name.email() {
+                subject_alt_names.email(email);
+            } else if let Some(uri) = name.uri() {
+                subject_alt_names.uri(uri);
+            } else {
+                return Err(CliError::ActionError(
+                    "Certificate signing request has an unsupported subject alternative name"
+                        .into(),
+                ));
+            }
+        }
+        let extension =
+            subject_alt_names.build(&cert_builder.x509v3_context(Some(ca_cert), None))?;
+        cert_builder.append_extension(extension)?;
+    }
+
+    // sign the cert by the ca
+    cert_builder.sign(&ca_privkey, MessageDigest::sha256())?;
+    Ok(cert_builder.build())
+}
+
+// Subject alternative names requested by a certificate signing request, if any. The extensions of
+// a request can only be inspected once they are added to a certificate, so they are added to a
+// scratch certificate that is not signed.
+fn requested_subject_alt_names(
+    request: &X509ReqRef,
+) -> Result<Option<Stack<GeneralName>>, CliError> {
+    // a request without any extensions has no extension request attribute, which fails to read
+    // without an error being reported; any reported error means the attribute is invalid
+    let mut extensions = match request.extensions() {
+        Ok(extensions) => extensions,
+        Err(err) if err.errors().is_empty() => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut scratch_builder = X509::builder()?;
+    while let Some(extension) = extensions.pop() {
+        scratch_builder.append_extension(extension)?;
+    }
+    Ok(scratch_builder.build().subject_alt_names())
+}
+
+// format an IP address subject alternative name, which holds the address's octets
+fn format_ip_address(octets: &[u8]) -> Result<String, CliError> {
+    match *octets {
+        [a, b, c, d] => Ok(Ipv4Addr::new(a, b, c, d).to_string()),
+        _ if octets.len() == 16 => {
+            let mut ipv6_octets = [0; 16];
+            ipv6_octets.copy_from_slice(octets);
+            Ok(Ipv6Addr::from(ipv6_octets).to_string())
+        }
+        _ => Err(CliError::ActionError(
+            "Certificate signing request has an invalid IP address".into(),
+        )),
+    }
+}
+
+// format the entries of a name as comma-separated key=value pairs, such as "CN=localhost"
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// split a file path into its directory and file name, as expected by write_file
+fn split_file_path(path: &Path) -> Result<(PathBuf, String), CliError> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| CliError::ActionError(format!("Invalid file path: {}", path.display())))?
+        .to_string();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Ok((dir, file_name))
+}
+
 /// write the a file to a temp file name and then rename to final filename
 /// this will guarantee that the final file will ony ever contain valid data
 fn write_file(path_buf: PathBuf, file_name: &str, bytes: &[u8]) -> Result<(), CliError> {
@@ -732,3 +1067,94 @@ impl From<ErrorStack> for CliError {
         CliError::ActionError(error_stack.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a certificate signing request with the given subject alternative names.
+    fn make_cert_request_with_names(dns: &str, ip: &str) -> X509Req {
+        let rsa = Rsa::generate(2048).unwrap();
+        let privkey = PKey::from_rsa(rsa).unwrap();
+
+        let mut x509_name = X509NameBuilder::new().unwrap();
+        x509_name.append_entry_by_text("CN", "node").unwrap();
+        let x509_name = x509_name.build();
+
+        let mut req_builder = X509ReqBuilder::new().unwrap();
+        req_builder.set_version(0).unwrap();
+        req_builder.set_subject_name(&x509_name).unwrap();
+        req_builder.set_pubkey(&privkey).unwrap();
+
+        let subject_alt_names = SubjectAlternativeName::new()
+            .dns(dns)
+            .ip(ip)
+            .build(&req_builder.x509v3_context(None))
+            .unwrap();
+        let mut extensions = Stack::new().unwrap();
+        extensions.push(subject_alt_names).unwrap();
+        req_builder.add_extensions(&extensions).unwrap();
+
+        req_builder.sign(&privkey, MessageDigest::sha256()).unwrap();
+        req_builder.build()
+    }
+
+    /// Test that a signed certificate keeps the subject alternative names of its request, is
+    /// issued by the CA, and may not act as a CA itself.
+    #[test]
+    fn test_sign_cert_request() {
+        let (ca_key, ca_cert) = make_ca_cert().unwrap();
+        let request = make_cert_request_with_names("node.example.com", "10.0.0.1");
+
+        let cert = sign_cert_request(&request, &ca_cert, &ca_key, 30).unwrap();
+
+        assert!(cert.verify(&ca_key).unwrap());
+        assert_eq!("CN=node", format_name(cert.subject_name()));
+
+        let names = cert
+            .subject_alt_names()
+            .expect("Certificate has no subject alternative names");
+        assert_eq!(
+            Some("node.example.com"),
+            names.iter().find_map(|name| name.dnsname())
+        );
+        assert_eq!(
+            Some(&[10, 0, 0, 1][..]),
+            names.iter().find_map(|name| name.ipaddress())
+        );
+
+        let text = String::from_utf8(cert.to_text().unwrap()).unwrap();
+        assert!(
+            text.contains("CA:FALSE"),
+            "unexpected certificate: {}",
+            text
+        );
+    }
+
+    /// Test that a request without subject alternative names is signed without any.
+    #[test]
+    fn test_sign_cert_request_without_names() {
+        let (ca_key, ca_cert) = make_ca_cert().unwrap();
+        let (_, request) = make_cert_request("node").unwrap();
+
+        let cert = sign_cert_request(&request, &ca_cert, &ca_key, 30).unwrap();
+
+        assert!(cert.subject_alt_names().is_none());
+    }
+
+    /// Test that a request is not signed with a CA key that does not match the CA certificate.
+    #[test]
+    fn test_sign_cert_request_mismatched_ca_key() {
+        let (_, ca_cert) = make_ca_cert().unwrap();
+        let (other_ca_key, _) = make_ca_cert().unwrap();
+        let (_, request) = make_cert_request("node").unwrap();
+
+        match sign_cert_request(&request, &ca_cert, &other_ca_key, 30) {
+            Err(CliError::ActionError(msg)) => {
+                assert_eq!("CA private key does not match the CA certificate", msg)
+            }
+            Err(err) => panic!("Unexpected error: {}", err),
+            Ok(_) => panic!("Unexpected successful result"),
+        }
+    }
+}
@@ -145,6 +145,39 @@ impl<'a> SplinterRestClient<'a> {
             })
     }
 
+    /// Fetches the node with the given identity from the node registry of this client's Splinter
+    /// node, or `None` if the node is not registered.
+    pub fn fetch_registered_node(
+        &self,
+        identity: &str,
+    ) -> Result<Option<RegisteredNode>, CliError> {
+        Client::new()
+            .get(&format!("{}/nodes/{}", self.url, identity))
+            .send()
+            .map_err(|err| CliError::ActionError(err.to_string()))
+            .and_then(|res| match res.status() {
+                StatusCode::OK => Ok(Some(
+                    res.json::<RegisteredNode>()
+                        .map_err(|err| CliError::ActionError(err.to_string()))?,
+                )),
+                StatusCode::NOT_FOUND => Ok(None),
+                StatusCode::INTERNAL_SERVER_ERROR => {
+                    let message = res.json::<String>().map_err(|err| {
+                        CliError::ActionError(format!("Unable to parse error response: {}", err))
+                    })?;
+
+                    Err(CliError::ActionError(format!(
+                        "Unable to fetch registered node: {}",
+                        message
+                    )))
+                }
+                _ => Err(CliError::ActionError(format!(
+                    "Received unknown response status: {}",
+                    res.status()
+                ))),
+            })
+    }
+
     pub fn list_proposals(&self, filter: Option<&str>) -> Result<ProposalListSlice, CliError> {
         let mut request = format!("{}/admin/proposals", self.url);
         if let Some(filter) = filter {
@@ -268,6 +301,12 @@ pub struct ProposalCircuitSlice {
     pub roster: Vec<CircuitService>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RegisteredNode {
+    pub identity: String,
+    pub endpoint: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CircuitMembers {
     pub node_id: String,
@@ -16,14 +16,13 @@ mod api;
 mod payload;
 
 use std::fs::File;
-use std::io::Read;
 
 use clap::ArgMatches;
 use splinter::admin::messages::CreateCircuit;
 
 use crate::error::CliError;
 
-use super::Action;
+use super::{read_private_key, Action};
 
 pub struct CircuitCreateAction;
 
@@ -56,9 +55,13 @@ fn create_circuit_proposal(
         CliError::EnvironmentError(format!("Unable to open {}: {}", proposal_path, err))
     })?;
 
-    let create_request: CreateCircuit = serde_yaml::from_reader(proposal_file).map_err(|err| {
-        CliError::EnvironmentError(format!("Unable to parse {}: {}", proposal_path, err))
-    })?;
+    let mut create_request: CreateCircuit =
+        serde_yaml::from_reader(proposal_file).map_err(|err| {
+            CliError::EnvironmentError(format!("Unable to parse {}: {}", proposal_path, err))
+        })?;
+
+    // The endpoints are part of the signed proposal, so they are filled in before signing
+    fill_member_endpoints(&client, &mut create_request)?;
 
     let signed_payload =
         payload::make_signed_payload(&requester_node, &private_key_hex, create_request)?;
@@ -66,19 +69,35 @@ fn create_circuit_proposal(
     client.submit_admin_payload(signed_payload)
 }
 
-/// Reads a private key from the given file name.
-pub fn read_private_key(file_name: &str) -> Result<String, CliError> {
-    let mut file = File::open(file_name).map_err(|err| {
-        CliError::EnvironmentError(format!("Unable to open {}: {}", file_name, err))
-    })?;
+/// Sets the empty endpoints of the circuit's members to the endpoints they are registered with
+/// in the node registry of the requester's node.
+fn fill_member_endpoints(
+    client: &api::SplinterRestClient,
+    create_request: &mut CreateCircuit,
+) -> Result<(), CliError> {
+    for member in create_request.members.iter_mut() {
+        if !member.endpoint.is_empty() {
+            continue;
+        }
 
-    let mut buf = String::new();
-    file.read_to_string(&mut buf).map_err(|err| {
-        CliError::EnvironmentError(format!("Unable to read {}: {}", file_name, err))
-    })?;
-    let key = buf.trim().to_string();
+        match client.fetch_registered_node(&member.node_id)? {
+            Some(node) => {
+                debug!(
+                    "Using registered endpoint {} for member {}",
+                    node.endpoint, node.identity
+                );
+                member.endpoint = node.endpoint;
+            }
+            None => {
+                return Err(CliError::ActionError(format!(
+                    "Member {} has no endpoint and is not registered",
+                    member.node_id
+                )))
+            }
+        }
+    }
 
-    Ok(key)
+    Ok(())
 }
 
 pub(self) enum Vote {
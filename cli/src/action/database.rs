@@ -24,6 +24,8 @@ use splinter::biome::notifications::database::run_migrations as run_biome_notifi
 #[cfg(feature = "database-migrate-biome-user")]
 use splinter::biome::user::store::run_postgres_migrations as run_biome_user_migrations;
 use splinter::database::run_migrations as run_setup_migrations;
+use splinter::keys::database::postgres::run_migrations as run_key_registry_migrations;
+use splinter::node_registry::database::postgres::run_migrations as run_node_registry_migrations;
 
 pub struct MigrateAction;
 
@@ -43,6 +45,14 @@ impl Action for MigrateAction {
             CliError::DatabaseError(format!("Unable to run Biome setup migrations: {}", err))
         })?;
 
+        run_node_registry_migrations(&connection).map_err(|err| {
+            CliError::DatabaseError(format!("Unable to run node registry migrations: {}", err))
+        })?;
+
+        run_key_registry_migrations(&connection).map_err(|err| {
+            CliError::DatabaseError(format!("Unable to run key registry migrations: {}", err))
+        })?;
+
         #[cfg(feature = "database-migrate-biome-user")]
         run_biome_user_migrations(&connection).map_err(|err| {
             CliError::DatabaseError(format!("Unable to run Biome users migrations: {}", err))
@@ -0,0 +1,302 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ArgMatches;
+use reqwest::{blocking::Client, Method, StatusCode};
+use sawtooth_sdk::signing::secp256k1;
+use serde::{Deserialize, Serialize};
+use splinter::keys::{signed_request_message, SIGNATURE_AUTHORIZATION_SCHEME};
+use splinter::signing::{sawtooth, Signer};
+
+use super::{read_private_key, Action};
+use crate::error::CliError;
+
+pub struct KeyListAction;
+
+impl Action for KeyListAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+
+        let keys = list_keys(url)?;
+        println!(
+            "{0: <66} | {1: <20} | {2: <30}",
+            "PUBLIC KEY", "NODE ID", "METADATA",
+        );
+        println!("{}", "-".repeat(122));
+        keys.iter().for_each(|key| {
+            println!(
+                "{0: <66} | {1: <20} | {2: <30}",
+                key.public_key,
+                key.node_id,
+                key.metadata
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+        });
+        Ok(())
+    }
+}
+
+pub struct KeyAddAction;
+
+impl Action for KeyAddAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+        let private_key_file = args
+            .value_of("private_key_file")
+            .unwrap_or("./splinter.priv");
+        let public_key = args
+            .value_of("public_key")
+            .ok_or_else(|| CliError::ActionError("Public key must be provided".to_string()))?;
+        let node_id = args
+            .value_of("node_id")
+            .ok_or_else(|| CliError::ActionError("Node ID must be provided".to_string()))?;
+        let metadata = args
+            .values_of("metadata")
+            .map(|values| values.map(parse_metadata).collect::<Result<_, _>>())
+            .unwrap_or_else(|| Ok(BTreeMap::new()))?;
+
+        let body = serde_json::to_vec(&KeyInfo {
+            public_key: public_key.into(),
+            node_id: node_id.into(),
+            metadata,
+        })
+        .map_err(|err| CliError::ActionError(format!("Unable to serialize key: {}", err)))?;
+
+        send_signed_request(
+            url,
+            Method::POST,
+            "/keys",
+            body,
+            &read_private_key(private_key_file)?,
+        )?;
+
+        info!("Added key {}", public_key);
+        Ok(())
+    }
+}
+
+pub struct KeyRemoveAction;
+
+impl Action for KeyRemoveAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+        let private_key_file = args
+            .value_of("private_key_file")
+            .unwrap_or("./splinter.priv");
+        let public_key = args
+            .value_of("public_key")
+            .ok_or_else(|| CliError::ActionError("Public key must be provided".to_string()))?;
+
+        send_signed_request(
+            url,
+            Method::DELETE,
+            &format!("/keys/{}", public_key),
+            vec![],
+            &read_private_key(private_key_file)?,
+        )?;
+
+        info!("Removed key {}", public_key);
+        Ok(())
+    }
+}
+
+pub struct KeyRotateAction;
+
+impl Action for KeyRotateAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+        let private_key_file = args
+            .value_of("private_key_file")
+            .unwrap_or("./splinter.priv");
+        let public_key = args
+            .value_of("public_key")
+            .ok_or_else(|| CliError::ActionError("Public key must be provided".to_string()))?;
+        let new_public_key = args
+            .value_of("new_public_key")
+            .ok_or_else(|| CliError::ActionError("New public key must be provided".to_string()))?;
+        let overlap_secs = args
+            .value_of("overlap")
+            .map(|overlap| {
+                overlap.parse::<u64>().map_err(|_| {
+                    CliError::ActionError(format!(
+                        "Overlap must be a number of seconds: {}",
+                        overlap
+                    ))
+                })
+            })
+            .transpose()?;
+        let metadata = args
+            .values_of("metadata")
+            .map(|values| values.map(parse_metadata).collect::<Result<_, _>>())
+            .unwrap_or_else(|| Ok(BTreeMap::new()))?;
+
+        let body = serde_json::to_vec(&KeyRotation {
+            public_key: new_public_key.into(),
+            metadata,
+            overlap_secs,
+        })
+        .map_err(|err| CliError::ActionError(format!("Unable to serialize key: {}", err)))?;
+
+        send_signed_request(
+            url,
+            Method::POST,
+            &format!("/keys/{}/rotate", public_key),
+            body,
+            &read_private_key(private_key_file)?,
+        )?;
+
+        info!("Replaced key {} with {}", public_key, new_public_key);
+        Ok(())
+    }
+}
+
+fn list_keys(url: &str) -> Result<Vec<KeyInfo>, CliError> {
+    Client::new()
+        .get(&format!("{}/keys", url))
+        .send()
+        .map_err(|err| CliError::ActionError(err.to_string()))
+        .and_then(|res| match res.status() {
+            StatusCode::OK => Ok(res
+                .json::<KeyListResponse>()
+                .map_err(|err| CliError::ActionError(err.to_string()))?
+                .data),
+            _ => Err(CliError::ActionError(format!(
+                "Unable to fetch keys: {}",
+                error_message(res)
+            ))),
+        })
+}
+
+/// Sends a request that changes the key registry, signed with the given private key as the
+/// `Authorization` header.
+fn send_signed_request(
+    url: &str,
+    method: Method,
+    path: &str,
+    body: Vec<u8>,
+    private_key: &str,
+) -> Result<(), CliError> {
+    let signing_context = secp256k1::Secp256k1Context::new();
+    let private_key = secp256k1::Secp256k1PrivateKey::from_hex(private_key)
+        .map_err(|_| CliError::ActionError("Invalid private key provided".into()))?;
+    let signer = sawtooth::SawtoothSecp256k1RefSigner::new(&signing_context, private_key).map_err(
+        |err| CliError::ActionError(format!("Unable to create signer from private key: {}", err)),
+    )?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| CliError::EnvironmentError(format!("Invalid system time: {}", err)))?
+        .as_secs();
+    let signature = signer
+        .sign(&signed_request_message(
+            method.as_str(),
+            path,
+            timestamp,
+            &body,
+        ))
+        .map_err(|err| CliError::ActionError(format!("Unable to sign request: {}", err)))?;
+
+    Client::new()
+        .request(method, &format!("{}{}", url, path))
+        .header("Content-Type", "application/json")
+        .header(
+            "Authorization",
+            format!(
+                "{} {}:{}:{}",
+                SIGNATURE_AUTHORIZATION_SCHEME,
+                to_hex(signer.public_key()),
+                timestamp,
+                to_hex(&signature)
+            ),
+        )
+        .body(body)
+        .send()
+        .map_err(|err| CliError::ActionError(err.to_string()))
+        .and_then(|res| match res.status() {
+            StatusCode::OK => Ok(()),
+            _ => Err(CliError::ActionError(format!(
+                "Unable to change key registry: {}",
+                error_message(res)
+            ))),
+        })
+}
+
+/// Parses a `key=value` metadata argument.
+fn parse_metadata(arg: &str) -> Result<(String, String), CliError> {
+    let mut parts = arg.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(CliError::ActionError(format!(
+            "Metadata must be of the form key=value: {}",
+            arg
+        ))),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut buf = String::new();
+    for b in bytes {
+        write!(&mut buf, "{:02x}", b).expect("Unable to write to string");
+    }
+    buf
+}
+
+/// Extracts the message from an error response, falling back to the response's status.
+fn error_message(res: reqwest::blocking::Response) -> String {
+    let status = res.status();
+    res.json::<ServerError>()
+        .map(|err| err.message)
+        .unwrap_or_else(|_| format!("Received unknown response status: {}", status))
+}
+
+#[derive(Deserialize)]
+struct ServerError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct KeyListResponse {
+    data: Vec<KeyInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyInfo {
+    public_key: String,
+    node_id: String,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct KeyRotation {
+    public_key: String,
+    metadata: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overlap_secs: Option<u64>,
+}
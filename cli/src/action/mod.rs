@@ -20,9 +20,17 @@ pub mod circuit;
 pub mod database;
 #[cfg(feature = "health")]
 pub mod health;
+#[cfg(feature = "keys")]
+pub mod keys;
+#[cfg(feature = "peer")]
+pub mod peer;
 
 use std::collections::HashMap;
 use std::ffi::CString;
+#[cfg(any(feature = "circuit", feature = "keys"))]
+use std::fs::File;
+#[cfg(any(feature = "circuit", feature = "keys"))]
+use std::io::Read;
 use std::path::Path;
 
 use clap::ArgMatches;
@@ -74,6 +82,22 @@ impl<'s> Action for SubcommandActions<'s> {
     }
 }
 
+/// Reads a private key from the given file name.
+#[cfg(any(feature = "circuit", feature = "keys"))]
+pub fn read_private_key(file_name: &str) -> Result<String, CliError> {
+    let mut file = File::open(file_name).map_err(|err| {
+        CliError::EnvironmentError(format!("Unable to open {}: {}", file_name, err))
+    })?;
+
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).map_err(|err| {
+        CliError::EnvironmentError(format!("Unable to read {}: {}", file_name, err))
+    })?;
+    let key = buf.trim().to_string();
+
+    Ok(key)
+}
+
 fn chown(path: &Path, uid: u32, gid: u32) -> Result<(), CliError> {
     let pathstr = path
         .to_str()
@@ -0,0 +1,159 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::ArgMatches;
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use super::Action;
+use crate::error::CliError;
+
+pub struct PeerListAction;
+
+impl Action for PeerListAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+
+        let peers = list_peers(url)?;
+        println!(
+            "{0: <40} | {1: <40} | {2: <12} | {3: <10}",
+            "PEER ID", "ENDPOINT", "STATE", "HEARTBEAT",
+        );
+        println!("{}", "-".repeat(110));
+        peers.iter().for_each(|peer| {
+            println!(
+                "{0: <40} | {1: <40} | {2: <12} | {3: <10}",
+                peer.peer_id,
+                peer.endpoint.as_deref().unwrap_or("-"),
+                peer.authorization_state,
+                peer.last_heartbeat
+                    .map(|time| time.to_string())
+                    .unwrap_or_else(|| "-".into()),
+            );
+        });
+        Ok(())
+    }
+}
+
+pub struct PeerShowAction;
+
+impl Action for PeerShowAction {
+    fn run<'a>(&mut self, arg_matches: Option<&ArgMatches<'a>>) -> Result<(), CliError> {
+        let args = arg_matches.ok_or_else(|| CliError::RequiresArgs)?;
+
+        let url = args.value_of("url").unwrap_or("http://127.0.0.1:8080");
+        let peer_id = args
+            .value_of("peer_id")
+            .ok_or_else(|| CliError::ActionError("Peer ID must be provided".to_string()))?;
+
+        // A value should always be passed because a default is defined
+        let format = args.value_of("format").expect("format was not provided");
+
+        let peer = fetch_peer(url, peer_id)?
+            .ok_or_else(|| CliError::ActionError(format!("Peer {} does not exist", peer_id)))?;
+
+        match format {
+            "json" => println!(
+                "\n {}",
+                serde_json::to_string(&peer).map_err(|err| CliError::ActionError(format!(
+                    "Cannot format peer into json: {}",
+                    err
+                )))?
+            ),
+            // default is yaml
+            _ => println!(
+                "{}",
+                serde_yaml::to_string(&peer).map_err(|err| CliError::ActionError(format!(
+                    "Cannot format peer into yaml: {}",
+                    err
+                )))?
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+fn list_peers(url: &str) -> Result<Vec<PeerStatus>, CliError> {
+    Client::new()
+        .get(&format!("{}/peers", url))
+        .send()
+        .map_err(|err| CliError::ActionError(err.to_string()))
+        .and_then(|res| match res.status() {
+            StatusCode::OK => Ok(res
+                .json::<PeerListResponse>()
+                .map_err(|err| CliError::ActionError(err.to_string()))?
+                .data),
+            _ => Err(CliError::ActionError(format!(
+                "Unable to fetch peers: {}",
+                error_message(res)
+            ))),
+        })
+}
+
+fn fetch_peer(url: &str, peer_id: &str) -> Result<Option<PeerStatus>, CliError> {
+    Client::new()
+        .get(&format!("{}/peers/{}", url, peer_id))
+        .send()
+        .map_err(|err| CliError::ActionError(err.to_string()))
+        .and_then(|res| match res.status() {
+            StatusCode::OK => Ok(Some(
+                res.json::<PeerResponse>()
+                    .map_err(|err| CliError::ActionError(err.to_string()))?
+                    .data,
+            )),
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Err(CliError::ActionError(format!(
+                "Unable to fetch peer: {}",
+                error_message(res)
+            ))),
+        })
+}
+
+/// Extracts the message from an error response, falling back to the response's status.
+fn error_message(res: reqwest::blocking::Response) -> String {
+    let status = res.status();
+    res.json::<ServerError>()
+        .map(|err| err.message)
+        .unwrap_or_else(|_| format!("Received unknown response status: {}", status))
+}
+
+#[derive(Deserialize)]
+struct ServerError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct PeerListResponse {
+    data: Vec<PeerStatus>,
+}
+
+#[derive(Deserialize)]
+struct PeerResponse {
+    data: PeerStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PeerStatus {
+    peer_id: String,
+    endpoint: Option<String>,
+    authorization_state: String,
+    last_heartbeat: Option<u64>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    messages_sent: u64,
+    messages_received: u64,
+}
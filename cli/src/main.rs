@@ -76,7 +76,7 @@ fn run() -> Result<(), CliError> {
             )
         )
         (@subcommand cert =>
-            (about: "Generate certificates that can be used for development")
+            (about: "Generate, sign and inspect certificates")
             (@subcommand generate =>
                 (about: "Generate certificates and keys for the ca, server and client")
                 (@arg common_name: --("common-name") +takes_value
@@ -86,6 +86,34 @@ fn run() -> Result<(), CliError> {
                 (@arg force: --force  conflicts_with[skip] "Overwrite files if they exist")
                 (@arg skip: --skip conflicts_with[force] "Check if files exists, generate if missing")
             )
+            (@subcommand request =>
+                (about: "Generate a private key and a certificate signing request, to be signed \
+                  by a certificate authority")
+                (@arg common_name: --("common-name") +takes_value
+                  "The common name that should be used in the request, default localhost")
+                (@arg cert_dir: -d --("cert-dir") +takes_value
+                  "Name of the directory in which to create the key and request")
+                (@arg name: --name +takes_value
+                  "Name of the key and request files, without extensions, default node")
+                (@arg force: --force "Overwrite files if they exist")
+            )
+            (@subcommand sign =>
+                (about: "Sign a certificate signing request with a certificate authority")
+                (@arg csr: +required +takes_value "Path of the certificate signing request")
+                (@arg ca_cert: --("ca-cert") +required +takes_value
+                  "Path of the certificate authority's certificate")
+                (@arg ca_key: --("ca-key") +required +takes_value
+                  "Path of the certificate authority's private key")
+                (@arg days: --days +takes_value
+                  "Number of days the certificate is valid for, default 365")
+                (@arg output: -o --output +takes_value
+                  "Path of the signed certificate, default the request path with a .crt extension")
+                (@arg force: --force "Overwrite the certificate if it exists")
+            )
+            (@subcommand show =>
+                (about: "Display the details and expiry of a certificate")
+                (@arg cert: +required +takes_value "Path of the certificate")
+            )
         )
     );
 
@@ -112,6 +140,192 @@ fn run() -> Result<(), CliError> {
         );
     }
 
+    #[cfg(feature = "peer")]
+    {
+        use clap::{AppSettings, Arg, SubCommand};
+
+        app = app.subcommand(
+            SubCommand::with_name("peer")
+                .about("Provides commands to inspect a node's peer connections")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List the node's peers and the state of their connections")
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Show the connection status of a specific peer")
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("peer_id")
+                                .help("The node ID of the peer to be shown")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .short("f")
+                                .long("format")
+                                .help("Format of the peer")
+                                .possible_values(&["yaml", "json"])
+                                .default_value("yaml")
+                                .takes_value(true),
+                        ),
+                ),
+        );
+    }
+
+    #[cfg(feature = "keys")]
+    {
+        use clap::{AppSettings, Arg, SubCommand};
+
+        app = app.subcommand(
+            SubCommand::with_name("keys")
+                .about("Provides commands to manage a node's key registry")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List the keys in the node's key registry")
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Add a key to the node's key registry")
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("private_key_file")
+                                .value_name("private-key-file")
+                                .short("k")
+                                .long("key")
+                                .takes_value(true)
+                                .help("Path to the private key file to sign the request with"),
+                        )
+                        .arg(
+                            Arg::with_name("public_key")
+                                .help("The hex-encoded public key to add")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("node_id")
+                                .long("node-id")
+                                .help("The ID of the node the key belongs to")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("metadata")
+                                .long("metadata")
+                                .help("Metadata of the key, in the form key=value")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("Remove a key from the node's key registry")
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("private_key_file")
+                                .value_name("private-key-file")
+                                .short("k")
+                                .long("key")
+                                .takes_value(true)
+                                .help("Path to the private key file to sign the request with"),
+                        )
+                        .arg(
+                            Arg::with_name("public_key")
+                                .help("The hex-encoded public key to remove")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rotate")
+                        .about(
+                            "Replace a key in the node's key registry with a new key; the old key \
+                             remains accepted until the end of the overlap window",
+                        )
+                        .arg(
+                            Arg::with_name("url")
+                                .short("U")
+                                .long("url")
+                                .help("The URL of the Splinter daemon REST API")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("private_key_file")
+                                .value_name("private-key-file")
+                                .short("k")
+                                .long("key")
+                                .takes_value(true)
+                                .help("Path to the private key file to sign the request with"),
+                        )
+                        .arg(
+                            Arg::with_name("public_key")
+                                .help("The hex-encoded public key to replace")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("new_public_key")
+                                .help("The hex-encoded public key that replaces it")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("overlap")
+                                .long("overlap")
+                                .help(
+                                    "How long the old key remains accepted, in seconds; \
+                                     defaults to one day",
+                                )
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("metadata")
+                                .long("metadata")
+                                .help("Metadata of the new key, in the form key=value")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                ),
+        );
+    }
+
     #[cfg(feature = "database")]
     {
         use clap::{Arg, SubCommand};
@@ -302,7 +516,11 @@ fn run() -> Result<(), CliError> {
         )
         .with_command(
             "cert",
-            SubcommandActions::new().with_command("generate", certs::CertGenAction),
+            SubcommandActions::new()
+                .with_command("generate", certs::CertGenAction)
+                .with_command("request", certs::CertRequestAction)
+                .with_command("sign", certs::CertSignAction)
+                .with_command("show", certs::CertShowAction),
         );
 
     #[cfg(feature = "health")]
@@ -314,6 +532,30 @@ fn run() -> Result<(), CliError> {
         );
     }
 
+    #[cfg(feature = "peer")]
+    {
+        use action::peer;
+        subcommands = subcommands.with_command(
+            "peer",
+            SubcommandActions::new()
+                .with_command("list", peer::PeerListAction)
+                .with_command("show", peer::PeerShowAction),
+        );
+    }
+
+    #[cfg(feature = "keys")]
+    {
+        use action::keys;
+        subcommands = subcommands.with_command(
+            "keys",
+            SubcommandActions::new()
+                .with_command("list", keys::KeyListAction)
+                .with_command("add", keys::KeyAddAction)
+                .with_command("remove", keys::KeyRemoveAction)
+                .with_command("rotate", keys::KeyRotateAction),
+        );
+    }
+
     #[cfg(feature = "database")]
     {
         use action::database;
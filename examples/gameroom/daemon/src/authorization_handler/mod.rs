@@ -1181,6 +1181,7 @@ mod test {
             members: vec![SplinterNode {
                 node_id: "Node-123".to_string(),
                 endpoint: "127.0.0.1:8282".to_string(),
+                display_name: None,
             }],
             authorization_type: AuthorizationType::Trust,
             persistence: PersistenceType::Any,
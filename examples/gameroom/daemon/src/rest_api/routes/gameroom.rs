@@ -123,12 +123,14 @@ pub fn propose_gameroom(
             .map(|node| SplinterNode {
                 node_id: node.identity.to_string(),
                 endpoint: node.endpoint.to_string(),
+                display_name: None,
             })
             .collect::<Vec<SplinterNode>>();
 
         members.push(SplinterNode {
             node_id: node_info.identity.to_string(),
             endpoint: node_info.endpoint.to_string(),
+            display_name: None,
         });
         let partial_circuit_id = members.iter().fold(String::new(), |mut acc, member| {
             acc.push_str(&format!("::{}", member.node_id));
@@ -245,6 +245,7 @@ mod test {
             endpoint: "tls://127.0.0.1:8080".to_string(),
             display_name: "Bitwise IO - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -256,6 +257,7 @@ mod test {
             endpoint: "tls://127.0.0.1:8082".to_string(),
             display_name: "Cargill - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
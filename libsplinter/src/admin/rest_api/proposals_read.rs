@@ -41,9 +41,6 @@ pub fn fetch_proposal<A: AdminCommands + Clone + 'static>(
                 .fetch_proposal(circuit_id.clone())
                 .map_err(|err| ProposalRouteError::InternalError(err.to_string()))?;
             if let Some(proposal) = proposal {
-                let proposal = CircuitProposal::from_proto(proposal)
-                    .map_err(|err| ProposalRouteError::InternalError(err.to_string()))?;
-
                 Ok(proposal)
             } else {
                 Err(ProposalRouteError::NotFound(format!(
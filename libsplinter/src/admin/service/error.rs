@@ -16,6 +16,7 @@ use std::error::Error;
 use std::fmt;
 
 use crate::consensus::error::ProposalManagerError;
+use crate::node_registry::NodeRegistryError;
 use crate::orchestrator::{InitializeServiceError, ShutdownServiceError};
 use crate::service::error::{ServiceError, ServiceSendError};
 use crate::signing;
@@ -130,6 +131,9 @@ pub enum AdminSharedError {
     // Returned if a circuit cannot be added to splinter state
     CommitError(String),
     UpdateProposalsError(OpenProposalError),
+
+    /// An error occurred while reading the circuit members from the node registry
+    NodeRegistryError(NodeRegistryError),
 }
 
 impl Error for AdminSharedError {
@@ -148,6 +152,7 @@ impl Error for AdminSharedError {
             AdminSharedError::CommitError(_) => None,
             AdminSharedError::UpdateProposalsError(err) => Some(err),
             AdminSharedError::UnableToAddSubscriber(_) => None,
+            AdminSharedError::NodeRegistryError(err) => Some(err),
         }
     }
 }
@@ -189,6 +194,9 @@ impl fmt::Display for AdminSharedError {
             AdminSharedError::UnableToAddSubscriber(msg) => {
                 write!(f, "unable to add admin service event subscriber: {}", msg)
             }
+            AdminSharedError::NodeRegistryError(err) => {
+                write!(f, "unable to read node registry: {}", err)
+            }
         }
     }
 }
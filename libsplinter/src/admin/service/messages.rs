@@ -15,6 +15,7 @@
 use protobuf::{self, RepeatedField};
 
 use crate::hex::{as_hex, deserialize_hex};
+use crate::node_registry::{NodeRegistryError, NodeRegistryReader};
 use crate::protos::admin::{self, CircuitCreateRequest};
 
 use super::error::MarshallingError;
@@ -59,6 +60,7 @@ impl CreateCircuit {
 
         let durability = match proto.get_durability() {
             admin::Circuit_DurabilityType::NO_DURABILITY => DurabilityType::NoDurability,
+            admin::Circuit_DurabilityType::STORE_AND_FORWARD => DurabilityType::StoreAndForward,
             admin::Circuit_DurabilityType::UNSET_DURABILITY_TYPE => {
                 return Err(MarshallingError::UnsetField(
                     "Unset durability type".to_string(),
@@ -68,6 +70,7 @@ impl CreateCircuit {
 
         let routes = match proto.get_routes() {
             admin::Circuit_RouteType::ANY_ROUTE => RouteType::Any,
+            admin::Circuit_RouteType::MULTI_HOP_ROUTE => RouteType::MultiHop,
             admin::Circuit_RouteType::UNSET_ROUTE_TYPE => {
                 return Err(MarshallingError::UnsetField("Unset route type".to_string()));
             }
@@ -130,10 +133,14 @@ impl CreateCircuit {
             DurabilityType::NoDurability => {
                 circuit.set_durability(admin::Circuit_DurabilityType::NO_DURABILITY);
             }
+            DurabilityType::StoreAndForward => {
+                circuit.set_durability(admin::Circuit_DurabilityType::STORE_AND_FORWARD);
+            }
         };
 
         match self.routes {
             RouteType::Any => circuit.set_routes(admin::Circuit_RouteType::ANY_ROUTE),
+            RouteType::MultiHop => circuit.set_routes(admin::Circuit_RouteType::MULTI_HOP_ROUTE),
         };
 
         let mut create_request = CircuitCreateRequest::new();
@@ -162,11 +169,13 @@ impl Default for PersistenceType {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum DurabilityType {
     NoDurability,
+    StoreAndForward,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum RouteType {
     Any,
+    MultiHop,
 }
 
 impl Default for RouteType {
@@ -178,7 +187,14 @@ impl Default for RouteType {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct SplinterNode {
     pub node_id: String,
+    /// May be left empty in a circuit definition for a member that is in the node registry; it
+    /// must be filled in before the proposal is signed.
+    #[serde(default)]
     pub endpoint: String,
+    /// The display name of the node in the node registry of the node that reports the circuit;
+    /// it is not part of the circuit itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
 }
 
 impl SplinterNode {
@@ -195,6 +211,7 @@ impl SplinterNode {
         Ok(Self {
             node_id: proto.take_node_id(),
             endpoint: proto.take_endpoint(),
+            display_name: None,
         })
     }
 }
@@ -320,6 +337,37 @@ impl CircuitProposal {
 
         Ok(proposal)
     }
+
+    /// Sets the display names of the members of the proposed circuit to the names returned by
+    /// the given lookup. Members without a display name are left without one.
+    pub fn with_display_names<F>(mut self, display_name: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        for member in self.circuit.members.iter_mut() {
+            member.display_name = display_name(&member.node_id);
+        }
+        self
+    }
+}
+
+/// Returns the display name the node is registered with in the node registry, if the node is
+/// registered.
+pub(super) fn registered_display_name(
+    node_registry: &dyn NodeRegistryReader,
+    node_id: &str,
+) -> Option<String> {
+    match node_registry.fetch_node(node_id) {
+        Ok(node) => Some(node.display_name),
+        Err(NodeRegistryError::NotFoundError(_)) => None,
+        Err(err) => {
+            warn!(
+                "Unable to read display name of {} from node registry: {}",
+                node_id, err
+            );
+            None
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -432,4 +480,35 @@ impl AdminServiceEvent {
             AdminServiceEvent::CircuitReady(proposal) => proposal,
         }
     }
+
+    /// Sets the display names of the members of the event's proposal to the names returned by
+    /// the given lookup.
+    pub fn with_display_names<F>(self, display_name: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            AdminServiceEvent::ProposalSubmitted(proposal) => {
+                AdminServiceEvent::ProposalSubmitted(proposal.with_display_names(display_name))
+            }
+            AdminServiceEvent::ProposalVote((proposal, signer)) => {
+                AdminServiceEvent::ProposalVote((proposal.with_display_names(display_name), signer))
+            }
+            AdminServiceEvent::ProposalAccepted((proposal, signer)) => {
+                AdminServiceEvent::ProposalAccepted((
+                    proposal.with_display_names(display_name),
+                    signer,
+                ))
+            }
+            AdminServiceEvent::ProposalRejected((proposal, signer)) => {
+                AdminServiceEvent::ProposalRejected((
+                    proposal.with_display_names(display_name),
+                    signer,
+                ))
+            }
+            AdminServiceEvent::CircuitReady(proposal) => {
+                AdminServiceEvent::CircuitReady(proposal.with_display_names(display_name))
+            }
+        }
+    }
 }
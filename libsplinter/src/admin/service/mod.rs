@@ -34,10 +34,9 @@ use crate::network::{
     auth::{AuthorizationCallbackError, AuthorizationInquisitor, PeerAuthorizationState},
     peer::PeerConnector,
 };
+use crate::node_registry::NodeRegistryReader;
 use crate::orchestrator::ServiceOrchestrator;
-use crate::protos::admin::{
-    AdminMessage, AdminMessage_Type, CircuitManagementPayload, CircuitProposal,
-};
+use crate::protos::admin::{AdminMessage, AdminMessage_Type, CircuitManagementPayload};
 use crate::service::{
     error::{ServiceDestroyError, ServiceError, ServiceStartError, ServiceStopError},
     Service, ServiceMessageContext, ServiceNetworkRegistry,
@@ -52,6 +51,11 @@ use self::shared::AdminServiceShared;
 pub use self::error::AdminServiceError;
 pub use self::error::AdminSubscriberError;
 
+/// The role a public key must have to propose changes to circuits.
+pub const PROPOSER_ROLE: &str = "proposer";
+/// The role a public key must have to vote on proposed changes to circuits.
+pub const VOTER_ROLE: &str = "voter";
+
 pub trait AdminServiceEventSubscriber: Send {
     fn handle_event(
         &self,
@@ -83,7 +87,7 @@ pub trait AdminCommands: Send + Sync {
     fn fetch_proposal(
         &self,
         circuit_id: String,
-    ) -> Result<Option<CircuitProposal>, AdminServiceError>;
+    ) -> Result<Option<messages::CircuitProposal>, AdminServiceError>;
 
     fn list_proposals(&self) -> Result<Proposals, AdminServiceError>;
 }
@@ -111,6 +115,7 @@ pub struct AdminService {
     service_id: String,
     admin_service_shared: Arc<Mutex<AdminServiceShared>>,
     consensus: Option<AdminConsensusManager>,
+    node_registry: Arc<dyn NodeRegistryReader>,
 }
 
 impl AdminService {
@@ -124,8 +129,10 @@ impl AdminService {
         signature_verifier: Box<dyn SignatureVerifier + Send>,
         key_registry: Box<dyn KeyRegistry>,
         key_permission_manager: Box<dyn KeyPermissionManager>,
+        node_registry: Box<dyn NodeRegistryReader>,
         storage_type: &str,
     ) -> Result<Self, ServiceError> {
+        let node_registry: Arc<dyn NodeRegistryReader> = node_registry.into();
         let new_service = Self {
             service_id: admin_service_id(node_id),
             admin_service_shared: Arc::new(Mutex::new(AdminServiceShared::new(
@@ -137,9 +144,11 @@ impl AdminService {
                 signature_verifier,
                 key_registry,
                 key_permission_manager,
+                node_registry.clone(),
                 storage_type,
             )?)),
             consensus: None,
+            node_registry,
         };
 
         let auth_callback_shared = Arc::clone(&new_service.admin_service_shared);
@@ -175,6 +184,7 @@ impl AdminService {
     pub fn commands(&self) -> impl AdminCommands + Clone {
         AdminServiceCommands {
             shared: Arc::clone(&self.admin_service_shared),
+            node_registry: Arc::clone(&self.node_registry),
         }
     }
 }
@@ -344,6 +354,9 @@ impl Service for AdminService {
 #[derive(Clone)]
 struct AdminServiceCommands {
     shared: Arc<Mutex<AdminServiceShared>>,
+    // the registry that the display names of proposed members are read from, once the lock on
+    // the shared state has been released
+    node_registry: Arc<dyn NodeRegistryReader>,
 }
 
 impl AdminCommands for AdminServiceCommands {
@@ -397,25 +410,34 @@ impl AdminCommands for AdminServiceCommands {
     fn fetch_proposal(
         &self,
         circuit_id: String,
-    ) -> Result<Option<CircuitProposal>, AdminServiceError> {
-        self.shared
+    ) -> Result<Option<messages::CircuitProposal>, AdminServiceError> {
+        let proposal = self
+            .shared
             .lock()
             .map_err(|_| AdminServiceError::general_error("Admin shared lock was lock poisoned"))?
-            .get_proposal(&circuit_id)
+            .fetch_proposal(&circuit_id)
             .map_err(|err| {
                 AdminServiceError::general_error_with_source(
                     "Unable to get proposal",
                     Box::new(err),
                 )
+            })?;
+
+        Ok(proposal.map(|proposal| {
+            proposal.with_display_names(|node_id| {
+                messages::registered_display_name(&*self.node_registry, node_id)
             })
+        }))
     }
 
     fn list_proposals(&self) -> Result<Proposals, AdminServiceError> {
-        Ok(self
+        let proposals = self
             .shared
             .lock()
             .map_err(|_| AdminServiceError::general_error("Admin shared lock was lock poisoned"))?
-            .get_proposals())
+            .get_proposals();
+
+        Ok(proposals.with_display_names(Arc::clone(&self.node_registry)))
     }
 }
 
@@ -449,6 +471,7 @@ mod tests {
     };
     use crate::mesh::Mesh;
     use crate::network::{auth::AuthorizationCallback, Network};
+    use crate::node_registry::noop::NoOpNodeRegistry;
     use crate::protos::{
         admin,
         authorization::{AuthorizationMessage, AuthorizationMessageType, AuthorizedMessage},
@@ -504,6 +527,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Box::new(NoOpNodeRegistry),
             "memory",
         )
         .expect("Service should have been created correctly");
@@ -13,14 +13,16 @@
 // limitations under the License.
 
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use serde_derive::{Deserialize, Serialize};
 
+use crate::node_registry::NodeRegistryReader;
 use crate::protos::admin::CircuitProposal;
 use crate::storage::get_storage;
 
 use super::error::OpenProposalError;
-use super::messages;
+use super::messages::{self, registered_display_name};
 
 pub struct OpenProposals {
     storage_location: String,
@@ -157,6 +159,21 @@ impl Proposals {
     pub fn total(&self) -> usize {
         self.size
     }
+
+    /// Sets the display names of the members of each proposal from the node registry.
+    pub fn with_display_names(self, node_registry: Arc<dyn NodeRegistryReader>) -> Self {
+        Self {
+            inner: Box::new(self.inner.map(move |(circuit_id, proposal)| {
+                (
+                    circuit_id,
+                    proposal.with_display_names(|node_id| {
+                        registered_display_name(&*node_registry, node_id)
+                    }),
+                )
+            })),
+            size: self.size,
+        }
+    }
 }
 
 impl Iterator for Proposals {
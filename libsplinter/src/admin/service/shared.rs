@@ -17,7 +17,7 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
 use std::iter::FromIterator;
 use std::sync::{Arc, RwLock};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use protobuf::{Message, RepeatedField};
 
@@ -25,16 +25,20 @@ use crate::circuit::SplinterState;
 use crate::circuit::{
     service::SplinterNode as StateNode,
     service::{Service, ServiceId},
+    store::CircuitDirectoryChange,
     AuthorizationType, Circuit as StateCircuit, DurabilityType, PersistenceType, RouteType,
     ServiceDefinition as StateServiceDefinition,
 };
 use crate::consensus::{Proposal, ProposalId};
 use crate::hex::to_hex;
-use crate::keys::{KeyPermissionManager, KeyRegistry};
+use crate::keys::{KeyInfo, KeyPermissionManager, KeyRegistry};
 use crate::network::{
     auth::{AuthorizationInquisitor, PeerAuthorizationState},
     peer::PeerConnector,
 };
+use crate::node_registry::{
+    ComparisonOperator, MetadataPredicate, Node, NodeField, NodeRegistryError, NodeRegistryReader,
+};
 use crate::orchestrator::{ServiceDefinition, ServiceOrchestrator, ShutdownServiceError};
 use crate::protos::admin::{
     AdminMessage, AdminMessage_Type, Circuit, CircuitManagementPayload,
@@ -52,15 +56,22 @@ use super::error::{AdminSharedError, MarshallingError};
 use super::mailbox::Mailbox;
 use super::messages;
 use super::open_proposals::{OpenProposals, Proposals};
-use super::{admin_service_id, sha256, AdminServiceEventSubscriber, AdminSubscriberError, Events};
+use super::{
+    admin_service_id, sha256, AdminServiceEventSubscriber, AdminSubscriberError, Events,
+    PROPOSER_ROLE, VOTER_ROLE,
+};
 
 const DEFAULT_STATE_DIR: &str = "/var/lib/splinter/";
 const STATE_DIR_ENV: &str = "SPLINTER_STATE_DIR";
-static VOTER_ROLE: &str = "voter";
-static PROPOSER_ROLE: &str = "proposer";
 
 const DEFAULT_IN_MEMORY_EVENT_LIMIT: usize = 100;
 
+/// How long a superseded key is still accepted after the end of its overlap window. The members
+/// of a circuit check a payload's key at different times, with different clocks, so without it a
+/// payload signed near the end of the window could be accepted by some members and rejected by
+/// others.
+const KEY_ACCEPTANCE_GRACE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
 type UnpeeredPendingPayload = (Vec<String>, CircuitManagementPayload);
 
 enum CircuitProposalStatus {
@@ -170,6 +181,11 @@ pub struct AdminServiceShared {
     signature_verifier: Box<dyn SignatureVerifier + Send>,
     key_registry: Box<dyn KeyRegistry>,
     key_permission_manager: Box<dyn KeyPermissionManager>,
+    // the node registry that the endpoints of circuit members are checked against
+    node_registry: Arc<dyn NodeRegistryReader>,
+    // the display names of the circuit members that have been checked against the node
+    // registry, so that events can be reported without reading the registry again
+    member_display_names: RefCell<HashMap<String, String>>,
 }
 
 impl AdminServiceShared {
@@ -183,6 +199,7 @@ impl AdminServiceShared {
         signature_verifier: Box<dyn SignatureVerifier + Send>,
         key_registry: Box<dyn KeyRegistry>,
         key_permission_manager: Box<dyn KeyPermissionManager>,
+        node_registry: Arc<dyn NodeRegistryReader>,
         storage_type: &str,
     ) -> Result<Self, ServiceError> {
         let location = {
@@ -195,6 +212,9 @@ impl AdminServiceShared {
 
         let storage_location = match storage_type {
             "yaml" => format!("{}{}", location, "/circuit_proposals.yaml"),
+            // Only the circuit directory is kept in LMDB
+            #[cfg(feature = "circuit-store-lmdb")]
+            "lmdb" => format!("{}{}", location, "/circuit_proposals.yaml"),
             "memory" => "memory".to_string(),
             _ => panic!("Storage type is not supported: {}", storage_type),
         };
@@ -225,6 +245,8 @@ impl AdminServiceShared {
             signature_verifier,
             key_registry,
             key_permission_manager,
+            node_registry,
+            member_display_names: RefCell::new(HashMap::new()),
         })
     }
 
@@ -635,6 +657,10 @@ impl AdminServiceShared {
         circuit_management_type: &str,
         event: messages::AdminServiceEvent,
     ) {
+        let event = {
+            let member_display_names = self.member_display_names.borrow();
+            event.with_display_names(|node_id| member_display_names.get(node_id).cloned())
+        };
         let (ts, event) = match self.event_mailbox.add(event) {
             Ok((ts, event)) => (ts, event),
             Err(err) => {
@@ -688,6 +714,19 @@ impl AdminServiceShared {
         Ok(self.open_proposals.get_proposal(circuit_id)?)
     }
 
+    /// Returns the open proposal for the circuit.
+    pub fn fetch_proposal(
+        &self,
+        circuit_id: &str,
+    ) -> Result<Option<messages::CircuitProposal>, AdminSharedError> {
+        self.get_proposal(circuit_id)?
+            .map(|proposal| {
+                messages::CircuitProposal::from_proto(proposal)
+                    .map_err(AdminSharedError::InvalidMessageFormat)
+            })
+            .transpose()
+    }
+
     pub fn get_proposals(&self) -> Proposals {
         self.open_proposals.get_proposals()
     }
@@ -825,16 +864,7 @@ impl AdminServiceShared {
             ));
         }
 
-        let key_info = self
-            .key_registry
-            .get_key(signer_public_key)
-            .map_err(|err| AdminSharedError::ValidationFailed(err.to_string()))?
-            .ok_or_else(|| {
-                AdminSharedError::ValidationFailed(format!(
-                    "{} is not registered for a node",
-                    to_hex(signer_public_key)
-                ))
-            })?;
+        let key_info = self.fetch_accepted_key(signer_public_key)?;
 
         if key_info.associated_node_id() != requester_node_id {
             return Err(AdminSharedError::ValidationFailed(format!(
@@ -949,6 +979,8 @@ impl AdminServiceShared {
             ));
         }
 
+        self.validate_members_against_registry(circuit)?;
+
         // check this node is in members
         if !members.contains(&self.node_id) {
             return Err(AdminSharedError::ValidationFailed(format!(
@@ -1010,15 +1042,68 @@ impl AdminServiceShared {
         Ok(())
     }
 
-    fn validate_circuit_vote(
-        &self,
-        proposal_vote: &CircuitProposalVote,
-        signer_public_key: &[u8],
-        circuit_proposal: &CircuitProposal,
-        node_id: &str,
-    ) -> Result<(), AdminSharedError> {
-        let circuit_hash = proposal_vote.get_circuit_hash();
+    /// Checks that the members of the circuit do not contradict the node registry: a registered
+    /// member must use its registered endpoint, and a member that is not registered must not use
+    /// the endpoint of a registered node.
+    ///
+    /// The display names of the registered members are kept for the events of the proposal.
+    fn validate_members_against_registry(&self, circuit: &Circuit) -> Result<(), AdminSharedError> {
+        for member in circuit.get_members() {
+            match self.fetch_registered_node(member.get_node_id())? {
+                Some(node) => {
+                    self.member_display_names
+                        .borrow_mut()
+                        .insert(node.identity.clone(), node.display_name.clone());
+                    if node.endpoint != member.get_endpoint() {
+                        return Err(AdminSharedError::ValidationFailed(format!(
+                            "Member {} has endpoint {}, but is registered with endpoint {}",
+                            member.get_node_id(),
+                            member.get_endpoint(),
+                            node.endpoint
+                        )));
+                    }
+                }
+                None => {
+                    self.member_display_names
+                        .borrow_mut()
+                        .remove(member.get_node_id());
+                    let predicates = [MetadataPredicate::Field(
+                        NodeField::Endpoint,
+                        ComparisonOperator::Eq,
+                        member.get_endpoint().to_string(),
+                    )];
+                    if let Some(node) = self
+                        .node_registry
+                        .list_nodes(&predicates)
+                        .map_err(AdminSharedError::NodeRegistryError)?
+                        .next()
+                    {
+                        return Err(AdminSharedError::ValidationFailed(format!(
+                            "Member {} has endpoint {}, which is registered to node {}",
+                            member.get_node_id(),
+                            member.get_endpoint(),
+                            node.identity
+                        )));
+                    }
+                }
+            }
+        }
 
+        Ok(())
+    }
+
+    fn fetch_registered_node(&self, node_id: &str) -> Result<Option<Node>, AdminSharedError> {
+        match self.node_registry.fetch_node(node_id) {
+            Ok(node) => Ok(Some(node)),
+            Err(NodeRegistryError::NotFoundError(_)) => Ok(None),
+            Err(err) => Err(AdminSharedError::NodeRegistryError(err)),
+        }
+    }
+
+    /// Returns the information of a signer's key, if the key is registered and still accepted: a
+    /// key that has been superseded by a rotation is only accepted until the end of its overlap
+    /// window, plus `KEY_ACCEPTANCE_GRACE_PERIOD`.
+    fn fetch_accepted_key(&self, signer_public_key: &[u8]) -> Result<KeyInfo, AdminSharedError> {
         let key_info = self
             .key_registry
             .get_key(signer_public_key)
@@ -1030,6 +1115,40 @@ impl AdminServiceShared {
                 ))
             })?;
 
+        if let Some(superseded_by) = key_info.superseded_by() {
+            let checked_at = SystemTime::now()
+                .checked_sub(KEY_ACCEPTANCE_GRACE_PERIOD)
+                .unwrap_or(UNIX_EPOCH);
+            if !key_info.is_accepted_at(checked_at) {
+                return Err(AdminSharedError::ValidationFailed(format!(
+                    "{} has been superseded by {} and is no longer accepted",
+                    to_hex(signer_public_key),
+                    to_hex(superseded_by)
+                )));
+            }
+
+            warn!(
+                "Accepting {}, which has been superseded by {}, until the end of its overlap \
+                 window",
+                to_hex(signer_public_key),
+                to_hex(superseded_by)
+            );
+        }
+
+        Ok(key_info)
+    }
+
+    fn validate_circuit_vote(
+        &self,
+        proposal_vote: &CircuitProposalVote,
+        signer_public_key: &[u8],
+        circuit_proposal: &CircuitProposal,
+        node_id: &str,
+    ) -> Result<(), AdminSharedError> {
+        let circuit_hash = proposal_vote.get_circuit_hash();
+
+        let key_info = self.fetch_accepted_key(signer_public_key)?;
+
         let signer_node = key_info.associated_node_id().to_string();
 
         if signer_node != node_id {
@@ -1313,6 +1432,7 @@ impl AdminServiceShared {
 
         let durability = match circuit.get_durability() {
             Circuit_DurabilityType::NO_DURABILITY => DurabilityType::NoDurability,
+            Circuit_DurabilityType::STORE_AND_FORWARD => DurabilityType::StoreAndForward,
             // This should never happen
             Circuit_DurabilityType::UNSET_DURABILITY_TYPE => {
                 return Err(AdminSharedError::CommitError(
@@ -1323,6 +1443,7 @@ impl AdminServiceShared {
 
         let routes = match circuit.get_routes() {
             Circuit_RouteType::ANY_ROUTE => RouteType::Any,
+            Circuit_RouteType::MULTI_HOP_ROUTE => RouteType::MultiHop,
             // This should never happen
             Circuit_RouteType::UNSET_ROUTE_TYPE => {
                 return Err(AdminSharedError::CommitError(
@@ -1354,24 +1475,22 @@ impl AdminServiceShared {
             AdminSharedError::CommitError(format!("Unable to unlock splinter state: {}", err))
         })?;
 
-        for member in members {
-            splinter_state
-                .add_node(member.id().to_string(), member)
-                .map_err(|err| {
-                    AdminSharedError::CommitError(format!(
-                        "Unable to add node to splinter state: {}",
-                        err
-                    ))
-                })?;
-        }
-        splinter_state
-            .add_circuit(new_circuit.id().to_string(), new_circuit)
-            .map_err(|err| {
-                AdminSharedError::CommitError(format!(
-                    "Unable to add circuit to splinter state: {}",
-                    err
-                ))
-            })?;
+        // The nodes and the circuit are committed together, so that a failure cannot leave the
+        // nodes without their circuit
+        let mut changes = members
+            .into_iter()
+            .map(|member| CircuitDirectoryChange::AddNode(member.id().to_string(), member))
+            .collect::<Vec<_>>();
+        changes.push(CircuitDirectoryChange::AddCircuit(
+            new_circuit.id().to_string(),
+            new_circuit,
+        ));
+        splinter_state.commit_changes(changes).map_err(|err| {
+            AdminSharedError::CommitError(format!(
+                "Unable to add circuit to splinter state: {}",
+                err
+            ))
+        })?;
 
         for service in roster {
             if service.allowed_nodes().contains(&self.node_id) {
@@ -1459,6 +1578,7 @@ mod tests {
         auth::{AuthorizationCallback, AuthorizationCallbackError},
         Network,
     };
+    use crate::node_registry::noop::NoOpNodeRegistry;
     use crate::protos::admin;
     use crate::protos::admin::{SplinterNode, SplinterService};
     use crate::protos::authorization::{
@@ -1502,6 +1622,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1578,6 +1699,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1644,6 +1766,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1673,6 +1796,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1683,6 +1807,51 @@ mod tests {
         }
     }
 
+    #[test]
+    // test that a circuit proposed by a key that has been superseded is valid during the key's
+    // overlap window and the grace period after it, and invalid once both have passed
+    fn test_validate_circuit_signer_key_superseded() {
+        let accepted_key = KeyInfo::builder(b"test_signer_a".to_vec(), "node_a".to_string())
+            .with_supersession(
+                b"test_signer_c".to_vec(),
+                SystemTime::now() + Duration::from_secs(3600),
+            )
+            .build();
+        let admin_shared = setup_admin_shared(accepted_key, Arc::new(NoOpNodeRegistry));
+        let circuit = setup_test_circuit();
+
+        if let Err(err) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a")
+        {
+            panic!("Should have been valid during the overlap window: {}", err);
+        }
+
+        let recently_expired_key =
+            KeyInfo::builder(b"test_signer_a".to_vec(), "node_a".to_string())
+                .with_supersession(
+                    b"test_signer_c".to_vec(),
+                    SystemTime::now() - Duration::from_secs(1),
+                )
+                .build();
+        let admin_shared = setup_admin_shared(recently_expired_key, Arc::new(NoOpNodeRegistry));
+
+        if let Err(err) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a")
+        {
+            panic!("Should have been valid during the grace period: {}", err);
+        }
+
+        let expired_key = KeyInfo::builder(b"test_signer_a".to_vec(), "node_a".to_string())
+            .with_supersession(
+                b"test_signer_c".to_vec(),
+                SystemTime::now() - KEY_ACCEPTANCE_GRACE_PERIOD - Duration::from_secs(1),
+            )
+            .build();
+        let admin_shared = setup_admin_shared(expired_key, Arc::new(NoOpNodeRegistry));
+
+        if let Ok(_) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a") {
+            panic!("Should have been invalid because the signer key is no longer accepted");
+        }
+    }
+
     #[test]
     // test that if a circuit has a service in its roster with an allowed node that is not in
     // members an error is returned
@@ -1704,6 +1873,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1741,6 +1911,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1781,6 +1952,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1818,6 +1990,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1860,6 +2033,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1891,6 +2065,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1924,6 +2099,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -1961,6 +2137,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2005,6 +2182,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2049,6 +2227,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2089,6 +2268,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2109,6 +2289,67 @@ mod tests {
         }
     }
 
+    #[test]
+    // test that if a registered member uses an endpoint other than its registered one an error is
+    // returned
+    fn test_validate_circuit_registered_endpoint_mismatch() {
+        let admin_shared = setup_admin_shared(
+            signer_a_key(),
+            Arc::new(MockNodeRegistry {
+                nodes: vec![Node::new("node_b", "test://registered_b:0")],
+            }),
+        );
+        let circuit = setup_test_circuit();
+
+        if let Ok(_) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a") {
+            panic!(
+                "Should have been invalid because a member does not use its registered endpoint"
+            );
+        }
+    }
+
+    #[test]
+    // test that if a member that is not registered uses the endpoint of a registered node an error
+    // is returned
+    fn test_validate_circuit_unregistered_member_uses_registered_endpoint() {
+        let admin_shared = setup_admin_shared(
+            signer_a_key(),
+            Arc::new(MockNodeRegistry {
+                nodes: vec![Node::new("node_c", "test://endpoint_b:0")],
+            }),
+        );
+        let circuit = setup_test_circuit();
+
+        if let Ok(_) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a") {
+            panic!("Should have been invalid because a member uses the endpoint of another node");
+        }
+    }
+
+    #[test]
+    // test that a circuit whose members match the node registry is valid, and that the display
+    // names of the members are kept for the events of the proposal
+    fn test_validate_circuit_matches_registry() {
+        let mut node_b = Node::new("node_b", "test://endpoint_b:0");
+        node_b.display_name = "Node B".into();
+        let admin_shared = setup_admin_shared(
+            signer_a_key(),
+            Arc::new(MockNodeRegistry {
+                nodes: vec![Node::new("node_a", "test://endpoint_a:0"), node_b],
+            }),
+        );
+        let circuit = setup_test_circuit();
+
+        if let Err(err) = admin_shared.validate_create_circuit(&circuit, b"test_signer_a", "node_a")
+        {
+            panic!("Should have been valid: {}", err);
+        }
+
+        assert_eq!(
+            Some(&"Node B".to_string()),
+            admin_shared.member_display_names.borrow().get("node_b")
+        );
+    }
+
     #[test]
     // test that if a circuit does not have authorization set an error is returned
     fn test_validate_circuit_no_authorization() {
@@ -2129,6 +2370,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2161,6 +2403,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2193,6 +2436,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2225,6 +2469,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2257,6 +2502,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2290,6 +2536,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2323,6 +2570,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2358,6 +2606,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2392,6 +2641,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2435,6 +2685,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2473,6 +2724,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2524,6 +2776,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2577,6 +2830,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2632,6 +2886,7 @@ mod tests {
             Box::new(HashVerifier),
             Box::new(key_registry),
             Box::new(AllowAllKeyPermissionManager),
+            Arc::new(NoOpNodeRegistry),
             "memory",
         )
         .unwrap();
@@ -2751,6 +3006,32 @@ mod tests {
             .expect("failed to create orchestrator")
     }
 
+    fn setup_admin_shared(
+        key_info: KeyInfo,
+        node_registry: Arc<dyn NodeRegistryReader>,
+    ) -> AdminServiceShared {
+        let mut key_registry = StorageKeyRegistry::new("memory".to_string()).unwrap();
+        key_registry.save_key(key_info).unwrap();
+
+        AdminServiceShared::new(
+            "node_a".into(),
+            setup_orchestrator(),
+            setup_peer_connector(),
+            Box::new(MockAuthInquisitor),
+            setup_splinter_state(),
+            Box::new(HashVerifier),
+            Box::new(key_registry),
+            Box::new(AllowAllKeyPermissionManager),
+            node_registry,
+            "memory",
+        )
+        .unwrap()
+    }
+
+    fn signer_a_key() -> KeyInfo {
+        KeyInfo::builder(b"test_signer_a".to_vec(), "node_a".to_string()).build()
+    }
+
     fn splinter_node(node_id: &str, endpoint: &str) -> admin::SplinterNode {
         let mut node = admin::SplinterNode::new();
         node.set_node_id(node_id.into());
@@ -2765,6 +3046,33 @@ mod tests {
         service
     }
 
+    struct MockNodeRegistry {
+        nodes: Vec<Node>,
+    }
+
+    impl NodeRegistryReader for MockNodeRegistry {
+        fn list_nodes<'a, 'b: 'a>(
+            &'b self,
+            predicates: &'a [MetadataPredicate],
+        ) -> Result<Box<dyn Iterator<Item = Node> + Send + 'a>, NodeRegistryError> {
+            Ok(Box::new(self.nodes.iter().cloned().filter(move |node| {
+                predicates.iter().all(|predicate| predicate.apply(node))
+            })))
+        }
+
+        fn count_nodes(&self, predicates: &[MetadataPredicate]) -> Result<u32, NodeRegistryError> {
+            Ok(self.list_nodes(predicates)?.count() as u32)
+        }
+
+        fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
+            self.nodes
+                .iter()
+                .find(|node| node.identity == identity)
+                .cloned()
+                .ok_or_else(|| NodeRegistryError::NotFoundError(identity.to_string()))
+        }
+    }
+
     struct MockAuthInquisitor;
 
     impl AuthorizationInquisitor for MockAuthInquisitor {
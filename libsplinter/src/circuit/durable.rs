@@ -0,0 +1,720 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Store-and-forward delivery for circuits with `DurabilityType::StoreAndForward`.
+//!
+//! Direct messages sent by a local service on a durable circuit are queued, per recipient, before
+//! they are delivered. Only the message at the head of a queue is in flight at any time, so
+//! messages are delivered in order. A message is removed from its queue once the recipient
+//! service acknowledges it, and is resent periodically until then, such as after the service or
+//! its node reconnects.
+//!
+//! Queued messages are persisted to an append-only journal: each message that is queued or
+//! removed from a queue appends a single line to the journal, so the cost of a change does not
+//! depend on the number of queued messages. The journal is compacted, by rewriting only the
+//! messages still queued, when it is opened and once most of its entries are obsolete.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use protobuf::Message;
+use uuid::Uuid;
+
+use crate::channel::Sender;
+use crate::hex::{parse_hex, to_hex};
+use crate::network::routing::RoutingTable;
+use crate::network::sender::SendRequest;
+use crate::protos::circuit::CircuitDirectMessage;
+
+use super::handlers::create_direct_message_request;
+use super::SplinterState;
+
+/// The default number of messages that may be queued for a single recipient.
+pub const DEFAULT_MAX_QUEUE_LEN: usize = 1024;
+/// The default amount of time a message may wait to be acknowledged before it is dropped.
+pub const DEFAULT_MESSAGE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// The default amount of time to wait for an acknowledgement before a message is resent.
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The minimum number of entries in the journal before it is compacted.
+const MIN_COMPACTION_ENTRIES: usize = 1024;
+
+#[derive(Debug)]
+pub enum DurableStoreError {
+    /// The recipient's queue has reached its maximum length.
+    QueueFull(String),
+    /// The queued messages could not be read or written.
+    StorageError(String),
+}
+
+impl Error for DurableStoreError {}
+
+impl fmt::Display for DurableStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DurableStoreError::QueueFull(msg) => write!(f, "queue is full: {}", msg),
+            DurableStoreError::StorageError(msg) => write!(f, "storage error: {}", msg),
+        }
+    }
+}
+
+/// A change to the queues, persisted as a line of the journal.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum JournalEntry {
+    Enqueue {
+        circuit: String,
+        recipient: String,
+        message: StoredMessage,
+    },
+    Remove {
+        circuit: String,
+        recipient: String,
+        message_id: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredMessage {
+    message_id: String,
+    /// Seconds since the UNIX epoch after which the message is dropped
+    expires_at: u64,
+    /// The hex-encoded `CircuitDirectMessage`
+    message: String,
+}
+
+#[derive(Default)]
+struct MessageQueue {
+    messages: VecDeque<StoredMessage>,
+    /// When the head of the queue was last sent; not persisted, so all queues are resent on
+    /// restart
+    last_sent: Option<Instant>,
+}
+
+type Queues = BTreeMap<(String, String), MessageQueue>;
+
+/// The append-only file the queued messages are persisted to.
+struct Journal {
+    path: PathBuf,
+    file: File,
+    /// The number of entries in the file
+    entries: usize,
+}
+
+impl Journal {
+    /// Opens the journal at the given path, creating it if it does not exist, and returns the
+    /// queues it contains. The journal is compacted once it has been read.
+    fn open(path: &Path) -> Result<(Self, Queues), DurableStoreError> {
+        let mut queues = Queues::new();
+        match File::open(path) {
+            Ok(file) => {
+                let mut lines = BufReader::new(file).lines().peekable();
+                while let Some(line) = lines.next() {
+                    let line = line.map_err(|err| {
+                        DurableStoreError::StorageError(format!(
+                            "Unable to read {:?}: {}",
+                            path, err
+                        ))
+                    })?;
+                    match serde_json::from_str(&line) {
+                        Ok(entry) => replay(&mut queues, entry),
+                        // The last entry may have been partially written before a crash
+                        Err(err) if lines.peek().is_none() => {
+                            warn!("Ignoring incomplete last entry of {:?}: {}", path, err)
+                        }
+                        Err(err) => {
+                            return Err(DurableStoreError::StorageError(format!(
+                                "Invalid entry in {:?}: {}",
+                                path, err
+                            )))
+                        }
+                    }
+                }
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => {
+                return Err(DurableStoreError::StorageError(format!(
+                    "Unable to open {:?}: {}",
+                    path, err
+                )))
+            }
+        }
+
+        let journal = Self::write(path, &queues)?;
+        Ok((journal, queues))
+    }
+
+    /// Replaces the journal at the given path with one that contains only the given queues.
+    ///
+    /// The new journal is written to a temporary file first, so the journal is never left
+    /// partially written.
+    fn write(path: &Path, queues: &Queues) -> Result<Self, DurableStoreError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut buf = vec![];
+        let mut entries = 0;
+        for ((circuit, recipient), queue) in queues.iter() {
+            for message in queue.messages.iter() {
+                write_entry(
+                    &mut buf,
+                    &JournalEntry::Enqueue {
+                        circuit: circuit.clone(),
+                        recipient: recipient.clone(),
+                        message: message.clone(),
+                    },
+                )?;
+                entries += 1;
+            }
+        }
+
+        let to_storage_error = |err: std::io::Error| {
+            DurableStoreError::StorageError(format!("Unable to write {:?}: {}", path, err))
+        };
+        let mut file = File::create(&tmp_path).map_err(to_storage_error)?;
+        file.write_all(&buf).map_err(to_storage_error)?;
+        file.sync_all().map_err(to_storage_error)?;
+        fs::rename(&tmp_path, path).map_err(to_storage_error)?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(to_storage_error)?;
+
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file,
+            entries,
+        })
+    }
+
+    /// Appends the given entries to the journal with a single write.
+    fn append(&mut self, entries: &[JournalEntry]) -> Result<(), DurableStoreError> {
+        let mut buf = vec![];
+        for entry in entries {
+            write_entry(&mut buf, entry)?;
+        }
+        self.file.write_all(&buf).map_err(|err| {
+            DurableStoreError::StorageError(format!("Unable to write {:?}: {}", self.path, err))
+        })?;
+        self.entries += entries.len();
+        Ok(())
+    }
+
+    /// Compacts the journal if most of its entries are for messages that are no longer queued.
+    fn compact_if_needed(&mut self, queues: &Queues) -> Result<(), DurableStoreError> {
+        let queued: usize = queues.values().map(|queue| queue.messages.len()).sum();
+        if self.entries >= MIN_COMPACTION_ENTRIES && self.entries > queued * 2 {
+            *self = Self::write(&self.path, queues)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_entry(buf: &mut Vec<u8>, entry: &JournalEntry) -> Result<(), DurableStoreError> {
+    serde_json::to_writer(&mut *buf, entry)
+        .map_err(|err| DurableStoreError::StorageError(err.to_string()))?;
+    buf.push(b'\n');
+    Ok(())
+}
+
+/// Applies a journal entry to the queues.
+fn replay(queues: &mut Queues, entry: JournalEntry) {
+    match entry {
+        JournalEntry::Enqueue {
+            circuit,
+            recipient,
+            message,
+        } => queues
+            .entry((circuit, recipient))
+            .or_default()
+            .messages
+            .push_back(message),
+        JournalEntry::Remove {
+            circuit,
+            recipient,
+            message_id,
+        } => {
+            if let Some(queue) = queues.get_mut(&(circuit, recipient)) {
+                queue
+                    .messages
+                    .retain(|message| message.message_id != message_id);
+            }
+        }
+    }
+}
+
+struct Inner {
+    /// The journal the queues are persisted to; `None` if the queues are only kept in memory
+    journal: Option<Journal>,
+    max_queue_len: usize,
+    message_ttl: Duration,
+    retry_interval: Duration,
+    queues: Queues,
+}
+
+impl Inner {
+    /// Persists the given changes, which have already been applied to the queues.
+    fn persist(&mut self, entries: &[JournalEntry]) -> Result<(), DurableStoreError> {
+        match self.journal {
+            Some(ref mut journal) if !entries.is_empty() => {
+                journal.append(entries)?;
+                journal.compact_if_needed(&self.queues)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops any expired messages from the head of the given queue, returning the ids of the
+    /// dropped messages.
+    fn drop_expired(queue: &mut MessageQueue, now: u64) -> Vec<String> {
+        let mut dropped = vec![];
+        while let Some(head) = queue.messages.front() {
+            if head.expires_at > now {
+                break;
+            }
+            warn!(
+                "Dropping unacknowledged message {}: time to live expired",
+                head.message_id
+            );
+            if let Some(head) = queue.messages.pop_front() {
+                dropped.push(head.message_id);
+            }
+            queue.last_sent = None;
+        }
+        dropped
+    }
+
+    /// Returns the head of the given queue if it is due to be sent, and marks it as sent.
+    fn take_due(
+        queue: &mut MessageQueue,
+        retry_interval: Duration,
+    ) -> Option<CircuitDirectMessage> {
+        let due = match queue.last_sent {
+            Some(last_sent) => last_sent.elapsed() >= retry_interval,
+            None => true,
+        };
+        if !due {
+            return None;
+        }
+
+        let head = queue.messages.front()?;
+        let message = parse_hex(&head.message)
+            .ok()
+            .and_then(|bytes| protobuf::parse_from_bytes(&bytes).ok());
+        match message {
+            Some(message) => {
+                queue.last_sent = Some(Instant::now());
+                Some(message)
+            }
+            None => {
+                // This should never happen, as only valid messages are stored
+                error!("Unable to parse stored message {}", head.message_id);
+                None
+            }
+        }
+    }
+}
+
+/// Queues direct messages on circuits with store-and-forward durability until the recipient
+/// acknowledges them.
+///
+/// The store is cheaply cloneable and all clones share the same queues.
+#[derive(Clone)]
+pub struct DurableMessageStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl DurableMessageStore {
+    /// Creates a store, loading any previously queued messages.
+    ///
+    /// Accepts `"memory"`, to only keep the queues in memory, or the path of the journal file.
+    pub fn new(storage_location: &str) -> Result<Self, DurableStoreError> {
+        let (journal, queues) = if storage_location == "memory" {
+            (None, Queues::new())
+        } else {
+            let (journal, queues) = Journal::open(Path::new(storage_location))?;
+            (Some(journal), queues)
+        };
+
+        Ok(DurableMessageStore {
+            inner: Arc::new(Mutex::new(Inner {
+                journal,
+                max_queue_len: DEFAULT_MAX_QUEUE_LEN,
+                message_ttl: DEFAULT_MESSAGE_TTL,
+                retry_interval: DEFAULT_RETRY_INTERVAL,
+                queues,
+            })),
+        })
+    }
+
+    pub fn with_max_queue_len(self, max_queue_len: usize) -> Self {
+        mutex_lock_unwrap!(self.inner).max_queue_len = max_queue_len;
+        self
+    }
+
+    pub fn with_message_ttl(self, message_ttl: Duration) -> Self {
+        mutex_lock_unwrap!(self.inner).message_ttl = message_ttl;
+        self
+    }
+
+    pub fn with_retry_interval(self, retry_interval: Duration) -> Self {
+        mutex_lock_unwrap!(self.inner).retry_interval = retry_interval;
+        self
+    }
+
+    /// Adds a message to the end of its recipient's queue, assigning it a message id.
+    pub fn enqueue(&self, message: &mut CircuitDirectMessage) -> Result<(), DurableStoreError> {
+        let mut inner = mutex_lock_unwrap!(self.inner);
+        let now = now_secs();
+        let expires_at = now + inner.message_ttl.as_secs();
+        let max_queue_len = inner.max_queue_len;
+
+        let key = (
+            message.get_circuit().to_string(),
+            message.get_recipient().to_string(),
+        );
+        let queue = inner.queues.entry(key.clone()).or_default();
+        let dropped = Inner::drop_expired(queue, now);
+        let mut entries = dropped
+            .into_iter()
+            .map(|message_id| JournalEntry::Remove {
+                circuit: key.0.clone(),
+                recipient: key.1.clone(),
+                message_id,
+            })
+            .collect::<Vec<_>>();
+        if queue.messages.len() >= max_queue_len {
+            let err = DurableStoreError::QueueFull(format!(
+                "{} messages are queued for {}",
+                queue.messages.len(),
+                message.get_recipient()
+            ));
+            inner.persist(&entries)?;
+            return Err(err);
+        }
+
+        message.set_message_id(Uuid::new_v4().to_string());
+        let bytes = message
+            .write_to_bytes()
+            .map_err(|err| DurableStoreError::StorageError(err.to_string()))?;
+        let stored_message = StoredMessage {
+            message_id: message.get_message_id().to_string(),
+            expires_at,
+            message: to_hex(&bytes),
+        };
+        queue.messages.push_back(stored_message.clone());
+        entries.push(JournalEntry::Enqueue {
+            circuit: key.0.clone(),
+            recipient: key.1.clone(),
+            message: stored_message,
+        });
+
+        // A message that could not be persisted is not queued
+        inner.persist(&entries).map_err(|err| {
+            if let Some(queue) = inner.queues.get_mut(&key) {
+                queue.messages.pop_back();
+            }
+            err
+        })
+    }
+
+    /// Returns the head of the recipient's queue if it is due to be sent, and marks it as sent.
+    pub fn next_message(&self, circuit: &str, recipient: &str) -> Option<CircuitDirectMessage> {
+        let mut inner = mutex_lock_unwrap!(self.inner);
+        let retry_interval = inner.retry_interval;
+        let queue = inner
+            .queues
+            .get_mut(&(circuit.to_string(), recipient.to_string()))?;
+        Inner::take_due(queue, retry_interval)
+    }
+
+    /// Removes the acknowledged message from the head of the recipient's queue.
+    ///
+    /// Returns false if the message is not at the head of the queue, such as a duplicate
+    /// acknowledgement for a message that was resent.
+    pub fn acknowledge(
+        &self,
+        circuit: &str,
+        recipient: &str,
+        message_id: &str,
+    ) -> Result<bool, DurableStoreError> {
+        let mut inner = mutex_lock_unwrap!(self.inner);
+        let queue = match inner
+            .queues
+            .get_mut(&(circuit.to_string(), recipient.to_string()))
+        {
+            Some(queue) => queue,
+            None => return Ok(false),
+        };
+
+        match queue.messages.front() {
+            Some(head) if head.message_id == message_id => (),
+            _ => return Ok(false),
+        }
+
+        queue.messages.pop_front();
+        queue.last_sent = None;
+        inner.persist(&[JournalEntry::Remove {
+            circuit: circuit.into(),
+            recipient: recipient.into(),
+            message_id: message_id.into(),
+        }])?;
+        Ok(true)
+    }
+
+    /// Returns the heads of all queues that are due to be sent, marking them as sent. Expired
+    /// messages are dropped.
+    pub fn due_messages(&self) -> Result<Vec<CircuitDirectMessage>, DurableStoreError> {
+        let mut inner = mutex_lock_unwrap!(self.inner);
+        let now = now_secs();
+        let retry_interval = inner.retry_interval;
+
+        let mut entries = vec![];
+        let mut messages = vec![];
+        for ((circuit, recipient), queue) in inner.queues.iter_mut() {
+            for message_id in Inner::drop_expired(queue, now) {
+                entries.push(JournalEntry::Remove {
+                    circuit: circuit.clone(),
+                    recipient: recipient.clone(),
+                    message_id,
+                });
+            }
+            if let Some(message) = Inner::take_due(queue, retry_interval) {
+                messages.push(message);
+            }
+        }
+
+        inner.persist(&entries)?;
+
+        Ok(messages)
+    }
+
+    /// The number of messages queued for the given recipient.
+    pub fn queue_len(&self, circuit: &str, recipient: &str) -> usize {
+        mutex_lock_unwrap!(self.inner)
+            .queues
+            .get(&(circuit.to_string(), recipient.to_string()))
+            .map(|queue| queue.messages.len())
+            .unwrap_or(0)
+    }
+}
+
+/// Resends queued messages that are due, such as messages that could not be delivered while the
+/// recipient was disconnected.
+pub struct DurableMessageResender {
+    node_id: String,
+    store: DurableMessageStore,
+    state: Arc<RwLock<SplinterState>>,
+    routing_table: Option<RoutingTable>,
+    sender: Box<dyn Sender<SendRequest>>,
+}
+
+impl DurableMessageResender {
+    pub fn new(
+        node_id: String,
+        store: DurableMessageStore,
+        state: Arc<RwLock<SplinterState>>,
+        sender: Box<dyn Sender<SendRequest>>,
+    ) -> Self {
+        DurableMessageResender {
+            node_id,
+            store,
+            state,
+            routing_table: None,
+            sender,
+        }
+    }
+
+    /// Allows messages on circuits with multi-hop routes to be resent through intermediate
+    /// nodes.
+    pub fn with_routing_table(mut self, routing_table: RoutingTable) -> Self {
+        self.routing_table = Some(routing_table);
+        self
+    }
+
+    /// Sends each queued message that is due. Messages whose recipient is not currently
+    /// connected remain queued, and are tried again after the retry interval.
+    pub fn resend(&self) -> Result<(), DurableStoreError> {
+        let messages = self.store.due_messages()?;
+        let state = rwlock_read_unwrap!(self.state);
+        for message in messages {
+            let send_request = match create_direct_message_request(
+                &state,
+                &self.node_id,
+                self.routing_table.as_ref(),
+                &message,
+            ) {
+                Ok(Some(send_request)) => send_request,
+                Ok(None) => continue,
+                Err(err) => {
+                    error!(
+                        "Unable to create message {}: {}",
+                        message.get_message_id(),
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            if let Err(err) = self.sender.send(send_request) {
+                debug!(
+                    "Unable to resend message {}: {:?}",
+                    message.get_message_id(),
+                    err
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    fn direct_message(recipient: &str, payload: &[u8]) -> CircuitDirectMessage {
+        let mut message = CircuitDirectMessage::new();
+        message.set_circuit("alpha".into());
+        message.set_sender("abc".into());
+        message.set_recipient(recipient.into());
+        message.set_payload(payload.to_vec());
+        message
+    }
+
+    /// Test that messages are delivered in order, one at a time:
+    ///
+    /// 1. Only the head of a queue is returned, and only once until it is acknowledged
+    /// 2. Acknowledging a message that is not the head of the queue has no effect
+    /// 3. Acknowledging the head makes the next message available
+    #[test]
+    fn test_in_order_delivery() {
+        let store = DurableMessageStore::new("memory").unwrap();
+
+        let mut first = direct_message("def", b"first");
+        let mut second = direct_message("def", b"second");
+        store.enqueue(&mut first).unwrap();
+        store.enqueue(&mut second).unwrap();
+        assert!(!first.get_message_id().is_empty());
+        assert_eq!(2, store.queue_len("alpha", "def"));
+
+        let head = store.next_message("alpha", "def").unwrap();
+        assert_eq!(b"first", head.get_payload());
+        assert!(store.next_message("alpha", "def").is_none());
+
+        assert!(!store
+            .acknowledge("alpha", "def", second.get_message_id())
+            .unwrap());
+        assert!(store
+            .acknowledge("alpha", "def", first.get_message_id())
+            .unwrap());
+
+        let head = store.next_message("alpha", "def").unwrap();
+        assert_eq!(b"second", head.get_payload());
+        assert_eq!(1, store.queue_len("alpha", "def"));
+    }
+
+    /// Test that unacknowledged messages are resent after the retry interval, that queues are
+    /// bounded and that expired messages are dropped.
+    #[test]
+    fn test_retry_bounds_and_expiry() {
+        let store = DurableMessageStore::new("memory")
+            .unwrap()
+            .with_max_queue_len(1)
+            .with_retry_interval(Duration::from_secs(0));
+
+        store.enqueue(&mut direct_message("def", b"first")).unwrap();
+        match store.enqueue(&mut direct_message("def", b"second")) {
+            Err(DurableStoreError::QueueFull(_)) => (),
+            res => panic!("Expected queue full error, got {:?}", res),
+        }
+
+        assert_eq!(1, store.due_messages().unwrap().len());
+        assert_eq!(1, store.due_messages().unwrap().len());
+
+        let store = store.with_message_ttl(Duration::from_secs(0));
+        store
+            .enqueue(&mut direct_message("ghi", b"expired"))
+            .unwrap();
+        assert_eq!(1, store.queue_len("alpha", "ghi"));
+        store.due_messages().unwrap();
+        assert_eq!(0, store.queue_len("alpha", "ghi"));
+    }
+
+    /// Test that queued messages are persisted and reloaded.
+    #[test]
+    fn test_persistence() {
+        let temp_dir = TempDir::new("test_durable_persistence").unwrap();
+        let mut temp_dir_path = temp_dir.path().to_path_buf();
+        temp_dir_path.push("messages.yaml");
+        let location = temp_dir_path.to_str().unwrap().to_string();
+
+        let mut message = direct_message("def", b"persisted");
+        {
+            let store = DurableMessageStore::new(&location).unwrap();
+            store.enqueue(&mut message).unwrap();
+        }
+
+        let store = DurableMessageStore::new(&location).unwrap();
+        let head = store.next_message("alpha", "def").unwrap();
+        assert_eq!(message, head);
+    }
+
+    /// Test that acknowledged messages are not reloaded, that an incomplete last entry of the
+    /// journal is ignored and that the journal is compacted when it is opened.
+    #[test]
+    fn test_journal_replay_and_compaction() {
+        let temp_dir = TempDir::new("test_durable_journal").unwrap();
+        let path = temp_dir.path().join("messages.log");
+        let location = path.to_str().unwrap().to_string();
+
+        let mut first = direct_message("def", b"first");
+        let mut second = direct_message("def", b"second");
+        {
+            let store = DurableMessageStore::new(&location).unwrap();
+            store.enqueue(&mut first).unwrap();
+            store.enqueue(&mut second).unwrap();
+            assert!(store
+                .acknowledge("alpha", "def", first.get_message_id())
+                .unwrap());
+        }
+        assert_eq!(3, fs::read_to_string(&path).unwrap().lines().count());
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"op\":\"enq").unwrap();
+
+        let store = DurableMessageStore::new(&location).unwrap();
+        assert_eq!(1, store.queue_len("alpha", "def"));
+        let head = store.next_message("alpha", "def").unwrap();
+        assert_eq!(second, head);
+        assert_eq!(1, fs::read_to_string(&path).unwrap().lines().count());
+    }
+}
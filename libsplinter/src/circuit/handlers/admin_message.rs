@@ -19,6 +19,7 @@ use crate::circuit::handlers::create_message;
 use crate::circuit::SplinterState;
 use crate::network::dispatch::{DispatchError, Handler, MessageContext};
 use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
 use crate::protos::circuit::{
     AdminDirectMessage, CircuitError, CircuitError_Error, CircuitMessageType,
 };
@@ -41,7 +42,7 @@ impl Handler<CircuitMessageType, AdminDirectMessage> for AdminDirectMessageHandl
         sender: &dyn Sender<SendRequest>,
     ) -> Result<(), DispatchError> {
         debug!(
-            "Handle Admin Direct Message {} on {} ({} => {}) [{} byte{}]",
+            "Handle Admin Direct Message {} on {} ({} => {}) [{} byte{}]{}",
             msg.get_correlation_id(),
             msg.get_circuit(),
             msg.get_sender(),
@@ -51,7 +52,8 @@ impl Handler<CircuitMessageType, AdminDirectMessage> for AdminDirectMessageHandl
                 ""
             } else {
                 "s"
-            }
+            },
+            TraceField(context.trace_id())
         );
 
         // msg bytes will either be message bytes of a direct message or an error message
@@ -89,7 +91,11 @@ impl AdminDirectMessageHandler {
                 ),
             )?;
             return Ok((
-                create_message(err_msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?,
+                create_message(
+                    err_msg_bytes,
+                    CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                    context.trace_id(),
+                )?,
                 context.source_peer_id().into(),
             ));
         }
@@ -104,7 +110,11 @@ impl AdminDirectMessageHandler {
                 ),
             )?;
             return Ok((
-                create_message(err_msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?,
+                create_message(
+                    err_msg_bytes,
+                    CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                    context.trace_id(),
+                )?,
                 context.source_peer_id().into(),
             ));
         }
@@ -127,8 +137,11 @@ impl AdminDirectMessageHandler {
             };
 
             let msg_bytes = context.message_bytes().to_vec();
-            let network_msg_bytes =
-                create_message(msg_bytes, CircuitMessageType::ADMIN_DIRECT_MESSAGE)?;
+            let network_msg_bytes = create_message(
+                msg_bytes,
+                CircuitMessageType::ADMIN_DIRECT_MESSAGE,
+                context.trace_id(),
+            )?;
             (network_msg_bytes, target_node.to_string())
         } else {
             // if the circuit does not exist, send circuit error
@@ -138,8 +151,11 @@ impl AdminDirectMessageHandler {
                 format!("Circuit does not exist: {}", circuit_name),
             )?;
 
-            let network_msg_bytes =
-                create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
+            let network_msg_bytes = create_message(
+                msg_bytes,
+                CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                context.trace_id(),
+            )?;
             (network_msg_bytes, context.source_peer_id().to_string())
         };
         Ok(response)
@@ -13,10 +13,12 @@
 // limitations under the License.
 
 use crate::channel::Sender;
-use crate::circuit::handlers::create_message;
-use crate::circuit::{ServiceId, SplinterState};
+use crate::circuit::handlers::create_send_request;
+use crate::circuit::{RouteType, ServiceId, SplinterState};
 use crate::network::dispatch::{DispatchError, Handler, MessageContext};
+use crate::network::routing::RoutingTable;
 use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
 use crate::protos::circuit::{CircuitError, CircuitMessageType};
 use crate::rwlock_read_unwrap;
 
@@ -26,6 +28,7 @@ use std::sync::{Arc, RwLock};
 pub struct CircuitErrorHandler {
     node_id: String,
     state: Arc<RwLock<SplinterState>>,
+    routing_table: Option<RoutingTable>,
 }
 
 // In most cases the error message will be returned directly back to service, but in the case
@@ -38,7 +41,11 @@ impl Handler<CircuitMessageType, CircuitError> for CircuitErrorHandler {
         context: &MessageContext<CircuitMessageType>,
         sender: &dyn Sender<SendRequest>,
     ) -> Result<(), DispatchError> {
-        debug!("Handle Circuit Error Message {:?}", msg);
+        debug!(
+            "Handle Circuit Error Message {:?}{}",
+            msg,
+            TraceField(context.trace_id())
+        );
         let circuit_name = msg.get_circuit_name();
         let service_id = msg.get_service_id();
         let unique_id = ServiceId::new(circuit_name.to_string(), service_id.to_string());
@@ -77,13 +84,19 @@ impl Handler<CircuitMessageType, CircuitError> for CircuitErrorHandler {
             }
         };
 
-        let network_msg_bytes = create_message(
+        let multi_hop = state
+            .circuit(circuit_name)
+            .map(|circuit| circuit.routes() == &RouteType::MultiHop)
+            .unwrap_or(false);
+
+        // forward error message
+        let send_request = create_send_request(
+            self.routing_table.as_ref().filter(|_| multi_hop),
+            recipient,
             context.message_bytes().to_vec(),
             CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+            context.trace_id(),
         )?;
-
-        // forward error message
-        let send_request = SendRequest::new(recipient.to_string(), network_msg_bytes);
         sender.send(send_request)?;
         Ok(())
     }
@@ -91,7 +104,18 @@ impl Handler<CircuitMessageType, CircuitError> for CircuitErrorHandler {
 
 impl CircuitErrorHandler {
     pub fn new(node_id: String, state: Arc<RwLock<SplinterState>>) -> Self {
-        CircuitErrorHandler { node_id, state }
+        CircuitErrorHandler {
+            node_id,
+            state,
+            routing_table: None,
+        }
+    }
+
+    /// Allows errors on circuits with multi-hop routes to be routed through intermediate nodes
+    /// using the given routing table.
+    pub fn with_routing_table(mut self, routing_table: RoutingTable) -> Self {
+        self.routing_table = Some(routing_table);
+        self
     }
 }
 
@@ -14,6 +14,7 @@
 use crate::channel::Sender;
 use crate::network::dispatch::{DispatchError, DispatchMessage, Handler, MessageContext};
 use crate::network::sender::SendRequest;
+use crate::network::trace::{parse_trace_id, TraceField};
 use crate::protos::circuit::{CircuitMessage, CircuitMessageType};
 use crate::protos::network::NetworkMessageType;
 
@@ -29,8 +30,12 @@ impl Handler<NetworkMessageType, CircuitMessage> for CircuitMessageHandler {
         context: &MessageContext<NetworkMessageType>,
         _: &dyn Sender<SendRequest>,
     ) -> Result<(), DispatchError> {
+        // The circuit message keeps the trace id of its network message, unless it has its own
+        let trace_id = parse_trace_id(msg.get_trace_id())
+            .or_else(|| context.trace_id())
+            .map(String::from);
         debug!(
-            "Handle CircuitMessage {:?} from {} [{} byte{}]",
+            "Handle CircuitMessage {:?} from {} [{} byte{}]{}",
             msg.get_message_type(),
             context.source_peer_id(),
             msg.get_payload().len(),
@@ -38,13 +43,15 @@ impl Handler<NetworkMessageType, CircuitMessage> for CircuitMessageHandler {
                 ""
             } else {
                 "s"
-            }
+            },
+            TraceField(trace_id.as_deref())
         );
         let dispatch_msg = DispatchMessage::new(
             msg.get_message_type(),
             msg.get_payload().to_vec(),
             context.source_peer_id().to_string(),
-        );
+        )
+        .with_trace_id(trace_id);
         self.sender.send(dispatch_msg)?;
         Ok(())
     }
@@ -99,5 +106,52 @@ mod tests {
             message.message_type(),
             &CircuitMessageType::SERVICE_CONNECT_REQUEST
         );
+        assert_eq!(None, message.trace_id());
+    }
+
+    #[test]
+    // Test that the trace id of the circuit message, or else of the network message, is passed on
+    // to the circuit dispatch sender
+    fn test_circuit_message_handler_trace_id() {
+        let network_sender = Box::new(MockSender::default());
+        let circuit_sender = Box::new(MockSender::default());
+        let mut network_dispatcher = Dispatcher::new(network_sender.box_clone());
+
+        let handler = CircuitMessageHandler::new(circuit_sender.box_clone());
+        network_dispatcher.set_handler(NetworkMessageType::CIRCUIT, Box::new(handler));
+
+        let mut circuit_msg = CircuitMessage::new();
+        circuit_msg.set_message_type(CircuitMessageType::CIRCUIT_DIRECT_MESSAGE);
+        circuit_msg.set_payload(b"test".to_vec());
+        let untraced_bytes = circuit_msg.write_to_bytes().unwrap();
+        circuit_msg.set_trace_id("circuit-trace".into());
+        let traced_bytes = circuit_msg.write_to_bytes().unwrap();
+
+        network_dispatcher
+            .dispatch_with_trace_id(
+                "PEER",
+                &NetworkMessageType::CIRCUIT,
+                untraced_bytes,
+                Some("network-trace".into()),
+            )
+            .unwrap();
+        network_dispatcher
+            .dispatch_with_trace_id(
+                "PEER",
+                &NetworkMessageType::CIRCUIT,
+                traced_bytes,
+                Some("network-trace".into()),
+            )
+            .unwrap();
+
+        let dispatched_messages = circuit_sender.sent();
+        assert_eq!(
+            Some("network-trace"),
+            dispatched_messages.get(0).unwrap().trace_id()
+        );
+        assert_eq!(
+            Some("circuit-trace"),
+            dispatched_messages.get(1).unwrap().trace_id()
+        );
     }
 }
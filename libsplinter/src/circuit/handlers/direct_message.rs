@@ -13,10 +13,13 @@
 // limitations under the License.
 
 use crate::channel::Sender;
-use crate::circuit::handlers::create_message;
-use crate::circuit::{ServiceId, SplinterState};
+use crate::circuit::durable::{DurableMessageStore, DurableStoreError};
+use crate::circuit::handlers::{create_direct_message_request, create_send_request};
+use crate::circuit::{DurabilityType, RouteType, ServiceId, SplinterState};
 use crate::network::dispatch::{DispatchError, Handler, MessageContext};
+use crate::network::routing::RoutingTable;
 use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
 use crate::protos::circuit::{
     CircuitDirectMessage, CircuitError, CircuitError_Error, CircuitMessageType,
 };
@@ -30,17 +33,19 @@ use protobuf::Message;
 pub struct CircuitDirectMessageHandler {
     node_id: String,
     state: Arc<RwLock<SplinterState>>,
+    routing_table: Option<RoutingTable>,
+    durable_store: Option<DurableMessageStore>,
 }
 
 impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageHandler {
     fn handle(
         &self,
-        msg: CircuitDirectMessage,
+        mut msg: CircuitDirectMessage,
         context: &MessageContext<CircuitMessageType>,
         sender: &dyn Sender<SendRequest>,
     ) -> Result<(), DispatchError> {
         debug!(
-            "Handle Circuit Direct Message {} on {} ({} => {}) [{} byte{}]",
+            "Handle Circuit Direct Message {} on {} ({} => {}) [{} byte{}]{}",
             msg.get_correlation_id(),
             msg.get_circuit(),
             msg.get_sender(),
@@ -50,7 +55,8 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                 ""
             } else {
                 "s"
-            }
+            },
+            TraceField(context.trace_id())
         );
 
         let circuit_name = msg.get_circuit();
@@ -62,10 +68,78 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
         // Get read lock on state
         let state = rwlock_read_unwrap!(self.state);
 
+        // Messages may only be routed through intermediate nodes if the circuit allows it
+        let multi_hop = state
+            .circuit(circuit_name)
+            .map(|circuit| circuit.routes() == &RouteType::MultiHop)
+            .unwrap_or(false);
+
+        // Messages sent by a local service on a circuit with store-and-forward durability are
+        // queued until they are acknowledged; messages that already have a message id have been
+        // queued by the sending node and are delivered as is.
+        if let Some(store) = self.durable_store.as_ref() {
+            let durable = state
+                .circuit(circuit_name)
+                .map(|circuit| {
+                    circuit.durability() == &DurabilityType::StoreAndForward
+                        && circuit.roster().contains(&msg_sender)
+                        && circuit.roster().contains(&recipient)
+                })
+                .unwrap_or(false)
+                && msg.get_message_id().is_empty()
+                && state
+                    .service_directory()
+                    .get(&sender_id)
+                    .map(|service| service.node().id() == self.node_id)
+                    .unwrap_or(false);
+
+            if durable {
+                let circuit_name = circuit_name.to_string();
+                let recipient = recipient.to_string();
+                match store.enqueue(&mut msg) {
+                    Ok(()) => {
+                        // Only the head of the queue is sent; the rest follow as each message is
+                        // acknowledged
+                        if let Some(next) = store.next_message(&circuit_name, &recipient) {
+                            if let Some(send_request) = create_direct_message_request(
+                                &state,
+                                &self.node_id,
+                                self.routing_table.as_ref(),
+                                &next,
+                            )? {
+                                sender.send(send_request)?;
+                            }
+                        }
+                    }
+                    Err(DurableStoreError::QueueFull(err)) => {
+                        let mut error_message = CircuitError::new();
+                        error_message.set_correlation_id(msg.get_correlation_id().to_string());
+                        error_message.set_service_id(msg.get_sender().into());
+                        error_message.set_circuit_name(circuit_name);
+                        error_message.set_error(CircuitError_Error::ERROR_RECIPIENT_QUEUE_FULL);
+                        error_message.set_error_message(format!(
+                            "Unable to queue message for {}: {}",
+                            recipient, err
+                        ));
+
+                        sender.send(create_send_request(
+                            None,
+                            context.source_peer_id(),
+                            error_message.write_to_bytes()?,
+                            CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                            context.trace_id(),
+                        )?)?;
+                    }
+                    Err(err) => return Err(DispatchError::HandleError(err.to_string())),
+                }
+                return Ok(());
+            }
+        }
+
         // msg bytes will either be message bytes of a direct message or an error message
         // the msg_recipient is either the service/node id to send the message to or is the
         // peer_id to send back the error message
-        let (msg_bytes, msg_recipient) = {
+        let (msg_bytes, msg_type, msg_recipient) = {
             if let Some(circuit) = state.circuit(circuit_name) {
                 // Check if the message sender is allowed on the circuit
                 // if the sender is not allowed on the circuit
@@ -80,10 +154,11 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                         msg_sender
                     ));
 
-                    let msg_bytes = error_message.write_to_bytes()?;
-                    let network_msg_bytes =
-                        create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
-                    (network_msg_bytes, context.source_peer_id())
+                    (
+                        error_message.write_to_bytes()?,
+                        CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                        context.source_peer_id(),
+                    )
                 } else if state.service_directory().get(&sender_id).is_none() {
                     // Check if the message sender is registered on the circuit
                     // if the sender is not connected, send circuit error
@@ -97,10 +172,11 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                         recipient
                     ));
 
-                    let msg_bytes = error_message.write_to_bytes()?;
-                    let network_msg_bytes =
-                        create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
-                    (network_msg_bytes, context.source_peer_id())
+                    (
+                        error_message.write_to_bytes()?,
+                        CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                        context.source_peer_id(),
+                    )
                 } else if circuit.roster().contains(&recipient) {
                     // check if the recipient service is allowed on the circuit and registered
                     if let Some(service) = state.service_directory().get(&recipient_id) {
@@ -108,18 +184,12 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                         // If the service is on this node send message to the service, otherwise
                         // send the message to the node the service is connected to
                         if node_id != self.node_id {
-                            let msg_bytes = context.message_bytes().to_vec();
-                            let network_msg_bytes = create_message(
-                                msg_bytes,
+                            (
+                                context.message_bytes().to_vec(),
                                 CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
-                            )?;
-                            (network_msg_bytes, node_id)
+                                node_id,
+                            )
                         } else {
-                            let msg_bytes = context.message_bytes().to_vec();
-                            let network_msg_bytes = create_message(
-                                msg_bytes,
-                                CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
-                            )?;
                             let peer_id = match service.peer_id() {
                                 Some(peer_id) => peer_id,
                                 None => {
@@ -129,7 +199,11 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                                     return Ok(());
                                 }
                             };
-                            (network_msg_bytes, &peer_id[..])
+                            (
+                                context.message_bytes().to_vec(),
+                                CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+                                &peer_id[..],
+                            )
                         }
                     } else {
                         // This should not happen as every service should be added on circuit
@@ -145,10 +219,11 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                             recipient
                         ));
 
-                        let msg_bytes = error_message.write_to_bytes()?;
-                        let network_msg_bytes =
-                            create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
-                        (network_msg_bytes, context.source_peer_id())
+                        (
+                            error_message.write_to_bytes()?,
+                            CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                            context.source_peer_id(),
+                        )
                     }
                 } else {
                     // if the recipient is not allowed on the circuit, send circuit error
@@ -163,10 +238,11 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                         recipient
                     ));
 
-                    let msg_bytes = error_message.write_to_bytes()?;
-                    let network_msg_bytes =
-                        create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
-                    (network_msg_bytes, context.source_peer_id())
+                    (
+                        error_message.write_to_bytes()?,
+                        CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                        context.source_peer_id(),
+                    )
                 }
             } else {
                 // if the circuit does not exist, send circuit error
@@ -178,15 +254,22 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
                 error_message
                     .set_error_message(format!("Circuit does not exist: {}", circuit_name));
 
-                let msg_bytes = error_message.write_to_bytes()?;
-                let network_msg_bytes =
-                    create_message(msg_bytes, CircuitMessageType::CIRCUIT_ERROR_MESSAGE)?;
-                (network_msg_bytes, context.source_peer_id())
+                (
+                    error_message.write_to_bytes()?,
+                    CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
+                    context.source_peer_id(),
+                )
             }
         };
 
         // either forward the direct message or send back an error message.
-        let send_request = SendRequest::new(msg_recipient.to_string(), msg_bytes);
+        let send_request = create_send_request(
+            self.routing_table.as_ref().filter(|_| multi_hop),
+            msg_recipient,
+            msg_bytes,
+            msg_type,
+            context.trace_id(),
+        )?;
         sender.send(send_request)?;
         Ok(())
     }
@@ -194,7 +277,26 @@ impl Handler<CircuitMessageType, CircuitDirectMessage> for CircuitDirectMessageH
 
 impl CircuitDirectMessageHandler {
     pub fn new(node_id: String, state: Arc<RwLock<SplinterState>>) -> Self {
-        CircuitDirectMessageHandler { node_id, state }
+        CircuitDirectMessageHandler {
+            node_id,
+            state,
+            routing_table: None,
+            durable_store: None,
+        }
+    }
+
+    /// Allows messages on circuits with multi-hop routes to be routed through intermediate nodes
+    /// using the given routing table.
+    pub fn with_routing_table(mut self, routing_table: RoutingTable) -> Self {
+        self.routing_table = Some(routing_table);
+        self
+    }
+
+    /// Queues messages on circuits with store-and-forward durability in the given store until
+    /// they are acknowledged by the recipient.
+    pub fn with_durable_store(mut self, durable_store: DurableMessageStore) -> Self {
+        self.durable_store = Some(durable_store);
+        self
     }
 }
 
@@ -208,7 +310,7 @@ mod tests {
     use crate::circuit::service::{Service, SplinterNode};
     use crate::circuit::{AuthorizationType, Circuit, DurabilityType, PersistenceType, RouteType};
     use crate::network::dispatch::Dispatcher;
-    use crate::protos::circuit::CircuitMessage;
+    use crate::protos::circuit::{CircuitMessage, CircuitRoutedMessage};
     use crate::protos::network::NetworkMessage;
 
     // Test that a direct message will be properly sent to the service if the message is meant for
@@ -390,6 +492,104 @@ mod tests {
         assert_eq!(direct_message.get_correlation_id(), "1234");
     }
 
+    // Test that a direct message on a circuit with multi-hop routes is wrapped in a routed message
+    // and sent to the next hop, if the recipient service's node is not directly connected
+    #[test]
+    fn test_circuit_direct_message_handler_multi_hop() {
+        // Set up disptacher and mock sender
+        let sender = Box::new(MockNetworkSender::default());
+        let mut dispatcher = Dispatcher::new(sender.box_clone());
+
+        // Add circuit and service to splinter state
+        let circuit = Circuit::builder()
+            .with_id("alpha".into())
+            .with_auth(AuthorizationType::Trust)
+            .with_members(vec!["123".into(), "345".into()])
+            .with_roster(vec!["abc".into(), "def".into()])
+            .with_persistence(PersistenceType::Any)
+            .with_durability(DurabilityType::NoDurability)
+            .with_routes(RouteType::MultiHop)
+            .with_circuit_management_type("circuit_direct_test_app".into())
+            .build()
+            .expect("Should have built a correct circuit");
+
+        let mut circuit_directory = CircuitDirectory::new();
+        circuit_directory.add_circuit("alpha".to_string(), circuit);
+
+        let state = Arc::new(RwLock::new(SplinterState::new(
+            "memory".to_string(),
+            circuit_directory,
+        )));
+
+        let node_123 = SplinterNode::new("123".to_string(), vec!["123.0.0.1:0".to_string()]);
+        let node_345 = SplinterNode::new("345".to_string(), vec!["123.0.0.1:0".to_string()]);
+
+        let service_abc =
+            Service::new("abc".to_string(), Some("abc_network".to_string()), node_123);
+        let service_def =
+            Service::new("def".to_string(), Some("def_network".to_string()), node_345);
+        let abc_id = ServiceId::new("alpha".into(), "abc".into());
+        let def_id = ServiceId::new("alpha".into(), "def".into());
+        state.write().unwrap().add_service(abc_id, service_abc);
+        state.write().unwrap().add_service(def_id, service_def);
+
+        // Node 345 is only connected to node 123 through node 678
+        let routing_table = RoutingTable::new("345".to_string());
+        routing_table.set_local_peers(vec!["678".to_string(), "def_network".to_string()]);
+        routing_table.update_node("678", vec!["123".to_string(), "345".to_string()], 1);
+
+        // Add direct message handler to dispatcher
+        let handler = CircuitDirectMessageHandler::new("345".to_string(), state)
+            .with_routing_table(routing_table);
+
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            Box::new(handler),
+        );
+
+        // create dispatch message
+        let mut direct_message = CircuitDirectMessage::new();
+        direct_message.set_circuit("alpha".into());
+        direct_message.set_sender("def".into());
+        direct_message.set_recipient("abc".into());
+        direct_message.set_payload(b"test".to_vec());
+        direct_message.set_correlation_id("1234".into());
+        let direct_bytes = direct_message.write_to_bytes().unwrap();
+
+        // dispatch the message
+        dispatcher
+            .dispatch(
+                "def",
+                &CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+                direct_bytes.clone(),
+            )
+            .unwrap();
+
+        // verify that the routed message was sent to the 678 node
+        let send_request = sender.sent().lock().unwrap().get(0).unwrap().clone();
+
+        assert_eq!(send_request.recipient(), "678");
+
+        let network_msg: NetworkMessage =
+            protobuf::parse_from_bytes(send_request.payload()).unwrap();
+        let circuit_msg: CircuitMessage =
+            protobuf::parse_from_bytes(network_msg.get_payload()).unwrap();
+        assert_eq!(
+            circuit_msg.get_message_type(),
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE
+        );
+
+        let routed_message: CircuitRoutedMessage =
+            protobuf::parse_from_bytes(circuit_msg.get_payload()).unwrap();
+        assert_eq!(routed_message.get_origin_node(), "345");
+        assert_eq!(routed_message.get_destination_node(), "123");
+        assert_eq!(
+            routed_message.get_message_type(),
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE
+        );
+        assert_eq!(routed_message.get_payload().to_vec(), direct_bytes);
+    }
+
     // Test that an error message is returned if the sender is not connected to the circuit
     #[test]
     fn test_circuit_direct_message_handler_sender_not_in_directory() {
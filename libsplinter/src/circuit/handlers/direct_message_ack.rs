@@ -0,0 +1,314 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::channel::Sender;
+use crate::circuit::durable::DurableMessageStore;
+use crate::circuit::handlers::{create_direct_message_request, create_send_request};
+use crate::circuit::{RouteType, ServiceId, SplinterState};
+use crate::network::dispatch::{DispatchError, Handler, MessageContext};
+use crate::network::routing::RoutingTable;
+use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
+use crate::protos::circuit::{CircuitDirectMessageAck, CircuitMessageType};
+use crate::rwlock_read_unwrap;
+
+use std::sync::{Arc, RwLock};
+
+// Implements a handler that handles CircuitDirectMessageAck
+//
+// If the service that sent the acknowledged message is connected to this node, the message is
+// removed from the durable store and the next queued message is sent. Otherwise, the
+// acknowledgement is forwarded to the node the service is connected to.
+pub struct CircuitDirectMessageAckHandler {
+    node_id: String,
+    state: Arc<RwLock<SplinterState>>,
+    durable_store: Option<DurableMessageStore>,
+    routing_table: Option<RoutingTable>,
+}
+
+impl Handler<CircuitMessageType, CircuitDirectMessageAck> for CircuitDirectMessageAckHandler {
+    fn handle(
+        &self,
+        msg: CircuitDirectMessageAck,
+        context: &MessageContext<CircuitMessageType>,
+        sender: &dyn Sender<SendRequest>,
+    ) -> Result<(), DispatchError> {
+        debug!(
+            "Handle Circuit Direct Message Ack {} on {} ({} => {}){}",
+            msg.get_message_id(),
+            msg.get_circuit(),
+            msg.get_sender(),
+            msg.get_recipient(),
+            TraceField(context.trace_id()),
+        );
+
+        let circuit_name = msg.get_circuit();
+
+        // Get read lock on state
+        let state = rwlock_read_unwrap!(self.state);
+
+        let circuit = match state.circuit(circuit_name) {
+            Some(circuit) => circuit,
+            None => {
+                warn!(
+                    "Dropping acknowledgement for message on unknown circuit: {}",
+                    circuit_name
+                );
+                return Ok(());
+            }
+        };
+
+        if !circuit.roster().contains(msg.get_sender())
+            || !circuit.roster().contains(msg.get_recipient())
+        {
+            warn!(
+                "Dropping acknowledgement from {}: services are not allowed in the circuit {}",
+                context.source_peer_id(),
+                circuit_name
+            );
+            return Ok(());
+        }
+
+        let unique_id = ServiceId::new(circuit_name.to_string(), msg.get_recipient().to_string());
+        let node_id = match state.service_directory().get(&unique_id) {
+            Some(service) => service.node().id(),
+            None => {
+                warn!(
+                    "Original message sender is not in the service directory: {}",
+                    msg.get_recipient()
+                );
+                return Ok(());
+            }
+        };
+
+        if node_id != self.node_id {
+            // The message was queued by the node the original sender is connected to
+            let multi_hop = circuit.routes() == &RouteType::MultiHop;
+            let send_request = create_send_request(
+                self.routing_table.as_ref().filter(|_| multi_hop),
+                node_id,
+                context.message_bytes().to_vec(),
+                CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK,
+                context.trace_id(),
+            )?;
+            sender.send(send_request)?;
+            return Ok(());
+        }
+
+        let store = match self.durable_store.as_ref() {
+            Some(store) => store,
+            None => {
+                debug!(
+                    "Ignoring acknowledgement for message {}: no durable store is configured",
+                    msg.get_message_id()
+                );
+                return Ok(());
+            }
+        };
+
+        // The queue is keyed by the recipient of the original message, which sent the ack
+        let acknowledged = store
+            .acknowledge(circuit_name, msg.get_sender(), msg.get_message_id())
+            .map_err(|err| DispatchError::HandleError(err.to_string()))?;
+        if !acknowledged {
+            debug!(
+                "Ignoring acknowledgement for message {}: not awaiting acknowledgement",
+                msg.get_message_id()
+            );
+            return Ok(());
+        }
+
+        if let Some(next) = store.next_message(circuit_name, msg.get_sender()) {
+            if let Some(send_request) = create_direct_message_request(
+                &state,
+                &self.node_id,
+                self.routing_table.as_ref(),
+                &next,
+            )? {
+                sender.send(send_request)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CircuitDirectMessageAckHandler {
+    pub fn new(node_id: String, state: Arc<RwLock<SplinterState>>) -> Self {
+        CircuitDirectMessageAckHandler {
+            node_id,
+            state,
+            durable_store: None,
+            routing_table: None,
+        }
+    }
+
+    /// Removes acknowledged messages from the given store.
+    pub fn with_durable_store(mut self, durable_store: DurableMessageStore) -> Self {
+        self.durable_store = Some(durable_store);
+        self
+    }
+
+    /// Allows acknowledgements on circuits with multi-hop routes to be routed through
+    /// intermediate nodes using the given routing table.
+    pub fn with_routing_table(mut self, routing_table: RoutingTable) -> Self {
+        self.routing_table = Some(routing_table);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::Message;
+
+    use super::*;
+    use crate::channel::mock::MockSender;
+    use crate::circuit::directory::CircuitDirectory;
+    use crate::circuit::handlers::CircuitDirectMessageHandler;
+    use crate::circuit::service::{Service, SplinterNode};
+    use crate::circuit::{AuthorizationType, Circuit, DurabilityType, PersistenceType};
+    use crate::network::dispatch::Dispatcher;
+    use crate::protos::circuit::{CircuitDirectMessage, CircuitMessage};
+    use crate::protos::network::NetworkMessage;
+
+    fn direct_message(payload: &[u8]) -> Vec<u8> {
+        let mut direct_message = CircuitDirectMessage::new();
+        direct_message.set_circuit("alpha".into());
+        direct_message.set_sender("def".into());
+        direct_message.set_recipient("abc".into());
+        direct_message.set_payload(payload.to_vec());
+        direct_message.write_to_bytes().unwrap()
+    }
+
+    fn sent_direct_message(send_request: &SendRequest) -> CircuitDirectMessage {
+        let network_msg: NetworkMessage =
+            protobuf::parse_from_bytes(send_request.payload()).unwrap();
+        let circuit_msg: CircuitMessage =
+            protobuf::parse_from_bytes(network_msg.get_payload()).unwrap();
+        assert_eq!(
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            circuit_msg.get_message_type()
+        );
+        protobuf::parse_from_bytes(circuit_msg.get_payload()).unwrap()
+    }
+
+    // Test that direct messages on a circuit with store-and-forward durability are delivered in
+    // order, with the next message only sent once the previous one is acknowledged
+    #[test]
+    fn test_store_and_forward_delivery() {
+        let sender = Box::new(MockSender::default());
+        let mut dispatcher = Dispatcher::new(sender.box_clone());
+
+        let circuit = Circuit::builder()
+            .with_id("alpha".into())
+            .with_auth(AuthorizationType::Trust)
+            .with_members(vec!["123".into(), "345".into()])
+            .with_roster(vec!["abc".into(), "def".into()])
+            .with_persistence(PersistenceType::Any)
+            .with_durability(DurabilityType::StoreAndForward)
+            .with_routes(RouteType::Any)
+            .with_circuit_management_type("durable_test_app".into())
+            .build()
+            .expect("Should have built a correct circuit");
+
+        let mut circuit_directory = CircuitDirectory::new();
+        circuit_directory.add_circuit("alpha".to_string(), circuit);
+
+        let state = Arc::new(RwLock::new(SplinterState::new(
+            "memory".to_string(),
+            circuit_directory,
+        )));
+
+        let node_123 = SplinterNode::new("123".to_string(), vec!["123.0.0.1:0".to_string()]);
+        let node_345 = SplinterNode::new("345".to_string(), vec!["123.0.0.1:1".to_string()]);
+        let service_abc =
+            Service::new("abc".to_string(), Some("abc_network".to_string()), node_123);
+        let service_def =
+            Service::new("def".to_string(), Some("def_network".to_string()), node_345);
+        state
+            .write()
+            .unwrap()
+            .add_service(ServiceId::new("alpha".into(), "abc".into()), service_abc);
+        state
+            .write()
+            .unwrap()
+            .add_service(ServiceId::new("alpha".into(), "def".into()), service_def);
+
+        let store = DurableMessageStore::new("memory").unwrap();
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            Box::new(
+                CircuitDirectMessageHandler::new("345".to_string(), state.clone())
+                    .with_durable_store(store.clone()),
+            ),
+        );
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK,
+            Box::new(
+                CircuitDirectMessageAckHandler::new("345".to_string(), state)
+                    .with_durable_store(store.clone()),
+            ),
+        );
+
+        for payload in &[b"first", b"other"] {
+            dispatcher
+                .dispatch(
+                    "def_network",
+                    &CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+                    direct_message(*payload),
+                )
+                .unwrap();
+        }
+
+        // only the first message is sent, with a message id assigned
+        let sent = sender.sent();
+        assert_eq!(1, sent.len());
+        assert_eq!("123", sent[0].recipient());
+        let first = sent_direct_message(&sent[0]);
+        assert_eq!(b"first", first.get_payload());
+        assert!(!first.get_message_id().is_empty());
+        assert_eq!(2, store.queue_len("alpha", "abc"));
+
+        let mut ack = CircuitDirectMessageAck::new();
+        ack.set_circuit("alpha".into());
+        ack.set_sender("abc".into());
+        ack.set_recipient("def".into());
+        ack.set_message_id(first.get_message_id().into());
+        dispatcher
+            .dispatch(
+                "123",
+                &CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK,
+                ack.write_to_bytes().unwrap(),
+            )
+            .unwrap();
+
+        // the acknowledged message is removed and the next one is sent
+        let sent = sender.sent();
+        assert_eq!(2, sent.len());
+        let second = sent_direct_message(&sent[1]);
+        assert_eq!(b"other", second.get_payload());
+        assert_eq!(1, store.queue_len("alpha", "abc"));
+
+        // a duplicate acknowledgement is ignored
+        dispatcher
+            .dispatch(
+                "123",
+                &CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK,
+                ack.write_to_bytes().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(2, sender.sent().len());
+        assert_eq!(1, store.queue_len("alpha", "abc"));
+    }
+}
@@ -16,31 +16,132 @@ mod admin_message;
 mod circuit_error;
 mod circuit_message;
 mod direct_message;
+mod direct_message_ack;
+mod routed_message;
 mod service_handlers;
 
 use protobuf::Message;
 
-use crate::protos::circuit::{CircuitMessage, CircuitMessageType};
+use crate::circuit::{RouteType, ServiceId, SplinterState};
+use crate::network::routing::{RoutingTable, DEFAULT_TIME_TO_LIVE};
+use crate::network::sender::SendRequest;
+use crate::network::trace::parse_trace_id;
+use crate::protos::circuit::{
+    CircuitDirectMessage, CircuitMessage, CircuitMessageType, CircuitRoutedMessage,
+};
 use crate::protos::network::{NetworkMessage, NetworkMessageType};
 
 pub use self::admin_message::AdminDirectMessageHandler;
 pub use self::circuit_error::CircuitErrorHandler;
 pub use self::circuit_message::CircuitMessageHandler;
 pub use self::direct_message::CircuitDirectMessageHandler;
+pub use self::direct_message_ack::CircuitDirectMessageAckHandler;
+pub use self::routed_message::CircuitRoutedMessageHandler;
 pub use self::service_handlers::ServiceConnectRequestHandler;
 pub use self::service_handlers::ServiceDisconnectRequestHandler;
 
+/// Creates a network message containing a circuit message with the given payload. The trace id,
+/// if any, is set on both envelopes.
 fn create_message(
     payload: Vec<u8>,
     circuit_message_type: CircuitMessageType,
+    trace_id: Option<&str>,
 ) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
     let mut circuit_msg = CircuitMessage::new();
     circuit_msg.set_message_type(circuit_message_type);
     circuit_msg.set_payload(payload);
+    if let Some(trace_id) = trace_id {
+        circuit_msg.set_trace_id(trace_id.to_string());
+    }
     let circuit_bytes = circuit_msg.write_to_bytes()?;
 
     let mut network_msg = NetworkMessage::new();
     network_msg.set_message_type(NetworkMessageType::CIRCUIT);
     network_msg.set_payload(circuit_bytes);
+    if let Some(trace_id) = trace_id {
+        network_msg.set_trace_id(trace_id.to_string());
+    }
     network_msg.write_to_bytes()
 }
+
+/// Creates the request for sending a circuit message to the given recipient.
+///
+/// If a routing table is provided and the recipient is a node that is not directly connected,
+/// the message is wrapped in a routed message and sent to the next hop towards the recipient.
+/// Otherwise, the message is sent to the recipient directly.
+fn create_send_request(
+    routing_table: Option<&RoutingTable>,
+    recipient: &str,
+    payload: Vec<u8>,
+    circuit_message_type: CircuitMessageType,
+    trace_id: Option<&str>,
+) -> Result<SendRequest, protobuf::error::ProtobufError> {
+    if let Some(routing_table) = routing_table {
+        if !routing_table.is_peer(recipient) {
+            if let Some(next_hop) = routing_table.next_hop(recipient) {
+                let mut routed_msg = CircuitRoutedMessage::new();
+                routed_msg.set_origin_node(routing_table.node_id().to_string());
+                routed_msg.set_destination_node(recipient.to_string());
+                routed_msg.set_time_to_live(DEFAULT_TIME_TO_LIVE);
+                routed_msg.set_message_type(circuit_message_type);
+                routed_msg.set_payload(payload);
+
+                let network_msg_bytes = create_message(
+                    routed_msg.write_to_bytes()?,
+                    CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                    trace_id,
+                )?;
+                return Ok(SendRequest::new(next_hop, network_msg_bytes));
+            }
+        }
+    }
+
+    Ok(SendRequest::new(
+        recipient.to_string(),
+        create_message(payload, circuit_message_type, trace_id)?,
+    ))
+}
+
+/// Creates the request for delivering a direct message to its recipient service, either to the
+/// node the service is connected to or to the service itself if it is connected to this node.
+///
+/// Returns `None` if the recipient is not currently reachable, such as a local service that has
+/// disconnected.
+pub(crate) fn create_direct_message_request(
+    state: &SplinterState,
+    node_id: &str,
+    routing_table: Option<&RoutingTable>,
+    msg: &CircuitDirectMessage,
+) -> Result<Option<SendRequest>, protobuf::error::ProtobufError> {
+    let circuit = match state.circuit(msg.get_circuit()) {
+        Some(circuit) => circuit,
+        None => return Ok(None),
+    };
+    let recipient_id = ServiceId::new(
+        msg.get_circuit().to_string(),
+        msg.get_recipient().to_string(),
+    );
+    let service = match state.service_directory().get(&recipient_id) {
+        Some(service) => service,
+        None => return Ok(None),
+    };
+
+    let recipient = if service.node().id() != node_id {
+        service.node().id()
+    } else {
+        match service.peer_id() {
+            Some(peer_id) => peer_id.as_str(),
+            None => return Ok(None),
+        }
+    };
+
+    let routing_table = routing_table.filter(|_| circuit.routes() == &RouteType::MultiHop);
+    create_send_request(
+        routing_table,
+        recipient,
+        msg.write_to_bytes()?,
+        CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+        parse_trace_id(msg.get_trace_id()),
+    )
+    .map(Some)
+}
@@ -0,0 +1,384 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::channel::Sender;
+use crate::circuit::handlers::create_message;
+use crate::circuit::{RouteType, SplinterState};
+use crate::network::dispatch::{DispatchError, DispatchMessage, Handler, MessageContext};
+use crate::network::routing::RoutingTable;
+use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
+use crate::protos::circuit::{
+    CircuitDirectMessage, CircuitDirectMessageAck, CircuitError, CircuitMessageType,
+    CircuitRoutedMessage,
+};
+use crate::rwlock_read_unwrap;
+
+use std::sync::{Arc, RwLock};
+
+use protobuf::Message;
+
+// Implements a handler that handles CircuitRoutedMessage
+//
+// If this node is the message's destination, the wrapped message is passed back to the circuit
+// dispatcher as though it was received from the node that originally sent it. Otherwise, the
+// message is forwarded to the next hop towards its destination.
+//
+// A routed message is only accepted from the peer on a shortest path back to its origin, and is
+// only delivered for a multi-hop circuit that its origin is a member of.
+pub struct CircuitRoutedMessageHandler {
+    routing_table: RoutingTable,
+    state: Arc<RwLock<SplinterState>>,
+    dispatch_sender: Box<dyn Sender<DispatchMessage<CircuitMessageType>>>,
+}
+
+impl Handler<CircuitMessageType, CircuitRoutedMessage> for CircuitRoutedMessageHandler {
+    fn handle(
+        &self,
+        mut msg: CircuitRoutedMessage,
+        context: &MessageContext<CircuitMessageType>,
+        sender: &dyn Sender<SendRequest>,
+    ) -> Result<(), DispatchError> {
+        debug!(
+            "Handle Circuit Routed Message {:?} from {} ({} => {}){}",
+            msg.get_message_type(),
+            context.source_peer_id(),
+            msg.get_origin_node(),
+            msg.get_destination_node(),
+            TraceField(context.trace_id()),
+        );
+
+        // The origin node is not an authorized peer of this node, so the claimed origin is
+        // checked against the route the message took
+        if !self
+            .routing_table
+            .is_return_hop(msg.get_origin_node(), context.source_peer_id())
+        {
+            warn!(
+                "Dropping routed message from {}: {} is not on a route from that node",
+                msg.get_origin_node(),
+                context.source_peer_id()
+            );
+            return Ok(());
+        }
+
+        if msg.get_destination_node() == self.routing_table.node_id() {
+            // Only messages that are checked against the circuit's state on delivery may be
+            // routed
+            let circuit_name = match routed_circuit_name(&msg)? {
+                Some(circuit_name) => circuit_name,
+                None => {
+                    warn!(
+                        "Dropping routed message from {} with unsupported type {:?}",
+                        msg.get_origin_node(),
+                        msg.get_message_type()
+                    );
+                    return Ok(());
+                }
+            };
+
+            let deliverable = rwlock_read_unwrap!(self.state)
+                .circuit(&circuit_name)
+                .map(|circuit| {
+                    circuit.routes() == &RouteType::MultiHop
+                        && circuit.members().contains(msg.get_origin_node())
+                })
+                .unwrap_or(false);
+            if !deliverable {
+                warn!(
+                    "Dropping routed message from {}: {} is not a multi-hop circuit of that node",
+                    msg.get_origin_node(),
+                    circuit_name
+                );
+                return Ok(());
+            }
+
+            let dispatch_msg = DispatchMessage::new(
+                msg.get_message_type(),
+                msg.take_payload(),
+                msg.get_origin_node().to_string(),
+            )
+            .with_trace_id(context.trace_id().map(String::from));
+            self.dispatch_sender.send(dispatch_msg)?;
+            return Ok(());
+        }
+
+        if msg.get_origin_node() == self.routing_table.node_id() {
+            warn!(
+                "Dropping routed message for {} that looped back to its origin",
+                msg.get_destination_node()
+            );
+            return Ok(());
+        }
+
+        msg.set_time_to_live(msg.get_time_to_live() - 1);
+        if msg.get_time_to_live() <= 0 {
+            warn!(
+                "Dropping routed message for {}: time to live expired",
+                msg.get_destination_node()
+            );
+            return Ok(());
+        }
+
+        let next_hop = match self.routing_table.next_hop(msg.get_destination_node()) {
+            Some(next_hop) => next_hop,
+            None => {
+                warn!(
+                    "Dropping routed message for {}: no route to node",
+                    msg.get_destination_node()
+                );
+                return Ok(());
+            }
+        };
+
+        let network_msg_bytes = create_message(
+            msg.write_to_bytes()?,
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+            context.trace_id(),
+        )?;
+        sender.send(SendRequest::new(next_hop, network_msg_bytes))?;
+        Ok(())
+    }
+}
+
+impl CircuitRoutedMessageHandler {
+    pub fn new(
+        routing_table: RoutingTable,
+        state: Arc<RwLock<SplinterState>>,
+        dispatch_sender: Box<dyn Sender<DispatchMessage<CircuitMessageType>>>,
+    ) -> Self {
+        CircuitRoutedMessageHandler {
+            routing_table,
+            state,
+            dispatch_sender,
+        }
+    }
+}
+
+/// Returns the name of the circuit the wrapped message was sent on, or `None` if messages of its
+/// type may not be routed.
+fn routed_circuit_name(msg: &CircuitRoutedMessage) -> Result<Option<String>, DispatchError> {
+    let circuit_name = match msg.get_message_type() {
+        CircuitMessageType::CIRCUIT_DIRECT_MESSAGE => {
+            protobuf::parse_from_bytes::<CircuitDirectMessage>(msg.get_payload())?.take_circuit()
+        }
+        CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK => {
+            protobuf::parse_from_bytes::<CircuitDirectMessageAck>(msg.get_payload())?.take_circuit()
+        }
+        CircuitMessageType::CIRCUIT_ERROR_MESSAGE => {
+            protobuf::parse_from_bytes::<CircuitError>(msg.get_payload())?.take_circuit_name()
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(circuit_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::channel::mock::MockSender;
+    use crate::circuit::directory::CircuitDirectory;
+    use crate::circuit::{AuthorizationType, Circuit, DurabilityType, PersistenceType};
+    use crate::network::dispatch::Dispatcher;
+    use crate::protos::circuit::CircuitMessage;
+    use crate::protos::network::NetworkMessage;
+
+    fn routed_message(destination: &str, time_to_live: i32, circuit: &str) -> Vec<u8> {
+        let mut direct_msg = CircuitDirectMessage::new();
+        direct_msg.set_circuit(circuit.into());
+        direct_msg.set_payload(b"payload".to_vec());
+
+        let mut msg = CircuitRoutedMessage::new();
+        msg.set_origin_node("origin".into());
+        msg.set_destination_node(destination.into());
+        msg.set_time_to_live(time_to_live);
+        msg.set_message_type(CircuitMessageType::CIRCUIT_DIRECT_MESSAGE);
+        msg.set_payload(direct_msg.write_to_bytes().unwrap());
+        msg.write_to_bytes().unwrap()
+    }
+
+    /// Creates a state with the multi-hop circuit "alpha" and the circuit "beta", which does not
+    /// allow routing, both between the origin and destination nodes.
+    fn setup_state() -> Arc<RwLock<SplinterState>> {
+        let mut circuit_directory = CircuitDirectory::new();
+        for (name, routes) in &[("alpha", RouteType::MultiHop), ("beta", RouteType::Any)] {
+            let circuit = Circuit::builder()
+                .with_id(name.to_string())
+                .with_auth(AuthorizationType::Trust)
+                .with_members(vec!["origin".into(), "destination".into()])
+                .with_roster(vec![])
+                .with_persistence(PersistenceType::Any)
+                .with_durability(DurabilityType::NoDurability)
+                .with_routes(routes.clone())
+                .with_circuit_management_type("test_app".into())
+                .build()
+                .expect("Should have built a correct circuit");
+            circuit_directory.add_circuit(name.to_string(), circuit);
+        }
+
+        Arc::new(RwLock::new(SplinterState::new(
+            "memory".into(),
+            circuit_directory,
+        )))
+    }
+
+    /// Creates the routing table of the destination node, which is connected to the origin
+    /// through "next":
+    ///
+    ///   destination -- next -- origin
+    ///        |
+    ///      other
+    fn setup_destination_routing_table() -> RoutingTable {
+        let routing_table = RoutingTable::new("destination".into());
+        routing_table.set_local_peers(vec!["next".to_string(), "other".to_string()]);
+        routing_table.update_node("next", vec!["destination".into(), "origin".into()], 1);
+        routing_table.update_node("other", vec!["destination".into()], 1);
+        routing_table
+    }
+
+    /// Test that a routed message is forwarded to the next hop towards its destination with a
+    /// decremented time to live, and is dropped once the time to live expires.
+    #[test]
+    fn test_forward_routed_message() {
+        let network_sender = Box::new(MockSender::default());
+        let dispatch_sender = MockSender::default();
+        let mut dispatcher = Dispatcher::new(network_sender.box_clone());
+
+        let routing_table = RoutingTable::new("local".into());
+        routing_table.set_local_peers(vec!["origin".to_string(), "next".to_string()]);
+        routing_table.update_node("next", vec!["destination".to_string()], 1);
+
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+            Box::new(CircuitRoutedMessageHandler::new(
+                routing_table,
+                setup_state(),
+                Box::new(dispatch_sender.clone()),
+            )),
+        );
+
+        dispatcher
+            .dispatch(
+                "origin",
+                &CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                routed_message("destination", 3, "alpha"),
+            )
+            .unwrap();
+
+        let sent = network_sender.sent();
+        assert_eq!(1, sent.len());
+        assert_eq!("next", sent[0].recipient());
+
+        let network_msg: NetworkMessage = protobuf::parse_from_bytes(sent[0].payload()).unwrap();
+        let circuit_msg: CircuitMessage =
+            protobuf::parse_from_bytes(network_msg.get_payload()).unwrap();
+        assert_eq!(
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+            circuit_msg.get_message_type()
+        );
+        let routed_msg: CircuitRoutedMessage =
+            protobuf::parse_from_bytes(circuit_msg.get_payload()).unwrap();
+        assert_eq!(2, routed_msg.get_time_to_live());
+        assert_eq!("destination", routed_msg.get_destination_node());
+
+        dispatcher
+            .dispatch(
+                "origin",
+                &CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                routed_message("destination", 1, "alpha"),
+            )
+            .unwrap();
+        assert_eq!(1, network_sender.sent().len());
+        assert!(dispatch_sender.sent().is_empty());
+    }
+
+    /// Test that a routed message that has reached its destination is passed back to the circuit
+    /// dispatcher as though it was received from its origin.
+    #[test]
+    fn test_deliver_routed_message() {
+        let network_sender = Box::new(MockSender::default());
+        let dispatch_sender = MockSender::default();
+        let mut dispatcher = Dispatcher::new(network_sender.box_clone());
+
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+            Box::new(CircuitRoutedMessageHandler::new(
+                setup_destination_routing_table(),
+                setup_state(),
+                Box::new(dispatch_sender.clone()),
+            )),
+        );
+
+        dispatcher
+            .dispatch(
+                "next",
+                &CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                routed_message("destination", 2, "alpha"),
+            )
+            .unwrap();
+
+        assert!(network_sender.sent().is_empty());
+        let dispatched = dispatch_sender.sent();
+        assert_eq!(1, dispatched.len());
+        assert_eq!(
+            &CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            dispatched[0].message_type()
+        );
+        assert_eq!("origin", dispatched[0].source_peer_id());
+        let direct_msg: CircuitDirectMessage =
+            protobuf::parse_from_bytes(dispatched[0].message_bytes()).unwrap();
+        assert_eq!(b"payload", direct_msg.get_payload());
+    }
+
+    /// Test that a routed message is dropped if it arrives from a peer that is not on a route
+    /// from its claimed origin, or if its circuit does not allow multi-hop routing.
+    #[test]
+    fn test_reject_routed_message() {
+        let network_sender = Box::new(MockSender::default());
+        let dispatch_sender = MockSender::default();
+        let mut dispatcher = Dispatcher::new(network_sender.box_clone());
+
+        dispatcher.set_handler(
+            CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+            Box::new(CircuitRoutedMessageHandler::new(
+                setup_destination_routing_table(),
+                setup_state(),
+                Box::new(dispatch_sender.clone()),
+            )),
+        );
+
+        // "other" is an authorized peer, but not on the route from the origin
+        dispatcher
+            .dispatch(
+                "other",
+                &CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                routed_message("destination", 2, "alpha"),
+            )
+            .unwrap();
+
+        // "beta" does not allow routing
+        dispatcher
+            .dispatch(
+                "next",
+                &CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+                routed_message("destination", 2, "beta"),
+            )
+            .unwrap();
+
+        assert!(network_sender.sent().is_empty());
+        assert!(dispatch_sender.sent().is_empty());
+    }
+}
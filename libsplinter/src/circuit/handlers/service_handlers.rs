@@ -122,8 +122,11 @@ impl Handler<CircuitMessageType, ServiceConnectRequest> for ServiceConnectReques
 
         // Return response
         let response_bytes = response.write_to_bytes()?;
-        let network_msg_bytes =
-            create_message(response_bytes, CircuitMessageType::SERVICE_CONNECT_RESPONSE)?;
+        let network_msg_bytes = create_message(
+            response_bytes,
+            CircuitMessageType::SERVICE_CONNECT_RESPONSE,
+            context.trace_id(),
+        )?;
 
         let recipient = context.source_peer_id().to_string();
         let send_request = SendRequest::new(recipient, network_msg_bytes);
@@ -205,6 +208,7 @@ impl Handler<CircuitMessageType, ServiceDisconnectRequest> for ServiceDisconnect
         let network_msg_bytes = create_message(
             response_bytes,
             CircuitMessageType::SERVICE_DISCONNECT_RESPONSE,
+            context.trace_id(),
         )?;
 
         let recipient = context.source_peer_id().to_string();
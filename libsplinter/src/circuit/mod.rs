@@ -13,10 +13,12 @@
 // limitations under the License.
 
 pub mod directory;
+pub mod durable;
 pub mod handlers;
 #[cfg(feature = "rest-api")]
 pub mod rest_api;
 pub mod service;
+pub mod store;
 
 use serde_derive::{Deserialize, Serialize};
 
@@ -27,7 +29,9 @@ use std::fmt::{self, Display, Formatter};
 
 use crate::circuit::directory::CircuitDirectory;
 use crate::circuit::service::{Service, ServiceId, SplinterNode};
-use crate::storage::get_storage;
+use crate::circuit::store::{
+    CircuitDirectoryChange, CircuitStore, CircuitStoreError, StorageCircuitStore,
+};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Circuit {
@@ -348,11 +352,17 @@ pub enum PersistenceType {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum DurabilityType {
     NoDurability,
+    /// Direct messages are stored by the sending node until they are acknowledged by the
+    /// recipient service.
+    StoreAndForward,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum RouteType {
     Any,
+    /// Messages may be forwarded through intermediate nodes when the destination node is not
+    /// directly connected.
+    MultiHop,
 }
 
 pub enum RosterIter<'r> {
@@ -381,8 +391,8 @@ impl<'r> IntoIterator for &'r Roster {
 }
 
 pub struct SplinterState {
-    // location of the persisted state
-    storage_location: String,
+    // where the state is persisted
+    store: Box<dyn CircuitStore>,
     // The state that is persisted
     circuit_directory: CircuitDirectory,
     // Service id to Service that contains the node the service is connected to. Not persisted.
@@ -390,25 +400,43 @@ pub struct SplinterState {
 }
 
 impl SplinterState {
+    /// Creates the state of the given circuit directory, which is persisted to the given `Storage`
+    /// location.
     pub fn new(storage_location: String, circuit_directory: CircuitDirectory) -> Self {
         SplinterState {
-            storage_location,
+            store: Box::new(StorageCircuitStore::new(storage_location)),
             circuit_directory,
             service_directory: HashMap::new(),
         }
     }
 
-    pub fn storage_location(&self) -> &str {
-        &self.storage_location
+    /// Creates the state of the circuit directory in the given store, which the changes to the
+    /// state are committed to.
+    pub fn from_store(store: Box<dyn CircuitStore>) -> Result<Self, CircuitStoreError> {
+        let circuit_directory = store.load()?;
+        Ok(SplinterState {
+            store,
+            circuit_directory,
+            service_directory: HashMap::new(),
+        })
     }
 
-    fn write_circuit_directory(&self) -> Result<(), WriteError> {
-        // Replace stored state with the current splinter state
-        let mut storage = get_storage(self.storage_location(), || self.circuit_directory.clone())
-            .map_err(WriteError::GetStorageError)?;
-
-        // when this is dropped the new state will be written to storage
-        **storage.write() = self.circuit_directory.clone();
+    pub fn storage_location(&self) -> &str {
+        self.store.location()
+    }
+
+    /// Commits the changes to the store in a single transaction, then applies them to the
+    /// in-memory directory; the directory is left unchanged if the commit fails.
+    pub fn commit_changes(
+        &mut self,
+        changes: Vec<CircuitDirectoryChange>,
+    ) -> Result<(), WriteError> {
+        self.store
+            .commit(&changes, &self.circuit_directory)
+            .map_err(WriteError::StoreError)?;
+        for change in changes {
+            change.apply(&mut self.circuit_directory);
+        }
         Ok(())
     }
 
@@ -427,27 +455,19 @@ impl SplinterState {
 
     // ---------- methods to access circuit directory ----------
     pub fn add_node(&mut self, id: String, node: SplinterNode) -> Result<(), WriteError> {
-        self.circuit_directory.add_node(id, node);
-        self.write_circuit_directory()?;
-        Ok(())
+        self.commit_changes(vec![CircuitDirectoryChange::AddNode(id, node)])
     }
 
     pub fn add_circuit(&mut self, name: String, circuit: Circuit) -> Result<(), WriteError> {
-        self.circuit_directory.add_circuit(name, circuit);
-        self.write_circuit_directory()?;
-        Ok(())
+        self.commit_changes(vec![CircuitDirectoryChange::AddCircuit(name, circuit)])
     }
 
     pub fn remove_node(&mut self, id: &str) -> Result<(), WriteError> {
-        self.circuit_directory.remove_node(id);
-        self.write_circuit_directory()?;
-        Ok(())
+        self.commit_changes(vec![CircuitDirectoryChange::RemoveNode(id.into())])
     }
 
     pub fn remove_circuit(&mut self, name: &str) -> Result<(), WriteError> {
-        self.circuit_directory.remove_circuit(name);
-        self.write_circuit_directory()?;
-        Ok(())
+        self.commit_changes(vec![CircuitDirectoryChange::RemoveCircuit(name.into())])
     }
 
     pub fn nodes(&self) -> &BTreeMap<String, SplinterNode> {
@@ -474,6 +494,7 @@ impl SplinterState {
 #[derive(Debug)]
 pub enum WriteError {
     GetStorageError(String),
+    StoreError(CircuitStoreError),
 }
 
 impl Error for WriteError {}
@@ -482,6 +503,7 @@ impl std::fmt::Display for WriteError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             WriteError::GetStorageError(msg) => write!(f, "Unable to get storage: {}", msg),
+            WriteError::StoreError(err) => write!(f, "Unable to commit to circuit store: {}", err),
         }
     }
 }
@@ -489,6 +511,7 @@ impl std::fmt::Display for WriteError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::get_storage;
     use std::path::PathBuf;
     use tempdir::TempDir;
 
@@ -618,4 +641,69 @@ mod tests {
         // Check that state does not have any nodes
         assert!(storage.read().nodes().len() == 0);
     }
+
+    /// Test that changes committed together are all written to storage and applied to the
+    /// in-memory directory.
+    #[test]
+    fn test_commit_changes() {
+        let temp_dir = TempDir::new("test_commit_changes").unwrap();
+        let path = setup_storage(temp_dir.path().to_path_buf());
+        let mut state =
+            SplinterState::from_store(Box::new(StorageCircuitStore::new(path.clone()))).unwrap();
+        state
+            .add_node(
+                "123".into(),
+                SplinterNode::new("123".into(), vec!["tcp://127.0.0.1:8000".into()]),
+            )
+            .unwrap();
+
+        state
+            .commit_changes(vec![
+                CircuitDirectoryChange::AddNode(
+                    "456".into(),
+                    SplinterNode::new("456".into(), vec!["tcp://127.0.0.1:8001".into()]),
+                ),
+                CircuitDirectoryChange::RemoveNode("123".into()),
+            ])
+            .unwrap();
+
+        assert!(state.node("456").is_some());
+        assert!(state.node("123").is_none());
+
+        let storage = get_storage(&path, CircuitDirectory::new).unwrap();
+        assert!(storage.read().nodes().contains_key("456"));
+        assert!(!storage.read().nodes().contains_key("123"));
+    }
+
+    /// Test that a change is not applied to the in-memory directory if the store fails to commit
+    /// it.
+    #[test]
+    fn test_failed_commit_leaves_directory_unchanged() {
+        let mut state = SplinterState::from_store(Box::new(FailingCircuitStore)).unwrap();
+
+        let node = SplinterNode::new("123".into(), vec!["tcp://127.0.0.1:8000".into()]);
+        assert!(state.add_node("123".into(), node).is_err());
+        assert!(state.nodes().is_empty());
+    }
+
+    /// A `CircuitStore` that fails every commit.
+    struct FailingCircuitStore;
+
+    impl CircuitStore for FailingCircuitStore {
+        fn load(&self) -> Result<CircuitDirectory, CircuitStoreError> {
+            Ok(CircuitDirectory::new())
+        }
+
+        fn commit(
+            &mut self,
+            _changes: &[CircuitDirectoryChange],
+            _circuit_directory: &CircuitDirectory,
+        ) -> Result<(), CircuitStoreError> {
+            Err(CircuitStoreError::new("commit failed"))
+        }
+
+        fn location(&self) -> &str {
+            "failing"
+        }
+    }
 }
@@ -0,0 +1,361 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A `CircuitStore` backed by an LMDB database.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use transact::database::{
+    lmdb::{LmdbContext, LmdbDatabase},
+    Database, DatabaseError,
+};
+
+use crate::circuit::directory::CircuitDirectory;
+
+use super::{CircuitDirectoryChange, CircuitStore, CircuitStoreError};
+
+/// The size of the LMDB database; this is the maximum size the database can grow to, not the
+/// space it takes up.
+pub const DEFAULT_CIRCUIT_STORE_SIZE: usize = 1024 * 1024 * 1024;
+
+const NODES_INDEX: &str = "nodes";
+const CIRCUITS_INDEX: &str = "circuits";
+const INDEXES: [&str; 2] = [NODES_INDEX, CIRCUITS_INDEX];
+
+/// The suffix added to the name of a YAML state file once it has been migrated to an
+/// `LmdbCircuitStore`.
+const MIGRATED_SUFFIX: &str = ".migrated";
+
+/// A `CircuitStore` that stores each node and circuit of the directory as its own entry of an
+/// LMDB database, so a commit only writes the changed entries.
+pub struct LmdbCircuitStore {
+    location: String,
+    db: LmdbDatabase,
+}
+
+impl LmdbCircuitStore {
+    /// Opens the LMDB database at the given path, creating it if it does not exist.
+    pub fn new(path: &Path, size: usize) -> Result<Self, CircuitStoreError> {
+        let location = path
+            .to_str()
+            .ok_or_else(|| CircuitStoreError::new(&format!("Invalid path: {:?}", path)))?
+            .to_string();
+
+        let context = LmdbContext::new(path, INDEXES.len(), Some(size))
+            .map_err(|err| database_error("Unable to open LMDB database", err))?;
+        let db = LmdbDatabase::new(context, &INDEXES)
+            .map_err(|err| database_error("Unable to open LMDB database", err))?;
+
+        Ok(Self { location, db })
+    }
+
+    /// Migrates the circuit directory in the given YAML state file into the store, once.
+    ///
+    /// The directory is committed in a single transaction, then `.migrated` is added to the name
+    /// of the file, so it is kept as a backup and not migrated again. The file is left
+    /// as it is if the store already contains nodes or circuits.
+    ///
+    /// Returns whether the file was migrated; a file that does not exist is not an error.
+    pub fn migrate_from_yaml(&mut self, yaml_path: &Path) -> Result<bool, CircuitStoreError> {
+        let file = match File::open(yaml_path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => {
+                return Err(CircuitStoreError::with_source(
+                    &format!("Unable to open {:?}", yaml_path),
+                    Box::new(err),
+                ))
+            }
+        };
+
+        let reader = self
+            .db
+            .get_reader()
+            .map_err(|err| database_error("Unable to read LMDB database", err))?;
+        let mut entry_count = 0;
+        for index in INDEXES.iter() {
+            entry_count += reader
+                .index_count(index)
+                .map_err(|err| database_error("Unable to read LMDB database", err))?;
+        }
+        drop(reader);
+        if entry_count > 0 {
+            warn!(
+                "Not migrating {:?}: {} already contains circuit state",
+                yaml_path, self.location
+            );
+            return Ok(false);
+        }
+
+        let circuit_directory: CircuitDirectory = serde_yaml::from_reader(file).map_err(|err| {
+            CircuitStoreError::with_source(
+                &format!("Unable to read {:?}", yaml_path),
+                Box::new(err),
+            )
+        })?;
+
+        let changes = circuit_directory
+            .nodes()
+            .iter()
+            .map(|(id, node)| CircuitDirectoryChange::AddNode(id.clone(), node.clone()))
+            .chain(circuit_directory.circuits().iter().map(|(name, circuit)| {
+                CircuitDirectoryChange::AddCircuit(name.clone(), circuit.clone())
+            }))
+            .collect::<Vec<_>>();
+        self.commit(&changes, &circuit_directory)?;
+
+        let mut migrated_path = yaml_path.as_os_str().to_owned();
+        migrated_path.push(MIGRATED_SUFFIX);
+        let migrated_path = PathBuf::from(migrated_path);
+        fs::rename(yaml_path, &migrated_path).map_err(|err| {
+            CircuitStoreError::with_source(
+                &format!("Unable to rename {:?} once migrated", yaml_path),
+                Box::new(err),
+            )
+        })?;
+
+        info!(
+            "Migrated {} node(s) and {} circuit(s) from {:?} to {}",
+            circuit_directory.nodes().len(),
+            circuit_directory.circuits().len(),
+            yaml_path,
+            self.location
+        );
+
+        Ok(true)
+    }
+}
+
+impl CircuitStore for LmdbCircuitStore {
+    fn load(&self) -> Result<CircuitDirectory, CircuitStoreError> {
+        let reader = self
+            .db
+            .get_reader()
+            .map_err(|err| database_error("Unable to read LMDB database", err))?;
+
+        let mut circuit_directory = CircuitDirectory::new();
+        for (key, value) in reader
+            .index_cursor(NODES_INDEX)
+            .map_err(|err| database_error("Unable to read nodes", err))?
+        {
+            let id = entry_key(key)?;
+            let node = serde_json::from_slice(&value).map_err(|err| {
+                CircuitStoreError::with_source(&format!("Invalid node {}", id), Box::new(err))
+            })?;
+            circuit_directory.add_node(id, node);
+        }
+        for (key, value) in reader
+            .index_cursor(CIRCUITS_INDEX)
+            .map_err(|err| database_error("Unable to read circuits", err))?
+        {
+            let name = entry_key(key)?;
+            let circuit = serde_json::from_slice(&value).map_err(|err| {
+                CircuitStoreError::with_source(&format!("Invalid circuit {}", name), Box::new(err))
+            })?;
+            circuit_directory.add_circuit(name, circuit);
+        }
+
+        Ok(circuit_directory)
+    }
+
+    fn commit(
+        &mut self,
+        changes: &[CircuitDirectoryChange],
+        _circuit_directory: &CircuitDirectory,
+    ) -> Result<(), CircuitStoreError> {
+        let mut writer = self
+            .db
+            .get_writer()
+            .map_err(|err| database_error("Unable to start LMDB transaction", err))?;
+        for change in changes {
+            match change {
+                CircuitDirectoryChange::AddNode(id, node) => {
+                    let value = serde_json::to_vec(node).map_err(|err| {
+                        CircuitStoreError::with_source(
+                            &format!("Unable to serialize node {}", id),
+                            Box::new(err),
+                        )
+                    })?;
+                    writer
+                        .index_put(NODES_INDEX, id.as_bytes(), &value)
+                        .map_err(|err| database_error("Unable to write node", err))?;
+                }
+                // LMDB fails to delete a missing entry, so it is looked up first, within the
+                // transaction
+                CircuitDirectoryChange::RemoveNode(id) => {
+                    if writer
+                        .index_get(NODES_INDEX, id.as_bytes())
+                        .map_err(|err| database_error("Unable to read node", err))?
+                        .is_some()
+                    {
+                        writer
+                            .index_delete(NODES_INDEX, id.as_bytes())
+                            .map_err(|err| database_error("Unable to remove node", err))?;
+                    }
+                }
+                CircuitDirectoryChange::AddCircuit(name, circuit) => {
+                    let value = serde_json::to_vec(circuit).map_err(|err| {
+                        CircuitStoreError::with_source(
+                            &format!("Unable to serialize circuit {}", name),
+                            Box::new(err),
+                        )
+                    })?;
+                    writer
+                        .index_put(CIRCUITS_INDEX, name.as_bytes(), &value)
+                        .map_err(|err| database_error("Unable to write circuit", err))?;
+                }
+                CircuitDirectoryChange::RemoveCircuit(name) => {
+                    if writer
+                        .index_get(CIRCUITS_INDEX, name.as_bytes())
+                        .map_err(|err| database_error("Unable to read circuit", err))?
+                        .is_some()
+                    {
+                        writer
+                            .index_delete(CIRCUITS_INDEX, name.as_bytes())
+                            .map_err(|err| database_error("Unable to remove circuit", err))?;
+                    }
+                }
+            }
+        }
+
+        writer
+            .commit()
+            .map_err(|err| database_error("Unable to commit LMDB transaction", err))
+    }
+
+    fn location(&self) -> &str {
+        &self.location
+    }
+}
+
+fn database_error(context: &str, err: DatabaseError) -> CircuitStoreError {
+    CircuitStoreError::with_source(context, Box::new(err))
+}
+
+fn entry_key(key: Vec<u8>) -> Result<String, CircuitStoreError> {
+    String::from_utf8(key).map_err(|err| {
+        CircuitStoreError::with_source("Invalid circuit state entry key", Box::new(err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    use crate::circuit::service::SplinterNode;
+    use crate::circuit::{AuthorizationType, Circuit, DurabilityType, PersistenceType, RouteType};
+    use crate::storage::get_storage;
+
+    /// Test that the committed changes are loaded from the store once it is reopened, and that
+    /// removing an entry that is not stored does not fail the transaction.
+    #[test]
+    fn test_commit_and_load() {
+        let temp_dir = TempDir::new("test_commit_and_load").unwrap();
+        let path = temp_dir.path().join("circuits.lmdb");
+
+        {
+            let mut store = LmdbCircuitStore::new(&path, 1024 * 1024).unwrap();
+            let circuit_directory = CircuitDirectory::new();
+            store
+                .commit(
+                    &[
+                        CircuitDirectoryChange::AddNode("123".into(), node("123")),
+                        CircuitDirectoryChange::AddNode("456".into(), node("456")),
+                        CircuitDirectoryChange::AddCircuit("alpha".into(), circuit("alpha")),
+                        CircuitDirectoryChange::AddCircuit("beta".into(), circuit("beta")),
+                    ],
+                    &circuit_directory,
+                )
+                .unwrap();
+            store
+                .commit(
+                    &[
+                        CircuitDirectoryChange::RemoveNode("456".into()),
+                        CircuitDirectoryChange::RemoveCircuit("beta".into()),
+                        CircuitDirectoryChange::RemoveCircuit("gamma".into()),
+                    ],
+                    &circuit_directory,
+                )
+                .unwrap();
+        }
+
+        let store = LmdbCircuitStore::new(&path, 1024 * 1024).unwrap();
+        let circuit_directory = store.load().unwrap();
+
+        assert_eq!(
+            vec!["123"],
+            circuit_directory.nodes().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["alpha"],
+            circuit_directory.circuits().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            &["tcp://127.0.0.1:8044".to_string()],
+            circuit_directory.node("123").unwrap().endpoints()
+        );
+    }
+
+    /// Test that a YAML state file is migrated into an empty store and renamed, and that it is
+    /// not migrated again.
+    #[test]
+    fn test_migrate_from_yaml() {
+        let temp_dir = TempDir::new("test_migrate_from_yaml").unwrap();
+        let yaml_path = temp_dir.path().join("circuits.yaml");
+        let yaml_location = yaml_path.to_str().unwrap().to_string();
+
+        {
+            let mut storage = get_storage(&yaml_location, CircuitDirectory::new).unwrap();
+            let mut circuit_directory = storage.write();
+            circuit_directory.add_node("123".into(), node("123"));
+            circuit_directory.add_circuit("alpha".into(), circuit("alpha"));
+        }
+
+        let mut store =
+            LmdbCircuitStore::new(&temp_dir.path().join("circuits.lmdb"), 1024 * 1024).unwrap();
+
+        assert!(store.migrate_from_yaml(&yaml_path).unwrap());
+        assert!(!yaml_path.exists());
+        assert!(temp_dir.path().join("circuits.yaml.migrated").exists());
+
+        let circuit_directory = store.load().unwrap();
+        assert!(circuit_directory.node("123").is_some());
+        assert!(circuit_directory.has_circuit("alpha"));
+
+        // The file is gone, so there is nothing left to migrate
+        assert!(!store.migrate_from_yaml(&yaml_path).unwrap());
+    }
+
+    fn node(id: &str) -> SplinterNode {
+        SplinterNode::new(id.into(), vec!["tcp://127.0.0.1:8044".into()])
+    }
+
+    fn circuit(id: &str) -> Circuit {
+        Circuit::builder()
+            .with_id(id.into())
+            .with_auth(AuthorizationType::Trust)
+            .with_members(vec!["123".into()])
+            .with_roster(vec![])
+            .with_persistence(PersistenceType::Any)
+            .with_durability(DurabilityType::NoDurability)
+            .with_routes(RouteType::Any)
+            .with_circuit_management_type("test_app".into())
+            .build()
+            .expect("Should have built a correct circuit")
+    }
+}
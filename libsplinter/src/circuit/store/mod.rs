@@ -0,0 +1,173 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistence of the circuit directory of a `SplinterState`.
+//!
+//! Each change to the circuit directory is committed to a `CircuitStore` as a transaction. The
+//! `StorageCircuitStore` writes the whole directory to a `Storage` location, such as a YAML file,
+//! on every commit; the `LmdbCircuitStore`, available with the `circuit-store-lmdb` feature, only
+//! writes the changed entries.
+
+#[cfg(feature = "circuit-store-lmdb")]
+pub mod lmdb;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::storage::get_storage;
+
+use super::directory::CircuitDirectory;
+use super::service::SplinterNode;
+use super::Circuit;
+
+/// A change to a circuit directory.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CircuitDirectoryChange {
+    /// Adds a node, or replaces the node with the same ID.
+    AddNode(String, SplinterNode),
+    /// Removes the node with the given ID, if any.
+    RemoveNode(String),
+    /// Adds a circuit, or replaces the circuit with the same name.
+    AddCircuit(String, Circuit),
+    /// Removes the circuit with the given name, if any.
+    RemoveCircuit(String),
+}
+
+impl CircuitDirectoryChange {
+    /// Applies the change to the given circuit directory.
+    pub fn apply(&self, circuit_directory: &mut CircuitDirectory) {
+        match self {
+            CircuitDirectoryChange::AddNode(id, node) => {
+                circuit_directory.add_node(id.clone(), node.clone())
+            }
+            CircuitDirectoryChange::RemoveNode(id) => circuit_directory.remove_node(id),
+            CircuitDirectoryChange::AddCircuit(name, circuit) => {
+                circuit_directory.add_circuit(name.clone(), circuit.clone())
+            }
+            CircuitDirectoryChange::RemoveCircuit(name) => circuit_directory.remove_circuit(name),
+        }
+    }
+}
+
+/// A transactional store of a circuit directory.
+pub trait CircuitStore: Send + Sync {
+    /// Returns the circuit directory in the store.
+    fn load(&self) -> Result<CircuitDirectory, CircuitStoreError>;
+
+    /// Commits the changes to the store in a single transaction: either all of them are stored,
+    /// or none are.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The changes, in the order they were made
+    /// * `circuit_directory` - The circuit directory before the changes are applied to it, for
+    ///   the stores that do not store individual entries
+    fn commit(
+        &mut self,
+        changes: &[CircuitDirectoryChange],
+        circuit_directory: &CircuitDirectory,
+    ) -> Result<(), CircuitStoreError>;
+
+    /// Returns the location of the store.
+    fn location(&self) -> &str;
+}
+
+/// A `CircuitStore` that writes the whole circuit directory to a `Storage` location on every
+/// commit.
+///
+/// Accepts the locations of `get_storage`: `"memory"` or the path of a YAML file.
+pub struct StorageCircuitStore {
+    location: String,
+}
+
+impl StorageCircuitStore {
+    pub fn new(location: String) -> Self {
+        Self { location }
+    }
+}
+
+impl CircuitStore for StorageCircuitStore {
+    fn load(&self) -> Result<CircuitDirectory, CircuitStoreError> {
+        let storage = get_storage(&self.location, CircuitDirectory::new)
+            .map_err(|err| CircuitStoreError::new(&err))?;
+        let circuit_directory = storage.read().clone();
+        Ok(circuit_directory)
+    }
+
+    fn commit(
+        &mut self,
+        changes: &[CircuitDirectoryChange],
+        circuit_directory: &CircuitDirectory,
+    ) -> Result<(), CircuitStoreError> {
+        let mut circuit_directory = circuit_directory.clone();
+        for change in changes {
+            change.apply(&mut circuit_directory);
+        }
+
+        // Replace stored state with the changed circuit directory
+        let mut storage = get_storage(&self.location, || circuit_directory.clone())
+            .map_err(|err| CircuitStoreError::new(&err))?;
+
+        // when this is dropped the new state will be written to storage
+        **storage.write() = circuit_directory;
+        Ok(())
+    }
+
+    fn location(&self) -> &str {
+        &self.location
+    }
+}
+
+/// An error that can occur in a `CircuitStore` implementation.
+#[derive(Debug)]
+pub struct CircuitStoreError {
+    pub context: String,
+    pub source: Option<Box<dyn Error + Send>>,
+}
+
+impl CircuitStoreError {
+    pub fn new(context: &str) -> Self {
+        Self {
+            context: context.into(),
+            source: None,
+        }
+    }
+
+    pub fn with_source(context: &str, source: Box<dyn Error + Send>) -> Self {
+        Self {
+            context: context.into(),
+            source: Some(source),
+        }
+    }
+}
+
+impl Error for CircuitStoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        if let Some(ref err) = self.source {
+            Some(&**err)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for CircuitStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref err) = self.source {
+            write!(f, "{}: {}", self.context, err)
+        } else {
+            f.write_str(&self.context)
+        }
+    }
+}
@@ -23,6 +23,7 @@ use std::sync::mpsc::Receiver;
 use protobuf::error::ProtobufError;
 use protobuf::Message;
 
+use crate::network::trace::parse_trace_id;
 use crate::protos::consensus::{
     ConsensusMessage as ConsensusMessageProto, Proposal as ProposalProto,
 };
@@ -206,11 +207,23 @@ pub enum ProposalUpdate {
 pub struct ConsensusMessage {
     pub message: Vec<u8>,
     pub origin_id: PeerId,
+    /// The trace id of the proposal the message is about, if any, used to follow a proposal
+    /// across nodes in the logs
+    pub trace_id: Option<String>,
 }
 
 impl ConsensusMessage {
     pub fn new(message: Vec<u8>, origin_id: PeerId) -> Self {
-        ConsensusMessage { message, origin_id }
+        ConsensusMessage {
+            message,
+            origin_id,
+            trace_id: None,
+        }
+    }
+
+    pub fn with_trace_id(mut self, trace_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self
     }
 }
 
@@ -219,6 +232,7 @@ impl From<ConsensusMessageProto> for ConsensusMessage {
         ConsensusMessage {
             message: msg.message,
             origin_id: msg.origin_id.into(),
+            trace_id: parse_trace_id(&msg.trace_id).map(String::from),
         }
     }
 }
@@ -228,6 +242,9 @@ impl Into<ConsensusMessageProto> for ConsensusMessage {
         let mut msg = ConsensusMessageProto::new();
         msg.set_message(self.message);
         msg.set_origin_id(self.origin_id.into());
+        if let Some(trace_id) = self.trace_id {
+            msg.set_trace_id(trace_id);
+        }
         msg
     }
 }
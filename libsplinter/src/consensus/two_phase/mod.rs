@@ -52,6 +52,7 @@ use crate::consensus::{
     ConsensusEngine, ConsensusEngineError, ConsensusMessage, ConsensusNetworkSender, PeerId,
     Proposal, ProposalId, ProposalManager, ProposalUpdate, StartupState,
 };
+use crate::network::trace::TraceField;
 use crate::protos::two_phase::{
     RequiredVerifiers, TwoPhaseMessage, TwoPhaseMessage_ProposalResult,
     TwoPhaseMessage_ProposalVerificationResponse, TwoPhaseMessage_Type,
@@ -149,6 +150,13 @@ impl TwoPhaseEngine {
     ) -> Result<(), ConsensusEngineError> {
         let two_phase_msg: TwoPhaseMessage = protobuf::parse_from_bytes(&consensus_msg.message)?;
         let proposal_id = ProposalId::from(two_phase_msg.get_proposal_id());
+        trace!(
+            "Received {:?} for proposal {} from {}{}",
+            two_phase_msg.get_message_type(),
+            proposal_id,
+            consensus_msg.origin_id,
+            TraceField(consensus_msg.trace_id.as_deref())
+        );
 
         match two_phase_msg.get_message_type() {
             TwoPhaseMessage_Type::PROPOSAL_VERIFICATION_REQUEST => {
@@ -0,0 +1,26 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of changes to files that are reloaded when they change.
+
+use std::fs;
+use std::time::SystemTime;
+
+/// Returns the modified time and size of the file, which together identify a version of it, or
+/// `None` if the file cannot be read.
+pub fn file_version(path: &str) -> Option<(SystemTime, u64)> {
+    fs::metadata(path)
+        .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+        .ok()
+}
@@ -0,0 +1,255 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A key registry backed by a database.
+//!
+//! The tables used by the `DatabaseKeyRegistry` are created by the migrations run with
+//! `postgres::run_migrations`.
+
+pub mod postgres;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use diesel::Connection as _;
+
+use crate::database::{Connection, ConnectionPool};
+use crate::hex::{parse_hex, to_hex};
+
+use super::{check_key_rotation, rotation_deadline, KeyInfo, KeyRegistry, KeyRegistryError};
+use postgres::helpers;
+use postgres::models::{KeyMetadataModel, KeyModel};
+
+/// A key registry backed by a Postgres database.
+///
+/// Keys are stored in a table of their own and their metadata in a table of key/value pairs. The
+/// keys saved together, such as the two keys of a rotation, are saved in a single transaction.
+#[derive(Clone)]
+pub struct DatabaseKeyRegistry {
+    connection_pool: ConnectionPool,
+}
+
+impl DatabaseKeyRegistry {
+    /// Creates a new DatabaseKeyRegistry
+    ///
+    /// # Arguments
+    ///
+    ///  * `connection_pool`: connection pool to the database
+    pub fn new(connection_pool: ConnectionPool) -> Self {
+        DatabaseKeyRegistry { connection_pool }
+    }
+
+    fn connection(&self) -> Result<Connection, KeyRegistryError> {
+        // The pool's errors cannot be sent between threads, so only their message is kept
+        self.connection_pool.get().map_err(|err| KeyRegistryError {
+            context: format!("unable to get a database connection: {}", err),
+            source: None,
+        })
+    }
+}
+
+impl KeyRegistry for DatabaseKeyRegistry {
+    fn save_key(&mut self, key_info: KeyInfo) -> Result<(), KeyRegistryError> {
+        self.save_keys(vec![key_info])
+    }
+
+    fn save_keys(&mut self, key_infos: Vec<KeyInfo>) -> Result<(), KeyRegistryError> {
+        let keys = key_infos
+            .iter()
+            .map(to_models)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        helpers::insert_keys(&*self.connection()?, &keys).map_err(|err| KeyRegistryError {
+            context: "Unable to save keys".into(),
+            source: Some(Box::new(err)),
+        })
+    }
+
+    fn delete_key(&mut self, public_key: &[u8]) -> Result<Option<KeyInfo>, KeyRegistryError> {
+        helpers::delete_key(&*self.connection()?, &to_hex(public_key))
+            .map_err(|err| KeyRegistryError {
+                context: format!("Unable to delete key {}", to_hex(public_key)),
+                source: Some(Box::new(err)),
+            })?
+            .map(|(key, metadata)| to_key_info(key, metadata))
+            .transpose()
+    }
+
+    fn get_key(&self, public_key: &[u8]) -> Result<Option<KeyInfo>, KeyRegistryError> {
+        helpers::fetch_key(&*self.connection()?, &to_hex(public_key))
+            .map_err(|err| KeyRegistryError {
+                context: format!("Unable to fetch key {}", to_hex(public_key)),
+                source: Some(Box::new(err)),
+            })?
+            .map(|(key, metadata)| to_key_info(key, metadata))
+            .transpose()
+    }
+
+    fn keys<'iter, 'a: 'iter>(
+        &'a self,
+    ) -> Result<Box<dyn Iterator<Item = KeyInfo> + 'iter>, KeyRegistryError> {
+        let keys = helpers::list_keys(&*self.connection()?).map_err(|err| KeyRegistryError {
+            context: "Unable to list keys".into(),
+            source: Some(Box::new(err)),
+        })?;
+
+        Ok(Box::new(keys.into_iter().filter_map(|(key, metadata)| {
+            to_key_info(key, metadata)
+                .map_err(|err| warn!("Skipping bad persisted key info: {}", err))
+                .ok()
+        })))
+    }
+
+    fn count(&self) -> Result<usize, KeyRegistryError> {
+        helpers::count_keys(&*self.connection()?)
+            .map(|count| count as usize)
+            .map_err(|err| KeyRegistryError {
+                context: "Unable to count keys".into(),
+                source: Some(Box::new(err)),
+            })
+    }
+
+    /// Replaces a node's key with a new key, checking and saving both keys in a single
+    /// transaction. The old key's row is locked for the length of the transaction, so concurrent
+    /// rotations of the same key cannot both succeed.
+    fn rotate_key(
+        &mut self,
+        old_public_key: &[u8],
+        new_key_info: KeyInfo,
+        overlap: Duration,
+    ) -> Result<(), KeyRegistryError> {
+        let accepted_until = rotation_deadline(overlap)?;
+        let conn = self.connection()?;
+
+        conn.transaction::<_, KeyRegistryError, _>(|| {
+            let old_key_info = check_key_rotation(
+                |public_key| {
+                    let public_key = to_hex(public_key);
+                    let key = if public_key == to_hex(old_public_key) {
+                        helpers::fetch_key_for_update(&*conn, &public_key)
+                    } else {
+                        helpers::fetch_key(&*conn, &public_key)
+                    };
+
+                    key.map_err(|err| KeyRegistryError {
+                        context: format!("Unable to fetch key {}", public_key),
+                        source: Some(Box::new(err)),
+                    })?
+                    .map(|(key, metadata)| to_key_info(key, metadata))
+                    .transpose()
+                },
+                old_public_key,
+                &new_key_info,
+            )?
+            .supersede(new_key_info.public_key().to_vec(), accepted_until);
+
+            let keys = vec![to_models(&old_key_info)?, to_models(&new_key_info)?];
+            helpers::insert_keys(&*conn, &keys).map_err(|err| KeyRegistryError {
+                context: "Unable to save keys".into(),
+                source: Some(Box::new(err)),
+            })
+        })
+    }
+
+    fn clone_box(&self) -> Box<dyn KeyRegistry> {
+        Box::new(self.clone())
+    }
+}
+
+impl From<diesel::result::Error> for KeyRegistryError {
+    fn from(err: diesel::result::Error) -> Self {
+        KeyRegistryError {
+            context: "Database transaction failed".into(),
+            source: Some(Box::new(err)),
+        }
+    }
+}
+
+fn to_models(key_info: &KeyInfo) -> Result<(KeyModel, Vec<KeyMetadataModel>), KeyRegistryError> {
+    let public_key = to_hex(key_info.public_key());
+
+    let accepted_until = key_info
+        .accepted_until()
+        .map(|accepted_until| {
+            accepted_until
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs() as i64)
+                .map_err(|err| KeyRegistryError {
+                    context: format!("Invalid end of overlap window of {}", public_key),
+                    source: Some(Box::new(err)),
+                })
+        })
+        .transpose()?;
+
+    let metadata = key_info
+        .metadata()
+        .iter()
+        .map(|(key, value)| KeyMetadataModel {
+            public_key: public_key.clone(),
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+
+    Ok((
+        KeyModel {
+            public_key,
+            associated_node_id: key_info.associated_node_id().to_string(),
+            superseded_by: key_info.superseded_by().map(to_hex),
+            accepted_until,
+        },
+        metadata,
+    ))
+}
+
+fn to_key_info(
+    key: KeyModel,
+    metadata: Vec<KeyMetadataModel>,
+) -> Result<KeyInfo, KeyRegistryError> {
+    let mut builder = KeyInfo::builder(
+        parse_hex(&key.public_key).map_err(|err| KeyRegistryError {
+            context: format!("Unable to parse public key: {}", key.public_key),
+            source: Some(Box::new(err)),
+        })?,
+        key.associated_node_id,
+    );
+
+    for metadata in metadata.into_iter() {
+        builder = builder.with_metadata(metadata.key, metadata.value);
+    }
+
+    match (key.superseded_by, key.accepted_until) {
+        (Some(superseded_by), Some(accepted_until)) => {
+            builder = builder.with_supersession(
+                parse_hex(&superseded_by).map_err(|err| KeyRegistryError {
+                    context: format!("Unable to parse public key: {}", superseded_by),
+                    source: Some(Box::new(err)),
+                })?,
+                UNIX_EPOCH + Duration::from_secs(accepted_until as u64),
+            );
+        }
+        (None, None) => (),
+        _ => {
+            return Err(KeyRegistryError {
+                context: format!(
+                    "Superseded key {} must have both a replacement and an end of its overlap \
+                     window",
+                    key.public_key
+                ),
+                source: None,
+            })
+        }
+    }
+
+    Ok(builder.build())
+}
@@ -0,0 +1,115 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use diesel::{
+    dsl::{delete, insert_into},
+    pg::PgConnection,
+    prelude::*,
+    QueryResult,
+};
+
+use super::models::{KeyMetadataModel, KeyModel};
+use super::schema::{splinter_keys, splinter_keys_metadata};
+
+pub fn fetch_key(
+    conn: &PgConnection,
+    public_key: &str,
+) -> QueryResult<Option<(KeyModel, Vec<KeyMetadataModel>)>> {
+    let key = match splinter_keys::table
+        .find(public_key)
+        .first::<KeyModel>(conn)
+        .optional()?
+    {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    let metadata = KeyMetadataModel::belonging_to(&key).load::<KeyMetadataModel>(conn)?;
+
+    Ok(Some((key, metadata)))
+}
+
+/// Fetches the key with the given public key, locking its row until the end of the current
+/// transaction.
+pub fn fetch_key_for_update(
+    conn: &PgConnection,
+    public_key: &str,
+) -> QueryResult<Option<(KeyModel, Vec<KeyMetadataModel>)>> {
+    let key = match splinter_keys::table
+        .find(public_key)
+        .for_update()
+        .first::<KeyModel>(conn)
+        .optional()?
+    {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    let metadata = KeyMetadataModel::belonging_to(&key).load::<KeyMetadataModel>(conn)?;
+
+    Ok(Some((key, metadata)))
+}
+
+pub fn list_keys(conn: &PgConnection) -> QueryResult<Vec<(KeyModel, Vec<KeyMetadataModel>)>> {
+    let keys = splinter_keys::table
+        .order(splinter_keys::public_key)
+        .load::<KeyModel>(conn)?;
+
+    let metadata = KeyMetadataModel::belonging_to(&keys)
+        .load::<KeyMetadataModel>(conn)?
+        .grouped_by(&keys);
+
+    Ok(keys.into_iter().zip(metadata).collect())
+}
+
+pub fn count_keys(conn: &PgConnection) -> QueryResult<i64> {
+    splinter_keys::table.count().get_result(conn)
+}
+
+/// Replaces the keys with the same public keys, if there are any, with the given keys, in a single
+/// transaction.
+pub fn insert_keys(
+    conn: &PgConnection,
+    keys: &[(KeyModel, Vec<KeyMetadataModel>)],
+) -> QueryResult<()> {
+    conn.transaction(|| {
+        for (key, metadata) in keys {
+            // The key's metadata is deleted with it
+            delete(splinter_keys::table.find(&key.public_key)).execute(conn)?;
+            insert_into(splinter_keys::table)
+                .values(key)
+                .execute(conn)?;
+            if !metadata.is_empty() {
+                insert_into(splinter_keys_metadata::table)
+                    .values(metadata)
+                    .execute(conn)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Deletes the key with the given public key, returning it if it existed.
+pub fn delete_key(
+    conn: &PgConnection,
+    public_key: &str,
+) -> QueryResult<Option<(KeyModel, Vec<KeyMetadataModel>)>> {
+    conn.transaction(|| {
+        let key = fetch_key(conn, public_key)?;
+        if key.is_some() {
+            delete(splinter_keys::table.find(public_key)).execute(conn)?;
+        }
+        Ok(key)
+    })
+}
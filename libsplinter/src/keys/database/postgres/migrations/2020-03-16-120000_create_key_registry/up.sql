@@ -0,0 +1,31 @@
+-- Copyright 2018-2020 Cargill Incorporated
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+-- -----------------------------------------------------------------------------
+
+-- A superseded key has both the key that replaced it and the end of its overlap window, in seconds
+-- since the Unix epoch
+CREATE TABLE IF NOT EXISTS splinter_keys (
+    public_key         TEXT PRIMARY KEY,
+    associated_node_id TEXT NOT NULL,
+    superseded_by      TEXT,
+    accepted_until     BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS splinter_keys_metadata (
+    public_key TEXT NOT NULL,
+    key        TEXT NOT NULL,
+    value      TEXT NOT NULL,
+    PRIMARY KEY(public_key, key),
+    FOREIGN KEY(public_key) REFERENCES splinter_keys(public_key) ON DELETE CASCADE
+);
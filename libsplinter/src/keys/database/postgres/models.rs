@@ -0,0 +1,34 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::schema::{splinter_keys, splinter_keys_metadata};
+
+#[derive(Insertable, Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "splinter_keys"]
+#[primary_key(public_key)]
+pub struct KeyModel {
+    pub public_key: String,
+    pub associated_node_id: String,
+    pub superseded_by: Option<String>,
+    pub accepted_until: Option<i64>,
+}
+
+#[derive(Insertable, Queryable, Associations, PartialEq, Debug)]
+#[table_name = "splinter_keys_metadata"]
+#[belongs_to(KeyModel, foreign_key = "public_key")]
+pub struct KeyMetadataModel {
+    pub public_key: String,
+    pub key: String,
+    pub value: String,
+}
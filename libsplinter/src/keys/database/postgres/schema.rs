@@ -0,0 +1,34 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+table! {
+    splinter_keys (public_key) {
+        public_key -> Text,
+        associated_node_id -> Text,
+        superseded_by -> Nullable<Text>,
+        accepted_until -> Nullable<Int8>,
+    }
+}
+
+table! {
+    splinter_keys_metadata (public_key, key) {
+        public_key -> Text,
+        key -> Text,
+        value -> Text,
+    }
+}
+
+joinable!(splinter_keys_metadata -> splinter_keys (public_key));
+
+allow_tables_to_appear_in_same_query!(splinter_keys, splinter_keys_metadata);
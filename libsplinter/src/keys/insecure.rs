@@ -16,6 +16,7 @@
 use super::{to_hex, KeyPermissionError, KeyPermissionManager};
 
 /// A KeyPermissioManager that allows all keys access to the requested role.
+#[derive(Clone)]
 pub struct AllowAllKeyPermissionManager;
 
 impl KeyPermissionManager for AllowAllKeyPermissionManager {
@@ -23,4 +24,8 @@ impl KeyPermissionManager for AllowAllKeyPermissionManager {
         debug!("Allowing {} access to {}", to_hex(public_key), role);
         Ok(true)
     }
+
+    fn clone_box(&self) -> Box<dyn KeyPermissionManager> {
+        Box::new(self.clone())
+    }
 }
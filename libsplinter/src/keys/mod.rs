@@ -23,15 +23,23 @@
 //! Key permissions, accessed via the `KeyPermissionManager` interface, are queried through a simple
 //! role-based access system.  The underlying implementation determines how those values are set
 //! and modified.
+//!
+//! A node's key can be rotated with `KeyRegistry::rotate_key`: the old key is marked as superseded
+//! by the new key, and remains accepted until the end of an overlap window, so that messages
+//! signed before the rotation reached every node are still accepted.
 
+#[cfg(feature = "database")]
+pub mod database;
 mod error;
 pub mod insecure;
 #[cfg(feature = "rest-api")]
 pub mod rest_api;
+pub mod roles;
 pub mod storage;
 
 use std::collections::HashMap;
 use std::fmt;
+use std::time::{Duration, SystemTime};
 
 use crate::hex::to_hex;
 
@@ -49,6 +57,14 @@ pub struct KeyInfo {
     public_key: Vec<u8>,
     associated_node_id: String,
     metadata: HashMap<String, String>,
+    supersession: Option<Supersession>,
+}
+
+/// The replacement of a key by a newer key of the same node.
+#[derive(Clone, Debug, PartialEq)]
+struct Supersession {
+    superseded_by: Vec<u8>,
+    accepted_until: SystemTime,
 }
 
 impl KeyInfo {
@@ -72,6 +88,7 @@ impl KeyInfo {
             public_key,
             associated_node_id,
             metadata: HashMap::default(),
+            supersession: None,
         }
     }
 
@@ -96,16 +113,51 @@ impl KeyInfo {
     pub fn metadata(&self) -> &HashMap<String, String> {
         &self.metadata
     }
+
+    /// The public key that replaced this key, if it has been rotated.
+    pub fn superseded_by(&self) -> Option<&[u8]> {
+        self.supersession
+            .as_ref()
+            .map(|supersession| &supersession.superseded_by[..])
+    }
+
+    /// The end of the overlap window of a superseded key, after which it is no longer accepted.
+    pub fn accepted_until(&self) -> Option<SystemTime> {
+        self.supersession
+            .as_ref()
+            .map(|supersession| supersession.accepted_until)
+    }
+
+    /// Whether the key is accepted at the given time: keys that have not been superseded are
+    /// always accepted, and superseded keys are accepted until the end of their overlap window.
+    pub fn is_accepted_at(&self, time: SystemTime) -> bool {
+        match self.accepted_until() {
+            Some(accepted_until) => time < accepted_until,
+            None => true,
+        }
+    }
+
+    /// Marks the key as superseded by the given key, and accepted until the given time.
+    fn supersede(mut self, superseded_by: Vec<u8>, accepted_until: SystemTime) -> Self {
+        self.supersession = Some(Supersession {
+            superseded_by,
+            accepted_until,
+        });
+        self
+    }
 }
 
 impl fmt::Debug for KeyInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            r#"KeyInfo {{ public_key: "{}", associated_node_id: {:?}, metadata: {:?} }}"#,
+            "KeyInfo {{ public_key: \"{}\", associated_node_id: {:?}, metadata: {:?}, \
+             superseded_by: {:?}, accepted_until: {:?} }}",
             to_hex(&self.public_key),
             &self.associated_node_id,
-            &self.metadata
+            &self.metadata,
+            self.superseded_by().map(to_hex),
+            self.accepted_until()
         )
     }
 }
@@ -115,6 +167,7 @@ pub struct KeyInfoBuilder {
     public_key: Vec<u8>,
     associated_node_id: String,
     metadata: HashMap<String, String>,
+    supersession: Option<Supersession>,
 }
 
 impl KeyInfoBuilder {
@@ -124,16 +177,40 @@ impl KeyInfoBuilder {
         self
     }
 
+    /// Mark the key as superseded by the given key, and accepted until the given time.
+    pub fn with_supersession(mut self, superseded_by: Vec<u8>, accepted_until: SystemTime) -> Self {
+        self.supersession = Some(Supersession {
+            superseded_by,
+            accepted_until,
+        });
+        self
+    }
+
     /// Build the key info
     pub fn build(self) -> KeyInfo {
         KeyInfo {
             public_key: self.public_key,
             associated_node_id: self.associated_node_id,
             metadata: self.metadata,
+            supersession: self.supersession,
         }
     }
 }
 
+/// The scheme of the `Authorization` header of the requests that change a key registry through
+/// the REST API. The header's credentials are the hex-encoded public key, the time at which the
+/// request was signed, in seconds since the Unix epoch, and the hex-encoded signature of the
+/// `signed_request_message`, separated by colons.
+pub const SIGNATURE_AUTHORIZATION_SCHEME: &str = "Splinter-Signature";
+
+/// Returns the message that is signed to authorize a change to a key registry through the REST
+/// API: the request's method and path, the time at which it was signed, and its body.
+pub fn signed_request_message(method: &str, path: &str, timestamp: u64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{} {}\n{}\n", method, path, timestamp).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
 type KeyRegistryResult<T> = Result<T, KeyRegistryError>;
 
 /// A registry of public key information.
@@ -194,6 +271,34 @@ pub trait KeyRegistry: Send + Sync {
     /// Return the total count of keys in the registry.
     fn count(&self) -> KeyRegistryResult<usize>;
 
+    /// Replace a node's key with a new key.
+    ///
+    /// The old key is marked as superseded by the new key and remains accepted for the given
+    /// overlap window; the new key is saved with its information. Both keys must belong to the
+    /// same node, the old key must not already be superseded and the new key must not already be
+    /// registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `KeyRegistryError` if the keys cannot be rotated, or if the underlying
+    /// implementation could not save the key information.
+    fn rotate_key(
+        &mut self,
+        old_public_key: &[u8],
+        new_key_info: KeyInfo,
+        overlap: Duration,
+    ) -> KeyRegistryResult<()> {
+        let accepted_until = rotation_deadline(overlap)?;
+        let old_key_info = check_key_rotation(
+            |public_key| self.get_key(public_key),
+            old_public_key,
+            &new_key_info,
+        )?
+        .supersede(new_key_info.public_key().to_vec(), accepted_until);
+
+        self.save_keys(vec![old_key_info, new_key_info])
+    }
+
     /// Clones this instance and returns a boxed, dynamic version.
     fn clone_box(&self) -> Box<dyn KeyRegistry>;
 }
@@ -204,6 +309,67 @@ impl Clone for Box<dyn KeyRegistry> {
     }
 }
 
+/// Returns the time until which a key rotated now with the given overlap remains accepted.
+pub(crate) fn rotation_deadline(overlap: Duration) -> KeyRegistryResult<SystemTime> {
+    SystemTime::now()
+        .checked_add(overlap)
+        .ok_or_else(|| KeyRegistryError {
+            context: format!("Overlap of {} seconds is too long", overlap.as_secs()),
+            source: None,
+        })
+}
+
+/// Checks that the old key can be replaced by the new key, looking keys up with the given
+/// function, and returns the old key's information.
+pub(crate) fn check_key_rotation<F>(
+    get_key: F,
+    old_public_key: &[u8],
+    new_key_info: &KeyInfo,
+) -> KeyRegistryResult<KeyInfo>
+where
+    F: Fn(&[u8]) -> KeyRegistryResult<Option<KeyInfo>>,
+{
+    let old_key_info = get_key(old_public_key)?.ok_or_else(|| KeyRegistryError {
+        context: format!("{} is not registered", to_hex(old_public_key)),
+        source: None,
+    })?;
+
+    if let Some(superseded_by) = old_key_info.superseded_by() {
+        return Err(KeyRegistryError {
+            context: format!(
+                "{} has already been superseded by {}",
+                to_hex(old_public_key),
+                to_hex(superseded_by)
+            ),
+            source: None,
+        });
+    }
+
+    if old_key_info.associated_node_id() != new_key_info.associated_node_id() {
+        return Err(KeyRegistryError {
+            context: format!(
+                "{} belongs to node {}, not {}",
+                to_hex(old_public_key),
+                old_key_info.associated_node_id(),
+                new_key_info.associated_node_id()
+            ),
+            source: None,
+        });
+    }
+
+    if get_key(new_key_info.public_key())?.is_some() {
+        return Err(KeyRegistryError {
+            context: format!(
+                "{} is already registered",
+                to_hex(new_key_info.public_key())
+            ),
+            source: None,
+        });
+    }
+
+    Ok(old_key_info)
+}
+
 type KeyPermissionResult<T> = Result<T, KeyPermissionError>;
 
 /// Manages role-based permissions associated with public keys.
@@ -221,4 +387,13 @@ pub trait KeyPermissionManager: Send {
     /// Returns a `KeyPermissionError` if the underling implementation encountered an error while
     /// checking the permissions.
     fn is_permitted(&self, public_key: &[u8], role: &str) -> KeyPermissionResult<bool>;
+
+    /// Clones this instance and returns a boxed, dynamic version.
+    fn clone_box(&self) -> Box<dyn KeyPermissionManager>;
+}
+
+impl Clone for Box<dyn KeyPermissionManager> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
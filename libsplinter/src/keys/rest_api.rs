@@ -16,17 +16,42 @@
 
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use openssl::sha::sha256;
 use serde::Serializer;
 
-use crate::actix_web::{error::BlockingError, web, HttpResponse};
+use crate::actix_web::{error::BlockingError, web, HttpRequest, HttpResponse};
 use crate::futures::{future::IntoFuture, Future};
+use crate::hex::to_hex;
 use crate::rest_api::{
+    into_bytes,
     paging::{get_response_paging_info, Paging, DEFAULT_LIMIT, DEFAULT_OFFSET},
-    Method, Resource, RestResourceProvider,
+    HandlerFunction, Method, Resource, RestResourceProvider,
 };
+use crate::signing::SignatureVerifier;
 
-use super::{KeyInfo, KeyRegistry, KeyRegistryError};
+use super::{
+    signed_request_message, KeyInfo, KeyPermissionManager, KeyRegistry, KeyRegistryError,
+    SIGNATURE_AUTHORIZATION_SCHEME,
+};
+
+/// The role a key must be permitted to act in to change the key registry through the REST API.
+pub const KEY_REGISTRY_ADMIN_ROLE: &str = "key-registry-admin";
+
+/// The role a key must also be permitted to act in to change the keys of nodes other than its own
+/// through the REST API.
+pub const KEY_REGISTRY_OTHER_NODES_ROLE: &str = "key-registry-other-nodes";
+
+/// How far the time at which a write request was signed may be from the time it is received.
+const MAX_REQUEST_CLOCK_SKEW_SECS: u64 = 300;
+
+/// How long a rotated key remains accepted if the rotation request does not specify it.
+pub const DEFAULT_KEY_ROTATION_OVERLAP_SECS: u64 = 24 * 60 * 60;
+
+/// The longest a rotated key may remain accepted.
+pub const MAX_KEY_ROTATION_OVERLAP_SECS: u64 = 30 * 24 * 60 * 60;
 
 #[derive(Debug, Serialize, Clone, PartialEq)]
 struct ListKeyInfoResponse {
@@ -41,6 +66,11 @@ struct KeyInfoResponse {
     node_id: String,
 
     metadata: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    superseded_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accepted_until: Option<u64>,
 }
 
 impl KeyInfoResponse {
@@ -53,29 +83,211 @@ impl KeyInfoResponse {
                 .iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
+            superseded_by: key_info.superseded_by().map(to_hex),
+            accepted_until: key_info.accepted_until().map(|accepted_until| {
+                accepted_until
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0)
+            }),
         }
     }
 }
 
+/// The body of a request that adds a key; the body of a request that updates a key has no public
+/// key, as it is given by the path.
+#[derive(Debug, Deserialize)]
+struct KeyInfoRequest {
+    #[serde(default)]
+    public_key: Option<String>,
+    node_id: String,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+/// The body of a request that rotates a key: the new key, its information, and how long the old
+/// key remains accepted, in seconds. The new key belongs to the old key's node if no node is given.
+#[derive(Debug, Deserialize)]
+struct KeyRotationRequest {
+    public_key: String,
+    #[serde(default)]
+    node_id: Option<String>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default = "default_overlap_secs")]
+    overlap_secs: u64,
+}
+
+fn default_overlap_secs() -> u64 {
+    DEFAULT_KEY_ROTATION_OVERLAP_SECS
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct KeyPermissionsResponse {
+    #[serde(serialize_with = "as_hex")]
+    public_key: Vec<u8>,
+    permissions: Vec<RolePermissionResponse>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct RolePermissionResponse {
+    role: String,
+    permitted: bool,
+}
+
 pub struct KeyRegistryManager {
     key_registry: Box<dyn KeyRegistry>,
+    key_permissions: Option<(Box<dyn KeyPermissionManager>, Vec<String>)>,
+    write_authorization: Option<Arc<WriteAuthorization>>,
 }
 
 impl KeyRegistryManager {
     pub fn new(key_registry: Box<dyn KeyRegistry>) -> Self {
-        Self { key_registry }
+        Self {
+            key_registry,
+            key_permissions: None,
+            write_authorization: None,
+        }
+    }
+
+    /// Provides the routes that change the key registry: `POST /keys` adds a key,
+    /// `PUT /keys/{public_key}` and `DELETE /keys/{public_key}` update and delete a key, and
+    /// `POST /keys/{public_key}/rotate` replaces a key with a new one.
+    ///
+    /// Each request must be signed, as described by `SIGNATURE_AUTHORIZATION_SCHEME`, with a key
+    /// that is registered to the given node and permitted to act in the `KEY_REGISTRY_ADMIN_ROLE`.
+    /// Only the keys of the given node may be changed, unless the request's key is also permitted
+    /// to act in the `KEY_REGISTRY_OTHER_NODES_ROLE`. A signed request is only accepted once, so it
+    /// cannot be replayed.
+    ///
+    /// As every key is permitted to act in every role by `AllowAllKeyPermissionManager`, these
+    /// routes should only be provided with a key permission manager that restricts the roles.
+    /// The changes are made to the manager's key registry, so they are seen by every clone of it,
+    /// such as the one used by the admin service.
+    pub fn with_write_authorization(
+        mut self,
+        node_id: String,
+        signature_verifier: Box<dyn SignatureVerifier>,
+        key_permission_manager: Box<dyn KeyPermissionManager>,
+    ) -> Self {
+        self.write_authorization = Some(Arc::new(WriteAuthorization {
+            node_id,
+            key_registry: self.key_registry.clone(),
+            signature_verifier: Mutex::new(signature_verifier),
+            key_permission_manager: Mutex::new(key_permission_manager),
+            accepted_requests: Mutex::new(HashMap::new()),
+        }));
+        self
+    }
+
+    /// Provides the `/keys/{public_key}/permissions` resource, which reports whether or not a key
+    /// is permitted to act in each of the given roles.
+    pub fn with_key_permission_manager(
+        mut self,
+        key_permission_manager: Box<dyn KeyPermissionManager>,
+        roles: Vec<String>,
+    ) -> Self {
+        self.key_permissions = Some((key_permission_manager, roles));
+        self
     }
 }
 
 impl RestResourceProvider for KeyRegistryManager {
     fn resources(&self) -> Vec<Resource> {
-        vec![
-            make_list_key_resources(self.key_registry.clone()),
-            make_fetch_key_resource(self.key_registry.clone()),
-        ]
+        let mut list_key_resource = make_list_key_resources(self.key_registry.clone());
+        let mut fetch_key_resource = make_fetch_key_resource(self.key_registry.clone());
+
+        if let Some(write_authorization) = &self.write_authorization {
+            list_key_resource = list_key_resource.add_method(
+                Method::Post,
+                make_add_key_handler(self.key_registry.clone(), write_authorization.clone()),
+            );
+            fetch_key_resource = fetch_key_resource
+                .add_method(
+                    Method::Put,
+                    make_update_key_handler(self.key_registry.clone(), write_authorization.clone()),
+                )
+                .add_method(
+                    Method::Delete,
+                    make_delete_key_handler(self.key_registry.clone(), write_authorization.clone()),
+                );
+        }
+
+        let mut resources = vec![list_key_resource, fetch_key_resource];
+
+        if let Some(write_authorization) = &self.write_authorization {
+            resources.push(Resource::build("/keys/{public_key}/rotate").add_method(
+                Method::Post,
+                make_rotate_key_handler(self.key_registry.clone(), write_authorization.clone()),
+            ));
+        }
+
+        if let Some((key_permission_manager, roles)) = &self.key_permissions {
+            resources.push(make_key_permissions_resource(
+                key_permission_manager.clone(),
+                roles.clone(),
+            ));
+        }
+
+        resources
     }
 }
 
+fn make_key_permissions_resource(
+    key_permission_manager: Box<dyn KeyPermissionManager>,
+    roles: Vec<String>,
+) -> Resource {
+    // The resource must be shareable between threads, which a KeyPermissionManager need not be
+    let key_permission_manager = Mutex::new(key_permission_manager);
+    Resource::build("/keys/{public_key}/permissions").add_method(Method::Get, move |req, _| {
+        let public_key = match parse_hex(req.match_info().get("public_key").unwrap_or("")) {
+            Ok(public_key) => public_key,
+            Err(err_msg) => {
+                return Box::new(
+                    HttpResponse::BadRequest()
+                        .json(json!({ "message": err_msg }))
+                        .into_future(),
+                )
+            }
+        };
+
+        let permission_manager = match key_permission_manager.lock() {
+            Ok(permission_manager) => permission_manager.clone(),
+            Err(_) => {
+                error!("Key permission manager lock was poisoned");
+                return Box::new(HttpResponse::InternalServerError().finish().into_future());
+            }
+        };
+        let roles = roles.clone();
+
+        Box::new(
+            web::block(move || {
+                roles
+                    .into_iter()
+                    .map(|role| {
+                        // KeyPermissionErrors cannot be sent between threads
+                        let permitted = permission_manager
+                            .is_permitted(&public_key, &role)
+                            .map_err(|err| err.to_string())?;
+                        Ok(RolePermissionResponse { role, permitted })
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+                    .map(|permissions| KeyPermissionsResponse {
+                        public_key,
+                        permissions,
+                    })
+            })
+            .then(|res| match res {
+                Ok(permissions) => Ok(HttpResponse::Ok().json(json!({ "data": permissions }))),
+                Err(err) => {
+                    error!("Unable to check key permissions: {}", err);
+                    Ok(HttpResponse::InternalServerError().into())
+                }
+            }),
+        )
+    })
+}
+
 fn make_fetch_key_resource(key_registry: Box<dyn KeyRegistry>) -> Resource {
     Resource::build("/keys/{public_key}").add_method(Method::Get, move |req, _| {
         let public_key = match parse_hex(req.match_info().get("public_key").unwrap_or("")) {
@@ -192,6 +404,515 @@ fn make_list_key_resources(key_registry: Box<dyn KeyRegistry>) -> Resource {
     })
 }
 
+/// Checks the signatures of the requests that change the key registry.
+struct WriteAuthorization {
+    node_id: String,
+    key_registry: Box<dyn KeyRegistry>,
+    // The handlers must be shareable between threads, which these need not be
+    signature_verifier: Mutex<Box<dyn SignatureVerifier>>,
+    key_permission_manager: Mutex<Box<dyn KeyPermissionManager>>,
+    // The public key and SHA-256 digest of the signed message of each request accepted within the
+    // clock skew, with the time at which it was signed, so that they are not accepted again
+    accepted_requests: Mutex<HashMap<(Vec<u8>, [u8; 32]), u64>>,
+}
+
+impl WriteAuthorization {
+    /// Checks that the request was recently signed by a key of this node that is permitted to
+    /// change the key registry, and that the request has not already been accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `authorization` - The value of the request's `Authorization` header
+    /// * `method` - The request's method
+    /// * `path` - The request's path
+    /// * `body` - The request's body
+    /// * `now` - The time at which the request is received
+    fn authorize(
+        &self,
+        authorization: &str,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Result<AuthorizedKey, KeyRegistryWriteError> {
+        let authorization = authorization.trim();
+        if !authorization.starts_with(SIGNATURE_AUTHORIZATION_SCHEME) {
+            return Err(KeyRegistryWriteError::Unauthorized(format!(
+                "Authorization must use the {} scheme",
+                SIGNATURE_AUTHORIZATION_SCHEME
+            )));
+        }
+        let credentials = authorization[SIGNATURE_AUTHORIZATION_SCHEME.len()..].trim();
+
+        let mut parts = credentials.split(':');
+        let (public_key, timestamp, signature) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(public_key), Some(timestamp), Some(signature), None) => {
+                    (public_key, timestamp, signature)
+                }
+                _ => {
+                    return Err(KeyRegistryWriteError::Unauthorized(
+                        "Authorization must be <public key>:<timestamp>:<signature>".into(),
+                    ))
+                }
+            };
+        let public_key = parse_hex(public_key).map_err(KeyRegistryWriteError::Unauthorized)?;
+        let signature = parse_hex(signature).map_err(KeyRegistryWriteError::Unauthorized)?;
+        let timestamp = timestamp.parse::<u64>().map_err(|_| {
+            KeyRegistryWriteError::Unauthorized(format!("Invalid timestamp: {}", timestamp))
+        })?;
+
+        let now_secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let skew = if now_secs > timestamp {
+            now_secs - timestamp
+        } else {
+            timestamp - now_secs
+        };
+        if skew > MAX_REQUEST_CLOCK_SKEW_SECS {
+            return Err(KeyRegistryWriteError::Unauthorized(
+                "Request was not signed recently".into(),
+            ));
+        }
+
+        let message = signed_request_message(method, path, timestamp, body);
+        let verified = self
+            .signature_verifier
+            .lock()
+            .map_err(|_| {
+                KeyRegistryWriteError::InternalError("Signature verifier lock was poisoned".into())
+            })?
+            .verify(&message, &signature, &public_key)
+            .map_err(|err| KeyRegistryWriteError::Unauthorized(err.to_string()))?;
+        if !verified {
+            return Err(KeyRegistryWriteError::Unauthorized(
+                "Invalid request signature".into(),
+            ));
+        }
+
+        let key_info = self
+            .key_registry
+            .get_key(&public_key)
+            .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+            .ok_or_else(|| {
+                KeyRegistryWriteError::Forbidden(format!(
+                    "{} is not registered",
+                    to_hex(&public_key)
+                ))
+            })?;
+        if key_info.associated_node_id() != self.node_id || !key_info.is_accepted_at(now) {
+            return Err(KeyRegistryWriteError::Forbidden(format!(
+                "{} is not an accepted key of node {}",
+                to_hex(&public_key),
+                self.node_id
+            )));
+        }
+
+        if !self.is_permitted(&public_key, KEY_REGISTRY_ADMIN_ROLE)? {
+            return Err(KeyRegistryWriteError::Forbidden(format!(
+                "{} is not permitted to change the key registry",
+                to_hex(&public_key)
+            )));
+        }
+
+        let mut accepted_requests = self.accepted_requests.lock().map_err(|_| {
+            KeyRegistryWriteError::InternalError("Accepted requests lock was poisoned".into())
+        })?;
+        // Requests signed outside of the clock skew are rejected before they get here
+        accepted_requests.retain(|_, signed_at| {
+            now_secs.saturating_sub(*signed_at) <= MAX_REQUEST_CLOCK_SKEW_SECS
+        });
+        if accepted_requests
+            .insert((public_key.clone(), sha256(&message)), timestamp)
+            .is_some()
+        {
+            return Err(KeyRegistryWriteError::Unauthorized(
+                "Request has already been made".into(),
+            ));
+        }
+
+        Ok(AuthorizedKey { public_key })
+    }
+
+    /// Checks that a request signed with the given key may change the keys of the given node.
+    fn authorize_node(
+        &self,
+        authorized_key: &AuthorizedKey,
+        node_id: &str,
+    ) -> Result<(), KeyRegistryWriteError> {
+        if node_id == self.node_id
+            || self.is_permitted(&authorized_key.public_key, KEY_REGISTRY_OTHER_NODES_ROLE)?
+        {
+            Ok(())
+        } else {
+            Err(KeyRegistryWriteError::Forbidden(format!(
+                "{} is not permitted to change the keys of node {}",
+                to_hex(&authorized_key.public_key),
+                node_id
+            )))
+        }
+    }
+
+    fn is_permitted(&self, public_key: &[u8], role: &str) -> Result<bool, KeyRegistryWriteError> {
+        self.key_permission_manager
+            .lock()
+            .map_err(|_| {
+                KeyRegistryWriteError::InternalError(
+                    "Key permission manager lock was poisoned".into(),
+                )
+            })?
+            .is_permitted(public_key, role)
+            .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))
+    }
+}
+
+/// The key an authorized request was signed with.
+#[derive(Debug)]
+struct AuthorizedKey {
+    public_key: Vec<u8>,
+}
+
+/// An error that rejects a request that changes the key registry.
+#[derive(Debug)]
+enum KeyRegistryWriteError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    InternalError(String),
+}
+
+impl KeyRegistryWriteError {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            KeyRegistryWriteError::BadRequest(message) => {
+                HttpResponse::BadRequest().json(json!({ "message": message }))
+            }
+            KeyRegistryWriteError::Unauthorized(message) => {
+                HttpResponse::Unauthorized().json(json!({ "message": message }))
+            }
+            KeyRegistryWriteError::Forbidden(message) => {
+                HttpResponse::Forbidden().json(json!({ "message": message }))
+            }
+            KeyRegistryWriteError::NotFound(message) => {
+                HttpResponse::NotFound().json(json!({ "message": message }))
+            }
+            KeyRegistryWriteError::Conflict(message) => {
+                HttpResponse::Conflict().json(json!({ "message": message }))
+            }
+            KeyRegistryWriteError::InternalError(message) => {
+                error!("Unable to change key registry: {}", message);
+                HttpResponse::InternalServerError().into()
+            }
+        }
+    }
+}
+
+/// Makes a handler for a request that changes the key registry, which is run once the request is
+/// authorized.
+///
+/// The operation is given the request's public key path parameter, if any, its body, and a check
+/// that the request may change the keys of a node, and returns the resulting key information.
+fn make_write_handler<F>(
+    key_registry: Box<dyn KeyRegistry>,
+    write_authorization: Arc<WriteAuthorization>,
+    operation: F,
+) -> HandlerFunction
+where
+    F: Fn(
+            &mut dyn KeyRegistry,
+            Option<Vec<u8>>,
+            &[u8],
+            &dyn Fn(&str) -> Result<(), KeyRegistryWriteError>,
+        ) -> Result<KeyInfo, KeyRegistryWriteError>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+{
+    Box::new(move |req: HttpRequest, payload: web::Payload| {
+        let public_key = match req.match_info().get("public_key").map(parse_hex) {
+            Some(Ok(public_key)) => Some(public_key),
+            Some(Err(err_msg)) => {
+                return Box::new(
+                    HttpResponse::BadRequest()
+                        .json(json!({ "message": err_msg }))
+                        .into_future(),
+                )
+            }
+            None => None,
+        };
+        let authorization = match req
+            .headers()
+            .get("Authorization")
+            .map(|value| value.to_str())
+        {
+            Some(Ok(authorization)) => authorization.to_string(),
+            _ => {
+                return Box::new(
+                    HttpResponse::Unauthorized()
+                        .json(json!({ "message": "A valid Authorization header is required" }))
+                        .into_future(),
+                )
+            }
+        };
+        let method = req.method().as_str().to_string();
+        let path = req.path().to_string();
+
+        let mut key_registry = key_registry.clone();
+        let write_authorization = write_authorization.clone();
+        let operation = operation.clone();
+
+        Box::new(into_bytes(payload).and_then(move |body| {
+            web::block(move || {
+                let authorized_key = write_authorization.authorize(
+                    &authorization,
+                    &method,
+                    &path,
+                    &body,
+                    SystemTime::now(),
+                )?;
+                operation(&mut *key_registry, public_key, &body, &|node_id: &str| {
+                    write_authorization.authorize_node(&authorized_key, node_id)
+                })
+            })
+            .then(|res| match res {
+                Ok(key_info) => {
+                    Ok(HttpResponse::Ok().json(json!({ "data": KeyInfoResponse::new(&key_info) })))
+                }
+                Err(BlockingError::Error(err)) => Ok(err.into_response()),
+                Err(BlockingError::Canceled) => {
+                    error!("Key registry change was canceled");
+                    Ok(HttpResponse::InternalServerError().into())
+                }
+            })
+        }))
+    })
+}
+
+fn make_add_key_handler(
+    key_registry: Box<dyn KeyRegistry>,
+    write_authorization: Arc<WriteAuthorization>,
+) -> HandlerFunction {
+    make_write_handler(
+        key_registry,
+        write_authorization,
+        |key_registry, _, body, authorize_node| {
+            let request = parse_key_info_request(body)?;
+            authorize_node(&request.node_id)?;
+            let public_key = match request.public_key.as_ref() {
+                Some(public_key) if !public_key.is_empty() => {
+                    parse_hex(public_key).map_err(KeyRegistryWriteError::BadRequest)?
+                }
+                _ => {
+                    return Err(KeyRegistryWriteError::BadRequest(
+                        "A public key is required".into(),
+                    ))
+                }
+            };
+
+            if key_registry
+                .get_key(&public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .is_some()
+            {
+                return Err(KeyRegistryWriteError::Conflict(format!(
+                    "{} is already registered",
+                    to_hex(&public_key)
+                )));
+            }
+
+            let key_info = to_key_info(public_key, request, None);
+            key_registry
+                .save_key(key_info.clone())
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?;
+            Ok(key_info)
+        },
+    )
+}
+
+fn make_update_key_handler(
+    key_registry: Box<dyn KeyRegistry>,
+    write_authorization: Arc<WriteAuthorization>,
+) -> HandlerFunction {
+    make_write_handler(
+        key_registry,
+        write_authorization,
+        |key_registry, public_key, body, authorize_node| {
+            let public_key = public_key.unwrap_or_default();
+            let request = parse_key_info_request(body)?;
+            authorize_node(&request.node_id)?;
+
+            let existing_key_info = key_registry
+                .get_key(&public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .ok_or_else(|| {
+                    KeyRegistryWriteError::NotFound(format!(
+                        "{} is not registered",
+                        to_hex(&public_key)
+                    ))
+                })?;
+            authorize_node(existing_key_info.associated_node_id())?;
+
+            // A rotated key stays superseded
+            let key_info = to_key_info(public_key, request, Some(&existing_key_info));
+            key_registry
+                .save_key(key_info.clone())
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?;
+            Ok(key_info)
+        },
+    )
+}
+
+fn make_delete_key_handler(
+    key_registry: Box<dyn KeyRegistry>,
+    write_authorization: Arc<WriteAuthorization>,
+) -> HandlerFunction {
+    make_write_handler(
+        key_registry,
+        write_authorization,
+        |key_registry, public_key, _, authorize_node| {
+            let public_key = public_key.unwrap_or_default();
+            let key_info = key_registry
+                .get_key(&public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .ok_or_else(|| {
+                    KeyRegistryWriteError::NotFound(format!(
+                        "{} is not registered",
+                        to_hex(&public_key)
+                    ))
+                })?;
+            authorize_node(key_info.associated_node_id())?;
+
+            key_registry
+                .delete_key(&public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .ok_or_else(|| {
+                    KeyRegistryWriteError::NotFound(format!(
+                        "{} is not registered",
+                        to_hex(&public_key)
+                    ))
+                })
+        },
+    )
+}
+
+fn make_rotate_key_handler(
+    key_registry: Box<dyn KeyRegistry>,
+    write_authorization: Arc<WriteAuthorization>,
+) -> HandlerFunction {
+    make_write_handler(
+        key_registry,
+        write_authorization,
+        |key_registry, old_public_key, body, authorize_node| {
+            let old_public_key = old_public_key.unwrap_or_default();
+            let request: KeyRotationRequest = serde_json::from_slice(body).map_err(|err| {
+                KeyRegistryWriteError::BadRequest(format!("Unable to parse key rotation: {}", err))
+            })?;
+            let new_public_key =
+                parse_hex(&request.public_key).map_err(KeyRegistryWriteError::BadRequest)?;
+            if new_public_key.is_empty() {
+                return Err(KeyRegistryWriteError::BadRequest(
+                    "A new public key is required".into(),
+                ));
+            }
+
+            if request.overlap_secs > MAX_KEY_ROTATION_OVERLAP_SECS {
+                return Err(KeyRegistryWriteError::BadRequest(format!(
+                    "Overlap of {} seconds exceeds the maximum of {} seconds",
+                    request.overlap_secs, MAX_KEY_ROTATION_OVERLAP_SECS
+                )));
+            }
+
+            let old_key_info = key_registry
+                .get_key(&old_public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .ok_or_else(|| {
+                    KeyRegistryWriteError::NotFound(format!(
+                        "{} is not registered",
+                        to_hex(&old_public_key)
+                    ))
+                })?;
+            authorize_node(old_key_info.associated_node_id())?;
+            if let Some(superseded_by) = old_key_info.superseded_by() {
+                return Err(KeyRegistryWriteError::Conflict(format!(
+                    "{} has already been superseded by {}",
+                    to_hex(&old_public_key),
+                    to_hex(superseded_by)
+                )));
+            }
+            if key_registry
+                .get_key(&new_public_key)
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?
+                .is_some()
+            {
+                return Err(KeyRegistryWriteError::Conflict(format!(
+                    "{} is already registered",
+                    to_hex(&new_public_key)
+                )));
+            }
+
+            let node_id = request
+                .node_id
+                .unwrap_or_else(|| old_key_info.associated_node_id().to_string());
+            if node_id != old_key_info.associated_node_id() {
+                return Err(KeyRegistryWriteError::BadRequest(format!(
+                    "{} belongs to node {}, not {}",
+                    to_hex(&old_public_key),
+                    old_key_info.associated_node_id(),
+                    node_id
+                )));
+            }
+
+            let mut builder = KeyInfo::builder(new_public_key, node_id);
+            for (key, value) in request.metadata.into_iter() {
+                builder = builder.with_metadata(key, value);
+            }
+            let new_key_info = builder.build();
+
+            key_registry
+                .rotate_key(
+                    &old_public_key,
+                    new_key_info.clone(),
+                    Duration::from_secs(request.overlap_secs),
+                )
+                .map_err(|err| KeyRegistryWriteError::InternalError(err.to_string()))?;
+            Ok(new_key_info)
+        },
+    )
+}
+
+fn parse_key_info_request(body: &[u8]) -> Result<KeyInfoRequest, KeyRegistryWriteError> {
+    serde_json::from_slice(body).map_err(|err| {
+        KeyRegistryWriteError::BadRequest(format!("Unable to parse key info: {}", err))
+    })
+}
+
+/// Builds the key information of a request, keeping the supersession of the existing key, if any.
+fn to_key_info(
+    public_key: Vec<u8>,
+    request: KeyInfoRequest,
+    existing_key_info: Option<&KeyInfo>,
+) -> KeyInfo {
+    let mut builder = KeyInfo::builder(public_key, request.node_id);
+    for (key, value) in request.metadata.into_iter() {
+        builder = builder.with_metadata(key, value);
+    }
+
+    if let Some(key_info) = existing_key_info {
+        if let (Some(superseded_by), Some(accepted_until)) =
+            (key_info.superseded_by(), key_info.accepted_until())
+        {
+            builder = builder.with_supersession(superseded_by.to_vec(), accepted_until);
+        }
+    }
+
+    builder.build()
+}
+
 fn as_hex<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -219,3 +940,182 @@ fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keys::{
+        insecure::AllowAllKeyPermissionManager, storage::StorageKeyRegistry, KeyPermissionError,
+    };
+    use crate::signing::{
+        hash::{HashSigner, HashVerifier},
+        Signer,
+    };
+
+    const PATH: &str = "/keys/abcd";
+    const BODY: &[u8] = b"{\"node_id\": \"node-a\"}";
+
+    /// Test that a request signed by an accepted key of the node is authorized.
+    #[test]
+    fn test_authorize() {
+        let authorization = setup_write_authorization("node-a");
+        let now = SystemTime::now();
+
+        let header = sign_request(&HashSigner, now, BODY);
+
+        assert!(authorization
+            .authorize(&header, "PUT", PATH, BODY, now)
+            .is_ok());
+    }
+
+    /// Test that a request signed too long before it is received is unauthorized.
+    #[test]
+    fn test_authorize_stale_request() {
+        let authorization = setup_write_authorization("node-a");
+        let now = SystemTime::now();
+
+        let header = sign_request(
+            &HashSigner,
+            now - Duration::from_secs(MAX_REQUEST_CLOCK_SKEW_SECS + 60),
+            BODY,
+        );
+
+        match authorization.authorize(&header, "PUT", PATH, BODY, now) {
+            Err(KeyRegistryWriteError::Unauthorized(_)) => (),
+            res => panic!("Expected unauthorized, got {:?}", res),
+        }
+    }
+
+    /// Test that a request that has already been authorized is unauthorized when it is replayed,
+    /// while a request signed at a different time is authorized.
+    #[test]
+    fn test_authorize_replayed_request() {
+        let authorization = setup_write_authorization("node-a");
+        let now = SystemTime::now();
+
+        let header = sign_request(&HashSigner, now, BODY);
+        assert!(authorization
+            .authorize(&header, "PUT", PATH, BODY, now)
+            .is_ok());
+
+        match authorization.authorize(&header, "PUT", PATH, BODY, now + Duration::from_secs(1)) {
+            Err(KeyRegistryWriteError::Unauthorized(_)) => (),
+            res => panic!("Expected unauthorized, got {:?}", res),
+        }
+
+        let header = sign_request(&HashSigner, now + Duration::from_secs(1), BODY);
+        assert!(authorization
+            .authorize(&header, "PUT", PATH, BODY, now + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    /// Test that a request whose signature does not match its content is unauthorized.
+    #[test]
+    fn test_authorize_invalid_signature() {
+        let authorization = setup_write_authorization("node-a");
+        let now = SystemTime::now();
+
+        let header = sign_request(&HashSigner, now, b"{\"node_id\": \"node-b\"}");
+
+        match authorization.authorize(&header, "PUT", PATH, BODY, now) {
+            Err(KeyRegistryWriteError::Unauthorized(_)) => (),
+            res => panic!("Expected unauthorized, got {:?}", res),
+        }
+    }
+
+    /// Test that a request signed by a key of another node is forbidden.
+    #[test]
+    fn test_authorize_key_of_other_node() {
+        let authorization = setup_write_authorization("node-b");
+        let now = SystemTime::now();
+
+        let header = sign_request(&HashSigner, now, BODY);
+
+        match authorization.authorize(&header, "PUT", PATH, BODY, now) {
+            Err(KeyRegistryWriteError::Forbidden(_)) => (),
+            res => panic!("Expected forbidden, got {:?}", res),
+        }
+    }
+
+    /// Test that an authorized request may only change the keys of other nodes if its key is
+    /// permitted to act in the `KEY_REGISTRY_OTHER_NODES_ROLE`.
+    #[test]
+    fn test_authorize_node() {
+        let mut authorization = setup_write_authorization("node-a");
+        authorization.key_permission_manager =
+            Mutex::new(Box::new(RoleKeyPermissionManager(vec![
+                KEY_REGISTRY_ADMIN_ROLE,
+            ])));
+        let now = SystemTime::now();
+
+        let header = sign_request(&HashSigner, now, BODY);
+        let authorized_key = authorization
+            .authorize(&header, "PUT", PATH, BODY, now)
+            .unwrap();
+
+        assert!(authorization
+            .authorize_node(&authorized_key, "node-a")
+            .is_ok());
+        match authorization.authorize_node(&authorized_key, "node-b") {
+            Err(KeyRegistryWriteError::Forbidden(_)) => (),
+            res => panic!("Expected forbidden, got {:?}", res),
+        }
+
+        authorization.key_permission_manager =
+            Mutex::new(Box::new(RoleKeyPermissionManager(vec![
+                KEY_REGISTRY_ADMIN_ROLE,
+                KEY_REGISTRY_OTHER_NODES_ROLE,
+            ])));
+        assert!(authorization
+            .authorize_node(&authorized_key, "node-b")
+            .is_ok());
+    }
+
+    /// A `KeyPermissionManager` that permits every key to act in the given roles only.
+    #[derive(Clone)]
+    struct RoleKeyPermissionManager(Vec<&'static str>);
+
+    impl KeyPermissionManager for RoleKeyPermissionManager {
+        fn is_permitted(&self, _: &[u8], role: &str) -> Result<bool, KeyPermissionError> {
+            Ok(self.0.contains(&role))
+        }
+
+        fn clone_box(&self) -> Box<dyn KeyPermissionManager> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Creates the write authorization of a node whose key registry contains the `HashSigner`'s
+    /// key, registered to node-a.
+    fn setup_write_authorization(node_id: &str) -> WriteAuthorization {
+        let mut key_registry = StorageKeyRegistry::new("memory".to_string()).unwrap();
+        key_registry
+            .save_key(KeyInfo::builder(HashSigner.public_key().to_vec(), "node-a".into()).build())
+            .unwrap();
+
+        WriteAuthorization {
+            node_id: node_id.into(),
+            key_registry: Box::new(key_registry),
+            signature_verifier: Mutex::new(Box::new(HashVerifier)),
+            key_permission_manager: Mutex::new(Box::new(AllowAllKeyPermissionManager)),
+            accepted_requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the `Authorization` header of a PUT request to `PATH`, signed at the given time.
+    fn sign_request(signer: &dyn Signer, signed_at: SystemTime, body: &[u8]) -> String {
+        let timestamp = signed_at.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let signature = signer
+            .sign(&signed_request_message("PUT", PATH, timestamp, body))
+            .unwrap();
+
+        format!(
+            "{} {}:{}:{}",
+            SIGNATURE_AUTHORIZATION_SCHEME,
+            to_hex(signer.public_key()),
+            timestamp,
+            to_hex(&signature)
+        )
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Role-based `KeyPermissionManager` implementations.
+//!
+//! The roles of a public key are read either from the `roles` metadata of its entry in a
+//! `KeyRegistry`, or from a policy file that maps public keys to roles. A policy file is a YAML
+//! file of the form:
+//!
+//! ```yaml
+//! keys:
+//!   "0384781f...": [proposer, voter]
+//!   "02c4d1ee...": [voter]
+//! ```
+//!
+//! The policy file is reloaded whenever its modified time or size changes.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde_derive::Deserialize;
+
+use crate::file_version::file_version;
+use crate::hex::{parse_hex, to_hex};
+use crate::mutex_lock_unwrap;
+
+use super::{KeyPermissionError, KeyPermissionManager, KeyRegistry};
+
+/// The metadata key of a `KeyInfo` that holds the comma-separated roles of the public key.
+pub const ROLES_METADATA_KEY: &str = "roles";
+
+/// A KeyPermissionManager that permits a public key to act in the roles listed in the `roles`
+/// metadata of its entry in a `KeyRegistry`.
+///
+/// Keys that are not in the registry are not permitted to act in any role. The registry is read on
+/// every check, so changes to a key's roles take effect immediately.
+#[derive(Clone)]
+pub struct KeyRegistryPermissionManager {
+    key_registry: Box<dyn KeyRegistry>,
+}
+
+impl KeyRegistryPermissionManager {
+    pub fn new(key_registry: Box<dyn KeyRegistry>) -> Self {
+        Self { key_registry }
+    }
+}
+
+impl KeyPermissionManager for KeyRegistryPermissionManager {
+    fn is_permitted(&self, public_key: &[u8], role: &str) -> Result<bool, KeyPermissionError> {
+        let key_info = self
+            .key_registry
+            .get_key(public_key)
+            .map_err(|err| KeyPermissionError {
+                context: format!("unable to read key info for {}", to_hex(public_key)),
+                source: Some(Box::new(err)),
+            })?;
+
+        Ok(key_info
+            .and_then(|key_info| {
+                key_info
+                    .get_metadata(ROLES_METADATA_KEY)
+                    .map(|roles| roles.split(',').any(|key_role| key_role.trim() == role))
+            })
+            .unwrap_or(false))
+    }
+
+    fn clone_box(&self) -> Box<dyn KeyPermissionManager> {
+        Box::new(self.clone())
+    }
+}
+
+/// A KeyPermissionManager that permits public keys to act in the roles listed for them in a
+/// policy file.
+///
+/// The policy file is checked for modifications before each permission check, and reloaded if it
+/// has changed. If a modified policy file cannot be loaded, the previous policy remains in effect.
+/// Clones share the same policy.
+#[derive(Clone)]
+pub struct PolicyFileKeyPermissionManager {
+    state: Arc<Mutex<PolicyState>>,
+}
+
+struct PolicyState {
+    path: String,
+    version: Option<(SystemTime, u64)>,
+    roles: HashMap<Vec<u8>, BTreeSet<String>>,
+}
+
+#[derive(Deserialize)]
+struct PolicyFile {
+    #[serde(default)]
+    keys: HashMap<String, Vec<String>>,
+}
+
+impl PolicyFileKeyPermissionManager {
+    /// Constructs a new PolicyFileKeyPermissionManager from the policy file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `KeyPermissionError` if the policy file cannot be loaded.
+    pub fn new(path: &str) -> Result<Self, KeyPermissionError> {
+        let version = file_version(path);
+        let roles = load_policy(path)?;
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(PolicyState {
+                path: path.to_string(),
+                version,
+                roles,
+            })),
+        })
+    }
+}
+
+impl PolicyState {
+    fn reload_if_modified(&mut self) {
+        let version = file_version(&self.path);
+        if version == self.version {
+            return;
+        }
+
+        // Only remember the new version once the file has been loaded, so that a file that
+        // is being written is loaded again on the next check
+        match load_policy(&self.path) {
+            Ok(roles) => {
+                debug!("Reloaded key permission policy from {}", self.path);
+                self.roles = roles;
+                self.version = version;
+            }
+            Err(err) => error!(
+                "Unable to reload key permission policy; keeping the previous policy: {}",
+                err
+            ),
+        }
+    }
+}
+
+impl KeyPermissionManager for PolicyFileKeyPermissionManager {
+    fn is_permitted(&self, public_key: &[u8], role: &str) -> Result<bool, KeyPermissionError> {
+        let mut state = mutex_lock_unwrap!(self.state);
+        state.reload_if_modified();
+
+        Ok(state
+            .roles
+            .get(public_key)
+            .map(|roles| roles.contains(role))
+            .unwrap_or(false))
+    }
+
+    fn clone_box(&self) -> Box<dyn KeyPermissionManager> {
+        Box::new(self.clone())
+    }
+}
+
+fn load_policy(path: &str) -> Result<HashMap<Vec<u8>, BTreeSet<String>>, KeyPermissionError> {
+    let file = File::open(path).map_err(|err| KeyPermissionError {
+        context: format!("unable to open key permission policy {}", path),
+        source: Some(Box::new(err)),
+    })?;
+
+    let policy: PolicyFile = serde_yaml::from_reader(file).map_err(|err| KeyPermissionError {
+        context: format!("unable to parse key permission policy {}", path),
+        source: Some(Box::new(err)),
+    })?;
+
+    policy
+        .keys
+        .into_iter()
+        .map(|(public_key, roles)| {
+            let public_key = parse_hex(&public_key).map_err(|err| KeyPermissionError {
+                context: format!("invalid public key {} in {}", public_key, path),
+                source: Some(Box::new(err)),
+            })?;
+            Ok((public_key, roles.into_iter().collect()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use tempdir::TempDir;
+
+    use crate::keys::{storage::StorageKeyRegistry, KeyInfo};
+
+    /// Test that the KeyRegistryPermissionManager permits keys to act in the roles listed in their
+    /// metadata, and does not permit keys without roles or without an entry in the registry.
+    #[test]
+    fn test_key_registry_permissions() {
+        let mut key_registry =
+            StorageKeyRegistry::new("memory".into()).expect("Unable to create registry");
+        key_registry
+            .save_keys(vec![
+                KeyInfo::builder(b"alice".to_vec(), "node-a".into())
+                    .with_metadata(ROLES_METADATA_KEY, "proposer, voter")
+                    .build(),
+                KeyInfo::builder(b"bob".to_vec(), "node-b".into()).build(),
+            ])
+            .expect("Unable to save keys");
+
+        let permission_manager = KeyRegistryPermissionManager::new(Box::new(key_registry));
+
+        assert!(permission_manager
+            .is_permitted(b"alice", "proposer")
+            .unwrap());
+        assert!(permission_manager.is_permitted(b"alice", "voter").unwrap());
+        assert!(!permission_manager.is_permitted(b"alice", "admin").unwrap());
+        assert!(!permission_manager.is_permitted(b"bob", "voter").unwrap());
+        assert!(!permission_manager.is_permitted(b"carol", "voter").unwrap());
+    }
+
+    /// Test that the PolicyFileKeyPermissionManager permits keys to act in the roles listed in the
+    /// policy file, that it reloads the file once it has been modified, and that it keeps the
+    /// previous policy if the modified file is invalid.
+    #[test]
+    fn test_policy_file_permissions() {
+        let temp_dir = TempDir::new("test_policy_file_permissions").expect("Unable to create dir");
+        let path = temp_dir
+            .path()
+            .join("key_permissions.yaml")
+            .to_str()
+            .expect("Path is not valid UTF-8")
+            .to_string();
+
+        write_policy(&path, "keys:\n  \"0a0b\": [proposer, voter]\n");
+
+        let permission_manager =
+            PolicyFileKeyPermissionManager::new(&path).expect("Unable to load policy");
+        assert!(permission_manager
+            .is_permitted(&[10, 11], "proposer")
+            .unwrap());
+        assert!(permission_manager.is_permitted(&[10, 11], "voter").unwrap());
+        assert!(!permission_manager.is_permitted(&[12], "voter").unwrap());
+
+        write_policy(&path, "keys:\n  \"0a0b\": [voter]\n  \"0c\": [voter]\n");
+
+        assert!(!permission_manager
+            .is_permitted(&[10, 11], "proposer")
+            .unwrap());
+        assert!(permission_manager.is_permitted(&[12], "voter").unwrap());
+
+        write_policy(&path, "keys: [not, a, map]\n");
+
+        assert!(permission_manager.is_permitted(&[12], "voter").unwrap());
+    }
+
+    /// Test that a PolicyFileKeyPermissionManager cannot be created from a missing or invalid
+    /// policy file.
+    #[test]
+    fn test_invalid_policy_file() {
+        let temp_dir = TempDir::new("test_invalid_policy_file").expect("Unable to create dir");
+        let path = temp_dir
+            .path()
+            .join("key_permissions.yaml")
+            .to_str()
+            .expect("Path is not valid UTF-8")
+            .to_string();
+
+        assert!(PolicyFileKeyPermissionManager::new(&path).is_err());
+
+        write_policy(&path, "keys:\n  \"not hex\": [voter]\n");
+        assert!(PolicyFileKeyPermissionManager::new(&path).is_err());
+    }
+
+    fn write_policy(path: &str, contents: &str) {
+        let mut file = File::create(path).expect("Unable to create policy file");
+        file.write_all(contents.as_bytes())
+            .expect("Unable to write policy file");
+    }
+}
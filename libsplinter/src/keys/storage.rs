@@ -17,6 +17,7 @@
 use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde_derive::{Deserialize, Serialize};
 
@@ -68,11 +69,23 @@ impl KeyRegistry for StorageKeyRegistry {
         self.write_key_registry(&key_registry)
     }
 
-    fn delete_key(&mut self, _public_key: &[u8]) -> Result<Option<KeyInfo>, KeyRegistryError> {
-        Err(KeyRegistryError {
-            context: "Operation not supported".into(),
-            source: None,
-        })
+    fn delete_key(&mut self, public_key: &[u8]) -> Result<Option<KeyInfo>, KeyRegistryError> {
+        let mut key_registry =
+            self.persisted_key_registry
+                .write()
+                .map_err(|_| KeyRegistryError {
+                    context: "Persisted Key Registry lock was poisoned".into(),
+                    source: None,
+                })?;
+
+        let deleted_key_info = match key_registry.keys.remove(&to_hex(public_key)) {
+            Some(persisted_key_info) => persisted_key_info.try_into()?,
+            None => return Ok(None),
+        };
+
+        self.write_key_registry(&key_registry)?;
+
+        Ok(Some(deleted_key_info))
     }
 
     fn get_key(&self, public_key: &[u8]) -> Result<Option<KeyInfo>, KeyRegistryError> {
@@ -220,6 +233,19 @@ impl PersistedKeyRegistry {
                 .iter()
                 .map(|(key, value)| (key.clone(), value.clone()))
                 .collect::<BTreeMap<String, String>>(),
+            superseded_by: key_info.superseded_by().map(to_hex),
+            accepted_until: key_info
+                .accepted_until()
+                .map(|accepted_until| {
+                    accepted_until
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_secs())
+                        .map_err(|err| KeyRegistryError {
+                            context: format!("Invalid end of overlap window of {}", hex_key),
+                            source: Some(Box::new(err)),
+                        })
+                })
+                .transpose()?,
         };
         self.keys.insert(hex_key, persisted_key_info);
         Ok(())
@@ -234,6 +260,15 @@ struct PersistedKeyInfo {
     #[serde(default = "BTreeMap::new")]
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     metadata: BTreeMap<String, String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    superseded_by: Option<String>,
+
+    /// The end of the overlap window of a superseded key, in seconds since the Unix epoch.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accepted_until: Option<u64>,
 }
 
 impl TryInto<KeyInfo> for PersistedKeyInfo {
@@ -252,6 +287,29 @@ impl TryInto<KeyInfo> for PersistedKeyInfo {
             builder = builder.with_metadata(key, value);
         }
 
+        match (self.superseded_by, self.accepted_until) {
+            (Some(superseded_by), Some(accepted_until)) => {
+                builder = builder.with_supersession(
+                    parse_hex(&superseded_by).map_err(|err| KeyRegistryError {
+                        context: format!("Unable to parse public key: {}", superseded_by),
+                        source: Some(Box::new(err)),
+                    })?,
+                    UNIX_EPOCH + Duration::from_secs(accepted_until),
+                );
+            }
+            (None, None) => (),
+            _ => {
+                return Err(KeyRegistryError {
+                    context: format!(
+                        "Superseded key {} must have both a replacement and an end of its \
+                         overlap window",
+                        self.public_key
+                    ),
+                    source: None,
+                })
+            }
+        }
+
         Ok(builder.build())
     }
 }
@@ -328,6 +386,114 @@ mod tests {
         assert_eq!(Some(&"value1".into()), key_info.get_metadata("meta1"));
     }
 
+    /// Test that rotating a key through the KeyRegistry trait supersedes the old key, and that
+    /// the supersession is persisted.
+    ///
+    /// 1. register a key and rotate it with an overlap window of one hour
+    /// 2. test that the old key is superseded by the new key and accepted during the window only
+    /// 3. test that the rotation is read back from the file
+    /// 4. test that the old key cannot be rotated again, that a key cannot be rotated to a key of
+    ///    another node, and that an overlap past the end of time is refused
+    #[test]
+    fn test_rotate_key() {
+        let temp_dir = TempDir::new("test_rotate_key").unwrap();
+        let temp_dir_path = temp_dir.path().join("key_reg.yaml");
+        let location = temp_dir_path
+            .to_str()
+            .expect("could not create path str")
+            .to_string();
+
+        let mut registry = StorageKeyRegistry::new(location.clone()).expect("could not load file");
+        registry
+            .save_key(KeyInfo::builder(b"old key".to_vec(), "my-node".into()).build())
+            .expect("unable to save key");
+
+        let before_rotation = SystemTime::now();
+        registry
+            .rotate_key(
+                b"old key",
+                KeyInfo::builder(b"new key".to_vec(), "my-node".into()).build(),
+                Duration::from_secs(3600),
+            )
+            .expect("unable to rotate key");
+
+        let mut registry = StorageKeyRegistry::new(location).expect("could not reload file");
+        let old_key_info = registry
+            .get_key(b"old key")
+            .expect("unable to get key info")
+            .expect("Key info for old key was none");
+        assert_eq!(Some(&b"new key"[..]), old_key_info.superseded_by());
+        assert!(old_key_info.is_accepted_at(before_rotation));
+        assert!(!old_key_info.is_accepted_at(before_rotation + Duration::from_secs(7200)));
+
+        let new_key_info = registry
+            .get_key(b"new key")
+            .expect("unable to get key info")
+            .expect("Key info for new key was none");
+        assert_eq!("my-node", new_key_info.associated_node_id());
+        assert_eq!(None, new_key_info.superseded_by());
+
+        assert!(registry
+            .rotate_key(
+                b"old key",
+                KeyInfo::builder(b"other key".to_vec(), "my-node".into()).build(),
+                Duration::from_secs(0),
+            )
+            .is_err());
+        assert!(registry
+            .rotate_key(
+                b"new key",
+                KeyInfo::builder(b"other key".to_vec(), "other-node".into()).build(),
+                Duration::from_secs(0),
+            )
+            .is_err());
+        assert!(registry
+            .rotate_key(
+                b"new key",
+                KeyInfo::builder(b"other key".to_vec(), "my-node".into()).build(),
+                Duration::from_secs(u64::MAX),
+            )
+            .is_err());
+    }
+
+    /// Test that a deleted key is returned and removed from the persisted key registry, and that
+    /// deleting a key that is not registered returns nothing.
+    #[test]
+    fn test_delete_key() {
+        let temp_dir = TempDir::new("test_delete_key").unwrap();
+        let temp_dir_path = temp_dir.path().join("key_reg.yaml");
+        let location = temp_dir_path
+            .to_str()
+            .expect("could not create path str")
+            .to_string();
+
+        let mut registry = StorageKeyRegistry::new(location.clone()).expect("could not load file");
+        registry
+            .save_keys(vec![
+                KeyInfo::builder(b"key a".to_vec(), "my-node".into()).build(),
+                KeyInfo::builder(b"key b".to_vec(), "my-node".into()).build(),
+            ])
+            .expect("unable to save keys");
+
+        let deleted_key_info = registry
+            .delete_key(b"key a")
+            .expect("unable to delete key")
+            .expect("Deleted key info was none");
+        assert_eq!(b"key a", deleted_key_info.public_key());
+
+        assert!(registry
+            .delete_key(b"key a")
+            .expect("unable to delete key")
+            .is_none());
+
+        let registry = StorageKeyRegistry::new(location).expect("could not reload file");
+        assert!(registry
+            .get_key(b"key a")
+            .expect("unable to get key info")
+            .is_none());
+        assert_eq!(1, registry.count().expect("unable to count keys"));
+    }
+
     fn make_key_info(
         public_key: &str,
         node_id: &str,
@@ -337,6 +503,8 @@ mod tests {
             public_key: public_key.into(),
             associated_node_id: node_id.into(),
             metadata: metadata.into_iter().collect(),
+            superseded_by: None,
+            accepted_until: None,
         }
     }
 }
@@ -67,11 +67,14 @@ pub mod consensus;
 pub mod database;
 #[cfg(feature = "events")]
 pub mod events;
+mod file_version;
 mod hex;
 pub mod keys;
 #[cfg(feature = "matrix")]
 mod matrix;
 pub mod mesh;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod network;
 pub mod node_registry;
 pub mod orchestrator;
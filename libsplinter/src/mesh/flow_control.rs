@@ -0,0 +1,265 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flow control for the send queues of the connections in a mesh.
+//!
+//! Each connection has its own send queue. Once the number of messages queued for a connection
+//! reaches the high watermark, the queue is congested and sends to the connection fail with
+//! `SendError::Full` until the queue has drained to the low watermark. A peer whose queue stays
+//! congested for longer than the slow peer timeout is handled according to the slow peer policy.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The default time a send queue may stay congested before its peer is considered slow.
+pub const DEFAULT_SLOW_PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the mesh handles a peer whose send queue has been congested for longer than the slow peer
+/// timeout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlowPeerPolicy {
+    /// Keep the peer and its queued messages; sends to it fail until its queue drains.
+    Backpressure,
+    /// Drop the messages queued for the peer, so that new messages can be queued.
+    DropMessages,
+    /// Remove the peer from the mesh and disconnect it.
+    Disconnect,
+}
+
+/// The flow control configuration for the send queues of a mesh.
+#[derive(Clone, Debug)]
+pub struct FlowControl {
+    high_watermark: usize,
+    low_watermark: usize,
+    slow_peer_policy: SlowPeerPolicy,
+    slow_peer_timeout: Duration,
+}
+
+impl FlowControl {
+    /// Creates a flow control configuration with the given watermarks, in number of messages. A
+    /// low watermark above the high watermark is lowered to the high watermark.
+    ///
+    /// Slow peers are handled with `SlowPeerPolicy::Backpressure` by default.
+    pub fn new(high_watermark: usize, low_watermark: usize) -> Self {
+        FlowControl {
+            high_watermark,
+            low_watermark: low_watermark.min(high_watermark),
+            slow_peer_policy: SlowPeerPolicy::Backpressure,
+            slow_peer_timeout: DEFAULT_SLOW_PEER_TIMEOUT,
+        }
+    }
+
+    /// Sets how peers are handled once their send queue has been congested for longer than the
+    /// given timeout.
+    pub fn with_slow_peer_policy(mut self, policy: SlowPeerPolicy, timeout: Duration) -> Self {
+        self.slow_peer_policy = policy;
+        self.slow_peer_timeout = timeout;
+        self
+    }
+
+    pub fn high_watermark(&self) -> usize {
+        self.high_watermark
+    }
+
+    pub fn low_watermark(&self) -> usize {
+        self.low_watermark
+    }
+
+    pub fn slow_peer_policy(&self) -> SlowPeerPolicy {
+        self.slow_peer_policy
+    }
+
+    pub fn slow_peer_timeout(&self) -> Duration {
+        self.slow_peer_timeout
+    }
+}
+
+/// The bit of a send queue's state that is set while the queue is congested; the other bits hold
+/// the depth of the queue.
+const CONGESTED: usize = !(std::usize::MAX >> 1);
+
+/// The depth and congestion state of a connection's send queue, shared between its `Outgoing`
+/// handles and the reactor.
+///
+/// The depth counts the messages that have been queued but not yet written to the connection,
+/// including a message the reactor is waiting to write. The depth and congestion state are kept
+/// in a single atomic, so that they are always updated together.
+#[derive(Debug)]
+pub(super) struct SendQueue {
+    state: AtomicUsize,
+    watermarks: Option<(usize, usize)>,
+}
+
+impl SendQueue {
+    pub fn new(flow_control: Option<&FlowControl>) -> Self {
+        SendQueue {
+            state: AtomicUsize::new(0),
+            watermarks: flow_control
+                .map(|flow_control| (flow_control.high_watermark, flow_control.low_watermark)),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.state.load(Ordering::SeqCst) & !CONGESTED
+    }
+
+    pub fn is_congested(&self) -> bool {
+        self.state.load(Ordering::SeqCst) & CONGESTED != 0
+    }
+
+    /// Counts a message being queued. Returns false, without counting the message, if the queue
+    /// is congested.
+    pub fn push(&self) -> bool {
+        self.update(|state| {
+            if state & CONGESTED != 0 {
+                return None;
+            }
+
+            let depth = state + 1;
+            match self.watermarks {
+                Some((high_watermark, _)) if depth >= high_watermark => Some(depth | CONGESTED),
+                _ => Some(depth),
+            }
+        })
+    }
+
+    /// Counts a message leaving the queue, either because it was written to the connection or
+    /// because it could not be queued. The depth does not drop below zero, as the queue may have
+    /// been cleared in the meantime.
+    pub fn pop(&self) {
+        self.update(|state| {
+            let depth = (state & !CONGESTED).saturating_sub(1);
+            match self.watermarks {
+                Some((_, low_watermark)) if depth > low_watermark => {
+                    Some(depth | (state & CONGESTED))
+                }
+                _ => Some(depth),
+            }
+        });
+    }
+
+    /// Resets the queue to empty, for when its messages will never be written.
+    pub fn clear(&self) {
+        self.state.store(0, Ordering::SeqCst);
+    }
+
+    /// Replaces the state with the one computed from it, retrying if the state was changed
+    /// concurrently. Returns false, leaving the state unchanged, if no new state is computed.
+    fn update<F>(&self, new_state: F) -> bool
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            let next = match new_state(state) {
+                Some(next) => next,
+                None => return false,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, next, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a send queue becomes congested at its high watermark and stays congested until
+    /// it drains to its low watermark.
+    #[test]
+    fn test_watermarks() {
+        let queue = SendQueue::new(Some(&FlowControl::new(4, 2)));
+
+        for _ in 0..4 {
+            assert!(queue.push());
+        }
+        assert_eq!(4, queue.depth());
+        assert!(queue.is_congested());
+        assert!(!queue.push());
+        assert_eq!(4, queue.depth());
+
+        queue.pop();
+        assert!(queue.is_congested());
+        assert!(!queue.push());
+
+        queue.pop();
+        assert_eq!(2, queue.depth());
+        assert!(!queue.is_congested());
+        assert!(queue.push());
+    }
+
+    /// Test that a send queue without flow control only counts its depth.
+    #[test]
+    fn test_no_flow_control() {
+        let queue = SendQueue::new(None);
+
+        for _ in 0..100 {
+            assert!(queue.push());
+        }
+        assert_eq!(100, queue.depth());
+        assert!(!queue.is_congested());
+
+        queue.clear();
+        assert_eq!(0, queue.depth());
+    }
+
+    /// Test that counting messages that leave a cleared queue does not wrap its depth, and that a
+    /// cleared queue is no longer congested.
+    #[test]
+    fn test_pop_after_clear() {
+        let queue = SendQueue::new(Some(&FlowControl::new(4, 2)));
+
+        for _ in 0..4 {
+            assert!(queue.push());
+        }
+        assert!(queue.is_congested());
+
+        queue.clear();
+        assert!(!queue.is_congested());
+
+        queue.pop();
+        queue.pop();
+        assert_eq!(0, queue.depth());
+        assert!(!queue.is_congested());
+        assert!(queue.push());
+        assert_eq!(1, queue.depth());
+    }
+
+    /// Test that concurrent pushes never queue more messages than the high watermark.
+    #[test]
+    fn test_concurrent_push() {
+        let queue = std::sync::Arc::new(SendQueue::new(Some(&FlowControl::new(100, 50))));
+
+        let handles = (0..4)
+            .map(|_| {
+                let queue = queue.clone();
+                std::thread::spawn(move || (0..100).filter(|_| queue.push()).count())
+            })
+            .collect::<Vec<_>>();
+        let pushed: usize = handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Unable to join thread"))
+            .sum();
+
+        assert_eq!(100, pushed);
+        assert_eq!(100, queue.depth());
+        assert!(queue.is_congested());
+    }
+}
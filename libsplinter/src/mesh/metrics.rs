@@ -0,0 +1,86 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::metrics::{Counter, MetricsRegistry};
+
+const BYTES_SENT: &str = "splinter_mesh_sent_bytes_total";
+const MESSAGES_SENT: &str = "splinter_mesh_sent_messages_total";
+const BYTES_RECEIVED: &str = "splinter_mesh_received_bytes_total";
+const MESSAGES_RECEIVED: &str = "splinter_mesh_received_messages_total";
+
+/// The traffic metrics of a single connection in a mesh, labeled with the connection's id and
+/// remote endpoint.
+pub(super) struct ConnectionMetrics {
+    registry: MetricsRegistry,
+    connection_id: String,
+    endpoint: String,
+    bytes_sent: Counter,
+    messages_sent: Counter,
+    bytes_received: Counter,
+    messages_received: Counter,
+}
+
+impl ConnectionMetrics {
+    pub fn new(registry: &MetricsRegistry, id: usize, endpoint: &str) -> Self {
+        let connection_id = id.to_string();
+        let labels = [
+            ("connection_id", connection_id.as_str()),
+            ("endpoint", endpoint),
+        ];
+
+        ConnectionMetrics {
+            registry: registry.clone(),
+            bytes_sent: registry.counter(BYTES_SENT, "Bytes sent on a connection", &labels),
+            messages_sent: registry.counter(
+                MESSAGES_SENT,
+                "Messages sent on a connection",
+                &labels,
+            ),
+            bytes_received: registry.counter(
+                BYTES_RECEIVED,
+                "Bytes received on a connection",
+                &labels,
+            ),
+            messages_received: registry.counter(
+                MESSAGES_RECEIVED,
+                "Messages received on a connection",
+                &labels,
+            ),
+            endpoint: endpoint.to_string(),
+            connection_id,
+        }
+    }
+
+    pub fn record_sent(&self, len: usize) {
+        self.bytes_sent.inc_by(len as u64);
+        self.messages_sent.inc();
+    }
+
+    pub fn record_received(&self, len: usize) {
+        self.bytes_received.inc_by(len as u64);
+        self.messages_received.inc();
+    }
+
+    /// Removes the connection's metrics from the registry, once the connection has been removed
+    /// from the mesh.
+    pub fn unregister(&self) {
+        let labels = [
+            ("connection_id", self.connection_id.as_str()),
+            ("endpoint", self.endpoint.as_str()),
+        ];
+        for name in &[BYTES_SENT, MESSAGES_SENT, BYTES_RECEIVED, MESSAGES_RECEIVED] {
+            self.registry.remove(name, &labels);
+        }
+    }
+}
@@ -47,12 +47,17 @@
 //!    for each Connection that can be polled in the event loop to accomplish this, but there may
 //!    be a more efficient implementation.
 //! 3. Backpressure should be built in. This means all queues should be bounded so that a
-//!    backpressure error can be returned when the queue is full.
+//!    backpressure error can be returned when the queue is full. A mesh created with
+//!    `Mesh::with_flow_control` also applies high and low watermarks to each connection's queue,
+//!    and can drop the queued messages of, or disconnect, peers that stay slow.
 
 mod control;
+mod flow_control;
 mod incoming;
 #[cfg(feature = "matrix")]
 mod matrix;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod outgoing;
 mod pool;
 mod reactor;
@@ -64,12 +69,15 @@ use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 pub use crate::mesh::control::{AddError, Control, RemoveError};
+pub use crate::mesh::flow_control::{FlowControl, SlowPeerPolicy, DEFAULT_SLOW_PEER_TIMEOUT};
 pub use crate::mesh::incoming::Incoming;
 #[cfg(feature = "matrix")]
 pub use crate::mesh::matrix::{MeshLifeCycle, MeshMatrixSender};
 pub use crate::mesh::outgoing::Outgoing;
 
 use crate::mesh::reactor::Reactor;
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsRegistry;
 use crate::transport::Connection;
 
 /// Wrapper around payload to include connection id
@@ -103,24 +111,67 @@ pub struct Mesh {
     outgoings: Arc<RwLock<HashMap<usize, Outgoing>>>,
     incoming: Incoming,
     ctrl: Control,
+    #[cfg(feature = "metrics")]
+    metrics: Option<MetricsRegistry>,
 }
 
 impl Mesh {
     /// Create a new mesh, spawning a background thread for sending and receiving, and setting up
     /// channels to communicate with it.
     pub fn new(incoming_capacity: usize, outgoing_capacity: usize) -> Self {
-        let (ctrl, incoming) = Reactor::spawn(incoming_capacity, outgoing_capacity);
+        let (ctrl, incoming) = Reactor::spawn(incoming_capacity, outgoing_capacity, None);
         Mesh {
             outgoings: Arc::new(RwLock::new(HashMap::new())),
             incoming,
             ctrl,
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
+    /// Create a new mesh whose connections' send queues are limited by the given flow control
+    /// configuration. Each queue holds at most the high watermark number of messages.
+    pub fn with_flow_control(incoming_capacity: usize, flow_control: FlowControl) -> Self {
+        let (ctrl, incoming) = Reactor::spawn(
+            incoming_capacity,
+            flow_control.high_watermark(),
+            Some(flow_control),
+        );
+        Mesh {
+            outgoings: Arc::new(RwLock::new(HashMap::new())),
+            incoming,
+            ctrl,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Record the bytes and messages sent and received on each connection in the given registry.
+    ///
+    /// Only connections added after this is called are recorded, and only messages received
+    /// through `recv` or `recv_timeout` are counted as received.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: MetricsRegistry) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Add a new connection to the mesh, moving it to the background thread, and return its id.
     pub fn add(&self, connection: Box<dyn Connection>) -> Result<usize, AddError> {
+        #[cfg(feature = "metrics")]
+        let endpoint = connection.remote_endpoint();
+
         let outgoing = self.ctrl.add(connection)?;
         let id = outgoing.id();
+
+        #[cfg(feature = "metrics")]
+        let outgoing = match self.metrics {
+            Some(ref registry) => {
+                outgoing.with_metrics(metrics::ConnectionMetrics::new(registry, id, &endpoint))
+            }
+            None => outgoing,
+        };
+
         rwlock_write_unwrap!(self.outgoings).insert(id, outgoing);
 
         Ok(id)
@@ -132,7 +183,15 @@ impl Mesh {
         // The outgoing channel needs to be removed after the control request completes, or else
         // the reactor will detect that the outgoing sender has dropped and clean it up
         // automatically, causing the control request to fail with NotFound.
-        rwlock_write_unwrap!(self.outgoings).remove(&id);
+        let _outgoing = rwlock_write_unwrap!(self.outgoings).remove(&id);
+
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(outgoing) = _outgoing {
+                outgoing.unregister_metrics();
+            }
+        }
+
         Ok(connection)
     }
 
@@ -154,14 +213,34 @@ impl Mesh {
 
     /// Receive a new envelope from the mesh.
     pub fn recv(&self) -> Result<Envelope, RecvError> {
-        self.incoming.recv().map_err(|_| RecvError)
+        let envelope = self.incoming.recv().map_err(|_| RecvError)?;
+
+        #[cfg(feature = "metrics")]
+        self.record_received(&envelope);
+
+        Ok(envelope)
     }
 
     /// Receive a new envelope from the mesh.
     pub fn recv_timeout(&self, timeout: Duration) -> Result<Envelope, RecvTimeoutError> {
-        self.incoming
+        let envelope = self
+            .incoming
             .recv_timeout(timeout)
-            .map_err(RecvTimeoutError::from)
+            .map_err(RecvTimeoutError::from)?;
+
+        #[cfg(feature = "metrics")]
+        self.record_received(&envelope);
+
+        Ok(envelope)
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record_received(&self, envelope: &Envelope) {
+        if self.metrics.is_some() {
+            if let Some(outgoing) = rwlock_read_unwrap!(self.outgoings).get(&envelope.id()) {
+                outgoing.record_received(envelope.payload().len());
+            }
+        }
     }
 
     /// Create a new handle for sending to the existing connection with the given id.
@@ -284,8 +363,84 @@ mod tests {
     use std::fmt::Debug;
     use std::sync::mpsc::channel;
     use std::thread;
+    use std::time::Instant;
+
+    use mio::{Evented, Registration, SetReadiness};
+
+    use crate::transport::{
+        raw::RawTransport, tls::tests::create_test_tls_transport, DisconnectError,
+        RecvError as TransportRecvError, SendError as TransportSendError, Transport,
+    };
+
+    /// A connection to a peer that never accepts any data, as a peer that has stopped reading
+    /// would.
+    struct StalledConnection {
+        registration: Registration,
+        _set_readiness: SetReadiness,
+    }
+
+    impl StalledConnection {
+        fn new() -> Self {
+            let (registration, set_readiness) = Registration::new2();
+            StalledConnection {
+                registration,
+                _set_readiness: set_readiness,
+            }
+        }
+    }
 
-    use crate::transport::{raw::RawTransport, tls::tests::create_test_tls_transport, Transport};
+    impl Connection for StalledConnection {
+        fn send(&mut self, _message: &[u8]) -> Result<(), TransportSendError> {
+            Err(TransportSendError::WouldBlock)
+        }
+
+        fn recv(&mut self) -> Result<Vec<u8>, TransportRecvError> {
+            Err(TransportRecvError::WouldBlock)
+        }
+
+        fn remote_endpoint(&self) -> String {
+            "stalled".into()
+        }
+
+        fn local_endpoint(&self) -> String {
+            "stalled".into()
+        }
+
+        fn disconnect(&mut self) -> Result<(), DisconnectError> {
+            Ok(())
+        }
+
+        fn evented(&self) -> &dyn Evented {
+            &self.registration
+        }
+    }
+
+    // Fill the send queue of a stalled connection until it is congested
+    fn fill_send_queue(mesh: &Mesh, id: usize) {
+        for _ in 0..4 {
+            assert_ok(mesh.send(Envelope::new(id, b"hello".to_vec())));
+        }
+        match mesh.send(Envelope::new(id, b"hello".to_vec())) {
+            Err(SendError::Full(_)) => (),
+            res => panic!("Expected Err(SendError::Full(..)), got {:?}", res),
+        }
+    }
+
+    // Wait for the result of sending to a connection to match the given predicate
+    fn wait_for_send_result<F: Fn(&Result<(), SendError>) -> bool>(
+        mesh: &Mesh,
+        id: usize,
+        predicate: F,
+    ) {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if predicate(&mesh.send(Envelope::new(id, b"hello".to_vec()))) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Send result did not match before timeout");
+    }
 
     fn assert_ok<T, E: Debug>(result: Result<T, E>) -> T {
         match result {
@@ -418,6 +573,54 @@ mod tests {
         handle.join().unwrap();
     }
 
+    // Test that a connection's send queue is congested once it reaches the high watermark, and
+    // that its depth is reported through its Outgoing handle
+    #[test]
+    fn test_flow_control_congestion() {
+        let mesh = Mesh::with_flow_control(1, FlowControl::new(4, 2));
+        let id = assert_ok(mesh.add(Box::new(StalledConnection::new())));
+
+        fill_send_queue(&mesh, id);
+
+        let outgoing = mesh.outgoing(id).unwrap();
+        assert_eq!(4, outgoing.queue_depth());
+        assert!(outgoing.is_congested());
+    }
+
+    // Test that the messages queued for a slow peer are dropped, apart from the message that is
+    // being written, once its send queue has been congested for longer than the timeout
+    #[test]
+    fn test_slow_peer_drop_messages() {
+        let mesh = Mesh::with_flow_control(
+            1,
+            FlowControl::new(4, 2)
+                .with_slow_peer_policy(SlowPeerPolicy::DropMessages, Duration::from_millis(200)),
+        );
+        let id = assert_ok(mesh.add(Box::new(StalledConnection::new())));
+
+        fill_send_queue(&mesh, id);
+        wait_for_send_result(&mesh, id, Result::is_ok);
+
+        assert_eq!(2, mesh.outgoing(id).unwrap().queue_depth());
+    }
+
+    // Test that a slow peer is disconnected once its send queue has been congested for longer
+    // than the timeout
+    #[test]
+    fn test_slow_peer_disconnect() {
+        let mesh = Mesh::with_flow_control(
+            1,
+            FlowControl::new(4, 2)
+                .with_slow_peer_policy(SlowPeerPolicy::Disconnect, Duration::from_millis(200)),
+        );
+        let id = assert_ok(mesh.add(Box::new(StalledConnection::new())));
+
+        fill_send_queue(&mesh, id);
+        wait_for_send_result(&mesh, id, |res| {
+            matches!(res, Err(SendError::Disconnected(_)))
+        });
+    }
+
     #[cfg(not(unix))]
     #[test]
     fn test_connection_send_receive_raw() {
@@ -15,7 +15,11 @@
 use mio_extras::channel::{SyncSender, TrySendError};
 
 use std::io;
+use std::sync::Arc;
 
+use crate::mesh::flow_control::SendQueue;
+#[cfg(feature = "metrics")]
+use crate::mesh::metrics::ConnectionMetrics;
 use crate::mesh::Envelope;
 
 /// Handle for sending to a specific connection in the mesh
@@ -23,21 +27,82 @@ use crate::mesh::Envelope;
 pub struct Outgoing {
     id: usize,
     tx: SyncSender<Envelope>,
+    queue: Arc<SendQueue>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<ConnectionMetrics>>,
 }
 
 impl Outgoing {
-    pub(super) fn new(id: usize, tx: SyncSender<Envelope>) -> Self {
-        Outgoing { id, tx }
+    pub(super) fn new(id: usize, tx: SyncSender<Envelope>, queue: Arc<SendQueue>) -> Self {
+        Outgoing {
+            id,
+            tx,
+            queue,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(super) fn with_metrics(mut self, metrics: ConnectionMetrics) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
     }
 
+    /// Queue the payload to be sent on the connection. Fails with `SendError::Full` if the
+    /// connection's send queue is full or congested.
     pub fn send(&self, payload: Vec<u8>) -> Result<(), SendError> {
-        self.tx.try_send(Envelope::new(self.id, payload))?;
+        if !self.queue.push() {
+            return Err(SendError::Full(payload));
+        }
+
+        #[cfg(feature = "metrics")]
+        let len = payload.len();
+
+        if let Err(err) = self.tx.try_send(Envelope::new(self.id, payload)) {
+            self.queue.pop();
+            return Err(SendError::from(err));
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(ref metrics) = self.metrics {
+                metrics.record_sent(len);
+            }
+        }
+
         Ok(())
     }
 
+    #[cfg(feature = "metrics")]
+    pub(super) fn record_received(&self, len: usize) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_received(len);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(super) fn unregister_metrics(&self) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.unregister();
+        }
+    }
+
     pub fn id(&self) -> usize {
         self.id
     }
+
+    /// Returns the number of messages queued for the connection that have not yet been written to
+    /// it.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.depth()
+    }
+
+    /// Returns whether the connection's send queue has reached its high watermark and not yet
+    /// drained to its low watermark. Sends fail while the queue is congested.
+    pub fn is_congested(&self) -> bool {
+        self.queue.is_congested()
+    }
 }
 
 #[derive(Debug)]
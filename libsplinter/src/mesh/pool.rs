@@ -16,12 +16,15 @@ use crossbeam_channel::TrySendError;
 use mio::{Event, Evented, Events, Poll, PollOpt, Ready, Token};
 use mio_extras::channel as mio_channel;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt;
 use std::io;
 use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::mesh::flow_control::{SendQueue, SlowPeerPolicy};
 use crate::mesh::Envelope;
 use crate::transport::{Connection, RecvError, SendError};
 
@@ -61,6 +64,7 @@ impl Pool {
         &mut self,
         connection: Box<dyn Connection>,
         outgoing: mio_channel::Receiver<Envelope>,
+        queue: Arc<SendQueue>,
     ) -> Result<usize, io::Error> {
         let connection_token = self.next_token();
         let outgoing_token = self.next_token();
@@ -84,7 +88,14 @@ impl Pool {
         self.tokens.insert(outgoing_token, id);
         self.entries.insert(
             id,
-            Entry::new(id, connection, connection_token, outgoing, outgoing_token),
+            Entry::new(
+                id,
+                connection,
+                connection_token,
+                outgoing,
+                outgoing_token,
+                queue,
+            ),
         );
 
         Ok(id)
@@ -96,6 +107,10 @@ impl Pool {
             let connection_token = entry.connection_token();
             let outgoing_token = entry.outgoing_token();
 
+            // The queued messages will never be sent; clearing the queue lets senders see that
+            // the connection is gone rather than that its queue is congested
+            entry.queue.clear();
+
             self.tokens.remove(&connection_token);
             self.tokens.remove(&outgoing_token);
 
@@ -118,8 +133,47 @@ impl Pool {
     }
 
     /// Poll all connections, outgoings, and externally registered types
-    pub fn poll(&self, events: &mut Events) -> Result<usize, io::Error> {
-        self.poll.poll(events, None)
+    pub fn poll(&self, events: &mut Events, timeout: Option<Duration>) -> Result<usize, io::Error> {
+        self.poll.poll(events, timeout)
+    }
+
+    /// Apply the slow peer policy to the connections whose send queues have been congested for
+    /// longer than the given timeout
+    pub fn handle_slow_peers(&mut self, policy: SlowPeerPolicy, timeout: Duration) {
+        let now = Instant::now();
+        let slow_ids = self
+            .entries
+            .values()
+            .filter(|entry| entry.is_slow(now, timeout))
+            .map(Entry::id)
+            .collect::<Vec<usize>>();
+
+        for id in slow_ids {
+            match policy {
+                SlowPeerPolicy::Backpressure => (),
+                SlowPeerPolicy::DropMessages => {
+                    if let Some(entry) = self.entries.get(&id) {
+                        let dropped = entry.drop_queued();
+                        warn!(
+                            "Dropped {} messages queued for slow connection {}",
+                            dropped, id
+                        );
+                    }
+                }
+                SlowPeerPolicy::Disconnect => {
+                    warn!("Removing slow connection {}", id);
+                    match self.remove(id) {
+                        Ok(Some(mut connection)) => {
+                            if let Err(err) = connection.disconnect() {
+                                debug!("Unable to disconnect slow connection {}: {}", id, err);
+                            }
+                        }
+                        Ok(None) => (),
+                        Err(err) => error!("Error removing connection: {:?}", err),
+                    }
+                }
+            }
+        }
     }
 
     pub fn handle_event(
@@ -179,6 +233,8 @@ struct Entry {
     outgoing_token: Token,
     cached: RefCell<Option<Vec<u8>>>,
     write_evented_guard: RefCell<bool>,
+    queue: Arc<SendQueue>,
+    congested_since: Cell<Option<Instant>>,
 }
 
 impl fmt::Debug for Entry {
@@ -198,6 +254,7 @@ impl Entry {
         connection_token: Token,
         outgoing: mio_channel::Receiver<Envelope>,
         outgoing_token: Token,
+        queue: Arc<SendQueue>,
     ) -> Self {
         Entry {
             id,
@@ -207,6 +264,8 @@ impl Entry {
             outgoing_token,
             cached: RefCell::new(None),
             write_evented_guard: RefCell::new(false),
+            queue,
+            congested_since: Cell::new(None),
         }
     }
 
@@ -226,6 +285,30 @@ impl Entry {
         (self.connection.into_inner(), self.outgoing)
     }
 
+    // Whether the send queue has been congested for at least the given timeout
+    fn is_slow(&self, now: Instant, timeout: Duration) -> bool {
+        if self.queue.is_congested() {
+            let congested_since = self.congested_since.get().unwrap_or(now);
+            self.congested_since.set(Some(congested_since));
+            now.duration_since(congested_since) >= timeout
+        } else {
+            self.congested_since.set(None);
+            false
+        }
+    }
+
+    // Drop the messages waiting in the outgoing queue, returning the number dropped. A message
+    // the reactor is already waiting to write to the connection is kept.
+    fn drop_queued(&self) -> usize {
+        let mut dropped = 0;
+        while self.outgoing.try_recv().is_ok() {
+            self.queue.pop();
+            dropped += 1;
+        }
+        self.congested_since.set(None);
+        dropped
+    }
+
     fn try_event(
         &self,
         event: &Event,
@@ -296,6 +379,7 @@ impl Entry {
 
         match connection.send(&payload) {
             Ok(()) => {
+                self.queue.pop();
                 // Return to readable only.
                 if self.write_evented_guard.replace(false) {
                     poll.reregister(
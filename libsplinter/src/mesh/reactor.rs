@@ -17,13 +17,16 @@ use mio::{Event, Events, Token};
 use mio_extras::channel as mio_channel;
 
 use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::mesh::{
     control::{
         AddError, AddRequest, AddResponse, Control, ControlRequest, RemoveError, RemoveRequest,
         RemoveResponse,
     },
+    flow_control::{FlowControl, SendQueue, SlowPeerPolicy},
     incoming::Incoming,
     outgoing::Outgoing,
     pool::Pool,
@@ -34,12 +37,17 @@ use crate::transport::Connection;
 // Maximum number of events to receive and handle per turn of the reactor
 const MAX_EVENTS_PER_TURN: usize = 1024;
 
+// Maximum time between checks for slow peers, when a slow peer policy is configured
+const SLOW_PEER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct Reactor {
     pool: Pool,
     ctrl_rx: mio_channel::Receiver<ControlRequest>,
     ctrl_token: Token,
     incoming_tx: crossbeam_channel::Sender<Envelope>,
     outgoing_capacity: usize,
+    flow_control: Option<FlowControl>,
+    last_slow_peer_check: Instant,
 }
 
 enum Turn {
@@ -52,6 +60,7 @@ impl Reactor {
         ctrl_rx: mio_channel::Receiver<ControlRequest>,
         incoming_tx: crossbeam_channel::Sender<Envelope>,
         outgoing_capacity: usize,
+        flow_control: Option<FlowControl>,
     ) -> Self {
         let mut pool = Pool::new();
 
@@ -65,17 +74,24 @@ impl Reactor {
             ctrl_token,
             incoming_tx,
             outgoing_capacity,
+            flow_control,
+            last_slow_peer_check: Instant::now(),
         }
     }
 
-    pub(super) fn spawn(incoming_capacity: usize, outgoing_capacity: usize) -> (Control, Incoming) {
+    pub(super) fn spawn(
+        incoming_capacity: usize,
+        outgoing_capacity: usize,
+        flow_control: Option<FlowControl>,
+    ) -> (Control, Incoming) {
         let (ctrl_tx, ctrl_rx) = mio_channel::channel();
         let (incoming_tx, incoming_rx) = crossbeam_channel::bounded(incoming_capacity);
 
         thread::Builder::new()
             .name(String::from("mesh::Reactor"))
             .spawn(move || {
-                let mut reactor = Reactor::new(ctrl_rx, incoming_tx, outgoing_capacity);
+                let mut reactor =
+                    Reactor::new(ctrl_rx, incoming_tx, outgoing_capacity, flow_control);
                 reactor.run();
             })
             .expect("Failed to spawn mesh::Reactor thread");
@@ -94,7 +110,7 @@ impl Reactor {
     }
 
     fn turn(&mut self, events: &mut Events) -> Turn {
-        if let Err(err) = self.pool.poll(events) {
+        if let Err(err) = self.pool.poll(events, self.poll_timeout()) {
             error!("Error polling: {:?}", err);
             return Turn::Shutdown;
         }
@@ -105,9 +121,39 @@ impl Reactor {
             }
         }
 
+        self.check_slow_peers();
+
         Turn::Continue
     }
 
+    // The reactor only needs to wake up without events if slow peers have to be checked for
+    fn slow_peer_policy(&self) -> Option<(SlowPeerPolicy, Duration)> {
+        match self.flow_control {
+            Some(ref flow_control)
+                if flow_control.slow_peer_policy() != SlowPeerPolicy::Backpressure =>
+            {
+                Some((
+                    flow_control.slow_peer_policy(),
+                    flow_control.slow_peer_timeout(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn poll_timeout(&self) -> Option<Duration> {
+        self.slow_peer_policy().map(|_| SLOW_PEER_CHECK_INTERVAL)
+    }
+
+    fn check_slow_peers(&mut self) {
+        if let Some((policy, timeout)) = self.slow_peer_policy() {
+            if self.last_slow_peer_check.elapsed() >= SLOW_PEER_CHECK_INTERVAL {
+                self.pool.handle_slow_peers(policy, timeout);
+                self.last_slow_peer_check = Instant::now();
+            }
+        }
+    }
+
     fn handle_event(&mut self, event: &Event) -> Turn {
         if event.token() == self.ctrl_token {
             self.handle_control_ready()
@@ -144,9 +190,10 @@ impl Reactor {
 
     fn add_connection(&mut self, connection: Box<dyn Connection>) -> AddResponse {
         let (tx, rx) = mio_channel::sync_channel(self.outgoing_capacity);
+        let queue = Arc::new(SendQueue::new(self.flow_control.as_ref()));
 
-        match self.pool.add(connection, rx) {
-            Ok(id) => Ok(Outgoing::new(id, tx)),
+        match self.pool.add(connection, rx, queue.clone()) {
+            Ok(id) => Ok(Outgoing::new(id, tx, queue)),
             Err(err) => Err(AddError::Io(err)),
         }
     }
@@ -0,0 +1,377 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics for monitoring a splinter node, exported in the Prometheus text format.
+//!
+//! A `MetricsRegistry` holds counters and histograms, each identified by a metric name and a set
+//! of labels. Components such as `Mesh` and `Dispatcher` record their metrics in a registry
+//! provided with `with_metrics`, and `MetricsRegistry::render` produces the text served to
+//! Prometheus.
+//!
+//!     use splinter::metrics::MetricsRegistry;
+//!
+//!     let metrics = MetricsRegistry::new();
+//!     metrics
+//!         .counter("requests_total", "Number of requests", &[("method", "GET")])
+//!         .inc();
+//!
+//!     assert!(metrics.render().contains("requests_total{method=\"GET\"} 1"));
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::{rwlock_read_unwrap, rwlock_write_unwrap};
+
+/// The upper bounds, in seconds, of the buckets of histograms that measure durations.
+pub const DEFAULT_DURATION_BUCKETS: &[f64] =
+    &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+type Labels = Vec<(String, String)>;
+
+/// A collection of metrics that can be rendered in the Prometheus text format.
+///
+/// The registry can be cloned cheaply; clones share the same metrics.
+#[derive(Clone, Default)]
+pub struct MetricsRegistry {
+    families: Arc<RwLock<BTreeMap<String, Family>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        MetricsRegistry::default()
+    }
+
+    /// Returns the counter with the given name and labels, creating it if it does not exist.
+    pub fn counter(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Counter {
+        match self.get_or_insert(name, help, labels, || Metric::Counter(Counter::default())) {
+            Metric::Counter(counter) => counter,
+            Metric::Histogram(_) => {
+                warn!("Metric {} is a histogram, not a counter", name);
+                Counter::default()
+            }
+        }
+    }
+
+    /// Returns the histogram with the given name and labels, creating it with the
+    /// `DEFAULT_DURATION_BUCKETS` if it does not exist.
+    pub fn histogram(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Histogram {
+        let new_histogram = || Metric::Histogram(Histogram::new(DEFAULT_DURATION_BUCKETS));
+        match self.get_or_insert(name, help, labels, new_histogram) {
+            Metric::Histogram(histogram) => histogram,
+            Metric::Counter(_) => {
+                warn!("Metric {} is a counter, not a histogram", name);
+                Histogram::new(DEFAULT_DURATION_BUCKETS)
+            }
+        }
+    }
+
+    /// Removes the metric with the given name and labels, such as the metrics of a connection
+    /// that has been closed. Handles to the metric remain usable but are no longer rendered.
+    pub fn remove(&self, name: &str, labels: &[(&str, &str)]) {
+        let mut families = rwlock_write_unwrap!(self.families);
+        if let Some(family) = families.get_mut(name) {
+            family.metrics.remove(&to_labels(labels));
+            if family.metrics.is_empty() {
+                families.remove(name);
+            }
+        }
+    }
+
+    /// Renders all of the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = rwlock_read_unwrap!(self.families);
+        let mut output = String::new();
+        for (name, family) in families.iter() {
+            family.render(name, &mut output);
+        }
+        output
+    }
+
+    fn get_or_insert<F>(&self, name: &str, help: &str, labels: &[(&str, &str)], new: F) -> Metric
+    where
+        F: FnOnce() -> Metric,
+    {
+        let labels = to_labels(labels);
+        if let Some(metric) = rwlock_read_unwrap!(self.families)
+            .get(name)
+            .and_then(|family| family.metrics.get(&labels))
+        {
+            return metric.clone();
+        }
+
+        rwlock_write_unwrap!(self.families)
+            .entry(name.to_string())
+            .or_insert_with(|| Family {
+                help: help.to_string(),
+                metrics: BTreeMap::new(),
+            })
+            .metrics
+            .entry(labels)
+            .or_insert_with(new)
+            .clone()
+    }
+}
+
+fn to_labels(labels: &[(&str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// All metrics with the same name.
+struct Family {
+    help: String,
+    metrics: BTreeMap<Labels, Metric>,
+}
+
+impl Family {
+    fn render(&self, name: &str, output: &mut String) {
+        let metric_type = match self.metrics.values().next() {
+            Some(Metric::Counter(_)) => "counter",
+            Some(Metric::Histogram(_)) => "histogram",
+            None => return,
+        };
+
+        // Writing to a String cannot fail
+        let _ = writeln!(output, "# HELP {} {}", name, escape_help(&self.help));
+        let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+
+        for (labels, metric) in self.metrics.iter() {
+            match metric {
+                Metric::Counter(counter) => {
+                    let _ = writeln!(
+                        output,
+                        "{}{} {}",
+                        name,
+                        format_labels(labels, None),
+                        counter.get()
+                    );
+                }
+                Metric::Histogram(histogram) => histogram.render(name, labels, output),
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Metric {
+    Counter(Counter),
+    Histogram(Histogram),
+}
+
+/// A count that only increases, such as the number of messages received.
+#[derive(Clone, Default)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A distribution of observed values, such as the time taken to handle messages, counted in
+/// buckets.
+#[derive(Clone)]
+pub struct Histogram {
+    inner: Arc<HistogramInner>,
+}
+
+struct HistogramInner {
+    bounds: Vec<f64>,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    // The bits of the f64 sum of the observed values
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            inner: Arc::new(HistogramInner {
+                bounds: bounds.to_vec(),
+                buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+                count: AtomicU64::new(0),
+                sum: AtomicU64::new(0f64.to_bits()),
+            }),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        // Buckets are counted individually and made cumulative when rendered
+        if let Some(index) = self.inner.bounds.iter().position(|bound| value <= *bound) {
+            self.inner.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner.count.fetch_add(1, Ordering::Relaxed);
+
+        let mut current = self.inner.sum.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(current) + value).to_bits();
+            match self.inner.sum.compare_exchange_weak(
+                current,
+                new,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn observe_duration(&self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+
+    pub fn count(&self) -> u64 {
+        self.inner.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.inner.sum.load(Ordering::Relaxed))
+    }
+
+    fn render(&self, name: &str, labels: &Labels, output: &mut String) {
+        let mut cumulative = 0;
+        for (bound, bucket) in self.inner.bounds.iter().zip(self.inner.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                output,
+                "{}_bucket{} {}",
+                name,
+                format_labels(labels, Some(&bound.to_string())),
+                cumulative
+            );
+        }
+
+        let count = self.count();
+        let _ = writeln!(
+            output,
+            "{}_bucket{} {}",
+            name,
+            format_labels(labels, Some("+Inf")),
+            count
+        );
+        let _ = writeln!(
+            output,
+            "{}_sum{} {}",
+            name,
+            format_labels(labels, None),
+            self.sum()
+        );
+        let _ = writeln!(
+            output,
+            "{}_count{} {}",
+            name,
+            format_labels(labels, None),
+            count
+        );
+    }
+}
+
+/// Formats labels as `{key="value",...}`, adding the `le` label of a histogram bucket if given.
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+        .collect::<Vec<_>>();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that counters with the same name and labels are shared, and that counters are
+    /// rendered with their help, type and labels.
+    #[test]
+    fn test_counter() {
+        let metrics = MetricsRegistry::new();
+        metrics
+            .counter("messages_total", "Number of messages", &[("type", "a")])
+            .inc();
+        metrics
+            .counter("messages_total", "Number of messages", &[("type", "a")])
+            .inc_by(2);
+        metrics
+            .counter("messages_total", "Number of messages", &[("type", "b\"c")])
+            .inc();
+
+        assert_eq!(
+            "# HELP messages_total Number of messages\n\
+             # TYPE messages_total counter\n\
+             messages_total{type=\"a\"} 3\n\
+             messages_total{type=\"b\\\"c\"} 1\n",
+            metrics.render()
+        );
+
+        metrics.remove("messages_total", &[("type", "a")]);
+        metrics.remove("messages_total", &[("type", "b\"c")]);
+        assert_eq!("", metrics.render());
+    }
+
+    /// Test that histograms are rendered with cumulative buckets, a sum and a count.
+    #[test]
+    fn test_histogram() {
+        let metrics = MetricsRegistry::new();
+        let histogram = metrics.histogram("duration_seconds", "Duration", &[]);
+        histogram.observe(0.0002);
+        histogram.observe(0.002);
+        histogram.observe(10.0);
+
+        assert_eq!(3, histogram.count());
+        assert!((histogram.sum() - 10.0022).abs() < 1e-9);
+
+        let output = metrics.render();
+        assert!(output.contains("# TYPE duration_seconds histogram\n"));
+        assert!(output.contains("duration_seconds_bucket{le=\"0.0001\"} 0\n"));
+        assert!(output.contains("duration_seconds_bucket{le=\"0.0005\"} 1\n"));
+        assert!(output.contains("duration_seconds_bucket{le=\"0.005\"} 2\n"));
+        assert!(output.contains("duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(output.contains("duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(output.contains("duration_seconds_count 3\n"));
+    }
+}
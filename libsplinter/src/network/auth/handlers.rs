@@ -24,7 +24,7 @@ use crate::network::dispatch::{
 use crate::network::sender::SendRequest;
 use crate::protos::authorization::{
     AuthorizationError, AuthorizationMessage, AuthorizationMessageType, AuthorizedMessage,
-    ConnectRequest, ConnectRequest_HandshakeMode, ConnectResponse,
+    CompressionType, ConnectRequest, ConnectRequest_HandshakeMode, ConnectResponse,
     ConnectResponse_AuthorizationType, TrustRequest,
 };
 use crate::protos::network::{NetworkMessage, NetworkMessageType};
@@ -196,6 +196,9 @@ impl Handler<AuthorizationMessageType, ConnectRequest> for ConnectRequestHandler
                 if msg.get_handshake_mode() == ConnectRequest_HandshakeMode::BIDIRECTIONAL {
                     let mut connect_req = ConnectRequest::new();
                     connect_req.set_handshake_mode(ConnectRequest_HandshakeMode::UNIDIRECTIONAL);
+                    connect_req.set_accepted_compression_types(
+                        self.auth_manager.network.accepted_compression_types(),
+                    );
                     sender.send(SendRequest::new(
                         context.source_peer_id().to_string(),
                         wrap_in_network_auth_envelopes(
@@ -213,6 +216,17 @@ impl Handler<AuthorizationMessageType, ConnectRequest> for ConnectRequestHandler
                 response.set_accepted_authorization_types(vec![
                     ConnectResponse_AuthorizationType::TRUST,
                 ]);
+
+                // Messages to the peer may be compressed once both nodes accept compression
+                let network = &self.auth_manager.network;
+                if network
+                    .accepted_compression_types()
+                    .iter()
+                    .any(|t| msg.get_accepted_compression_types().contains(t))
+                {
+                    response.set_compression_type(CompressionType::ZLIB);
+                    network.set_peer_compression(context.source_peer_id(), true);
+                }
                 sender.send(SendRequest::new(
                     context.source_peer_id().to_string(),
                     wrap_in_network_auth_envelopes(
@@ -262,6 +276,15 @@ impl Handler<AuthorizationMessageType, ConnectResponse> for ConnectResponseHandl
             context.source_peer_id(),
             msg
         );
+        let network = &self.auth_manager.network;
+        if msg.get_compression_type() != CompressionType::UNSET_COMPRESSION_TYPE
+            && network
+                .accepted_compression_types()
+                .contains(&msg.get_compression_type())
+        {
+            network.set_peer_compression(context.source_peer_id(), true);
+        }
+
         if msg
             .get_accepted_authorization_types()
             .iter()
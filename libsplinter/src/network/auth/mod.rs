@@ -25,7 +25,7 @@ use crate::network::Network;
 
 /// The states of a connection during authorization.
 #[derive(PartialEq, Debug, Clone)]
-enum AuthorizationState {
+pub enum AuthorizationState {
     Unknown,
     Connecting,
     Authorized,
@@ -252,6 +252,26 @@ impl AuthorizationManager {
         }
     }
 
+    /// Returns the current authorization state of the given peer.
+    ///
+    /// Peers that have not begun authorization, or have been disconnected, are in the `Unknown`
+    /// state.
+    pub fn authorization_state(&self, peer_id: &str) -> AuthorizationState {
+        let mut shared = mutex_lock_unwrap!(self.shared);
+
+        // drain the removals
+        let removals = shared.disconnect_receiver.try_iter().collect::<Vec<_>>();
+        for peer_id in removals.into_iter() {
+            shared.states.remove(&peer_id);
+        }
+
+        shared
+            .states
+            .get(peer_id)
+            .cloned()
+            .unwrap_or(AuthorizationState::Unknown)
+    }
+
     fn notify_callbacks(
         callbacks: &[Box<dyn AuthorizationCallback>],
         peer_id: &str,
@@ -395,6 +415,44 @@ mod tests {
         assert_eq!(vec![new_peer_id.clone()], network.peer_ids());
     }
 
+    /// This test verifies that the reported authorization state of a peer follows the trust
+    /// state machine, and that an unknown peer is reported as Unknown.
+    #[test]
+    fn trust_state_machine_authorization_state() {
+        let (network, peer_id) = create_network_with_initial_temp_peer();
+
+        let auth_manager = AuthorizationManager::new(network.clone(), "mock_identity".into());
+
+        assert_eq!(
+            AuthorizationState::Unknown,
+            auth_manager.authorization_state(&peer_id)
+        );
+
+        auth_manager
+            .next_state(&peer_id, AuthorizationAction::Connecting)
+            .expect("Unable to transition to connecting");
+        assert_eq!(
+            AuthorizationState::Connecting,
+            auth_manager.authorization_state(&peer_id)
+        );
+
+        let new_peer_id = "abcd".to_string();
+        auth_manager
+            .next_state(
+                &peer_id,
+                AuthorizationAction::TrustIdentifying(new_peer_id.clone()),
+            )
+            .expect("Unable to transition to authorized");
+        assert_eq!(
+            AuthorizationState::Authorized,
+            auth_manager.authorization_state(&new_peer_id)
+        );
+        assert_eq!(
+            AuthorizationState::Unknown,
+            auth_manager.authorization_state(&peer_id)
+        );
+    }
+
     /// This test begins a connection, and then unauthorizes the peer.  Verify that the auth
     /// manager reports the correct value for is_authorized, and that the peer is removed.
     #[test]
@@ -0,0 +1,98 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compression of network messages.
+//!
+//! A compressed message is a `NetworkMessage` of type `NETWORK_COMPRESSED_MESSAGE`, whose payload
+//! is the zlib-compressed bytes of the original `NetworkMessage`. Compression is negotiated per
+//! connection during the connect handshake and applied by `Network`, so it is transparent to the
+//! senders and handlers of network messages.
+
+use std::io::{self, Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use protobuf::Message;
+
+use crate::protos::network::{NetworkMessage, NetworkMessageType};
+
+/// The default size, in bytes, above which messages are compressed.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 4096;
+
+/// The largest size, in bytes, a compressed message may expand to; larger messages are rejected
+/// rather than exhausting memory.
+const MAX_DECOMPRESSED_LEN: u64 = 256 * 1024 * 1024;
+
+/// Wraps the bytes of a network message in a compressed network message.
+pub(super) fn compress(msg: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(msg)?;
+
+    let mut compressed_msg = NetworkMessage::new();
+    compressed_msg.set_message_type(NetworkMessageType::NETWORK_COMPRESSED_MESSAGE);
+    compressed_msg.set_payload(encoder.finish()?);
+    compressed_msg
+        .write_to_bytes()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Returns the original bytes of a compressed network message, or the given bytes unchanged if
+/// they are not a compressed message.
+pub(super) fn decompress(msg: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut network_msg: NetworkMessage = match protobuf::parse_from_bytes(&msg) {
+        Ok(network_msg) => network_msg,
+        // Messages that cannot be parsed are left for the dispatcher to reject
+        Err(_) => return Ok(msg),
+    };
+
+    if network_msg.get_message_type() != NetworkMessageType::NETWORK_COMPRESSED_MESSAGE {
+        return Ok(msg);
+    }
+
+    let payload = network_msg.take_payload();
+    let mut decompressed = vec![];
+    ZlibDecoder::new(&payload[..])
+        .take(MAX_DECOMPRESSED_LEN + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "decompressed message is too large",
+        ));
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a compressed message is decompressed to the original bytes, and that messages
+    /// which are not compressed are returned unchanged.
+    #[test]
+    fn test_compress_decompress() {
+        let mut network_msg = NetworkMessage::new();
+        network_msg.set_message_type(NetworkMessageType::CIRCUIT);
+        network_msg.set_payload(vec![7; 10000]);
+        let msg_bytes = network_msg.write_to_bytes().unwrap();
+
+        let compressed = compress(&msg_bytes).unwrap();
+        assert!(compressed.len() < msg_bytes.len());
+        assert_eq!(msg_bytes, decompress(compressed).unwrap());
+
+        assert_eq!(msg_bytes, decompress(msg_bytes.clone()).unwrap());
+    }
+}
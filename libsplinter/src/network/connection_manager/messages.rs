@@ -33,6 +33,7 @@ pub enum CmPayload {
     AddConnection { endpoint: String },
     RemoveConnection { endpoint: String },
     ListConnections,
+    ConnectionId { endpoint: String },
 }
 
 #[derive(Debug, PartialEq)]
@@ -48,6 +49,9 @@ pub enum CmResponse {
     ListConnections {
         endpoints: Vec<String>,
     },
+    ConnectionId {
+        id: Option<usize>,
+    },
 }
 
 #[derive(Debug, PartialEq)]
@@ -67,6 +67,12 @@ where
         }
     }
 
+    /// Sets the interval, in seconds, at which heartbeats are sent to all managed connections.
+    pub fn with_heartbeat_interval(mut self, interval: u64) -> Self {
+        self.pacemaker = Pacemaker::new(interval);
+        self
+    }
+
     pub fn start(&mut self) -> Result<Connector, ConnectionManagerError> {
         let (sender, recv) = sync_channel(CHANNEL_CAPACITY);
         let mut state = self.connection_state.take().ok_or_else(|| {
@@ -178,6 +184,17 @@ impl Connector {
         }
     }
 
+    /// Returns the matrix id of the connection to the given endpoint, if one exists.
+    pub fn connection_id(&self, endpoint: &str) -> Result<Option<usize>, ConnectionManagerError> {
+        match self.send_payload(CmPayload::ConnectionId {
+            endpoint: endpoint.to_string(),
+        }) {
+            Ok(CmResponse::ConnectionId { id }) => Ok(id),
+            Err(err) => Err(err),
+            _ => panic!("This cannot return a response type that is not CmResponse::ConnectionId"),
+        }
+    }
+
     fn send_payload(&self, payload: CmPayload) -> Result<CmResponse, ConnectionManagerError> {
         let (sender, recv) = sync_channel(1);
 
@@ -378,6 +395,12 @@ fn handle_request<T: MatrixLifeCycle, U: MatrixSender>(
                 .map(|(key, _)| key.to_string())
                 .collect(),
         },
+        CmPayload::ConnectionId { ref endpoint } => CmResponse::ConnectionId {
+            id: state
+                .connection_metadata()
+                .get(endpoint)
+                .map(|meta| meta.id),
+        },
     };
 
     if req.sender.send(response).is_err() {
@@ -638,6 +661,31 @@ pub mod tests {
         );
     }
 
+    /// Test that the connection id of an added connection can be retrieved by its endpoint, and
+    /// that no id is returned for an unknown endpoint.
+    #[test]
+    fn test_connection_id() {
+        let mut transport = Box::new(InprocTransport::default());
+        let mut listener = transport.listen("inproc://test").unwrap();
+
+        thread::spawn(move || {
+            listener.accept().unwrap();
+        });
+
+        let mesh = Mesh::new(512, 128);
+        let mut cm = ConnectionManager::new(mesh.get_life_cycle(), mesh.get_sender(), transport);
+        let connector = cm.start().unwrap();
+
+        assert_eq!(None, connector.connection_id("inproc://test").unwrap());
+
+        connector.request_connection("inproc://test").unwrap();
+
+        assert!(connector.connection_id("inproc://test").unwrap().is_some());
+        assert_eq!(None, connector.connection_id("inproc://unknown").unwrap());
+
+        cm.shutdown_and_wait();
+    }
+
     #[test]
     fn test_remove_connection() {
         let mut transport = Box::new(RawTransport::default());
@@ -16,15 +16,25 @@
 //!
 use std::any::Any;
 use std::borrow::Borrow;
+#[cfg(feature = "metrics")]
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
 use crate::channel::{Receiver, RecvTimeoutError, SendError, Sender};
+#[cfg(feature = "metrics")]
+use crate::metrics::{Counter, Histogram, MetricsRegistry};
+use crate::network::rate_limit::{
+    create_rate_limited_message, PeerDisconnector, RateLimitDecision, RateLimiter,
+};
 use crate::network::sender::SendRequest;
+use crate::network::trace::TraceField;
 
 // Recv timeout in secs
 const TIMEOUT_SEC: u64 = 2;
@@ -32,13 +42,14 @@ const TIMEOUT_SEC: u64 = 2;
 /// The Message Context
 ///
 /// The message context provides information about an incoming message beyond its parsed bytes.  It
-/// includes the source peer id, the message type, the original bytes, and potentially other,
-/// future items.
+/// includes the source peer id, the message type, the original bytes, the trace id, and
+/// potentially other, future items.
 #[derive(Clone, Debug)]
 pub struct MessageContext<MT: Hash + Eq + Debug + Clone> {
     source_peer_id: String,
     message_type: MT,
     message_bytes: Vec<u8>,
+    trace_id: Option<String>,
 }
 
 impl<MT: Hash + Eq + Debug + Clone> MessageContext<MT> {
@@ -61,6 +72,14 @@ impl<MT: Hash + Eq + Debug + Clone> MessageContext<MT> {
     pub fn message_bytes(&self) -> &[u8] {
         &self.message_bytes
     }
+
+    /// The Trace ID.
+    ///
+    /// This is the trace id of the envelope the message was received in, if it was traced.
+    /// Handlers should keep it on any messages they forward or send in response.
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_id.as_deref()
+    }
 }
 
 /// A Handler for a network message.
@@ -203,6 +222,20 @@ impl From<SendError> for DispatchError {
     }
 }
 
+#[cfg(feature = "metrics")]
+impl DispatchError {
+    /// A short name for the kind of error, used to label metrics.
+    fn kind(&self) -> &'static str {
+        match self {
+            DispatchError::DeserializationError(_) => "deserialization",
+            DispatchError::SerializationError(_) => "serialization",
+            DispatchError::UnknownMessageType(_) => "unknown_message_type",
+            DispatchError::NetworkSendError(_) => "network_send",
+            DispatchError::HandleError(_) => "handle",
+        }
+    }
+}
+
 /// Dispatches messages to handlers.
 ///
 /// The dispatcher routes messages of a specific message type to one of a set of handlers that have
@@ -220,6 +253,10 @@ impl From<SendError> for DispatchError {
 pub struct Dispatcher<MT: Any + Hash + Eq + Debug + Clone> {
     handlers: HashMap<MT, HandlerWrapper<MT>>,
     network_sender: Box<dyn Sender<SendRequest>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<MetricsRegistry>,
+    #[cfg(feature = "metrics")]
+    message_type_metrics: HashMap<MT, MessageTypeMetrics>,
 }
 
 impl<MT: Any + Hash + Eq + Debug + Clone> Dispatcher<MT> {
@@ -230,9 +267,31 @@ impl<MT: Any + Hash + Eq + Debug + Clone> Dispatcher<MT> {
         Dispatcher {
             handlers: HashMap::new(),
             network_sender,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "metrics")]
+            message_type_metrics: HashMap::new(),
         }
     }
 
+    /// Record the number of messages dispatched, the time taken to handle them and the errors
+    /// returned by handlers, per message type, in the given registry.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: MetricsRegistry) -> Self {
+        self.message_type_metrics = self
+            .handlers
+            .keys()
+            .map(|message_type| {
+                (
+                    message_type.clone(),
+                    MessageTypeMetrics::new::<MT>(&metrics, message_type),
+                )
+            })
+            .collect();
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Set a handler for a given Message Type.
     ///
     /// This sets a handler for a given message type.  Only one handler may exist per message type.
@@ -242,6 +301,16 @@ impl<MT: Any + Hash + Eq + Debug + Clone> Dispatcher<MT> {
     where
         T: FromMessageBytes,
     {
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(ref metrics) = self.metrics {
+                self.message_type_metrics.insert(
+                    message_type.clone(),
+                    MessageTypeMetrics::new::<MT>(metrics, &message_type),
+                );
+            }
+        }
+
         self.handlers.insert(
             message_type,
             HandlerWrapper {
@@ -268,12 +337,34 @@ impl<MT: Any + Hash + Eq + Debug + Clone> Dispatcher<MT> {
         message_type: &MT,
         message_bytes: Vec<u8>,
     ) -> Result<(), DispatchError> {
+        self.dispatch_with_trace_id(source_peer_id, message_type, message_bytes, None)
+    }
+
+    /// Dispatch a message with the trace id of the envelope it was received in.
+    ///
+    /// The trace id is available to the handler through the message context.
+    ///
+    /// Errors
+    ///
+    /// A DispatchError is returned under the same conditions as `dispatch`.
+    pub fn dispatch_with_trace_id(
+        &self,
+        source_peer_id: &str,
+        message_type: &MT,
+        message_bytes: Vec<u8>,
+        trace_id: Option<String>,
+    ) -> Result<(), DispatchError> {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+
         let message_context = MessageContext {
             message_type: message_type.clone(),
             message_bytes,
             source_peer_id: source_peer_id.into(),
+            trace_id,
         };
-        self.handlers
+        let result = self
+            .handlers
             .get(message_type)
             .ok_or_else(|| {
                 DispatchError::UnknownMessageType(format!("No handler for type {:?}", message_type))
@@ -284,10 +375,137 @@ impl<MT: Any + Hash + Eq + Debug + Clone> Dispatcher<MT> {
                     &message_context,
                     self.network_sender.borrow(),
                 )
-            })
+            });
+
+        #[cfg(feature = "metrics")]
+        self.record_dispatch(message_type, &result, start.elapsed());
+
+        result
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record_dispatch(
+        &self,
+        message_type: &MT,
+        result: &Result<(), DispatchError>,
+        duration: Duration,
+    ) {
+        let metrics = match self.metrics {
+            Some(ref metrics) => metrics,
+            None => return,
+        };
+
+        match self.message_type_metrics.get(message_type) {
+            Some(message_type_metrics) => message_type_metrics.record(metrics, result, duration),
+            // Messages without a handler are not expected, so their metrics are not cached
+            None => MessageTypeMetrics::new::<MT>(metrics, message_type)
+                .record(metrics, result, duration),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record_rate_limited(&self, message_type: &MT, disconnected: bool) {
+        if let Some(ref metrics) = self.metrics {
+            let message_type_label = match self.message_type_metrics.get(message_type) {
+                Some(message_type_metrics) => {
+                    Cow::Borrowed(message_type_metrics.message_type.as_str())
+                }
+                None => Cow::Owned(format!("{:?}", message_type)),
+            };
+            metrics
+                .counter(
+                    "splinter_dispatch_rate_limited_total",
+                    "Messages that exceeded a peer's rate limits",
+                    &[
+                        ("dispatcher", dispatcher_label::<MT>()),
+                        ("message_type", message_type_label.as_ref()),
+                        (
+                            "action",
+                            if disconnected {
+                                "disconnected"
+                            } else {
+                                "dropped"
+                            },
+                        ),
+                    ],
+                )
+                .inc();
+        }
+    }
+}
+
+/// The metrics of the messages of one type, created when its handler is set so that dispatching a
+/// message does not look them up in the registry.
+#[cfg(feature = "metrics")]
+struct MessageTypeMetrics {
+    dispatcher: &'static str,
+    message_type: String,
+    messages: Counter,
+    handler_duration: Histogram,
+}
+
+#[cfg(feature = "metrics")]
+impl MessageTypeMetrics {
+    fn new<MT: Debug>(registry: &MetricsRegistry, message_type: &MT) -> Self {
+        let dispatcher = dispatcher_label::<MT>();
+        let message_type = format!("{:?}", message_type);
+        let labels = [
+            ("dispatcher", dispatcher),
+            ("message_type", message_type.as_str()),
+        ];
+
+        MessageTypeMetrics {
+            dispatcher,
+            messages: registry.counter(
+                "splinter_dispatch_messages_total",
+                "Messages dispatched to handlers",
+                &labels,
+            ),
+            handler_duration: registry.histogram(
+                "splinter_dispatch_handler_duration_seconds",
+                "Time taken to handle dispatched messages",
+                &labels,
+            ),
+            message_type,
+        }
+    }
+
+    fn record(
+        &self,
+        registry: &MetricsRegistry,
+        result: &Result<(), DispatchError>,
+        duration: Duration,
+    ) {
+        self.messages.inc();
+        self.handler_duration.observe_duration(duration);
+
+        // Errors are rare, so their counters are only looked up when one occurs
+        if let Err(err) = result {
+            registry
+                .counter(
+                    "splinter_dispatch_errors_total",
+                    "Dispatched messages that could not be handled",
+                    &[
+                        ("dispatcher", self.dispatcher),
+                        ("message_type", self.message_type.as_str()),
+                        ("error", err.kind()),
+                    ],
+                )
+                .inc();
+        }
     }
 }
 
+/// Label the metrics of each dispatcher with the name of its message type, such as
+/// "CircuitMessageType"
+#[cfg(feature = "metrics")]
+fn dispatcher_label<MT>() -> &'static str {
+    std::any::type_name::<MT>()
+        .rsplit("::")
+        .next()
+        .unwrap_or_default()
+}
+
 /// A function that handles inbound message bytes.
 type InnerHandler<MT> = Box<
     dyn Fn(&[u8], &MessageContext<MT>, &dyn Sender<SendRequest>) -> Result<(), DispatchError>
@@ -319,6 +537,7 @@ pub struct DispatchMessage<MT: Any + Hash + Eq + Debug + Clone> {
     message_type: MT,
     message_bytes: Vec<u8>,
     source_peer_id: String,
+    trace_id: Option<String>,
 }
 
 impl<MT: Any + Hash + Eq + Debug + Clone> DispatchMessage<MT> {
@@ -328,9 +547,16 @@ impl<MT: Any + Hash + Eq + Debug + Clone> DispatchMessage<MT> {
             message_type,
             message_bytes,
             source_peer_id,
+            trace_id: None,
         }
     }
 
+    /// Sets the trace id of the message, which is passed on to its handler.
+    pub fn with_trace_id(mut self, trace_id: Option<String>) -> Self {
+        self.trace_id = trace_id;
+        self
+    }
+
     pub fn message_type(&self) -> &MT {
         &self.message_type
     }
@@ -342,6 +568,10 @@ impl<MT: Any + Hash + Eq + Debug + Clone> DispatchMessage<MT> {
     pub fn source_peer_id(&self) -> &str {
         &self.source_peer_id
     }
+
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_id.as_deref()
+    }
 }
 
 /// Errors that may occur during the operation of the Dispatch Loop.
@@ -357,6 +587,13 @@ pub struct DispatchLoop<MT: Any + Hash + Eq + Debug + Clone> {
     receiver: Box<dyn Receiver<DispatchMessage<MT>>>,
     dispatcher: Dispatcher<MT>,
     running: Arc<AtomicBool>,
+    rate_limiting: Option<RateLimiting<MT>>,
+}
+
+/// The rate limiter of a dispatch loop, and the means to disconnect the peers it rejects.
+struct RateLimiting<MT: Hash + Eq + Clone> {
+    rate_limiter: Mutex<RateLimiter<MT>>,
+    disconnector: Box<dyn PeerDisconnector>,
 }
 
 impl<MT: Any + Hash + Eq + Debug + Clone> DispatchLoop<MT> {
@@ -373,9 +610,27 @@ impl<MT: Any + Hash + Eq + Debug + Clone> DispatchLoop<MT> {
             receiver,
             dispatcher,
             running,
+            rate_limiting: None,
         }
     }
 
+    /// Limits the rate at which the messages of each peer are dispatched.
+    ///
+    /// Messages that exceed a peer's limits are dropped, and the peer is sent a
+    /// `NETWORK_ERROR_MESSAGE`. Peers that exceed the rate limiter's disconnect threshold are
+    /// disconnected using the given disconnector.
+    pub fn with_rate_limiter(
+        mut self,
+        rate_limiter: RateLimiter<MT>,
+        disconnector: Box<dyn PeerDisconnector>,
+    ) -> Self {
+        self.rate_limiting = Some(RateLimiting {
+            rate_limiter: Mutex::new(rate_limiter),
+            disconnector,
+        });
+        self
+    }
+
     /// Runs the loop.
     ///
     /// Errors
@@ -396,29 +651,82 @@ impl<MT: Any + Hash + Eq + Debug + Clone> DispatchLoop<MT> {
                 }
             };
 
-            match self.dispatcher.dispatch(
-                &dispatch_msg.source_peer_id,
-                &dispatch_msg.message_type,
-                dispatch_msg.message_bytes,
-            ) {
-                Ok(_) => (),
-                Err(err) => warn!("Unable to dispatch message: {:?}", err),
-            }
+            self.dispatch(dispatch_msg);
         }
 
         // finish handling any incoming messages
         while let Ok(dispatch_msg) = self.receiver.try_recv() {
-            match self.dispatcher.dispatch(
-                &dispatch_msg.source_peer_id,
-                &dispatch_msg.message_type,
-                dispatch_msg.message_bytes,
-            ) {
-                Ok(_) => (),
-                Err(err) => warn!("Unable to dispatch message: {:?}", err),
-            }
+            self.dispatch(dispatch_msg);
         }
         Ok(())
     }
+
+    fn dispatch(&self, dispatch_msg: DispatchMessage<MT>) {
+        if !self.check_rate_limit(&dispatch_msg) {
+            return;
+        }
+
+        let trace_field = TraceField(dispatch_msg.trace_id()).to_string();
+        if let Err(err) = self.dispatcher.dispatch_with_trace_id(
+            &dispatch_msg.source_peer_id,
+            &dispatch_msg.message_type,
+            dispatch_msg.message_bytes,
+            dispatch_msg.trace_id,
+        ) {
+            warn!("Unable to dispatch message: {:?}{}", err, trace_field);
+        }
+    }
+
+    /// Returns whether the message is within its peer's rate limits, notifying or disconnecting
+    /// the peer if it is not.
+    fn check_rate_limit(&self, dispatch_msg: &DispatchMessage<MT>) -> bool {
+        let rate_limiting = match self.rate_limiting {
+            Some(ref rate_limiting) => rate_limiting,
+            None => return true,
+        };
+
+        let peer_id = dispatch_msg.source_peer_id();
+        let message_type = dispatch_msg.message_type();
+        let decision = mutex_lock_unwrap!(rate_limiting.rate_limiter).check(peer_id, message_type);
+
+        match decision {
+            RateLimitDecision::Allow => return true,
+            RateLimitDecision::Reject { notify: true } => {
+                warn!(
+                    "Peer {} exceeded its rate limit for {:?} messages; dropping messages",
+                    peer_id, message_type
+                );
+                match create_rate_limited_message(message_type) {
+                    Ok(error_bytes) => {
+                        if let Err(err) = self
+                            .dispatcher
+                            .network_sender
+                            .send(SendRequest::new(peer_id.to_string(), error_bytes))
+                        {
+                            warn!("Unable to notify peer {} of rate limit: {}", peer_id, err);
+                        }
+                    }
+                    Err(err) => error!("Unable to create rate limit error message: {}", err),
+                }
+            }
+            RateLimitDecision::Reject { notify: false } => (),
+            RateLimitDecision::Disconnect => {
+                warn!(
+                    "Disconnecting peer {} for repeatedly exceeding its rate limits",
+                    peer_id
+                );
+                if let Err(err) = rate_limiting.disconnector.disconnect_peer(peer_id) {
+                    warn!("Unable to disconnect peer {}: {}", peer_id, err);
+                }
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        self.dispatcher
+            .record_rate_limited(message_type, decision == RateLimitDecision::Disconnect);
+
+        false
+    }
 }
 
 #[cfg(test)]
@@ -431,8 +739,13 @@ mod tests {
     use protobuf::Message;
 
     use crate::channel::mock::MockSender;
+    use crate::network::rate_limit::RateLimit;
     use crate::network::sender::SendRequest;
-    use crate::protos::network::{NetworkEcho, NetworkMessageType};
+    use crate::network::ConnectionError;
+    use crate::protos::circuit::{
+        CircuitMessage, CircuitMessageType, NetworkError, NetworkError_Error,
+    };
+    use crate::protos::network::{NetworkEcho, NetworkMessage, NetworkMessageType};
 
     /// Verify that messages can be dispatched to handlers implemented as closures.
     ///
@@ -476,6 +789,186 @@ mod tests {
         assert_eq!(true, flag.load(Ordering::SeqCst));
     }
 
+    /// Verify that the trace id of a dispatched message is available to its handler.
+    ///
+    /// This test does the following:
+    ///
+    /// * Create a Dispatcher with a handler that records the trace id of its message context
+    /// * Dispatch a message with a trace id and verify the handler received it
+    /// * Dispatch a message without a trace id and verify the handler received none
+    #[test]
+    fn dispatch_with_trace_id() {
+        let trace_ids = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Box::new(MockSender::default()));
+        let handler_trace_ids = trace_ids.clone();
+        dispatcher.set_handler(
+            NetworkMessageType::NETWORK_ECHO,
+            Box::new(
+                move |_: NetworkEcho,
+                      context: &MessageContext<NetworkMessageType>,
+                      _: &dyn Sender<SendRequest>| {
+                    handler_trace_ids
+                        .lock()
+                        .unwrap()
+                        .push(context.trace_id().map(String::from));
+                    Ok(())
+                },
+            ),
+        );
+
+        dispatcher
+            .dispatch_with_trace_id(
+                "TestPeer",
+                &NetworkMessageType::NETWORK_ECHO,
+                Vec::new(),
+                Some("trace-1".into()),
+            )
+            .unwrap();
+        dispatcher
+            .dispatch("TestPeer", &NetworkMessageType::NETWORK_ECHO, Vec::new())
+            .unwrap();
+
+        assert_eq!(
+            vec![Some("trace-1".to_string()), None],
+            *trace_ids.lock().unwrap()
+        );
+    }
+
+    /// Verify that a dispatcher with metrics records the messages dispatched and the errors
+    /// returned, per message type.
+    ///
+    /// This test does the following:
+    ///
+    /// * Create a Dispatcher with a metrics registry
+    /// * Dispatch two messages with a handler and a message without one
+    /// * Verify that all messages, and the error for the last, were recorded
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn dispatch_with_metrics() {
+        let metrics = MetricsRegistry::new();
+        let mut dispatcher =
+            Dispatcher::new(Box::new(MockSender::default())).with_metrics(metrics.clone());
+        dispatcher.set_handler(
+            NetworkMessageType::NETWORK_ECHO,
+            Box::new(
+                |_: NetworkEcho,
+                 _: &MessageContext<NetworkMessageType>,
+                 _: &dyn Sender<SendRequest>| Ok(()),
+            ),
+        );
+
+        assert!(dispatcher
+            .dispatch("TestPeer", &NetworkMessageType::NETWORK_ECHO, Vec::new())
+            .is_ok());
+        assert!(dispatcher
+            .dispatch("TestPeer", &NetworkMessageType::NETWORK_ECHO, Vec::new())
+            .is_ok());
+        assert!(dispatcher
+            .dispatch("TestPeer", &NetworkMessageType::CIRCUIT, Vec::new())
+            .is_err());
+
+        let output = metrics.render();
+        assert!(output.contains(
+            "splinter_dispatch_messages_total{dispatcher=\"NetworkMessageType\",\
+             message_type=\"NETWORK_ECHO\"} 2"
+        ));
+        assert!(output.contains(
+            "splinter_dispatch_errors_total{dispatcher=\"NetworkMessageType\",\
+             message_type=\"CIRCUIT\",error=\"unknown_message_type\"} 1"
+        ));
+        assert!(output.contains(
+            "splinter_dispatch_handler_duration_seconds_count{\
+             dispatcher=\"NetworkMessageType\",message_type=\"NETWORK_ECHO\"} 2"
+        ));
+    }
+
+    /// Verify that a dispatch loop with a rate limiter drops the messages that exceed a peer's
+    /// limits, notifies the peer and disconnects it once it exceeds the disconnect threshold.
+    ///
+    /// This test does the following:
+    ///
+    /// * Create a DispatchLoop with a rate limiter that allows a burst of two messages and
+    ///   disconnects peers after two rejected messages
+    /// * Queue four messages from the same peer and run the loop
+    /// * Verify that two messages were handled, a NETWORK_ERROR_MESSAGE with a rate limited error
+    ///   was sent to the peer and the peer was disconnected
+    #[test]
+    fn dispatch_loop_with_rate_limiter() {
+        let handled = Arc::new(Mutex::new(0));
+        let sender = MockSender::default();
+
+        let mut dispatcher = Dispatcher::new(Box::new(sender.clone()));
+        let handler_count = handled.clone();
+        dispatcher.set_handler(
+            NetworkMessageType::NETWORK_ECHO,
+            Box::new(
+                move |_: NetworkEcho,
+                      _: &MessageContext<NetworkMessageType>,
+                      _: &dyn Sender<SendRequest>| {
+                    *handler_count.lock().unwrap() += 1;
+                    Ok(())
+                },
+            ),
+        );
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        for _ in 0..4 {
+            tx.send(DispatchMessage::new(
+                NetworkMessageType::NETWORK_ECHO,
+                Vec::new(),
+                "TestPeer".into(),
+            ))
+            .unwrap();
+        }
+
+        let disconnector = MockDisconnector::default();
+        let rate_limiter = RateLimiter::new(RateLimit::new(2, 0.001).unwrap())
+            .with_disconnect_threshold(2, Duration::from_secs(60));
+
+        // The loop is not running, so it handles the queued messages and returns
+        DispatchLoop::new(Box::new(rx), dispatcher, Arc::new(AtomicBool::new(false)))
+            .with_rate_limiter(rate_limiter, Box::new(disconnector.clone()))
+            .run()
+            .unwrap();
+
+        assert_eq!(2, *handled.lock().unwrap());
+
+        let sent = sender.sent();
+        assert_eq!(1, sent.len());
+        assert_eq!("TestPeer", sent[0].recipient());
+        let network_msg: NetworkMessage = protobuf::parse_from_bytes(sent[0].payload()).unwrap();
+        let circuit_msg: CircuitMessage =
+            protobuf::parse_from_bytes(network_msg.get_payload()).unwrap();
+        assert_eq!(
+            CircuitMessageType::NETWORK_ERROR_MESSAGE,
+            circuit_msg.get_message_type()
+        );
+        let network_error: NetworkError =
+            protobuf::parse_from_bytes(circuit_msg.get_payload()).unwrap();
+        assert_eq!(
+            NetworkError_Error::ERROR_RATE_LIMITED,
+            network_error.get_error()
+        );
+
+        assert_eq!(
+            vec!["TestPeer".to_string()],
+            *disconnector.disconnected.lock().unwrap()
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct MockDisconnector {
+        disconnected: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PeerDisconnector for MockDisconnector {
+        fn disconnect_peer(&self, peer_id: &str) -> Result<(), ConnectionError> {
+            self.disconnected.lock().unwrap().push(peer_id.to_string());
+            Ok(())
+        }
+    }
+
     /// Verify that messages can be dispatched to handlers via the trait.
     ///
     /// This test does the following:
@@ -13,8 +13,12 @@
 // limitations under the License.
 use crate::channel::Sender;
 use crate::network::dispatch::{DispatchError, Handler, MessageContext};
+use crate::network::routing::RoutingTable;
 use crate::network::sender::SendRequest;
-use crate::protos::network::{NetworkEcho, NetworkHeartbeat, NetworkMessage, NetworkMessageType};
+use crate::network::Network;
+use crate::protos::network::{
+    NetworkEcho, NetworkHeartbeat, NetworkMessage, NetworkMessageType, NetworkRouteAdvertisement,
+};
 
 use protobuf::Message;
 
@@ -68,7 +72,9 @@ impl NetworkEchoHandler {
 
 // Implements a handler that handles NetworkHeartbeat Messages
 #[derive(Default)]
-pub struct NetworkHeartbeatHandler {}
+pub struct NetworkHeartbeatHandler {
+    network: Option<Network>,
+}
 
 impl Handler<NetworkMessageType, NetworkHeartbeat> for NetworkHeartbeatHandler {
     fn handle(
@@ -78,13 +84,67 @@ impl Handler<NetworkMessageType, NetworkHeartbeat> for NetworkHeartbeatHandler {
         _sender: &dyn Sender<SendRequest>,
     ) -> Result<(), DispatchError> {
         trace!("Received Heartbeat from {}", context.source_peer_id());
+        if let Some(network) = &self.network {
+            network.record_heartbeat(context.source_peer_id());
+        }
         Ok(())
     }
 }
 
 impl NetworkHeartbeatHandler {
     pub fn new() -> Self {
-        NetworkHeartbeatHandler {}
+        NetworkHeartbeatHandler { network: None }
+    }
+
+    /// Constructs a handler that records received heartbeats in the network's peer statistics.
+    pub fn with_network(network: Network) -> Self {
+        NetworkHeartbeatHandler {
+            network: Some(network),
+        }
+    }
+}
+
+// Implements a handler that handles NetworkRouteAdvertisement Messages
+pub struct NetworkRouteAdvertisementHandler {
+    routing_table: RoutingTable,
+}
+
+impl Handler<NetworkMessageType, NetworkRouteAdvertisement> for NetworkRouteAdvertisementHandler {
+    fn handle(
+        &self,
+        msg: NetworkRouteAdvertisement,
+        context: &MessageContext<NetworkMessageType>,
+        _sender: &dyn Sender<SendRequest>,
+    ) -> Result<(), DispatchError> {
+        trace!(
+            "Received route advertisement for {} from {}",
+            msg.get_node_id(),
+            context.source_peer_id()
+        );
+
+        // Advertisements are not signed, so a peer may only advertise its own peers
+        if msg.get_node_id() != context.source_peer_id() {
+            debug!(
+                "Ignoring route advertisement for {} from {}",
+                msg.get_node_id(),
+                context.source_peer_id()
+            );
+            return Ok(());
+        }
+
+        self.routing_table.update_node(
+            msg.get_node_id(),
+            msg.get_peers().to_vec(),
+            msg.get_sequence(),
+        );
+
+        Ok(())
+    }
+}
+
+impl NetworkRouteAdvertisementHandler {
+    pub fn new(routing_table: RoutingTable) -> Self {
+        NetworkRouteAdvertisementHandler { routing_table }
     }
 }
 
@@ -134,4 +194,54 @@ mod tests {
         assert_eq!(echo.get_time_to_live(), 2);
         assert_eq!(echo.get_payload().to_vec(), b"HelloWorld".to_vec());
     }
+
+    /// Test that a route advertisement updates the routing table only if it was received from the
+    /// node it describes, and that it is not forwarded to the other peers.
+    #[test]
+    fn route_advertisement_from_peer() {
+        let sender = Box::new(MockSender::default());
+        let mut dispatcher = Dispatcher::new(sender.box_clone());
+
+        let routing_table = RoutingTable::new("local".to_string());
+        routing_table.set_local_peers(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+
+        let handler = NetworkRouteAdvertisementHandler::new(routing_table.clone());
+        dispatcher.set_handler(
+            NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT,
+            Box::new(handler),
+        );
+
+        let msg = {
+            let mut advertisement = NetworkRouteAdvertisement::new();
+            advertisement.set_node_id("c".to_string());
+            advertisement.set_peers(vec!["local".to_string(), "d".to_string()].into());
+            advertisement.set_sequence(1);
+            advertisement.set_time_to_live(1);
+            advertisement
+        };
+        let msg_bytes = msg.write_to_bytes().unwrap();
+
+        // a peer may not advertise the peers of another node
+        assert_eq!(
+            Ok(()),
+            dispatcher.dispatch(
+                "a",
+                &NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT,
+                msg_bytes.clone()
+            )
+        );
+        assert_eq!(None, routing_table.next_hop("d"));
+
+        assert_eq!(
+            Ok(()),
+            dispatcher.dispatch(
+                "c",
+                &NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT,
+                msg_bytes
+            )
+        );
+        assert_eq!(Some("c".to_string()), routing_table.next_hop("d"));
+
+        assert!(sender.sent().is_empty());
+    }
 }
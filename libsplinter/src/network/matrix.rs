@@ -0,0 +1,146 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Matrix implementations backed by a `Network`.
+//!
+//! These allow a `ConnectionManager` to own the connections of a `Network`, such that connections
+//! it creates are registered as (temporary) peers and are visible to authorization, and removals
+//! notify the network's disconnect listeners.
+
+use uuid::Uuid;
+
+use crate::matrix::{
+    MatrixAddError, MatrixLifeCycle, MatrixRemoveError, MatrixSendError, MatrixSender,
+};
+use crate::mesh::Envelope;
+use crate::transport::Connection;
+
+use super::Network;
+
+#[derive(Clone)]
+pub struct NetworkLifeCycle {
+    network: Network,
+}
+
+impl NetworkLifeCycle {
+    pub fn new(network: Network) -> Self {
+        NetworkLifeCycle { network }
+    }
+}
+
+impl MatrixLifeCycle for NetworkLifeCycle {
+    fn add(&self, connection: Box<dyn Connection>) -> Result<usize, MatrixAddError> {
+        let mut peers = rwlock_write_unwrap!(self.network.peers);
+        let endpoint = connection.remote_endpoint();
+        let mesh_id = self.network.mesh.add(connection).map_err(|err| {
+            MatrixAddError::new(
+                "Unable to add connection to Network.".to_string(),
+                Some(Box::new(err)),
+            )
+        })?;
+        // Temp peer id until the connection has completed authorization
+        let peer_id = format!("temp-{}", Uuid::new_v4());
+        peers.insert(peer_id, mesh_id, endpoint);
+
+        Ok(mesh_id)
+    }
+
+    fn remove(&self, id: usize) -> Result<Box<dyn Connection>, MatrixRemoveError> {
+        let peer_id = {
+            let mut peers = rwlock_write_unwrap!(self.network.peers);
+            let peer_id = peers.get_peer_id(id).cloned();
+            if let Some(ref peer_id) = peer_id {
+                peers.remove(peer_id);
+            }
+            peer_id
+        };
+
+        let connection = self.network.mesh.remove(id).map_err(|err| {
+            MatrixRemoveError::new(
+                "Unable to remove connection from Network.".to_string(),
+                Some(Box::new(err)),
+            )
+        })?;
+
+        if let Some(peer_id) = peer_id {
+            self.network.notify_disconnect_listeners(&peer_id);
+        }
+
+        Ok(connection)
+    }
+}
+
+#[derive(Clone)]
+pub struct NetworkMatrixSender {
+    network: Network,
+}
+
+impl NetworkMatrixSender {
+    pub fn new(network: Network) -> Self {
+        NetworkMatrixSender { network }
+    }
+}
+
+impl MatrixSender for NetworkMatrixSender {
+    fn send(&self, id: usize, message: Vec<u8>) -> Result<(), MatrixSendError> {
+        let envelope = Envelope::new(id, message);
+        self.network.mesh.send(envelope).map_err(|err| {
+            MatrixSendError::new(
+                "Unable to send message to connection.".to_string(),
+                Some(Box::new(err)),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    use crate::mesh::Mesh;
+    use crate::transport::{inproc::InprocTransport, Transport};
+
+    /// Test that a connection added through the life cycle is registered as a temporary peer, and
+    /// that removing it notifies the network's disconnect listeners.
+    #[test]
+    fn test_life_cycle_add_remove() {
+        let mut transport = InprocTransport::default();
+        let mut listener = transport.listen("inproc://test_life_cycle").unwrap();
+
+        let handle = thread::spawn(move || listener.accept().unwrap());
+
+        let network = Network::new(Mesh::new(4, 4), 0).unwrap();
+        let (disconnect_tx, disconnect_rx) = channel();
+        network.add_disconnect_listener(Box::new(move |peer_id: &str| {
+            disconnect_tx.send(peer_id.to_string()).unwrap();
+        }));
+
+        let life_cycle = network.get_life_cycle();
+        let connection = transport.connect("inproc://test_life_cycle").unwrap();
+        let id = life_cycle.add(connection).unwrap();
+        let _remote = handle.join().unwrap();
+
+        let peer_ids = network.peer_ids();
+        assert_eq!(1, peer_ids.len());
+        assert!(peer_ids[0].starts_with("temp-"));
+
+        life_cycle.remove(id).unwrap();
+
+        assert!(network.peer_ids().is_empty());
+        assert_eq!(peer_ids[0], disconnect_rx.recv().unwrap());
+    }
+}
@@ -12,19 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod auth;
+#[cfg(feature = "compression")]
+pub mod compression;
 #[cfg(feature = "connection-manager")]
 pub mod connection_manager;
 pub mod dispatch;
 mod dispatch_proto;
 pub mod handlers;
+#[cfg(feature = "connection-manager")]
+mod matrix;
 pub mod peer;
+pub mod rate_limit;
 pub(crate) mod reply;
+pub mod routing;
 pub mod sender;
+mod stats;
+pub mod trace;
 
 use protobuf::Message;
 use uuid::Uuid;
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
@@ -34,9 +43,15 @@ use crate::mesh::{
     AddError, Envelope, Mesh, RecvError as MeshRecvError, RecvTimeoutError as MeshRecvTimeoutError,
     RemoveError, SendError as MeshSendError,
 };
+#[cfg(feature = "connection-manager")]
+pub use crate::network::matrix::{NetworkLifeCycle, NetworkMatrixSender};
+use crate::protos::authorization::CompressionType;
 use crate::protos::network::{NetworkHeartbeat, NetworkMessage, NetworkMessageType};
 use crate::transport::Connection;
 
+use self::stats::PeerCounters;
+pub use self::stats::PeerStats;
+
 #[derive(Debug)]
 pub struct NetworkMessageWrapper {
     peer_id: String,
@@ -75,6 +90,9 @@ struct PeerMap {
     peers: BiHashMap<String, usize>,
     redirects: HashMap<String, String>,
     endpoints: BiHashMap<String, String>,
+    counters: HashMap<usize, Arc<PeerCounters>>,
+    // mesh ids of the connections whose peers accepted compression
+    compressed: HashSet<usize>,
 }
 
 /// A map of Peer IDs to mesh IDs, which also maintains a redirect table for updated peer ids.
@@ -84,6 +102,8 @@ impl PeerMap {
             peers: BiHashMap::new(),
             redirects: HashMap::new(),
             endpoints: BiHashMap::new(),
+            counters: HashMap::new(),
+            compressed: HashSet::new(),
         }
     }
 
@@ -101,6 +121,8 @@ impl PeerMap {
     fn insert(&mut self, peer_id: String, mesh_id: usize, endpoint: String) {
         self.peers.insert(peer_id.clone(), mesh_id);
         self.endpoints.insert(peer_id, endpoint);
+        self.counters
+            .insert(mesh_id, Arc::new(PeerCounters::default()));
     }
 
     /// Remove a peer id, its endpoint and all of its redirects
@@ -110,9 +132,15 @@ impl PeerMap {
         self.redirects
             .retain(|_, target_peer_id| target_peer_id != peer_id);
         self.endpoints.remove_by_key(&peer_id_key);
-        self.peers
+        let mesh_id = self
+            .peers
             .remove_by_key(&peer_id_key)
-            .map(|(_, mesh_id)| mesh_id)
+            .map(|(_, mesh_id)| mesh_id);
+        if let Some(mesh_id) = mesh_id {
+            self.counters.remove(&mesh_id);
+            self.compressed.remove(&mesh_id);
+        }
+        mesh_id
     }
 
     /// Updates a peer id, and creates a redirect for the old id to the given new one.
@@ -172,6 +200,16 @@ impl PeerMap {
     fn get_peer_by_endpoint(&self, endpoint: &str) -> Option<String> {
         self.endpoints.get_by_value(&endpoint.to_string()).cloned()
     }
+
+    /// Returns the traffic counters for the given mesh id
+    fn get_counters(&self, mesh_id: usize) -> Option<Arc<PeerCounters>> {
+        self.counters.get(&mesh_id).cloned()
+    }
+
+    /// Returns true if the peer on the given mesh id accepted compression
+    fn is_compressed(&self, mesh_id: usize) -> bool {
+        self.compressed.contains(&mesh_id)
+    }
 }
 
 #[derive(Clone)]
@@ -179,6 +217,7 @@ pub struct Network {
     peers: Arc<RwLock<PeerMap>>,
     mesh: Mesh,
     disconnect_listeners: Arc<Mutex<Vec<Box<dyn DisconnectListener>>>>,
+    compression_threshold: Option<usize>,
 }
 
 impl Network {
@@ -187,6 +226,7 @@ impl Network {
             peers: Arc::new(RwLock::new(PeerMap::new())),
             mesh,
             disconnect_listeners: Arc::new(Mutex::new(vec![])),
+            compression_threshold: None,
         };
 
         if heartbeat_interval != 0 {
@@ -220,6 +260,43 @@ impl Network {
         Ok(network)
     }
 
+    /// Enables compression of messages larger than the given number of bytes, for peers that
+    /// accept compression during the connect handshake.
+    ///
+    /// This must be called before the network is cloned, as clones do not share this setting.
+    #[cfg(feature = "compression")]
+    pub fn with_compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// Returns true if this network is able to compress and decompress messages.
+    pub fn compression_enabled(&self) -> bool {
+        self.compression_threshold.is_some()
+    }
+
+    /// Returns the compression types this network accepts in the connect handshake.
+    pub(crate) fn accepted_compression_types(&self) -> Vec<CompressionType> {
+        if self.compression_enabled() {
+            vec![CompressionType::ZLIB]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Sets whether messages to the given peer may be compressed, as negotiated during the
+    /// connect handshake.
+    pub(crate) fn set_peer_compression(&self, peer_id: &str, enabled: bool) {
+        let mut peers = rwlock_write_unwrap!(self.peers);
+        if let Some(mesh_id) = peers.get_mesh_id(peer_id).copied() {
+            if enabled {
+                peers.compressed.insert(mesh_id);
+            } else {
+                peers.compressed.remove(&mesh_id);
+            }
+        }
+    }
+
     pub fn peer_ids(&self) -> Vec<String> {
         rwlock_read_unwrap!(self.peers).peer_ids()
     }
@@ -232,6 +309,44 @@ impl Network {
         rwlock_read_unwrap!(self.peers).get_peer_by_endpoint(endpoint)
     }
 
+    /// Returns the traffic statistics for the given peer's connection.
+    pub fn get_peer_stats(&self, peer_id: &str) -> Option<PeerStats> {
+        let peers = rwlock_read_unwrap!(self.peers);
+        peers
+            .get_mesh_id(peer_id)
+            .and_then(|mesh_id| peers.get_counters(*mesh_id))
+            .map(|counters| counters.snapshot())
+    }
+
+    /// Records that a heartbeat has been received from the given peer.
+    pub fn record_heartbeat(&self, peer_id: &str) {
+        let peers = rwlock_read_unwrap!(self.peers);
+        if let Some(counters) = peers
+            .get_mesh_id(peer_id)
+            .and_then(|mesh_id| peers.get_counters(*mesh_id))
+        {
+            counters.record_heartbeat();
+        }
+    }
+
+    /// Returns the peer id for the connection with the given mesh id.
+    #[cfg(feature = "connection-manager")]
+    pub(crate) fn get_peer_by_mesh_id(&self, mesh_id: usize) -> Option<String> {
+        rwlock_read_unwrap!(self.peers)
+            .get_peer_id(mesh_id)
+            .cloned()
+    }
+
+    #[cfg(feature = "connection-manager")]
+    pub fn get_life_cycle(&self) -> NetworkLifeCycle {
+        NetworkLifeCycle::new(self.clone())
+    }
+
+    #[cfg(feature = "connection-manager")]
+    pub fn get_sender(&self) -> NetworkMatrixSender {
+        NetworkMatrixSender::new(self.clone())
+    }
+
     pub fn add_disconnect_listener(&self, listener: Box<dyn DisconnectListener>) {
         match self.disconnect_listeners.lock() {
             Ok(mut listeners) => {
@@ -304,15 +419,28 @@ impl Network {
     }
 
     pub fn send(&self, peer_id: &str, msg: &[u8]) -> Result<(), SendError> {
-        let mesh_id = match rwlock_read_unwrap!(self.peers).get_mesh_id(peer_id) {
-            Some(mesh_id) => *mesh_id,
-            None => {
-                return Err(SendError::NoPeerError(peer_id.to_string()));
+        let (mesh_id, counters, compress) = {
+            let peers = rwlock_read_unwrap!(self.peers);
+            match peers.get_mesh_id(peer_id) {
+                Some(mesh_id) => (
+                    *mesh_id,
+                    peers.get_counters(*mesh_id),
+                    peers.is_compressed(*mesh_id),
+                ),
+                None => {
+                    return Err(SendError::NoPeerError(peer_id.to_string()));
+                }
             }
         };
+        let msg = self.encode(msg, compress)?;
 
-        match self.mesh.send(Envelope::new(mesh_id, msg.to_vec())) {
-            Ok(()) => (),
+        let msg_len = msg.len();
+        match self.mesh.send(Envelope::new(mesh_id, msg.into_owned())) {
+            Ok(()) => {
+                if let Some(counters) = counters {
+                    counters.record_sent(msg_len);
+                }
+            }
             Err(MeshSendError::Disconnected(err)) => {
                 rwlock_write_unwrap!(self.peers).remove(peer_id);
                 self.notify_disconnect_listeners(peer_id);
@@ -326,8 +454,8 @@ impl Network {
 
     pub fn recv(&self) -> Result<NetworkMessageWrapper, RecvError> {
         let envelope = self.mesh.recv()?;
-        let peer_id = match rwlock_read_unwrap!(self.peers).get_peer_id(envelope.id()) {
-            Some(peer_id) => peer_id.to_string(),
+        let peer_id = match self.received_from(&envelope) {
+            Some(peer_id) => peer_id,
             None => {
                 return Err(RecvError::NoPeerError(format!(
                     "Recv Error: No Peer with mesh id {} found",
@@ -336,7 +464,10 @@ impl Network {
             }
         };
 
-        Ok(NetworkMessageWrapper::new(peer_id, envelope.take_payload()))
+        let payload = self
+            .decode(envelope.take_payload())
+            .map_err(RecvError::DecompressionError)?;
+        Ok(NetworkMessageWrapper::new(peer_id, payload))
     }
 
     pub fn recv_timeout(
@@ -344,8 +475,8 @@ impl Network {
         timeout: Duration,
     ) -> Result<NetworkMessageWrapper, RecvTimeoutError> {
         let envelope = self.mesh.recv_timeout(timeout)?;
-        let peer_id = match rwlock_read_unwrap!(self.peers).get_peer_id(envelope.id()) {
-            Some(peer_id) => peer_id.to_string(),
+        let peer_id = match self.received_from(&envelope) {
+            Some(peer_id) => peer_id,
             None => {
                 return Err(RecvTimeoutError::NoPeerError(format!(
                     "Recv Error: No Peer with mesh id {} found",
@@ -354,7 +485,53 @@ impl Network {
             }
         };
 
-        Ok(NetworkMessageWrapper::new(peer_id, envelope.take_payload()))
+        let payload = self
+            .decode(envelope.take_payload())
+            .map_err(RecvTimeoutError::DecompressionError)?;
+        Ok(NetworkMessageWrapper::new(peer_id, payload))
+    }
+
+    /// Compresses an outbound message if the peer accepted compression and the message is larger
+    /// than the compression threshold.
+    #[cfg(feature = "compression")]
+    fn encode<'a>(&self, msg: &'a [u8], compress: bool) -> Result<Cow<'a, [u8]>, SendError> {
+        match self.compression_threshold {
+            Some(threshold) if compress && msg.len() > threshold => compression::compress(msg)
+                .map(Cow::Owned)
+                .map_err(|err| SendError::CompressionError(err.to_string())),
+            _ => Ok(Cow::Borrowed(msg)),
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn encode<'a>(&self, msg: &'a [u8], _compress: bool) -> Result<Cow<'a, [u8]>, SendError> {
+        Ok(Cow::Borrowed(msg))
+    }
+
+    /// Decompresses an inbound message if it was compressed by the peer.
+    #[cfg(feature = "compression")]
+    fn decode(&self, msg: Vec<u8>) -> Result<Vec<u8>, String> {
+        if self.compression_enabled() {
+            compression::decompress(msg).map_err(|err| err.to_string())
+        } else {
+            Ok(msg)
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decode(&self, msg: Vec<u8>) -> Result<Vec<u8>, String> {
+        Ok(msg)
+    }
+
+    /// Returns the id of the peer that sent the given envelope, recording the received message
+    /// in the peer's traffic statistics.
+    fn received_from(&self, envelope: &Envelope) -> Option<String> {
+        let peers = rwlock_read_unwrap!(self.peers);
+        let peer_id = peers.get_peer_id(envelope.id())?.to_string();
+        if let Some(counters) = peers.get_counters(envelope.id()) {
+            counters.record_received(envelope.payload().len());
+        }
+        Some(peer_id)
     }
 }
 
@@ -364,6 +541,7 @@ impl Network {
 pub enum RecvError {
     NoPeerError(String),
     MeshError(String),
+    DecompressionError(String),
 }
 
 impl From<MeshRecvError> for RecvError {
@@ -377,6 +555,7 @@ pub enum RecvTimeoutError {
     NoPeerError(String),
     Timeout,
     Disconnected,
+    DecompressionError(String),
 }
 
 impl From<MeshRecvTimeoutError> for RecvTimeoutError {
@@ -392,6 +571,7 @@ impl From<MeshRecvTimeoutError> for RecvTimeoutError {
 pub enum SendError {
     NoPeerError(String),
     MeshError(String),
+    CompressionError(String),
 }
 
 impl std::error::Error for SendError {}
@@ -401,6 +581,7 @@ impl std::fmt::Display for SendError {
         match self {
             SendError::NoPeerError(msg) => write!(f, "no peer with peer_id {} found", msg),
             SendError::MeshError(msg) => write!(f, "received error from mesh: {}", msg),
+            SendError::CompressionError(msg) => write!(f, "unable to compress message: {}", msg),
         }
     }
 }
@@ -547,4 +728,91 @@ pub mod tests {
         assert_eq!("123", message.peer_id());
         assert_eq!(heartbeat_bytes, message.payload());
     }
+
+    /// Test that the traffic statistics of a peer are updated on send, receive and heartbeat, and
+    /// that they are dropped along with the peer.
+    #[test]
+    fn test_peer_stats() {
+        let network = Network::new(Mesh::new(5, 5), 0).unwrap();
+
+        let mut transport = RawTransport::default();
+        let mut listener = assert_ok(transport.listen("127.0.0.1:0"));
+        let endpoint = listener.endpoint();
+
+        let handle = thread::spawn(move || {
+            let mesh = Mesh::new(5, 5);
+            let id = assert_ok(mesh.add(assert_ok(transport.connect(&endpoint))));
+            assert_ok(mesh.send(Envelope::new(id, b"hello".to_vec())));
+            // wait for the reply before dropping the connection
+            assert_ok(mesh.recv());
+        });
+
+        let connection = assert_ok(listener.accept());
+        assert_ok(network.add_peer("123".into(), connection));
+        assert_eq!(Some(PeerStats::default()), network.get_peer_stats("123"));
+
+        let message = assert_ok(network.recv());
+        assert_eq!(b"hello", message.payload());
+        assert_ok(network.send("123", b"world!"));
+        network.record_heartbeat("123");
+
+        let stats = network.get_peer_stats("123").expect("No stats for peer");
+        assert_eq!(5, stats.bytes_received);
+        assert_eq!(1, stats.messages_received);
+        assert_eq!(6, stats.bytes_sent);
+        assert_eq!(1, stats.messages_sent);
+        assert!(stats.last_heartbeat.is_some());
+
+        handle.join().unwrap();
+
+        assert_ok(network.remove_connection("123"));
+        assert_eq!(None, network.get_peer_stats("123"));
+    }
+
+    /// Test that messages larger than the compression threshold are compressed for peers that
+    /// accepted compression, and are received unchanged.
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compression() {
+        let network = Network::new(Mesh::new(5, 5), 0)
+            .unwrap()
+            .with_compression_threshold(100);
+
+        let mut transport = RawTransport::default();
+        let mut listener = assert_ok(transport.listen("127.0.0.1:0"));
+        let endpoint = listener.endpoint();
+
+        let mut network_msg = NetworkMessage::new();
+        network_msg.set_message_type(NetworkMessageType::CIRCUIT);
+        network_msg.set_payload(vec![1; 1000]);
+        let msg_bytes = network_msg.write_to_bytes().unwrap();
+        let remote_msg_bytes = msg_bytes.clone();
+
+        let handle = thread::spawn(move || {
+            let remote_network = Network::new(Mesh::new(5, 5), 0)
+                .unwrap()
+                .with_compression_threshold(100);
+            let connection = assert_ok(transport.connect(&endpoint));
+            assert_ok(remote_network.add_peer("local".into(), connection));
+            remote_network.set_peer_compression("local", true);
+            assert_ok(remote_network.send("local", &remote_msg_bytes));
+            // wait for the reply before dropping the connection
+            assert_ok(remote_network.recv());
+        });
+
+        let connection = assert_ok(listener.accept());
+        assert_ok(network.add_peer("remote".into(), connection));
+
+        let message = assert_ok(network.recv());
+        assert_eq!(&msg_bytes[..], message.payload());
+        let stats = network.get_peer_stats("remote").expect("No stats for peer");
+        assert!(stats.bytes_received < msg_bytes.len() as u64);
+
+        // the peer has not accepted compression, so the message is sent uncompressed
+        assert_ok(network.send("remote", &msg_bytes));
+        let stats = network.get_peer_stats("remote").expect("No stats for peer");
+        assert_eq!(msg_bytes.len() as u64, stats.bytes_sent);
+
+        handle.join().unwrap();
+    }
 }
@@ -16,6 +16,8 @@ use std::sync::{Arc, Mutex};
 
 use protobuf::Message;
 
+#[cfg(feature = "connection-manager")]
+use crate::network::connection_manager::{CmResponse, CmResponseStatus, Connector};
 use crate::protos::{
     authorization::{
         AuthorizationMessage, AuthorizationMessageType, ConnectRequest,
@@ -74,9 +76,20 @@ impl fmt::Display for PeerConnectorError {
     }
 }
 
+/// The means by which a `PeerConnector` opens new connections.
+#[derive(Clone)]
+enum PeerConnectionSource {
+    /// Connections are created directly from a transport and added to the network.
+    Transport(Arc<Mutex<Box<dyn Transport + Send>>>),
+    /// Connections are requested from a connection manager, which adds them to the network and
+    /// maintains them via heartbeats.
+    #[cfg(feature = "connection-manager")]
+    Connector(Connector),
+}
+
 #[derive(Clone)]
 pub struct PeerConnector {
-    transport: Arc<Mutex<Box<dyn Transport + Send>>>,
+    source: PeerConnectionSource,
     network: Network,
 }
 
@@ -84,13 +97,53 @@ impl PeerConnector {
     pub fn new(network: Network, transport: Box<dyn Transport + Send>) -> PeerConnector {
         Self {
             network,
-            transport: Arc::new(Mutex::new(transport)),
+            source: PeerConnectionSource::Transport(Arc::new(Mutex::new(transport))),
+        }
+    }
+
+    /// Constructs a PeerConnector that opens its connections through a connection manager.
+    ///
+    /// The connection manager must have been created with the life cycle and sender of the given
+    /// network (see `Network::get_life_cycle` and `Network::get_sender`).
+    #[cfg(feature = "connection-manager")]
+    pub fn with_connector(network: Network, connector: Connector) -> PeerConnector {
+        Self {
+            network,
+            source: PeerConnectionSource::Connector(connector),
         }
     }
 
     pub fn connect_peer(&self, node_id: &str, endpoint: &str) -> Result<(), PeerConnectorError> {
-        let mut transport = self
-            .transport
+        match self.source {
+            PeerConnectionSource::Transport(ref transport) => {
+                self.connect_peer_with_transport(transport, node_id, endpoint)
+            }
+            #[cfg(feature = "connection-manager")]
+            PeerConnectionSource::Connector(ref connector) => {
+                self.connect_peer_with_connector(connector, node_id, endpoint)
+            }
+        }
+    }
+
+    pub fn connect_unidentified_peer(&self, endpoint: &str) -> Result<(), PeerConnectorError> {
+        match self.source {
+            PeerConnectionSource::Transport(ref transport) => {
+                self.connect_unidentified_peer_with_transport(transport, endpoint)
+            }
+            #[cfg(feature = "connection-manager")]
+            PeerConnectionSource::Connector(ref connector) => {
+                self.connect_unidentified_peer_with_connector(connector, endpoint)
+            }
+        }
+    }
+
+    fn connect_peer_with_transport(
+        &self,
+        transport: &Mutex<Box<dyn Transport + Send>>,
+        node_id: &str,
+        endpoint: &str,
+    ) -> Result<(), PeerConnectorError> {
+        let mut transport = transport
             .lock()
             .map_err(|err| PeerConnectorError::PoisonedLock(err.to_string()))?;
 
@@ -111,7 +164,7 @@ impl PeerConnector {
             .add_peer(node_id.to_string(), connection)
             .map_err(|err| PeerConnectorError::add_peer_failed(node_id, err.to_string()))?;
 
-        let connect_request_msg_bytes = create_connect_request().map_err(|err| {
+        let connect_request_msg_bytes = create_connect_request(&self.network).map_err(|err| {
             PeerConnectorError::connection_failed(
                 node_id,
                 format!("unable to create message: {}", err),
@@ -129,8 +182,12 @@ impl PeerConnector {
         Ok(())
     }
 
-    pub fn connect_unidentified_peer(&self, endpoint: &str) -> Result<(), PeerConnectorError> {
-        let mut transport = self.transport.lock().map_err(|err| {
+    fn connect_unidentified_peer_with_transport(
+        &self,
+        transport: &Mutex<Box<dyn Transport + Send>>,
+        endpoint: &str,
+    ) -> Result<(), PeerConnectorError> {
+        let mut transport = transport.lock().map_err(|err| {
             PeerConnectorError::PoisonedLock(format!("Unable to acquire transport lock: {}", err))
         })?;
 
@@ -147,7 +204,7 @@ impl PeerConnector {
             .add_connection(connection)
             .map_err(|err| PeerConnectorError::add_peer_failed(endpoint, err.to_string()))?;
 
-        let connect_request_msg_bytes = create_connect_request().map_err(|err| {
+        let connect_request_msg_bytes = create_connect_request(&self.network).map_err(|err| {
             PeerConnectorError::connection_failed(
                 endpoint,
                 format!("unable to create message: {}", err),
@@ -164,11 +221,152 @@ impl PeerConnector {
 
         Ok(())
     }
+
+    /// Restarts the authorization handshake for the connection to the given endpoint.
+    ///
+    /// This is used after the connection manager has reestablished a connection that failed its
+    /// heartbeat, as the new connection is added to the network under a temporary peer id.
+    #[cfg(feature = "connection-manager")]
+    pub fn reauthorize_peer(&self, endpoint: &str) -> Result<(), PeerConnectorError> {
+        let connector = match self.source {
+            PeerConnectionSource::Connector(ref connector) => connector,
+            PeerConnectionSource::Transport(_) => {
+                return Err(PeerConnectorError::connection_failed(
+                    endpoint,
+                    "connections are not managed by a connection manager".into(),
+                ))
+            }
+        };
+
+        let peer_id = self.managed_peer_id(connector, endpoint)?.ok_or_else(|| {
+            PeerConnectorError::connection_failed(
+                endpoint,
+                "no connection exists for endpoint".into(),
+            )
+        })?;
+
+        self.send_connect_request(&peer_id, endpoint)
+    }
+
+    #[cfg(feature = "connection-manager")]
+    fn connect_peer_with_connector(
+        &self,
+        connector: &Connector,
+        node_id: &str,
+        endpoint: &str,
+    ) -> Result<(), PeerConnectorError> {
+        if self.managed_peer_id(connector, endpoint)?.is_some() {
+            return Ok(());
+        }
+
+        debug!("Requesting connection to {} at {}...", node_id, endpoint);
+        let temp_peer_id = self.request_connection(connector, node_id, endpoint)?;
+        self.network
+            .update_peer_id(temp_peer_id, node_id.to_string())
+            .map_err(|err| PeerConnectorError::add_peer_failed(node_id, err.to_string()))?;
+
+        self.send_connect_request(node_id, node_id)
+    }
+
+    #[cfg(feature = "connection-manager")]
+    fn connect_unidentified_peer_with_connector(
+        &self,
+        connector: &Connector,
+        endpoint: &str,
+    ) -> Result<(), PeerConnectorError> {
+        if self.managed_peer_id(connector, endpoint)?.is_some() {
+            return Ok(());
+        }
+
+        let temp_peer_id = self.request_connection(connector, endpoint, endpoint)?;
+
+        self.send_connect_request(&temp_peer_id, endpoint)
+    }
+
+    /// Requests a connection from the connection manager, returning the temporary peer id that
+    /// the network assigned to it.
+    #[cfg(feature = "connection-manager")]
+    fn request_connection(
+        &self,
+        connector: &Connector,
+        peer_id: &str,
+        endpoint: &str,
+    ) -> Result<String, PeerConnectorError> {
+        match connector.request_connection(endpoint) {
+            Ok(CmResponse::AddConnection {
+                status: CmResponseStatus::OK,
+                ..
+            }) => (),
+            Ok(CmResponse::AddConnection { error_message, .. }) => {
+                return Err(PeerConnectorError::connection_failed(
+                    peer_id,
+                    error_message.unwrap_or_else(|| "unknown error".into()),
+                ));
+            }
+            Ok(res) => {
+                return Err(PeerConnectorError::connection_failed(
+                    peer_id,
+                    format!("unexpected response from connection manager: {:?}", res),
+                ));
+            }
+            Err(err) => {
+                return Err(PeerConnectorError::connection_failed(
+                    peer_id,
+                    err.to_string(),
+                ));
+            }
+        }
+
+        self.managed_peer_id(connector, endpoint)?.ok_or_else(|| {
+            PeerConnectorError::add_peer_failed(
+                peer_id,
+                "connection was not added to the network".into(),
+            )
+        })
+    }
+
+    /// Returns the network peer id of the connection the connection manager holds for the given
+    /// endpoint.
+    ///
+    /// The endpoint that was requested may differ from the remote endpoint reported by the
+    /// connection (for example, a host name versus its resolved address), so the lookup is done
+    /// via the connection's id.
+    #[cfg(feature = "connection-manager")]
+    fn managed_peer_id(
+        &self,
+        connector: &Connector,
+        endpoint: &str,
+    ) -> Result<Option<String>, PeerConnectorError> {
+        let connection_id = connector
+            .connection_id(endpoint)
+            .map_err(|err| PeerConnectorError::connection_failed(endpoint, err.to_string()))?;
+
+        Ok(connection_id.and_then(|id| self.network.get_peer_by_mesh_id(id)))
+    }
+
+    #[cfg(feature = "connection-manager")]
+    fn send_connect_request(&self, peer_id: &str, label: &str) -> Result<(), PeerConnectorError> {
+        let connect_request_msg_bytes = create_connect_request(&self.network).map_err(|err| {
+            PeerConnectorError::connection_failed(
+                label,
+                format!("unable to create message: {}", err),
+            )
+        })?;
+        self.network
+            .send(peer_id, &connect_request_msg_bytes)
+            .map_err(|err| {
+                PeerConnectorError::connection_failed(
+                    label,
+                    format!("unable to send connect request: {:?}", err),
+                )
+            })
+    }
 }
 
-fn create_connect_request() -> Result<Vec<u8>, protobuf::ProtobufError> {
+fn create_connect_request(network: &Network) -> Result<Vec<u8>, protobuf::ProtobufError> {
     let mut connect_request = ConnectRequest::new();
     connect_request.set_handshake_mode(ConnectRequest_HandshakeMode::BIDIRECTIONAL);
+    connect_request.set_accepted_compression_types(network.accepted_compression_types());
 
     let mut auth_msg_env = AuthorizationMessage::new();
     auth_msg_env.set_message_type(AuthorizationMessageType::CONNECT_REQUEST);
@@ -302,6 +500,49 @@ mod tests {
         assert!(network.peer_ids().is_empty());
     }
 
+    /// Add a connection with a known node id through a connection manager, and verify that the
+    /// connection is added to the network under the node id.  Requesting the same peer a second
+    /// time should not add another connection.
+    #[cfg(feature = "connection-manager")]
+    #[test]
+    fn test_connect_peer_with_connector() {
+        use std::thread;
+
+        use crate::network::connection_manager::ConnectionManager;
+        use crate::transport::inproc::InprocTransport;
+
+        let mut transport = InprocTransport::default();
+        let mut listener = transport.listen("inproc://test_connector").unwrap();
+        let handle = thread::spawn(move || listener.accept().unwrap());
+
+        let mesh = Mesh::new(4, 16);
+        let network = Network::new(mesh, 0).unwrap();
+        let mut cm = ConnectionManager::new(
+            network.get_life_cycle(),
+            network.get_sender(),
+            Box::new(transport),
+        );
+        let connector = cm.start().unwrap();
+
+        let peer_connector = PeerConnector::with_connector(network.clone(), connector);
+
+        assert_eq!(
+            Ok(()),
+            peer_connector.connect_peer("test_node_id", "inproc://test_connector")
+        );
+        let _remote = handle.join().unwrap();
+
+        assert_eq!(vec!["test_node_id".to_string()], network.peer_ids());
+
+        assert_eq!(
+            Ok(()),
+            peer_connector.connect_peer("test_node_id", "inproc://test_connector")
+        );
+        assert_eq!(vec!["test_node_id".to_string()], network.peer_ids());
+
+        cm.shutdown_and_wait();
+    }
+
     struct MockConnectingTransport {
         connection_results: VecDeque<Result<Box<dyn Connection>, ConnectError>>,
     }
@@ -0,0 +1,449 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-peer rate limiting of the messages handled by a `DispatchLoop`.
+//!
+//! Each peer has a token bucket that limits all of the messages it sends, and a token bucket for
+//! each message type that has a limit of its own. A message is dispatched only if a token can be
+//! taken from its buckets. Peers that keep exceeding their limits are disconnected.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use protobuf::Message;
+
+use crate::network::{ConnectionError, Network};
+use crate::protos::circuit::{
+    CircuitMessage, CircuitMessageType, NetworkError, NetworkError_Error,
+};
+use crate::protos::network::{NetworkMessage, NetworkMessageType};
+
+/// The default time within which a peer's rejected messages are counted towards disconnecting it.
+pub const DEFAULT_VIOLATION_WINDOW: Duration = Duration::from_secs(10);
+
+/// A token bucket rate: up to `burst` messages at once, refilled at `per_second` messages per
+/// second.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    burst: u32,
+    per_second: f64,
+}
+
+impl RateLimit {
+    /// Creates a rate limit of up to `burst` messages at once, refilled at `per_second` messages
+    /// per second.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidRateLimitError` if `burst` is zero, if `per_second` is not a positive
+    /// number, or if an empty bucket would take too long to fill up again.
+    pub fn new(burst: u32, per_second: f64) -> Result<Self, InvalidRateLimitError> {
+        if burst == 0 {
+            return Err(InvalidRateLimitError(
+                "burst must be at least 1".to_string(),
+            ));
+        }
+        if !per_second.is_finite() || per_second <= 0.0 {
+            return Err(InvalidRateLimitError(format!(
+                "rate must be a positive number of messages per second, not {}",
+                per_second
+            )));
+        }
+        // Duration::from_secs_f64 panics if the refill time does not fit in a Duration
+        if f64::from(burst) / per_second >= u64::MAX as f64 {
+            return Err(InvalidRateLimitError(format!(
+                "rate of {} messages per second is too low",
+                per_second
+            )));
+        }
+
+        Ok(RateLimit { burst, per_second })
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+
+    /// The time taken for an empty bucket to fill up again.
+    fn refill_time(&self) -> Duration {
+        Duration::from_secs_f64(f64::from(self.burst) / self.per_second)
+    }
+}
+
+/// An error returned when a rate limit is invalid.
+#[derive(Debug)]
+pub struct InvalidRateLimitError(String);
+
+impl std::error::Error for InvalidRateLimitError {}
+
+impl std::fmt::Display for InvalidRateLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid rate limit: {}", self.0)
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        TokenBucket {
+            tokens: f64::from(limit.burst),
+            last_refill: now,
+        }
+    }
+
+    /// Adds the tokens accrued since the last refill, and returns whether a token can be taken.
+    fn refill(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * limit.per_second).min(f64::from(limit.burst));
+        self.last_refill = now;
+
+        self.tokens >= 1.0
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+/// What to do with a message, as decided by a `RateLimiter`.
+#[derive(Debug, PartialEq)]
+pub enum RateLimitDecision {
+    /// The message is within the peer's limits and should be dispatched.
+    Allow,
+    /// The message exceeds the peer's limits and should be dropped. `notify` is true for the first
+    /// message rejected within a violation window, when the peer should be told that it is being
+    /// rate limited.
+    Reject { notify: bool },
+    /// The peer has exceeded its limits too often and should be disconnected.
+    Disconnect,
+}
+
+struct PeerState<MT> {
+    peer_bucket: TokenBucket,
+    message_type_buckets: HashMap<MT, TokenBucket>,
+    violations: u32,
+    window_start: Option<Instant>,
+    last_seen: Instant,
+}
+
+/// Limits the rate at which each peer may send messages, in total and per message type.
+pub struct RateLimiter<MT: Hash + Eq + Clone> {
+    peer_limit: RateLimit,
+    message_type_limits: HashMap<MT, RateLimit>,
+    max_violations: Option<u32>,
+    violation_window: Duration,
+    peers: HashMap<String, PeerState<MT>>,
+    last_pruned: Option<Instant>,
+    limit_hits: u64,
+}
+
+impl<MT: Hash + Eq + Clone> RateLimiter<MT> {
+    /// Creates a rate limiter that limits all of the messages sent by each peer to the given
+    /// rate. Peers are never disconnected unless a disconnect threshold is set.
+    pub fn new(peer_limit: RateLimit) -> Self {
+        RateLimiter {
+            peer_limit,
+            message_type_limits: HashMap::new(),
+            max_violations: None,
+            violation_window: DEFAULT_VIOLATION_WINDOW,
+            peers: HashMap::new(),
+            last_pruned: None,
+            limit_hits: 0,
+        }
+    }
+
+    /// Additionally limits the messages of the given type sent by each peer.
+    pub fn with_message_type_limit(mut self, message_type: MT, limit: RateLimit) -> Self {
+        self.message_type_limits.insert(message_type, limit);
+        self
+    }
+
+    /// Disconnects peers once `max_violations` of their messages have been rejected within the
+    /// given window.
+    pub fn with_disconnect_threshold(mut self, max_violations: u32, window: Duration) -> Self {
+        self.max_violations = Some(max_violations);
+        self.violation_window = window;
+        self
+    }
+
+    /// The number of messages that have exceeded a limit.
+    pub fn limit_hits(&self) -> u64 {
+        self.limit_hits
+    }
+
+    /// Decides what to do with a message of the given type received from the given peer.
+    pub fn check(&mut self, peer_id: &str, message_type: &MT) -> RateLimitDecision {
+        self.check_at(peer_id, message_type, Instant::now())
+    }
+
+    /// Forgets the state of the given peer, such as when it has disconnected.
+    pub fn remove_peer(&mut self, peer_id: &str) {
+        self.peers.remove(peer_id);
+    }
+
+    fn check_at(&mut self, peer_id: &str, message_type: &MT, now: Instant) -> RateLimitDecision {
+        self.prune(now);
+
+        let peer_limit = &self.peer_limit;
+        let peer = self
+            .peers
+            .entry(peer_id.to_string())
+            .or_insert_with(|| PeerState {
+                peer_bucket: TokenBucket::new(peer_limit, now),
+                message_type_buckets: HashMap::new(),
+                violations: 0,
+                window_start: None,
+                last_seen: now,
+            });
+        peer.last_seen = now;
+
+        // A token is only taken once both buckets have one, so that a message rejected by one
+        // bucket does not use up the other
+        let peer_allowed = peer.peer_bucket.refill(peer_limit, now);
+        let message_type_bucket = match self.message_type_limits.get(message_type) {
+            Some(limit) => {
+                let bucket = peer
+                    .message_type_buckets
+                    .entry(message_type.clone())
+                    .or_insert_with(|| TokenBucket::new(limit, now));
+                let allowed = bucket.refill(limit, now);
+                Some((bucket, allowed))
+            }
+            None => None,
+        };
+
+        let message_type_allowed = match message_type_bucket {
+            Some((_, allowed)) => allowed,
+            None => true,
+        };
+        if peer_allowed && message_type_allowed {
+            peer.peer_bucket.take();
+            if let Some((bucket, _)) = message_type_bucket {
+                bucket.take();
+            }
+            return RateLimitDecision::Allow;
+        }
+
+        self.limit_hits += 1;
+
+        match peer.window_start {
+            Some(start) if now.saturating_duration_since(start) < self.violation_window => {
+                peer.violations += 1;
+            }
+            _ => {
+                peer.window_start = Some(now);
+                peer.violations = 1;
+            }
+        }
+
+        match self.max_violations {
+            Some(max_violations) if peer.violations >= max_violations => {
+                self.peers.remove(peer_id);
+                RateLimitDecision::Disconnect
+            }
+            _ => RateLimitDecision::Reject {
+                notify: peer.violations == 1,
+            },
+        }
+    }
+
+    /// Removes the state of peers that have been idle long enough for all of their buckets to be
+    /// full again, so that peers that have disconnected are not tracked forever.
+    fn prune(&mut self, now: Instant) {
+        let idle_timeout = self
+            .message_type_limits
+            .values()
+            .map(RateLimit::refill_time)
+            .chain(vec![self.peer_limit.refill_time(), self.violation_window])
+            .max()
+            .unwrap_or(self.violation_window);
+
+        match self.last_pruned {
+            Some(last_pruned) if now.saturating_duration_since(last_pruned) < idle_timeout => (),
+            _ => {
+                self.peers
+                    .retain(|_, peer| now.saturating_duration_since(peer.last_seen) < idle_timeout);
+                self.last_pruned = Some(now);
+            }
+        }
+    }
+}
+
+/// Disconnects peers that have exceeded their rate limits.
+pub trait PeerDisconnector: Send {
+    fn disconnect_peer(&self, peer_id: &str) -> Result<(), ConnectionError>;
+}
+
+impl PeerDisconnector for Network {
+    fn disconnect_peer(&self, peer_id: &str) -> Result<(), ConnectionError> {
+        self.remove_connection(peer_id)
+    }
+}
+
+/// Creates the `NETWORK_ERROR_MESSAGE` sent to a peer whose messages are being rejected.
+pub(crate) fn create_rate_limited_message<MT: Debug>(
+    message_type: &MT,
+) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
+    let mut network_error = NetworkError::new();
+    network_error.set_error(NetworkError_Error::ERROR_RATE_LIMITED);
+    network_error.set_error_message(format!(
+        "Rate limit exceeded; {:?} messages are being dropped",
+        message_type
+    ));
+
+    let mut circuit_msg = CircuitMessage::new();
+    circuit_msg.set_message_type(CircuitMessageType::NETWORK_ERROR_MESSAGE);
+    circuit_msg.set_payload(network_error.write_to_bytes()?);
+
+    let mut network_msg = NetworkMessage::new();
+    network_msg.set_message_type(NetworkMessageType::CIRCUIT);
+    network_msg.set_payload(circuit_msg.write_to_bytes()?);
+    network_msg.write_to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a peer may send a burst of messages, after which its messages are rejected until
+    /// its bucket has been refilled, and that peers are limited independently.
+    #[test]
+    fn test_peer_limit() {
+        let mut limiter = RateLimiter::new(RateLimit::new(2, 1.0).unwrap());
+        let start = Instant::now();
+
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, start));
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &2, start));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: true },
+            limiter.check_at("a", &1, start)
+        );
+        assert_eq!(
+            RateLimitDecision::Reject { notify: false },
+            limiter.check_at("a", &1, start)
+        );
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("b", &1, start));
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, later));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: false },
+            limiter.check_at("a", &1, later)
+        );
+
+        assert_eq!(3, limiter.limit_hits());
+    }
+
+    /// Test that a message type with a limit of its own is limited separately from the peer's
+    /// other messages.
+    #[test]
+    fn test_message_type_limit() {
+        let mut limiter = RateLimiter::new(RateLimit::new(10, 10.0).unwrap())
+            .with_message_type_limit(1, RateLimit::new(1, 1.0).unwrap());
+        let now = Instant::now();
+
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, now));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: true },
+            limiter.check_at("a", &1, now)
+        );
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &2, now));
+    }
+
+    /// Test that a message rejected by its message type's bucket does not take a token from the
+    /// peer's bucket, and a message rejected by the peer's bucket does not take a token from its
+    /// message type's bucket.
+    #[test]
+    fn test_rejected_message_takes_no_tokens() {
+        let mut limiter = RateLimiter::new(RateLimit::new(2, 0.001).unwrap())
+            .with_message_type_limit(1, RateLimit::new(1, 0.001).unwrap())
+            .with_message_type_limit(3, RateLimit::new(1, 0.001).unwrap());
+        let now = Instant::now();
+
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, now));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: true },
+            limiter.check_at("a", &1, now)
+        );
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &2, now));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: false },
+            limiter.check_at("a", &3, now)
+        );
+
+        let peer = limiter.peers.get("a").expect("Peer is not tracked");
+        assert!(peer.message_type_buckets[&3].tokens >= 1.0);
+    }
+
+    /// Test that a peer is disconnected once enough of its messages have been rejected within the
+    /// violation window, and that violations outside of the window are not counted.
+    #[test]
+    fn test_disconnect_threshold() {
+        let mut limiter = RateLimiter::new(RateLimit::new(1, 0.01).unwrap())
+            .with_disconnect_threshold(3, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, start));
+        assert_eq!(
+            RateLimitDecision::Reject { notify: true },
+            limiter.check_at("a", &1, start)
+        );
+        assert_eq!(
+            RateLimitDecision::Reject { notify: false },
+            limiter.check_at("a", &1, start)
+        );
+
+        // the window has passed, so counting starts over
+        let later = start + Duration::from_secs(11);
+        assert_eq!(
+            RateLimitDecision::Reject { notify: true },
+            limiter.check_at("a", &1, later)
+        );
+        assert_eq!(
+            RateLimitDecision::Reject { notify: false },
+            limiter.check_at("a", &1, later)
+        );
+        assert_eq!(
+            RateLimitDecision::Disconnect,
+            limiter.check_at("a", &1, later)
+        );
+
+        // the peer's state is forgotten once it has been disconnected
+        assert_eq!(RateLimitDecision::Allow, limiter.check_at("a", &1, later));
+    }
+
+    /// Test that a rate limit must allow at least one message at once and refill at a positive,
+    /// finite rate that does not take too long to fill an empty bucket.
+    #[test]
+    fn test_invalid_rate_limit() {
+        assert!(RateLimit::new(0, 1.0).is_err());
+        assert!(RateLimit::new(1, 0.0).is_err());
+        assert!(RateLimit::new(1, -1.0).is_err());
+        assert!(RateLimit::new(1, f64::NAN).is_err());
+        assert!(RateLimit::new(1, f64::INFINITY).is_err());
+        assert!(RateLimit::new(1, 1e-300).is_err());
+        assert!(RateLimit::new(1, 1e-3).is_ok());
+    }
+}
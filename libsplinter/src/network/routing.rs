@@ -0,0 +1,447 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A routing table for delivering messages through intermediate nodes.
+//!
+//! Each node periodically advertises its directly connected peers to its own peers. As
+//! advertisements are not signed, a node only accepts the advertisement of a peer about itself,
+//! so no peer can change the advertised peers of another node. The routing table collects the
+//! advertised connectivity and derives the next hop towards nodes which are not directly
+//! connected, through the peer they are connected to.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use protobuf::{Message, RepeatedField};
+
+use crate::protos::network::{NetworkMessage, NetworkMessageType, NetworkRouteAdvertisement};
+
+/// The default number of hops a routed message may take.
+pub const DEFAULT_TIME_TO_LIVE: i32 = 8;
+
+/// How far ahead of the local clock the sequence number of an advertisement may be, in
+/// milliseconds. Sequence numbers are at least the time an advertisement was created, so a node
+/// cannot make its advertisements supersede all of its later ones.
+const MAX_SEQUENCE_CLOCK_SKEW_MILLIS: u64 = 5 * 60 * 1000;
+
+struct AdvertisedPeers {
+    peers: HashSet<String>,
+    sequence: u64,
+    updated_at: Instant,
+}
+
+impl Default for AdvertisedPeers {
+    fn default() -> Self {
+        AdvertisedPeers {
+            peers: HashSet::new(),
+            sequence: 0,
+            updated_at: Instant::now(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Topology {
+    local: AdvertisedPeers,
+    nodes: HashMap<String, AdvertisedPeers>,
+}
+
+impl Topology {
+    /// Returns the peers of the given node, as advertised by it, or the local peers for the local
+    /// node.
+    fn peers_of<'a>(&'a self, local_node_id: &str, node_id: &str) -> Option<&'a HashSet<String>> {
+        if node_id == local_node_id {
+            Some(&self.local.peers)
+        } else {
+            self.nodes.get(node_id).map(|advertised| &advertised.peers)
+        }
+    }
+
+    /// Returns the number of hops between the start and destination nodes, through the advertised
+    /// connectivity, or `None` if the destination is unreachable.
+    fn distance(&self, local_node_id: &str, start: &str, destination: &str) -> Option<usize> {
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut queue = VecDeque::new();
+        queue.push_back((start, 0));
+
+        while let Some((node, distance)) = queue.pop_front() {
+            if node == destination {
+                return Some(distance);
+            }
+
+            if let Some(peers) = self.peers_of(local_node_id, node) {
+                for peer in peers {
+                    if visited.insert(peer.as_str()) {
+                        queue.push_back((peer.as_str(), distance + 1));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A routing table, derived from the peer connectivity advertised by the nodes in the network.
+///
+/// The table is cheaply cloneable and all clones share the same state.
+#[derive(Clone)]
+pub struct RoutingTable {
+    node_id: String,
+    topology: Arc<RwLock<Topology>>,
+}
+
+impl RoutingTable {
+    pub fn new(node_id: String) -> Self {
+        RoutingTable {
+            node_id,
+            topology: Arc::new(RwLock::new(Topology::default())),
+        }
+    }
+
+    /// The id of the local node.
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Replaces the local node's directly connected peers.
+    pub fn set_local_peers<I: IntoIterator<Item = String>>(&self, peers: I) {
+        let mut topology = rwlock_write_unwrap!(self.topology);
+        topology.local.peers = peers.into_iter().collect();
+    }
+
+    /// Returns the local node's directly connected peers.
+    pub fn local_peers(&self) -> Vec<String> {
+        let topology = rwlock_read_unwrap!(self.topology);
+        let mut peers = topology.local.peers.iter().cloned().collect::<Vec<_>>();
+        peers.sort();
+        peers
+    }
+
+    /// Returns true if the given node is directly connected to the local node.
+    pub fn is_peer(&self, node_id: &str) -> bool {
+        rwlock_read_unwrap!(self.topology)
+            .local
+            .peers
+            .contains(node_id)
+    }
+
+    /// Updates the peers advertised by a remote node.
+    ///
+    /// Returns true if the advertisement was newer than the last one received from the node.
+    /// Advertisements about the local node, and advertisements whose sequence number is too far
+    /// ahead of the local clock, are ignored.
+    pub fn update_node(&self, node_id: &str, peers: Vec<String>, sequence: u64) -> bool {
+        if node_id == self.node_id {
+            return false;
+        }
+        if sequence > now_millis().saturating_add(MAX_SEQUENCE_CLOCK_SKEW_MILLIS) {
+            return false;
+        }
+
+        let mut topology = rwlock_write_unwrap!(self.topology);
+        let entry = topology.nodes.entry(node_id.to_string()).or_default();
+        // A sequence of 0 is never sent, so a new entry will always accept the advertisement
+        if sequence <= entry.sequence {
+            return false;
+        }
+
+        entry.peers = peers.into_iter().collect();
+        entry.sequence = sequence;
+        entry.updated_at = Instant::now();
+        true
+    }
+
+    /// Removes all routing information advertised by the given node.
+    pub fn remove_node(&self, node_id: &str) {
+        rwlock_write_unwrap!(self.topology).nodes.remove(node_id);
+    }
+
+    /// Removes the routing information of the nodes that have not advertised their peers within
+    /// the given time, such as nodes that have left the network.
+    ///
+    /// Returns the IDs of the removed nodes.
+    pub fn expire_nodes(&self, max_age: Duration) -> Vec<String> {
+        let mut topology = rwlock_write_unwrap!(self.topology);
+        let expired = topology
+            .nodes
+            .iter()
+            .filter(|(_, advertised)| advertised.updated_at.elapsed() > max_age)
+            .map(|(node_id, _)| node_id.clone())
+            .collect::<Vec<_>>();
+
+        for node_id in expired.iter() {
+            topology.nodes.remove(node_id);
+        }
+
+        expired
+    }
+
+    /// Returns true if the given peer is the next hop on a shortest path from the local node back
+    /// to the given origin node.
+    ///
+    /// A message that is routed from the origin along a shortest path arrives from such a peer,
+    /// so a routed message that arrives from any other peer did not come from its claimed origin.
+    pub fn is_return_hop(&self, origin: &str, peer: &str) -> bool {
+        let topology = rwlock_read_unwrap!(self.topology);
+        if !topology.local.peers.contains(peer) {
+            return false;
+        }
+
+        match (
+            topology.distance(&self.node_id, &self.node_id, origin),
+            topology.distance(&self.node_id, peer, origin),
+        ) {
+            (Some(local_distance), Some(peer_distance)) => peer_distance + 1 == local_distance,
+            _ => false,
+        }
+    }
+
+    /// Returns the peer a message for the given destination node should be sent to, or `None` if
+    /// the destination is unreachable.
+    ///
+    /// Directly connected nodes are their own next hop; otherwise the first hop of a shortest
+    /// path through the advertised connectivity is used.
+    pub fn next_hop(&self, destination: &str) -> Option<String> {
+        let topology = rwlock_read_unwrap!(self.topology);
+        if topology.local.peers.contains(destination) {
+            return Some(destination.to_string());
+        }
+
+        let mut visited = HashSet::new();
+        visited.insert(self.node_id.as_str());
+
+        // Start from the local peers in a consistent order, so the chosen route is stable
+        let mut first_hops = topology
+            .local
+            .peers
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        first_hops.sort();
+
+        let mut queue = VecDeque::new();
+        for peer in first_hops {
+            visited.insert(peer);
+            queue.push_back((peer, peer));
+        }
+
+        while let Some((node, first_hop)) = queue.pop_front() {
+            let peers = match topology.nodes.get(node) {
+                Some(advertised) => &advertised.peers,
+                None => continue,
+            };
+
+            if peers.contains(destination) {
+                return Some(first_hop.to_string());
+            }
+
+            let mut peers = peers.iter().map(String::as_str).collect::<Vec<_>>();
+            peers.sort();
+            for peer in peers {
+                if visited.insert(peer) {
+                    queue.push_back((peer, first_hop));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Creates a network message advertising the local node's peers, which are first replaced
+    /// with the given peers.
+    ///
+    /// The sequence number of the advertisement is at least the current time in milliseconds, so
+    /// that the advertisements of a restarted node are newer than those it sent before it
+    /// restarted.
+    pub fn create_advertisement<I: IntoIterator<Item = String>>(
+        &self,
+        peers: I,
+    ) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
+        let now = now_millis();
+
+        let (peers, sequence) = {
+            let mut topology = rwlock_write_unwrap!(self.topology);
+            topology.local.peers = peers.into_iter().collect();
+            topology.local.sequence = std::cmp::max(topology.local.sequence + 1, now);
+
+            let mut peers = topology.local.peers.iter().cloned().collect::<Vec<_>>();
+            peers.sort();
+            (peers, topology.local.sequence)
+        };
+
+        let mut advertisement = NetworkRouteAdvertisement::new();
+        advertisement.set_node_id(self.node_id.clone());
+        advertisement.set_peers(RepeatedField::from_vec(peers));
+        advertisement.set_sequence(sequence);
+        // Advertisements are only accepted from the node they describe, so they are not forwarded
+        advertisement.set_time_to_live(1);
+
+        create_advertisement_message(&advertisement)
+    }
+}
+
+/// The current time in milliseconds since the Unix epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Wraps a route advertisement in a network message.
+fn create_advertisement_message(
+    advertisement: &NetworkRouteAdvertisement,
+) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
+    let mut network_msg = NetworkMessage::new();
+    network_msg.set_message_type(NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT);
+    network_msg.set_payload(advertisement.write_to_bytes()?);
+    network_msg.write_to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the next hop is derived from advertised connectivity:
+    ///
+    ///   local -- a -- b -- c
+    ///
+    /// 1. Directly connected nodes are their own next hop
+    /// 2. Nodes reachable through advertised peers are routed through the local peer on the path
+    /// 3. Nodes without an advertised path are unreachable
+    /// 4. Stale advertisements, and advertisements from the future, are ignored
+    /// 5. Removing a node's advertisement removes the routes through it
+    #[test]
+    fn test_next_hop() {
+        let table = RoutingTable::new("local".into());
+        table.set_local_peers(vec!["a".to_string()]);
+
+        assert_eq!(Some("a".to_string()), table.next_hop("a"));
+        assert_eq!(None, table.next_hop("b"));
+
+        assert!(table.update_node("a", vec!["local".into(), "b".into()], 1));
+        assert!(table.update_node("b", vec!["a".into(), "c".into()], 1));
+
+        assert_eq!(Some("a".to_string()), table.next_hop("b"));
+        assert_eq!(Some("a".to_string()), table.next_hop("c"));
+        assert_eq!(None, table.next_hop("d"));
+
+        // an advertisement with an old sequence number is ignored
+        assert!(!table.update_node("b", vec!["a".into()], 1));
+        assert_eq!(Some("a".to_string()), table.next_hop("c"));
+
+        // advertisements about the local node are ignored
+        assert!(!table.update_node("local", vec!["d".into()], 5));
+        assert_eq!(None, table.next_hop("d"));
+
+        // advertisements with a sequence number far ahead of the local clock are ignored
+        assert!(!table.update_node("b", vec!["a".into(), "d".into()], u64::max_value()));
+        assert_eq!(None, table.next_hop("d"));
+
+        table.remove_node("a");
+        assert_eq!(None, table.next_hop("c"));
+    }
+
+    /// Test that creating an advertisement updates the local peers and increases the sequence
+    /// number with each advertisement.
+    #[test]
+    fn test_create_advertisement() {
+        let table = RoutingTable::new("local".into());
+
+        let parse = |bytes: Vec<u8>| {
+            let network_msg: NetworkMessage = protobuf::parse_from_bytes(&bytes).unwrap();
+            assert_eq!(
+                NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT,
+                network_msg.get_message_type()
+            );
+            protobuf::parse_from_bytes::<NetworkRouteAdvertisement>(network_msg.get_payload())
+                .unwrap()
+        };
+
+        let advertisement = parse(
+            table
+                .create_advertisement(vec!["b".to_string(), "a".to_string()])
+                .unwrap(),
+        );
+        assert_eq!("local", advertisement.get_node_id());
+        assert_eq!(
+            &["a".to_string(), "b".to_string()],
+            advertisement.get_peers()
+        );
+        let first_sequence = advertisement.get_sequence();
+        assert_eq!(1, advertisement.get_time_to_live());
+        assert!(table.is_peer("a"));
+
+        let advertisement = parse(table.create_advertisement(vec!["a".to_string()]).unwrap());
+        assert!(advertisement.get_sequence() > first_sequence);
+        assert!(!table.is_peer("b"));
+
+        // A restarted node's advertisements supersede those it sent before restarting
+        let restarted_table = RoutingTable::new("local".into());
+        let advertisement = parse(
+            restarted_table
+                .create_advertisement(vec!["a".to_string()])
+                .unwrap(),
+        );
+        assert!(advertisement.get_sequence() > first_sequence);
+    }
+
+    /// Test that nodes that have not advertised their peers recently are removed.
+    #[test]
+    fn test_expire_nodes() {
+        let table = RoutingTable::new("local".into());
+        table.set_local_peers(vec!["a".to_string()]);
+        assert!(table.update_node("a", vec!["local".into(), "b".into()], 1));
+        assert_eq!(Some("a".to_string()), table.next_hop("b"));
+
+        assert!(table.expire_nodes(Duration::from_secs(60)).is_empty());
+        assert_eq!(Some("a".to_string()), table.next_hop("b"));
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(
+            vec!["a".to_string()],
+            table.expire_nodes(Duration::from_millis(1))
+        );
+        assert_eq!(None, table.next_hop("b"));
+    }
+
+    /// Test that only a peer on a shortest path back to a node is its return hop:
+    ///
+    ///   local -- a -- b -- c
+    ///     |
+    ///     d
+    #[test]
+    fn test_is_return_hop() {
+        let table = RoutingTable::new("local".into());
+        table.set_local_peers(vec!["a".to_string(), "d".to_string()]);
+        table.update_node("a", vec!["local".into(), "b".into()], 1);
+        table.update_node("b", vec!["a".into(), "c".into()], 1);
+        table.update_node("d", vec!["local".into()], 1);
+
+        assert!(table.is_return_hop("c", "a"));
+        assert!(table.is_return_hop("a", "a"));
+        assert!(table.is_return_hop("d", "d"));
+
+        // d is not on the path to c, and a direct peer cannot claim to be another
+        assert!(!table.is_return_hop("c", "d"));
+        assert!(!table.is_return_hop("d", "a"));
+        // b is not a direct peer
+        assert!(!table.is_return_hop("c", "b"));
+        // unknown nodes have no path
+        assert!(!table.is_return_hop("e", "a"));
+    }
+}
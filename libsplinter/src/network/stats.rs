@@ -0,0 +1,67 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A snapshot of the traffic on a peer's connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// The time at which the last heartbeat was received from the peer, if any.
+    pub last_heartbeat: Option<SystemTime>,
+}
+
+/// Counters for a single connection.
+///
+/// The counters are updated while only holding the network's peer map read lock, so they are
+/// atomic.
+#[derive(Default)]
+pub(super) struct PeerCounters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    last_heartbeat: Mutex<Option<SystemTime>>,
+}
+
+impl PeerCounters {
+    pub fn record_sent(&self, len: usize) {
+        self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, len: usize) {
+        self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_heartbeat(&self) {
+        *mutex_lock_unwrap!(self.last_heartbeat) = Some(SystemTime::now());
+    }
+
+    pub fn snapshot(&self) -> PeerStats {
+        PeerStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            last_heartbeat: *mutex_lock_unwrap!(self.last_heartbeat),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Trace ids, which correlate the messages sent across nodes on behalf of one operation.
+//!
+//! A trace id is carried in the `trace_id` field of the `NetworkMessage`, `CircuitMessage` and
+//! `ConsensusMessage` envelopes, and is passed to handlers and services in their message contexts.
+//! Messages that are forwarded or sent in response to a traced message keep its trace id. Log
+//! messages about a traced message end with a `trace_id=<id>` field, so that the logs of every
+//! node can be searched for a single operation, such as a scabbard proposal.
+
+use std::fmt;
+
+/// Formats an optional trace id as a ` trace_id=<id>` log field, or as nothing if there is no
+/// trace id.
+pub struct TraceField<'a>(pub Option<&'a str>);
+
+impl<'a> fmt::Display for TraceField<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(trace_id) => write!(f, " trace_id={}", trace_id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the trace id held in the `trace_id` field of an envelope, which is empty if the
+/// message is not traced.
+pub fn parse_trace_id(field: &str) -> Option<&str> {
+    if field.is_empty() {
+        None
+    } else {
+        Some(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a trace id is formatted as a log field, and that an unset trace id is omitted.
+    #[test]
+    fn test_trace_field() {
+        assert_eq!(
+            "Handled message trace_id=abc",
+            format!("Handled message{}", TraceField(Some("abc")))
+        );
+        assert_eq!(
+            "Handled message",
+            format!("Handled message{}", TraceField(None))
+        );
+
+        assert_eq!(Some("abc"), parse_trace_id("abc"));
+        assert_eq!(None, parse_trace_id(""));
+    }
+}
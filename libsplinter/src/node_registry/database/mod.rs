@@ -0,0 +1,234 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A node registry backed by a database.
+//!
+//! The tables used by the `DatabaseNodeRegistry` are created by the migrations run with
+//! `postgres::run_migrations`.
+
+pub mod postgres;
+
+use std::error::Error;
+use std::fmt;
+
+use diesel::result::{DatabaseErrorKind, Error as QueryError};
+
+use crate::database::{Connection, ConnectionPool};
+
+use super::{
+    check_node_required_fields_are_not_empty, InvalidNodeError, MetadataPredicate, Node,
+    NodeRegistryError, NodeRegistryEvent, NodeRegistryReader, NodeRegistrySubscriber,
+    NodeRegistrySubscribers, NodeRegistryWriter, NodeSignature, RwNodeRegistry,
+};
+use postgres::helpers;
+use postgres::models::{NodeMetadataModel, NodeModel};
+
+/// A node registry backed by a Postgres database.
+///
+/// Nodes are stored in a table of their own and their metadata in an indexed table of key/value
+/// pairs, so that most predicates are evaluated by the database. Comparisons of numbers and the
+/// ordering of node fields are evaluated once the matching nodes have been loaded.
+///
+/// Subscribers are notified of the changes made through this registry or its clones; changes
+/// made to the database by other processes do not produce events.
+#[derive(Clone)]
+pub struct DatabaseNodeRegistry {
+    connection_pool: ConnectionPool,
+    subscribers: NodeRegistrySubscribers,
+}
+
+impl DatabaseNodeRegistry {
+    /// Creates a new DatabaseNodeRegistry
+    ///
+    /// # Arguments
+    ///
+    ///  * `connection_pool`: connection pool to the database
+    pub fn new(connection_pool: ConnectionPool) -> Self {
+        DatabaseNodeRegistry {
+            connection_pool,
+            subscribers: NodeRegistrySubscribers::default(),
+        }
+    }
+
+    fn connection(&self) -> Result<Connection, NodeRegistryError> {
+        self.connection_pool.get().map_err(|err| {
+            NodeRegistryError::InternalError(Box::new(ConnectionError(err.to_string())))
+        })
+    }
+}
+
+impl NodeRegistryReader for DatabaseNodeRegistry {
+    fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
+        match helpers::fetch_node(&*self.connection()?, identity)
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))?
+        {
+            Some((node, metadata)) => Ok(to_node(node, metadata)),
+            None => Err(NodeRegistryError::NotFoundError(format!(
+                "Could not find node with identity {}",
+                identity
+            ))),
+        }
+    }
+
+    fn list_nodes<'a, 'b: 'a>(
+        &'b self,
+        predicates: &'a [MetadataPredicate],
+    ) -> Result<Box<dyn Iterator<Item = Node> + Send + 'a>, NodeRegistryError> {
+        let nodes = helpers::list_nodes(&*self.connection()?, predicates)
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))?;
+
+        // The database only evaluates the predicates it supports, so the others are applied here
+        let unsupported_predicates = predicates
+            .iter()
+            .filter(|predicate| !helpers::is_supported(predicate))
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(
+            nodes
+                .into_iter()
+                .map(|(node, metadata)| to_node(node, metadata))
+                .filter(move |node| {
+                    unsupported_predicates
+                        .iter()
+                        .all(|predicate| predicate.apply(node))
+                }),
+        ))
+    }
+
+    fn count_nodes(&self, predicates: &[MetadataPredicate]) -> Result<u32, NodeRegistryError> {
+        if !predicates.iter().all(helpers::is_supported) {
+            return Ok(self.list_nodes(predicates)?.count() as u32);
+        }
+
+        helpers::count_nodes(&*self.connection()?, predicates)
+            .map(|count| count as u32)
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))
+    }
+
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        self.subscribers.add(subscriber);
+        Ok(())
+    }
+}
+
+impl NodeRegistryWriter for DatabaseNodeRegistry {
+    fn insert_node(&self, node: Node) -> Result<(), NodeRegistryError> {
+        check_node_required_fields_are_not_empty(&node)?;
+
+        let inserted_node = node.clone();
+
+        let metadata = node
+            .metadata
+            .iter()
+            .map(|(key, value)| NodeMetadataModel {
+                identity: node.identity.clone(),
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>();
+        let (signer_public_key, signature) = match node.signature {
+            Some(node_signature) => (
+                Some(node_signature.public_key),
+                Some(node_signature.signature),
+            ),
+            None => (None, None),
+        };
+        let node = NodeModel {
+            identity: node.identity,
+            endpoint: node.endpoint,
+            display_name: node.display_name,
+            signer_public_key,
+            signature,
+        };
+
+        let replaced = helpers::insert_node(&*self.connection()?, &node, &metadata).map_err(
+            |err| match err {
+                // The node with the same identity is replaced, so only the endpoint can be a
+                // duplicate
+                QueryError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    NodeRegistryError::from(InvalidNodeError::DuplicateEndpoint(
+                        node.endpoint.clone(),
+                    ))
+                }
+                err => NodeRegistryError::InternalError(Box::new(err)),
+            },
+        )?;
+
+        self.subscribers.notify(&if replaced {
+            NodeRegistryEvent::NodeUpdated(inserted_node)
+        } else {
+            NodeRegistryEvent::NodeInserted(inserted_node)
+        });
+
+        Ok(())
+    }
+
+    fn delete_node(&self, identity: &str) -> Result<(), NodeRegistryError> {
+        let deleted = helpers::delete_node(&*self.connection()?, identity)
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))?;
+
+        if deleted == 0 {
+            return Err(NodeRegistryError::NotFoundError(format!(
+                "Could not find node with identity: {}",
+                identity
+            )));
+        }
+
+        self.subscribers
+            .notify(&NodeRegistryEvent::NodeDeleted(identity.to_string()));
+
+        Ok(())
+    }
+}
+
+impl RwNodeRegistry for DatabaseNodeRegistry {
+    fn clone_box(&self) -> Box<dyn RwNodeRegistry> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+fn to_node(node: NodeModel, metadata: Vec<NodeMetadataModel>) -> Node {
+    Node {
+        identity: node.identity,
+        endpoint: node.endpoint,
+        display_name: node.display_name,
+        metadata: metadata
+            .into_iter()
+            .map(|metadata| (metadata.key, metadata.value))
+            .collect(),
+        signature: match (node.signer_public_key, node.signature) {
+            (Some(public_key), Some(signature)) => Some(NodeSignature {
+                public_key,
+                signature,
+            }),
+            _ => None,
+        },
+    }
+}
+
+/// An error getting a connection from the pool. The pool's errors cannot be sent between threads,
+/// so only their message is kept.
+#[derive(Debug)]
+struct ConnectionError(String);
+
+impl Error for ConnectionError {}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to get a database connection: {}", self.0)
+    }
+}
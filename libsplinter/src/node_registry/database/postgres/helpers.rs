@@ -0,0 +1,233 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use diesel::{
+    dsl::{delete, insert_into, not, sql},
+    expression::SqlLiteral,
+    pg::{Pg, PgConnection},
+    prelude::*,
+    sql_types::{Bool, Text},
+    QueryResult,
+};
+
+use crate::node_registry::{ComparisonOperator, MetadataPredicate, NodeField};
+
+use super::models::{NodeMetadataModel, NodeModel};
+use super::schema::{splinter_nodes, splinter_nodes_metadata};
+
+pub fn fetch_node(
+    conn: &PgConnection,
+    identity: &str,
+) -> QueryResult<Option<(NodeModel, Vec<NodeMetadataModel>)>> {
+    let node = match splinter_nodes::table
+        .find(identity)
+        .first::<NodeModel>(conn)
+        .optional()?
+    {
+        Some(node) => node,
+        None => return Ok(None),
+    };
+
+    let metadata = NodeMetadataModel::belonging_to(&node).load::<NodeMetadataModel>(conn)?;
+
+    Ok(Some((node, metadata)))
+}
+
+pub fn list_nodes(
+    conn: &PgConnection,
+    predicates: &[MetadataPredicate],
+) -> QueryResult<Vec<(NodeModel, Vec<NodeMetadataModel>)>> {
+    let nodes = filter_nodes(predicates)
+        .order(splinter_nodes::identity)
+        .load::<NodeModel>(conn)?;
+
+    let metadata = NodeMetadataModel::belonging_to(&nodes)
+        .load::<NodeMetadataModel>(conn)?
+        .grouped_by(&nodes);
+
+    Ok(nodes.into_iter().zip(metadata).collect())
+}
+
+pub fn count_nodes(conn: &PgConnection, predicates: &[MetadataPredicate]) -> QueryResult<i64> {
+    filter_nodes(predicates).count().get_result(conn)
+}
+
+/// Replaces the node with the same identity, if there is one, with the given node. Returns whether
+/// a node was replaced.
+pub fn insert_node(
+    conn: &PgConnection,
+    node: &NodeModel,
+    metadata: &[NodeMetadataModel],
+) -> QueryResult<bool> {
+    conn.transaction(|| {
+        // The node's metadata is deleted with it
+        let replaced = delete(splinter_nodes::table.find(&node.identity)).execute(conn)? > 0;
+        insert_into(splinter_nodes::table)
+            .values(node)
+            .execute(conn)?;
+        if !metadata.is_empty() {
+            insert_into(splinter_nodes_metadata::table)
+                .values(metadata)
+                .execute(conn)?;
+        }
+        Ok(replaced)
+    })
+}
+
+pub fn delete_node(conn: &PgConnection, identity: &str) -> QueryResult<usize> {
+    delete(splinter_nodes::table.find(identity)).execute(conn)
+}
+
+/// Returns whether the predicate can be evaluated by the database.
+///
+/// Numbers and the ordering of node fields are compared by the registry once the nodes are loaded,
+/// as is any group that contains such a comparison.
+pub fn is_supported(predicate: &MetadataPredicate) -> bool {
+    match predicate {
+        MetadataPredicate::Numeric(..) => false,
+        MetadataPredicate::Field(_, operator, _) => match operator {
+            ComparisonOperator::Eq | ComparisonOperator::Ne => true,
+            _ => false,
+        },
+        MetadataPredicate::And(predicates) | MetadataPredicate::Or(predicates) => {
+            predicates.iter().all(is_supported)
+        }
+        MetadataPredicate::Not(predicate) => is_supported(predicate),
+        _ => true,
+    }
+}
+
+/// Returns a query for the nodes that match all of the given predicates that are supported by the
+/// database, which are evaluated against the indexed metadata table. The other predicates are
+/// ignored.
+fn filter_nodes(predicates: &[MetadataPredicate]) -> splinter_nodes::BoxedQuery<'_, Pg> {
+    let mut query = splinter_nodes::table.into_boxed();
+
+    for predicate in predicates
+        .iter()
+        .filter(|predicate| is_supported(predicate))
+    {
+        query = query.filter(to_sql(predicate));
+    }
+
+    query
+}
+
+type NodeFilter = Box<dyn BoxableExpression<splinter_nodes::table, Pg, SqlType = Bool>>;
+
+/// Returns a subselect of the identities of the nodes with the given metadata key, whose value
+/// matches the given condition.
+macro_rules! metadata_matching {
+    ($key:expr, $condition:expr) => {
+        splinter_nodes_metadata::table
+            .select(splinter_nodes_metadata::identity)
+            .filter(splinter_nodes_metadata::key.eq($key.clone()))
+            .filter($condition)
+    };
+}
+
+/// Returns the SQL expression for a predicate that is supported by the database.
+fn to_sql(predicate: &MetadataPredicate) -> NodeFilter {
+    match predicate {
+        MetadataPredicate::Eq(key, value) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, splinter_nodes_metadata::value.eq(value.clone())),
+        )),
+        // Nodes without the key match as well, as with `MetadataPredicate::apply`
+        MetadataPredicate::Ne(key, value) => Box::new(splinter_nodes::identity.ne_all(
+            metadata_matching!(key, splinter_nodes_metadata::value.eq(value.clone())),
+        )),
+        MetadataPredicate::Gt(key, value) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, metadata_value_bytewise().gt(value.clone())),
+        )),
+        MetadataPredicate::Ge(key, value) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, metadata_value_bytewise().ge(value.clone())),
+        )),
+        MetadataPredicate::Lt(key, value) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, metadata_value_bytewise().lt(value.clone())),
+        )),
+        MetadataPredicate::Le(key, value) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, metadata_value_bytewise().le(value.clone())),
+        )),
+        MetadataPredicate::In(key, values) => Box::new(splinter_nodes::identity.eq_any(
+            metadata_matching!(key, splinter_nodes_metadata::value.eq_any(values.clone())),
+        )),
+        MetadataPredicate::Exists(key) => Box::new(
+            splinter_nodes::identity.eq_any(
+                splinter_nodes_metadata::table
+                    .select(splinter_nodes_metadata::identity)
+                    .filter(splinter_nodes_metadata::key.eq(key.clone())),
+            ),
+        ),
+        MetadataPredicate::StartsWith(key, prefix) => {
+            Box::new(splinter_nodes::identity.eq_any(metadata_matching!(
+                key,
+                splinter_nodes_metadata::value.like(like_prefix(prefix))
+            )))
+        }
+        MetadataPredicate::Field(NodeField::DisplayName, ComparisonOperator::Eq, value) => {
+            Box::new(splinter_nodes::display_name.eq(value.clone()))
+        }
+        MetadataPredicate::Field(NodeField::DisplayName, ComparisonOperator::Ne, value) => {
+            Box::new(splinter_nodes::display_name.ne(value.clone()))
+        }
+        MetadataPredicate::Field(NodeField::Endpoint, ComparisonOperator::Eq, value) => {
+            Box::new(splinter_nodes::endpoint.eq(value.clone()))
+        }
+        MetadataPredicate::Field(NodeField::Endpoint, ComparisonOperator::Ne, value) => {
+            Box::new(splinter_nodes::endpoint.ne(value.clone()))
+        }
+        MetadataPredicate::FieldStartsWith(NodeField::DisplayName, prefix) => {
+            Box::new(splinter_nodes::display_name.like(like_prefix(prefix)))
+        }
+        MetadataPredicate::FieldStartsWith(NodeField::Endpoint, prefix) => {
+            Box::new(splinter_nodes::endpoint.like(like_prefix(prefix)))
+        }
+        MetadataPredicate::And(predicates) => {
+            let none: NodeFilter = Box::new(sql::<Bool>("TRUE"));
+            predicates
+                .iter()
+                .map(to_sql)
+                .fold(none, |all, predicate| Box::new(all.and(predicate)))
+        }
+        MetadataPredicate::Or(predicates) => {
+            let none: NodeFilter = Box::new(sql::<Bool>("FALSE"));
+            predicates
+                .iter()
+                .map(to_sql)
+                .fold(none, |any, predicate| Box::new(any.or(predicate)))
+        }
+        MetadataPredicate::Not(predicate) => Box::new(not(to_sql(predicate))),
+        // Unsupported predicates are applied by the registry, and are not passed to this function
+        MetadataPredicate::Numeric(..) | MetadataPredicate::Field(..) => {
+            Box::new(sql::<Bool>("TRUE"))
+        }
+    }
+}
+
+/// Returns the metadata value, to be compared byte by byte, as `MetadataPredicate::apply` compares
+/// values, rather than by the database's collation.
+fn metadata_value_bytewise() -> SqlLiteral<Text> {
+    sql::<Text>(r#"splinter_nodes_metadata.value COLLATE "C""#)
+}
+
+/// Returns a `LIKE` pattern that matches the values that start with the given prefix.
+fn like_prefix(prefix: &str) -> String {
+    format!(
+        "{}%",
+        prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
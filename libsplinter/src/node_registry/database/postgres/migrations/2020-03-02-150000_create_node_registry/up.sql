@@ -0,0 +1,32 @@
+-- Copyright 2018-2020 Cargill Incorporated
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+-- -----------------------------------------------------------------------------
+
+CREATE TABLE IF NOT EXISTS splinter_nodes (
+    identity     TEXT PRIMARY KEY,
+    endpoint     TEXT NOT NULL UNIQUE,
+    display_name TEXT NOT NULL
+);
+
+-- Values use the "C" collation so that they are compared byte by byte, like Rust strings
+CREATE TABLE IF NOT EXISTS splinter_nodes_metadata (
+    identity TEXT NOT NULL,
+    key      TEXT NOT NULL,
+    value    TEXT COLLATE "C" NOT NULL,
+    PRIMARY KEY(identity, key),
+    FOREIGN KEY(identity) REFERENCES splinter_nodes(identity) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS splinter_nodes_metadata_key_value_idx
+    ON splinter_nodes_metadata (key, value);
@@ -0,0 +1,35 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::schema::{splinter_nodes, splinter_nodes_metadata};
+
+#[derive(Insertable, Queryable, Identifiable, PartialEq, Debug)]
+#[table_name = "splinter_nodes"]
+#[primary_key(identity)]
+pub struct NodeModel {
+    pub identity: String,
+    pub endpoint: String,
+    pub display_name: String,
+    pub signer_public_key: Option<String>,
+    pub signature: Option<String>,
+}
+
+#[derive(Insertable, Queryable, Associations, PartialEq, Debug)]
+#[table_name = "splinter_nodes_metadata"]
+#[belongs_to(NodeModel, foreign_key = "identity")]
+pub struct NodeMetadataModel {
+    pub identity: String,
+    pub key: String,
+    pub value: String,
+}
@@ -70,7 +70,8 @@ pub enum InvalidNodeError {
     EmptyEndpoint,
     EmptyIdentity,
     EmptyDisplayName,
-    InvalidIdentity(String, String), // (identity, message)
+    InvalidIdentity(String, String),  // (identity, message)
+    InvalidSignature(String, String), // (identity, message)
 }
 
 impl Error for InvalidNodeError {
@@ -82,6 +83,7 @@ impl Error for InvalidNodeError {
             InvalidNodeError::EmptyIdentity => None,
             InvalidNodeError::EmptyDisplayName => None,
             InvalidNodeError::InvalidIdentity(..) => None,
+            InvalidNodeError::InvalidSignature(..) => None,
         }
     }
 }
@@ -103,6 +105,28 @@ impl fmt::Display for InvalidNodeError {
             InvalidNodeError::InvalidIdentity(identity, msg) => {
                 write!(f, "identity {} is invalid: {}", identity, msg)
             }
+            InvalidNodeError::InvalidSignature(identity, msg) => {
+                write!(f, "signature of node {} is invalid: {}", identity, msg)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NodeRegistrySubscriberError {
+    UnableToHandleEvent(String),
+    Unsubscribe,
+}
+
+impl Error for NodeRegistrySubscriberError {}
+
+impl fmt::Display for NodeRegistrySubscriberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeRegistrySubscriberError::UnableToHandleEvent(msg) => {
+                write!(f, "Unable to handle event: {}", msg)
+            }
+            NodeRegistrySubscriberError::Unsubscribe => f.write_str("Unsubscribe"),
         }
     }
 }
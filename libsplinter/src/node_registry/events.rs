@@ -0,0 +1,189 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Notifications of the changes to a node registry.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::mutex_lock_unwrap;
+
+use super::{error::NodeRegistrySubscriberError, Node};
+
+/// A change to the nodes of a registry.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "eventType", content = "message")]
+pub enum NodeRegistryEvent {
+    /// A node was added to the registry.
+    NodeInserted(Node),
+    /// The entry of a node in the registry was replaced; contains the new entry.
+    NodeUpdated(Node),
+    /// A node was removed from the registry; contains its identity.
+    NodeDeleted(String),
+}
+
+/// Receives the changes to a node registry.
+pub trait NodeRegistrySubscriber: Send {
+    /// Handles a change to the registry.
+    ///
+    /// Returning `NodeRegistrySubscriberError::Unsubscribe` removes the subscriber from the
+    /// registry.
+    fn handle_event(&self, event: &NodeRegistryEvent) -> Result<(), NodeRegistrySubscriberError>;
+}
+
+/// The subscribers of a node registry; clones share the same subscribers.
+#[derive(Clone, Default)]
+pub struct NodeRegistrySubscribers {
+    subscribers: Arc<Mutex<Vec<Box<dyn NodeRegistrySubscriber>>>>,
+}
+
+impl NodeRegistrySubscribers {
+    pub fn add(&self, subscriber: Box<dyn NodeRegistrySubscriber>) {
+        mutex_lock_unwrap!(self.subscribers).push(subscriber);
+    }
+
+    /// Sends the event to every subscriber, removing the subscribers that unsubscribe.
+    pub fn notify(&self, event: &NodeRegistryEvent) {
+        mutex_lock_unwrap!(self.subscribers).retain(|subscriber| {
+            match subscriber.handle_event(event) {
+                Ok(()) => true,
+                Err(NodeRegistrySubscriberError::Unsubscribe) => false,
+                Err(err @ NodeRegistrySubscriberError::UnableToHandleEvent(_)) => {
+                    error!("{}", err);
+                    true
+                }
+            }
+        })
+    }
+}
+
+/// Returns the events that change the `previous` list of nodes into the `current` one.
+pub fn diff_nodes(previous: &[Node], current: &[Node]) -> Vec<NodeRegistryEvent> {
+    let previous_nodes = previous
+        .iter()
+        .map(|node| (node.identity.as_str(), node))
+        .collect::<HashMap<_, _>>();
+    let current_identities = current
+        .iter()
+        .map(|node| node.identity.as_str())
+        .collect::<Vec<_>>();
+
+    let inserted_or_updated =
+        current
+            .iter()
+            .filter_map(|node| match previous_nodes.get(node.identity.as_str()) {
+                None => Some(NodeRegistryEvent::NodeInserted(node.clone())),
+                Some(previous_node) if *previous_node != node => {
+                    Some(NodeRegistryEvent::NodeUpdated(node.clone()))
+                }
+                Some(_) => None,
+            });
+
+    let deleted = previous
+        .iter()
+        .filter(|node| !current_identities.contains(&node.identity.as_str()))
+        .map(|node| NodeRegistryEvent::NodeDeleted(node.identity.clone()));
+
+    inserted_or_updated.chain(deleted).collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Collects the events it receives.
+    #[derive(Clone, Default)]
+    pub struct EventCollector {
+        events: Arc<Mutex<Vec<NodeRegistryEvent>>>,
+    }
+
+    impl EventCollector {
+        pub fn events(&self) -> Vec<NodeRegistryEvent> {
+            mutex_lock_unwrap!(self.events).clone()
+        }
+    }
+
+    impl NodeRegistrySubscriber for EventCollector {
+        fn handle_event(
+            &self,
+            event: &NodeRegistryEvent,
+        ) -> Result<(), NodeRegistrySubscriberError> {
+            mutex_lock_unwrap!(self.events).push(event.clone());
+            Ok(())
+        }
+    }
+
+    /// Test that the differences between two lists of nodes are the inserted, updated and deleted
+    /// nodes, and that unchanged nodes are left out.
+    #[test]
+    fn test_diff_nodes() {
+        let unchanged = Node::new("unchanged", "tcp://a:8044");
+        let deleted = Node::new("deleted", "tcp://b:8044");
+        let updated = Node::new("updated", "tcp://c:8044");
+        let mut new_updated = updated.clone();
+        new_updated.display_name = "Updated".into();
+        let inserted = Node::new("inserted", "tcp://d:8044");
+
+        let events = diff_nodes(
+            &[unchanged.clone(), deleted, updated],
+            &[unchanged, new_updated.clone(), inserted.clone()],
+        );
+
+        assert_eq!(
+            vec![
+                NodeRegistryEvent::NodeUpdated(new_updated),
+                NodeRegistryEvent::NodeInserted(inserted),
+                NodeRegistryEvent::NodeDeleted("deleted".into()),
+            ],
+            events
+        );
+    }
+
+    /// Test that subscribers are notified of events until they unsubscribe.
+    #[test]
+    fn test_subscribers() {
+        /// Handles a single event, then unsubscribes.
+        struct Subscriber {
+            calls: Arc<Mutex<Vec<NodeRegistryEvent>>>,
+        }
+
+        impl NodeRegistrySubscriber for Subscriber {
+            fn handle_event(
+                &self,
+                event: &NodeRegistryEvent,
+            ) -> Result<(), NodeRegistrySubscriberError> {
+                let mut calls = mutex_lock_unwrap!(self.calls);
+                calls.push(event.clone());
+                if calls.len() > 1 {
+                    return Err(NodeRegistrySubscriberError::Unsubscribe);
+                }
+                Ok(())
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let subscribers = NodeRegistrySubscribers::default();
+        subscribers.add(Box::new(Subscriber {
+            calls: calls.clone(),
+        }));
+
+        let event = NodeRegistryEvent::NodeDeleted("node-a".into());
+        subscribers.notify(&event);
+        subscribers.notify(&event);
+        subscribers.notify(&event);
+
+        // The subscriber is not called again once it has unsubscribed
+        assert_eq!(vec![event.clone(), event], *mutex_lock_unwrap!(calls));
+    }
+}
@@ -12,17 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "database")]
+pub mod database;
 pub mod error;
+mod events;
 pub mod noop;
+#[cfg(feature = "node-registry-remote")]
+pub mod remote;
 #[cfg(feature = "rest-api")]
 pub mod rest_api;
+#[cfg(feature = "node-registry-signing")]
+pub mod signing;
 #[cfg(feature = "node-registry-unified")]
 pub mod unified;
 pub mod yaml;
 
 use std::collections::HashMap;
 
-pub use error::{InvalidNodeError, NodeRegistryError};
+pub use error::{InvalidNodeError, NodeRegistryError, NodeRegistrySubscriberError};
+pub(crate) use events::{diff_nodes, NodeRegistrySubscribers};
+pub use events::{NodeRegistryEvent, NodeRegistrySubscriber};
 #[cfg(feature = "node-registry-unified")]
 pub use unified::UnifiedNodeRegistry;
 
@@ -36,6 +45,9 @@ pub struct Node {
     pub display_name: String,
     /// A map with node metadata.
     pub metadata: HashMap<String, String>,
+    /// The signature of the node's entry, made with the node's own key, if the entry is signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<NodeSignature>,
 }
 
 impl Node {
@@ -48,17 +60,53 @@ impl Node {
             endpoint: endpoint.into(),
             display_name: String::new(),
             metadata: Default::default(),
+            signature: None,
         }
     }
+
+    /// Returns the bytes of the node's entry that are covered by its signature.
+    ///
+    /// These are the identity, endpoint, display name and the metadata entries in order of their
+    /// keys, each prefixed with its length so that the encoding is unambiguous.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut metadata = self.metadata.iter().collect::<Vec<_>>();
+        metadata.sort();
+
+        let fields = vec![&self.identity, &self.endpoint, &self.display_name]
+            .into_iter()
+            .chain(
+                metadata
+                    .into_iter()
+                    .flat_map(|(key, value)| vec![key, value]),
+            );
+
+        let mut bytes = vec![];
+        for field in fields {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes
+    }
+}
+
+/// The signature of a node's registry entry.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NodeSignature {
+    /// The hex-encoded public key that signed the entry.
+    pub public_key: String,
+    /// The hex-encoded signature of the entry's signing bytes.
+    pub signature: String,
 }
 
-/// A predicate on a key/value pair in a Node's metadata table.
+/// A predicate on a node, usually on a key/value pair in the node's metadata table.
 ///
-/// Each variant is an operator, and supplies a tuple representing a key/value pair. It is applied
-/// by the comparison operator on the value found at the given key (the first item in the tuple)
-/// against the predicate's value (the second item in the tuple).
+/// The `Eq`, `Ne`, `Gt`, `Ge`, `Lt` and `Le` variants are operators, and supply a tuple
+/// representing a key/value pair. They are applied by the comparison operator on the value found
+/// at the given key (the first item in the tuple) against the predicate's value (the second item in
+/// the tuple), compared as strings.
 ///
-/// If the item is missing in a node's metadata table, the predicate returns false.
+/// Unless stated otherwise, if the item is missing in a node's metadata table, the predicate
+/// returns false.
 #[derive(Clone)]
 pub enum MetadataPredicate {
     /// Applies the `==` operator.
@@ -73,6 +121,26 @@ pub enum MetadataPredicate {
     Lt(String, String),
     /// Applies the `<=` operator.
     Le(String, String),
+    /// Matches if the value at the key is one of the given values.
+    In(String, Vec<String>),
+    /// Matches if the key is present, whatever its value.
+    Exists(String),
+    /// Matches if the value at the key starts with the given prefix.
+    StartsWith(String, String),
+    /// Applies the operator to the value at the key and the given number, compared as numbers. If
+    /// the value is not a number, it is treated as missing, so only the `Ne` operator matches.
+    Numeric(String, ComparisonOperator, f64),
+    /// Applies the operator to the given field of the node and the given value, compared as
+    /// strings.
+    Field(NodeField, ComparisonOperator, String),
+    /// Matches if the given field of the node starts with the given prefix.
+    FieldStartsWith(NodeField, String),
+    /// Matches if all of the predicates match, or if there are none.
+    And(Vec<MetadataPredicate>),
+    /// Matches if any of the predicates match; never matches if there are none.
+    Or(Vec<MetadataPredicate>),
+    /// Matches if the predicate does not match.
+    Not(Box<MetadataPredicate>),
 }
 
 impl MetadataPredicate {
@@ -98,6 +166,36 @@ impl MetadataPredicate {
             MetadataPredicate::Le(key, val) => {
                 node.metadata.get(key).map(|v| v <= val).unwrap_or(false)
             }
+            MetadataPredicate::In(key, vals) => node
+                .metadata
+                .get(key)
+                .map(|v| vals.contains(v))
+                .unwrap_or(false),
+            MetadataPredicate::Exists(key) => node.metadata.contains_key(key),
+            MetadataPredicate::StartsWith(key, prefix) => node
+                .metadata
+                .get(key)
+                .map(|v| v.starts_with(prefix.as_str()))
+                .unwrap_or(false),
+            MetadataPredicate::Numeric(key, operator, val) => {
+                match node.metadata.get(key).and_then(|v| v.parse::<f64>().ok()) {
+                    Some(v) => operator.compare(&v, val),
+                    None => *operator == ComparisonOperator::Ne,
+                }
+            }
+            MetadataPredicate::Field(field, operator, val) => {
+                operator.compare(field.value(node), val.as_str())
+            }
+            MetadataPredicate::FieldStartsWith(field, prefix) => {
+                field.value(node).starts_with(prefix.as_str())
+            }
+            MetadataPredicate::And(predicates) => {
+                predicates.iter().all(|predicate| predicate.apply(node))
+            }
+            MetadataPredicate::Or(predicates) => {
+                predicates.iter().any(|predicate| predicate.apply(node))
+            }
+            MetadataPredicate::Not(predicate) => !predicate.apply(node),
         }
     }
 
@@ -112,6 +210,75 @@ impl MetadataPredicate {
     }
 }
 
+/// A comparison operator, used by the predicates on numbers and node fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComparisonOperator {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl ComparisonOperator {
+    /// Applies the operator to the given operands.
+    pub fn compare<T: PartialOrd + ?Sized>(self, left: &T, right: &T) -> bool {
+        match self {
+            ComparisonOperator::Eq => left == right,
+            ComparisonOperator::Ne => left != right,
+            ComparisonOperator::Gt => left > right,
+            ComparisonOperator::Ge => left >= right,
+            ComparisonOperator::Lt => left < right,
+            ComparisonOperator::Le => left <= right,
+        }
+    }
+}
+
+/// A field of a node, other than its metadata, that predicates can be applied to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeField {
+    DisplayName,
+    Endpoint,
+}
+
+impl NodeField {
+    /// Returns the value of the field of the given node.
+    pub fn value(self, node: &Node) -> &str {
+        match self {
+            NodeField::DisplayName => &node.display_name,
+            NodeField::Endpoint => &node.endpoint,
+        }
+    }
+}
+
+fn check_node_required_fields_are_not_empty(node: &Node) -> Result<(), InvalidNodeError> {
+    if node.identity.is_empty() {
+        Err(InvalidNodeError::EmptyIdentity)
+    } else if node.endpoint.is_empty() {
+        Err(InvalidNodeError::EmptyEndpoint)
+    } else if node.display_name.is_empty() {
+        Err(InvalidNodeError::EmptyDisplayName)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_if_node_is_duplicate(
+    node: &Node,
+    existing_nodes: &[Node],
+) -> Result<(), InvalidNodeError> {
+    existing_nodes.iter().try_for_each(|existing_node| {
+        if existing_node.identity == node.identity {
+            Err(InvalidNodeError::DuplicateIdentity(node.identity.clone()))
+        } else if existing_node.endpoint == node.endpoint {
+            Err(InvalidNodeError::DuplicateEndpoint(node.endpoint.clone()))
+        } else {
+            Ok(())
+        }
+    })
+}
+
 /// Provides Node Registry read capabilities.
 pub trait NodeRegistryReader: Send + Sync {
     /// Returns an iterator over the nodes in the registry.
@@ -150,6 +317,19 @@ pub trait NodeRegistryReader: Send + Sync {
             Err(err) => Err(err),
         }
     }
+
+    /// Adds a subscriber that is notified of the nodes inserted into, updated in and deleted from
+    /// the registry.
+    ///
+    /// The subscriber is removed from the registry once it returns
+    /// `NodeRegistrySubscriberError::Unsubscribe`. Registries that cannot detect changes to their
+    /// nodes accept the subscriber without ever notifying it.
+    fn add_subscriber(
+        &self,
+        _subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        Ok(())
+    }
 }
 
 /// Provides Node Registry write capabilities.
@@ -189,6 +369,12 @@ impl Clone for Box<dyn RwNodeRegistry> {
     }
 }
 
+impl RwNodeRegistry for Box<dyn RwNodeRegistry> {
+    fn clone_box(&self) -> Box<dyn RwNodeRegistry> {
+        (**self).clone_box()
+    }
+}
+
 impl<NR> NodeRegistryReader for Box<NR>
 where
     NR: NodeRegistryReader + ?Sized,
@@ -211,6 +397,13 @@ where
     fn has_node(&self, identity: &str) -> Result<bool, NodeRegistryError> {
         (**self).has_node(identity)
     }
+
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        (**self).add_subscriber(subscriber)
+    }
 }
 
 impl<NW> NodeRegistryWriter for Box<NW>
@@ -225,3 +418,110 @@ where
         (**self).delete_node(identity)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the `In`, `Exists` and `StartsWith` predicates match on the node's metadata, and
+    /// do not match nodes without the key.
+    #[test]
+    fn test_metadata_predicates() {
+        let node = create_node();
+
+        assert!(MetadataPredicate::In(
+            "company".into(),
+            vec!["Cargill".into(), "Bitwise IO".into()]
+        )
+        .apply(&node));
+        assert!(!MetadataPredicate::In("company".into(), vec!["Cargill".into()]).apply(&node));
+        assert!(MetadataPredicate::Exists("company".into()).apply(&node));
+        assert!(!MetadataPredicate::Exists("region".into()).apply(&node));
+        assert!(MetadataPredicate::StartsWith("company".into(), "Bitwise".into()).apply(&node));
+        assert!(!MetadataPredicate::StartsWith("region".into(), "".into()).apply(&node));
+    }
+
+    /// Test that the `Numeric` predicate compares values as numbers, and that a value that is not
+    /// a number only matches the `Ne` operator.
+    #[test]
+    fn test_numeric_predicates() {
+        let node = create_node();
+
+        // As strings, "9" > "10"
+        assert!(MetadataPredicate::Gt("rank".into(), "10".into()).apply(&node));
+        assert!(
+            !MetadataPredicate::Numeric("rank".into(), ComparisonOperator::Gt, 10.0).apply(&node)
+        );
+        assert!(
+            MetadataPredicate::Numeric("rank".into(), ComparisonOperator::Lt, 10.0).apply(&node)
+        );
+        assert!(
+            MetadataPredicate::Numeric("rank".into(), ComparisonOperator::Eq, 9.0).apply(&node)
+        );
+
+        assert!(
+            !MetadataPredicate::Numeric("company".into(), ComparisonOperator::Eq, 0.0).apply(&node)
+        );
+        assert!(
+            MetadataPredicate::Numeric("company".into(), ComparisonOperator::Ne, 0.0).apply(&node)
+        );
+        assert!(
+            MetadataPredicate::Numeric("region".into(), ComparisonOperator::Ne, 0.0).apply(&node)
+        );
+    }
+
+    /// Test that the `Field` and `FieldStartsWith` predicates match on the node's display name and
+    /// endpoint.
+    #[test]
+    fn test_field_predicates() {
+        let node = create_node();
+
+        assert!(MetadataPredicate::Field(
+            NodeField::DisplayName,
+            ComparisonOperator::Eq,
+            "Bitwise IO - Node 1".into()
+        )
+        .apply(&node));
+        assert!(MetadataPredicate::Field(
+            NodeField::Endpoint,
+            ComparisonOperator::Ne,
+            "tcp://other:8044".into()
+        )
+        .apply(&node));
+        assert!(
+            MetadataPredicate::FieldStartsWith(NodeField::Endpoint, "tcp://".into()).apply(&node)
+        );
+        assert!(
+            !MetadataPredicate::FieldStartsWith(NodeField::DisplayName, "Cargill".into())
+                .apply(&node)
+        );
+    }
+
+    /// Test that the `And`, `Or` and `Not` predicates combine the predicates they contain.
+    #[test]
+    fn test_grouping_predicates() {
+        let node = create_node();
+        let matching = || MetadataPredicate::eq("company", "Bitwise IO");
+        let not_matching = || MetadataPredicate::eq("company", "Cargill");
+
+        assert!(MetadataPredicate::And(vec![]).apply(&node));
+        assert!(MetadataPredicate::And(vec![matching(), matching()]).apply(&node));
+        assert!(!MetadataPredicate::And(vec![matching(), not_matching()]).apply(&node));
+
+        assert!(!MetadataPredicate::Or(vec![]).apply(&node));
+        assert!(MetadataPredicate::Or(vec![not_matching(), matching()]).apply(&node));
+        assert!(!MetadataPredicate::Or(vec![not_matching(), not_matching()]).apply(&node));
+
+        assert!(MetadataPredicate::Not(Box::new(not_matching())).apply(&node));
+        assert!(!MetadataPredicate::Not(Box::new(matching())).apply(&node));
+    }
+
+    fn create_node() -> Node {
+        let mut node = Node::new("Node-123", "tcp://12.0.0.123:8431");
+        node.display_name = "Bitwise IO - Node 1".into();
+        node.metadata
+            .insert("company".to_string(), "Bitwise IO".to_string());
+        node.metadata.insert("rank".to_string(), "9".to_string());
+        node
+    }
+}
@@ -0,0 +1,440 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A read-only node registry fetched from a URL.
+//!
+//! Consortia may publish a shared list of nodes, in the same YAML format as the file used by the
+//! `YamlNodeRegistry`, at a URL. A `RemoteYamlNodeRegistry` fetches the list on a background thread
+//! when it is created and again after each refresh interval, and reads return the last list that
+//! was fetched successfully, so a slow or unavailable URL never delays them. The list is requested
+//! with the `ETag` of the cached list, so that an unchanged list is not downloaded again.
+//!
+//! Subscribers are notified of the changes to the list when a changed list is fetched.
+//!
+//! Remote registries are meant to be combined with a local registry using the
+//! `UnifiedNodeRegistry`.
+//!
+//! This module is behind the `"node-registry-remote"` feature, and is considered experimental.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::{
+    blocking::Client,
+    header::{ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+
+use super::{
+    check_if_node_is_duplicate, check_node_required_fields_are_not_empty, diff_nodes,
+    MetadataPredicate, Node, NodeRegistryError, NodeRegistryReader, NodeRegistrySubscriber,
+    NodeRegistrySubscribers,
+};
+
+/// The default time after which a remote node registry is fetched again.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// A read-only node registry that is fetched from a URL and cached.
+///
+/// Clones share the same cache and subscribers. The background thread that fetches the registry
+/// stops once every clone has been dropped.
+#[derive(Clone)]
+pub struct RemoteYamlNodeRegistry {
+    url: String,
+    cache: Arc<Mutex<Option<Vec<Node>>>>,
+    subscribers: NodeRegistrySubscribers,
+    // Never sent on; the refresh thread stops when it is dropped with the last clone
+    _shutdown_sender: Arc<Mutex<Sender<()>>>,
+}
+
+impl RemoteYamlNodeRegistry {
+    /// Constructs a new RemoteYamlNodeRegistry for the given URL, and starts the background thread
+    /// that fetches it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `RemoteNodeRegistryError` if the background thread cannot be started.
+    pub fn new(url: &str, refresh_interval: Duration) -> Result<Self, RemoteNodeRegistryError> {
+        let cache = Arc::new(Mutex::new(None));
+        let subscribers = NodeRegistrySubscribers::default();
+        let (shutdown_sender, shutdown_receiver) = channel();
+
+        let refresher = Refresher {
+            url: url.to_string(),
+            client: Client::new(),
+            etag: None,
+            cache: cache.clone(),
+            subscribers: subscribers.clone(),
+        };
+        thread::Builder::new()
+            .name(format!("RemoteNodeRegistry-{}", url))
+            .spawn(move || refresher.run(refresh_interval, shutdown_receiver))
+            .map_err(|err| {
+                RemoteNodeRegistryError::new(
+                    &format!("unable to start refresh thread for {}", url),
+                    Some(Box::new(err)),
+                )
+            })?;
+
+        Ok(Self {
+            url: url.to_string(),
+            cache,
+            subscribers,
+            _shutdown_sender: Arc::new(Mutex::new(shutdown_sender)),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the cached nodes, or an error if the registry has not been fetched yet.
+    fn get_nodes(&self) -> Result<Vec<Node>, NodeRegistryError> {
+        let cache = self.cache.lock().map_err(|_| {
+            NodeRegistryError::InternalError(Box::new(RemoteNodeRegistryError::new(
+                "remote node registry cache lock was poisoned",
+                None,
+            )))
+        })?;
+
+        cache.clone().ok_or_else(|| {
+            NodeRegistryError::InternalError(Box::new(RemoteNodeRegistryError::new(
+                &format!("remote node registry {} has not been fetched", self.url),
+                None,
+            )))
+        })
+    }
+}
+
+/// Fetches a remote node registry into the cache it shares with the registry's clones.
+struct Refresher {
+    url: String,
+    client: Client,
+    etag: Option<String>,
+    cache: Arc<Mutex<Option<Vec<Node>>>>,
+    subscribers: NodeRegistrySubscribers,
+}
+
+impl Refresher {
+    /// Fetches the registry now and after each refresh interval, until the shutdown sender is
+    /// dropped.
+    fn run(mut self, refresh_interval: Duration, shutdown_receiver: Receiver<()>) {
+        loop {
+            self.refresh();
+
+            match shutdown_receiver.recv_timeout(refresh_interval) {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => break,
+            }
+        }
+        debug!("Stopped refreshing remote node registry {}", self.url);
+    }
+
+    /// Fetches the registry, replacing the cached nodes and notifying the subscribers if it has
+    /// changed. If it cannot be fetched, the cached nodes are kept.
+    fn refresh(&mut self) {
+        let (nodes, etag) = match self.fetch() {
+            Ok(Some(fetched)) => fetched,
+            Ok(None) => {
+                debug!("Remote node registry {} has not changed", self.url);
+                return;
+            }
+            Err(err) => {
+                warn!(
+                    "Unable to refresh remote node registry; using the last fetched nodes, if \
+                     any: {}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let previous_nodes = match self.cache.lock() {
+            Ok(mut cache) => cache.replace(nodes.clone()).unwrap_or_default(),
+            Err(_) => {
+                error!("Remote node registry cache lock was poisoned");
+                return;
+            }
+        };
+        self.etag = etag;
+
+        for event in diff_nodes(&previous_nodes, &nodes) {
+            self.subscribers.notify(&event);
+        }
+    }
+
+    /// Fetches the nodes and their ETag, or returns `None` if they match the cached ETag.
+    #[allow(clippy::type_complexity)]
+    fn fetch(&self) -> Result<Option<(Vec<Node>, Option<String>)>, RemoteNodeRegistryError> {
+        let mut request = self.client.get(&self.url);
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+
+        let response = request.send().map_err(|err| {
+            RemoteNodeRegistryError::new(
+                &format!("unable to fetch {}", self.url),
+                Some(Box::new(err)),
+            )
+        })?;
+
+        match response.status() {
+            StatusCode::NOT_MODIFIED if self.etag.is_some() => return Ok(None),
+            status if !status.is_success() => {
+                return Err(RemoteNodeRegistryError::new(
+                    &format!("unable to fetch {}: {}", self.url, status),
+                    None,
+                ))
+            }
+            _ => (),
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from);
+
+        let body = response.bytes().map_err(|err| {
+            RemoteNodeRegistryError::new(
+                &format!("unable to read {}", self.url),
+                Some(Box::new(err)),
+            )
+        })?;
+
+        let nodes: Vec<Node> = serde_yaml::from_slice(&body).map_err(|err| {
+            RemoteNodeRegistryError::new(
+                &format!("unable to parse {}", self.url),
+                Some(Box::new(err)),
+            )
+        })?;
+
+        for (idx, node) in nodes.iter().enumerate() {
+            check_node_required_fields_are_not_empty(node)
+                .and_then(|_| check_if_node_is_duplicate(node, &nodes[idx + 1..]))
+                .map_err(|err| {
+                    RemoteNodeRegistryError::new(
+                        &format!("invalid node in {}", self.url),
+                        Some(Box::new(err)),
+                    )
+                })?;
+        }
+
+        Ok(Some((nodes, etag)))
+    }
+}
+
+impl NodeRegistryReader for RemoteYamlNodeRegistry {
+    fn list_nodes<'a, 'b: 'a>(
+        &'b self,
+        predicates: &'a [MetadataPredicate],
+    ) -> Result<Box<dyn Iterator<Item = Node> + Send + 'a>, NodeRegistryError> {
+        let nodes = self.get_nodes()?;
+
+        Ok(Box::new(nodes.into_iter().filter(move |node| {
+            predicates.iter().all(|predicate| predicate.apply(node))
+        })))
+    }
+
+    fn count_nodes(&self, predicates: &[MetadataPredicate]) -> Result<u32, NodeRegistryError> {
+        Ok(self
+            .get_nodes()?
+            .iter()
+            .filter(|node| predicates.iter().all(|predicate| predicate.apply(node)))
+            .count() as u32)
+    }
+
+    fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
+        self.get_nodes()?
+            .into_iter()
+            .find(|node| node.identity == identity)
+            .ok_or_else(|| {
+                NodeRegistryError::NotFoundError(format!(
+                    "Could not find node with identity {}",
+                    identity
+                ))
+            })
+    }
+
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        self.subscribers.add(subscriber);
+        Ok(())
+    }
+}
+
+/// An error that occurs while fetching a remote node registry.
+#[derive(Debug)]
+pub struct RemoteNodeRegistryError {
+    context: String,
+    source: Option<Box<dyn Error + Send>>,
+}
+
+impl RemoteNodeRegistryError {
+    fn new(context: &str, source: Option<Box<dyn Error + Send>>) -> Self {
+        Self {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl Error for RemoteNodeRegistryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        if let Some(ref err) = self.source {
+            Some(&**err)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for RemoteNodeRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref err) = self.source {
+            write!(f, "{}: {}", self.context, err)
+        } else {
+            f.write_str(&self.context)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::node_registry::events::tests::EventCollector;
+    use crate::node_registry::NodeRegistryEvent;
+
+    const NODES_YAML: &str = "- identity: Node-123\n  \
+                              endpoint: tcp://12.0.0.123:8431\n  \
+                              display_name: Bitwise IO - Node 1\n  \
+                              metadata:\n    \
+                              company: Bitwise IO\n";
+
+    /// Test that a remote node registry is fetched in the background once it is created, that it
+    /// is not downloaded again while it has not changed, and that the last fetched nodes are still
+    /// read once the registry cannot be fetched. Subscribers are only notified of the nodes of the
+    /// first fetch.
+    #[test]
+    fn test_remote_registry_caching() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to bind");
+        let url = format!("http://{}/nodes.yaml", listener.local_addr().unwrap());
+
+        // the first fetch is answered once the test has subscribed
+        let (subscribed_sender, subscribed_receiver) = channel();
+        let server = thread::spawn(move || {
+            subscribed_receiver
+                .recv()
+                .expect("Unable to wait for subscriber");
+            let mut requests = vec![];
+            let responses = vec![
+                response("200 OK", NODES_YAML),
+                response("304 Not Modified", ""),
+                response("500 Internal Server Error", ""),
+            ];
+            for response in responses {
+                let (mut stream, _) = listener.accept().expect("Unable to accept");
+                let mut headers = vec![];
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("Unable to read request");
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    headers.push(line.trim_end().to_lowercase());
+                }
+                requests.push(headers);
+                stream
+                    .write_all(response.as_bytes())
+                    .expect("Unable to write response");
+            }
+            requests
+        });
+
+        let registry = RemoteYamlNodeRegistry::new(&url, Duration::from_millis(10))
+            .expect("Unable to create registry");
+        let collector = EventCollector::default();
+        registry
+            .add_subscriber(Box::new(collector.clone()))
+            .expect("Unable to add subscriber");
+        subscribed_sender.send(()).unwrap();
+
+        // the server stops once it has answered the fetch, the unchanged fetch and the failed fetch
+        let requests = server.join().expect("Server thread panicked");
+
+        assert_eq!(
+            "Bitwise IO - Node 1",
+            registry.fetch_node("Node-123").unwrap().display_name
+        );
+        assert_eq!(1, registry.count_nodes(&[]).unwrap());
+        assert_eq!(
+            1,
+            registry
+                .list_nodes(&[MetadataPredicate::eq("company", "Bitwise IO")])
+                .unwrap()
+                .count()
+        );
+
+        assert!(!requests[0]
+            .iter()
+            .any(|header| header.starts_with("if-none-match")));
+        assert!(requests[1].contains(&"if-none-match: \"v1\"".to_string()));
+        assert!(requests[2].contains(&"if-none-match: \"v1\"".to_string()));
+
+        let events = collector.events();
+        assert_eq!(1, events.len());
+        match &events[0] {
+            NodeRegistryEvent::NodeInserted(node) => assert_eq!("Node-123", node.identity),
+            event => panic!("Expected the node to be inserted, but got {:?}", event),
+        }
+    }
+
+    fn response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    /// Test that reading a remote node registry that has never been fetched successfully returns
+    /// an error, without waiting for the registry to be fetched.
+    #[test]
+    fn test_remote_registry_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to bind");
+        let url = format!("http://{}/nodes.yaml", listener.local_addr().unwrap());
+        drop(listener);
+
+        let registry = RemoteYamlNodeRegistry::new(&url, DEFAULT_REFRESH_INTERVAL)
+            .expect("Unable to create registry");
+
+        match registry.fetch_node("Node-123") {
+            Err(NodeRegistryError::InternalError(_)) => (),
+            res => panic!("Expected an internal error, but got {:?}", res),
+        }
+    }
+}
@@ -14,20 +14,22 @@
 
 use std::collections::HashMap;
 
+use serde_json::Value;
+
 use crate::actix_web::{error::BlockingError, web, Error, HttpRequest, HttpResponse};
 use crate::futures::{future::IntoFuture, stream::Stream, Future};
 use crate::rest_api::{
+    new_websocket_event_sender,
     paging::{get_response_paging_info, Paging, DEFAULT_LIMIT, DEFAULT_OFFSET},
-    percent_encode_filter_query, Method, Resource,
+    percent_encode_filter_query, EventSender, Method, Request, Resource,
 };
 
 use super::{
-    error::{InvalidNodeError, NodeRegistryError},
-    MetadataPredicate, Node, NodeRegistryReader, NodeRegistryWriter,
+    error::{InvalidNodeError, NodeRegistryError, NodeRegistrySubscriberError},
+    ComparisonOperator, MetadataPredicate, Node, NodeField, NodeRegistryEvent, NodeRegistryReader,
+    NodeRegistrySubscriber, NodeRegistryWriter, RwNodeRegistry,
 };
 
-type Filter = HashMap<String, (String, String)>;
-
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ListNodesResponse {
     data: Vec<Node>,
@@ -66,6 +68,45 @@ where
         })
 }
 
+/// Makes the websocket resource that sends the changes to the registry as they occur.
+pub fn make_nodes_subscribe_resource<N>(registry: N) -> Resource
+where
+    N: RwNodeRegistry + Clone + 'static,
+{
+    Resource::build("/ws/nodes").add_method(Method::Get, move |request, payload| {
+        let request = Request::from((request, payload));
+        match new_websocket_event_sender(request, Box::new(std::iter::empty())) {
+            Ok((sender, res)) => {
+                if let Err(err) =
+                    registry.add_subscriber(Box::new(WsNodeRegistrySubscriber { sender }))
+                {
+                    error!("Unable to add node registry subscriber: {}", err);
+                    return Box::new(HttpResponse::InternalServerError().finish().into_future());
+                }
+                debug!("Websocket response: {:?}", res);
+                Box::new(res.into_future())
+            }
+            Err(err) => {
+                debug!("Failed to create websocket: {:?}", err);
+                Box::new(HttpResponse::InternalServerError().finish().into_future())
+            }
+        }
+    })
+}
+
+struct WsNodeRegistrySubscriber {
+    sender: EventSender<NodeRegistryEvent>,
+}
+
+impl NodeRegistrySubscriber for WsNodeRegistrySubscriber {
+    fn handle_event(&self, event: &NodeRegistryEvent) -> Result<(), NodeRegistrySubscriberError> {
+        self.sender.send(event.clone()).map_err(|_| {
+            debug!("Dropping node registry event and unsubscribing due to websocket being closed");
+            NodeRegistrySubscriberError::Unsubscribe
+        })
+    }
+}
+
 fn fetch_node<NR>(
     request: HttpRequest,
     registry: web::Data<NR>,
@@ -307,24 +348,150 @@ where
     })
 }
 
-fn to_predicates(filters: Option<Filter>) -> Result<Vec<MetadataPredicate>, String> {
+/// Parses the `filter` query parameter, a JSON object whose entries must all match. Each entry
+/// is one of:
+///
+/// * a metadata key with a condition, such as `"company": ["=", "Cargill"]`. The comparison
+///   operators `=`, `!=`, `>`, `>=`, `<` and `<=` compare strings, or numbers if the value is a
+///   number; `"in"` takes a list of values, `"prefix"` a prefix, and `["exists"]` has no value.
+/// * `$display_name` or `$endpoint` with a comparison or `"prefix"` condition on that field.
+/// * `$and` or `$or` with a list of filter objects, or `$not` with a filter object.
+fn to_predicates(filters: Option<Value>) -> Result<Vec<MetadataPredicate>, String> {
     match filters {
-        Some(filters) => filters
-            .into_iter()
-            .map(|(key, (operator, value))| match operator.as_str() {
-                "=" => Ok(MetadataPredicate::Eq(key, value)),
-                ">" => Ok(MetadataPredicate::Gt(key, value)),
-                "<" => Ok(MetadataPredicate::Lt(key, value)),
-                ">=" => Ok(MetadataPredicate::Ge(key, value)),
-                "<=" => Ok(MetadataPredicate::Le(key, value)),
-                "!=" => Ok(MetadataPredicate::Ne(key, value)),
-                _ => Err(format!("{} is not a valid operator", operator)),
-            })
-            .collect(),
+        Some(filters) => parse_filter(&filters),
         None => Ok(vec![]),
     }
 }
 
+fn parse_filter(filter: &Value) -> Result<Vec<MetadataPredicate>, String> {
+    filter
+        .as_object()
+        .ok_or_else(|| format!("{} is not a filter object", filter))?
+        .iter()
+        .map(|(key, condition)| match key.as_str() {
+            "$and" => parse_filter_list(key, condition).map(MetadataPredicate::And),
+            "$or" => parse_filter_list(key, condition).map(MetadataPredicate::Or),
+            "$not" => parse_filter(condition).map(|predicates| match predicates.len() {
+                1 => MetadataPredicate::Not(Box::new(predicates.into_iter().next().unwrap())),
+                _ => MetadataPredicate::Not(Box::new(MetadataPredicate::And(predicates))),
+            }),
+            "$display_name" => parse_field_condition(NodeField::DisplayName, key, condition),
+            "$endpoint" => parse_field_condition(NodeField::Endpoint, key, condition),
+            _ if key.starts_with('$') => Err(format!("{} is not a valid filter key", key)),
+            _ => parse_metadata_condition(key, condition),
+        })
+        .collect()
+}
+
+/// Parses a list of filter objects, each of which becomes a single predicate.
+fn parse_filter_list(key: &str, filters: &Value) -> Result<Vec<MetadataPredicate>, String> {
+    filters
+        .as_array()
+        .ok_or_else(|| format!("{} requires a list of filter objects", key))?
+        .iter()
+        .map(|filter| {
+            parse_filter(filter).map(|predicates| match predicates.len() {
+                1 => predicates.into_iter().next().unwrap(),
+                _ => MetadataPredicate::And(predicates),
+            })
+        })
+        .collect()
+}
+
+fn parse_metadata_condition(key: &str, condition: &Value) -> Result<MetadataPredicate, String> {
+    let key = key.to_string();
+    match split_condition(&key, condition)? {
+        ("exists", None) => Ok(MetadataPredicate::Exists(key)),
+        ("prefix", Some(Value::String(prefix))) => {
+            Ok(MetadataPredicate::StartsWith(key, prefix.to_string()))
+        }
+        ("in", Some(Value::Array(values))) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| format!("the values of {} must be strings", key))
+            })
+            .collect::<Result<_, _>>()
+            .map(|values| MetadataPredicate::In(key, values)),
+        (operator, Some(Value::String(value))) => {
+            let value = value.to_string();
+            Ok(match to_comparison_operator(operator)? {
+                ComparisonOperator::Eq => MetadataPredicate::Eq(key, value),
+                ComparisonOperator::Ne => MetadataPredicate::Ne(key, value),
+                ComparisonOperator::Gt => MetadataPredicate::Gt(key, value),
+                ComparisonOperator::Ge => MetadataPredicate::Ge(key, value),
+                ComparisonOperator::Lt => MetadataPredicate::Lt(key, value),
+                ComparisonOperator::Le => MetadataPredicate::Le(key, value),
+            })
+        }
+        (operator, Some(Value::Number(number))) => {
+            let number = number
+                .as_f64()
+                .ok_or_else(|| format!("{} is not a valid number", number))?;
+            Ok(MetadataPredicate::Numeric(
+                key,
+                to_comparison_operator(operator)?,
+                number,
+            ))
+        }
+        (operator, _) => Err(format!(
+            "{} is not a valid operator for the value of {}",
+            operator, key
+        )),
+    }
+}
+
+fn parse_field_condition(
+    field: NodeField,
+    key: &str,
+    condition: &Value,
+) -> Result<MetadataPredicate, String> {
+    match split_condition(key, condition)? {
+        ("prefix", Some(Value::String(prefix))) => Ok(MetadataPredicate::FieldStartsWith(
+            field,
+            prefix.to_string(),
+        )),
+        (operator, Some(Value::String(value))) => Ok(MetadataPredicate::Field(
+            field,
+            to_comparison_operator(operator)?,
+            value.to_string(),
+        )),
+        (operator, _) => Err(format!(
+            "{} is not a valid operator for the value of {}",
+            operator, key
+        )),
+    }
+}
+
+/// Splits a condition, such as `["=", "Cargill"]` or `["exists"]`, into its operator and value.
+fn split_condition<'a>(
+    key: &str,
+    condition: &'a Value,
+) -> Result<(&'a str, Option<&'a Value>), String> {
+    match condition.as_array().map(Vec::as_slice) {
+        Some([Value::String(operator)]) => Ok((operator.as_str(), None)),
+        Some([Value::String(operator), value]) => Ok((operator.as_str(), Some(value))),
+        _ => Err(format!(
+            "the condition on {} must be an [operator, value] list",
+            key
+        )),
+    }
+}
+
+fn to_comparison_operator(operator: &str) -> Result<ComparisonOperator, String> {
+    match operator {
+        "=" => Ok(ComparisonOperator::Eq),
+        "!=" => Ok(ComparisonOperator::Ne),
+        ">" => Ok(ComparisonOperator::Gt),
+        ">=" => Ok(ComparisonOperator::Ge),
+        "<" => Ok(ComparisonOperator::Lt),
+        "<=" => Ok(ComparisonOperator::Le),
+        _ => Err(format!("{} is not a valid operator", operator)),
+    }
+}
+
 fn add_node<NW>(
     payload: web::Payload,
     registry: web::Data<NW>,
@@ -636,6 +803,79 @@ mod tests {
         })
     }
 
+    #[test]
+    /// Tests a GET /nodes request with a filter that groups conditions on metadata and node fields
+    /// returns the expected node, and that a malformed group returns a BadRequest response.
+    fn test_list_node_with_grouped_filters() {
+        run_test(|test_yaml_file_path| {
+            write_to_file(&test_yaml_file_path, &[get_node_1(), get_node_2()]);
+
+            let node_registry = new_yaml_node_registry(test_yaml_file_path);
+
+            let mut app = test::init_service(App::new().data(node_registry.clone()).service(
+                web::resource("/nodes").route(web::get().to_async(list_nodes::<YamlNodeRegistry>)),
+            ));
+
+            let filter = percent_encode_filter_query(
+                "{\"$or\":[{\"company\":[\"in\",[\"Cargill\"]]},\
+                 {\"$endpoint\":[\"prefix\",\"12.0.\"]}],\
+                 \"$not\":{\"$display_name\":[\"=\",\"Cargill - Node 1\"]}}",
+            );
+
+            let req = test::TestRequest::get()
+                .uri(&format!("/nodes?filter={}", filter))
+                .header(header::CONTENT_TYPE, "application/json")
+                .to_request();
+
+            let resp = test::call_service(&mut app, req);
+
+            assert_eq!(resp.status(), StatusCode::OK);
+            let nodes: ListNodesResponse = serde_yaml::from_slice(&test::read_body(resp)).unwrap();
+            assert_eq!(nodes.data, vec![get_node_1()]);
+
+            let filter = percent_encode_filter_query("{\"$or\":{\"company\":[\"exists\"]}}");
+
+            let req = test::TestRequest::get()
+                .uri(&format!("/nodes?filter={}", filter))
+                .header(header::CONTENT_TYPE, "application/json")
+                .to_request();
+
+            let resp = test::call_service(&mut app, req);
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        })
+    }
+
+    #[test]
+    /// Test that the filter of a GET /nodes request is parsed into the expected predicates, with
+    /// numbers compared as numbers.
+    fn test_to_predicates() {
+        let filter = serde_json::from_str(
+            "{\"rank\":[\">=\",10],\"region\":[\"exists\"],\"company\":[\">=\",\"B\"]}",
+        )
+        .unwrap();
+        let predicates = to_predicates(Some(filter)).expect("Unable to parse filter");
+
+        let mut node = get_node_1();
+        node.metadata.insert("rank".into(), "9".into());
+        node.metadata.insert("region".into(), "us".into());
+        assert_eq!(3, predicates.len());
+        assert!(!predicates.iter().all(|predicate| predicate.apply(&node)));
+
+        node.metadata.insert("rank".into(), "10".into());
+        assert!(predicates.iter().all(|predicate| predicate.apply(&node)));
+
+        for filter in &[
+            "{\"company\":[\"in\",\"Cargill\"]}",
+            "{\"company\":[\"exists\",\"Cargill\"]}",
+            "{\"$company\":[\"=\",\"Cargill\"]}",
+            "{\"$endpoint\":[\"=\",1]}",
+            "[\"company\"]",
+        ] {
+            assert!(to_predicates(Some(serde_json::from_str(filter).unwrap())).is_err());
+        }
+    }
+
     #[test]
     /// Test the POST /nodes route for adding a node to the registry.
     fn test_add_node() {
@@ -727,6 +967,7 @@ mod tests {
             endpoint: "12.0.0.123:8431".to_string(),
             display_name: "Bitwise IO - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -738,6 +979,7 @@ mod tests {
             endpoint: "13.0.0.123:8434".to_string(),
             display_name: "Cargill - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -0,0 +1,602 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing and verification of node registry entries.
+//!
+//! A node signs its own entry with `sign_node`. A signature is only accepted if it is valid for
+//! the entry's signing bytes and its public key belongs to the node, according to the `KeyRegistry`
+//! (that is, the key's `associated_node_id` is the node's identity). This prevents an entry from
+//! being signed by any key other than the node's own.
+//!
+//! A `VerifyingNodeRegistry` wraps another node registry and applies a `SignaturePolicy` to the
+//! entries that are read from it, and to the entries in the events of its subscribers.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::hex::{parse_hex, to_hex};
+use crate::keys::KeyRegistry;
+use crate::mutex_lock_unwrap;
+use crate::signing::{Error as SigningError, SignatureVerifier, Signer};
+
+use super::{
+    InvalidNodeError, MetadataPredicate, Node, NodeRegistryError, NodeRegistryEvent,
+    NodeRegistryReader, NodeRegistrySubscriber, NodeRegistrySubscriberError, NodeRegistryWriter,
+    NodeSignature, RwNodeRegistry,
+};
+
+/// The metadata key that is set to the signature status of each entry under the `Flag` policy.
+///
+/// This key is reserved: it is removed from entries before they are verified or written.
+pub const SIGNATURE_STATUS_METADATA_KEY: &str = "signature_status";
+
+/// Signs the node's entry with the given signer, replacing any previous signature.
+pub fn sign_node(node: &mut Node, signer: &dyn Signer) -> Result<(), SigningError> {
+    let signature = signer.sign(&node.signing_bytes())?;
+    node.signature = Some(NodeSignature {
+        public_key: to_hex(signer.public_key()),
+        signature: to_hex(&signature),
+    });
+    Ok(())
+}
+
+/// The result of verifying the signature of a node's entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignatureStatus {
+    /// The entry is signed with a key that belongs to the node.
+    Valid,
+    /// The entry has no signature.
+    Unsigned,
+    /// The entry has a signature that is not valid; the reason is included.
+    Invalid(String),
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureStatus::Valid => f.write_str("valid"),
+            SignatureStatus::Unsigned => f.write_str("unsigned"),
+            SignatureStatus::Invalid(_) => f.write_str("invalid"),
+        }
+    }
+}
+
+/// What to do with entries that are unsigned or have an invalid signature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignaturePolicy {
+    /// Keep the entries, and set the `signature_status` metadata of every entry to `valid`,
+    /// `unsigned` or `invalid`. Since the metadata is part of the signed bytes, clients that verify
+    /// the signatures themselves must remove this entry first.
+    Flag,
+    /// Drop the entries when reading, and refuse to write them.
+    Reject,
+}
+
+/// Verifies the signatures of node entries against the keys in a `KeyRegistry`.
+#[derive(Clone)]
+pub struct NodeSignatureVerifier {
+    signature_verifier: Arc<Mutex<Box<dyn SignatureVerifier>>>,
+    key_registry: Box<dyn KeyRegistry>,
+}
+
+impl NodeSignatureVerifier {
+    /// Constructs a new NodeSignatureVerifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `signature_verifier` - Verifies the signatures themselves.
+    /// * `key_registry` - Determines which node each public key belongs to.
+    pub fn new(
+        signature_verifier: Box<dyn SignatureVerifier>,
+        key_registry: Box<dyn KeyRegistry>,
+    ) -> Self {
+        Self {
+            signature_verifier: Arc::new(Mutex::new(signature_verifier)),
+            key_registry,
+        }
+    }
+
+    /// Returns the signature status of the node's entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NodeRegistryError` if the key registry cannot be read.
+    pub fn verify(&self, node: &Node) -> Result<SignatureStatus, NodeRegistryError> {
+        let node_signature = match node.signature {
+            Some(ref node_signature) => node_signature,
+            None => return Ok(SignatureStatus::Unsigned),
+        };
+
+        let (public_key, signature) = match (
+            parse_hex(&node_signature.public_key),
+            parse_hex(&node_signature.signature),
+        ) {
+            (Ok(public_key), Ok(signature)) => (public_key, signature),
+            _ => {
+                return Ok(SignatureStatus::Invalid(
+                    "public key and signature must be hex-encoded".into(),
+                ))
+            }
+        };
+
+        let key_info = self
+            .key_registry
+            .get_key(&public_key)
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))?;
+
+        match key_info {
+            Some(ref key_info) if key_info.associated_node_id() == node.identity => (),
+            Some(_) => {
+                return Ok(SignatureStatus::Invalid(format!(
+                    "key {} does not belong to the node",
+                    node_signature.public_key
+                )))
+            }
+            None => {
+                return Ok(SignatureStatus::Invalid(format!(
+                    "key {} is not in the key registry",
+                    node_signature.public_key
+                )))
+            }
+        }
+
+        let verified = mutex_lock_unwrap!(self.signature_verifier).verify(
+            &node.signing_bytes(),
+            &signature,
+            &public_key,
+        );
+
+        match verified {
+            Ok(true) => Ok(SignatureStatus::Valid),
+            Ok(false) => Ok(SignatureStatus::Invalid(
+                "signature does not match the entry".into(),
+            )),
+            Err(err) => Ok(SignatureStatus::Invalid(err.to_string())),
+        }
+    }
+
+    /// Applies the policy to an entry that has been read, returning `None` if it is rejected.
+    fn apply_policy(
+        &self,
+        mut node: Node,
+        policy: SignaturePolicy,
+    ) -> Result<Option<Node>, NodeRegistryError> {
+        node.metadata.remove(SIGNATURE_STATUS_METADATA_KEY);
+        let status = self.verify(&node)?;
+
+        match policy {
+            SignaturePolicy::Reject if status != SignatureStatus::Valid => {
+                warn!(
+                    "Ignoring node registry entry for {}; signature is {}",
+                    node.identity, status
+                );
+                Ok(None)
+            }
+            SignaturePolicy::Reject => Ok(Some(node)),
+            SignaturePolicy::Flag => {
+                node.metadata
+                    .insert(SIGNATURE_STATUS_METADATA_KEY.into(), status.to_string());
+                Ok(Some(node))
+            }
+        }
+    }
+}
+
+/// A node registry that verifies the signatures of the entries of another registry.
+///
+/// Under the `Reject` policy, entries that are unsigned or invalid are not returned, and can not
+/// be inserted. Under the `Flag` policy, all entries are returned and inserted, and the entries
+/// that are read have their signature status in the `signature_status` metadata.
+#[derive(Clone)]
+pub struct VerifyingNodeRegistry<R> {
+    inner: R,
+    verifier: NodeSignatureVerifier,
+    policy: SignaturePolicy,
+}
+
+impl<R> VerifyingNodeRegistry<R> {
+    pub fn new(inner: R, verifier: NodeSignatureVerifier, policy: SignaturePolicy) -> Self {
+        Self {
+            inner,
+            verifier,
+            policy,
+        }
+    }
+
+    /// Applies the policy to an entry that has been read, returning `None` if it is rejected.
+    fn check_node(&self, node: Node) -> Result<Option<Node>, NodeRegistryError> {
+        self.verifier.apply_policy(node, self.policy)
+    }
+}
+
+impl<R> NodeRegistryReader for VerifyingNodeRegistry<R>
+where
+    R: NodeRegistryReader,
+{
+    fn list_nodes<'a, 'b: 'a>(
+        &'b self,
+        predicates: &'a [MetadataPredicate],
+    ) -> Result<Box<dyn Iterator<Item = Node> + Send + 'a>, NodeRegistryError> {
+        // Flagging changes the metadata, so the predicates can only be applied by the inner
+        // registry if the policy is to reject
+        let inner_predicates: &[MetadataPredicate] = match self.policy {
+            SignaturePolicy::Reject => predicates,
+            SignaturePolicy::Flag => &[],
+        };
+
+        let nodes = self
+            .inner
+            .list_nodes(inner_predicates)?
+            .map(|node| self.check_node(node))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(nodes.into_iter().flatten().filter(move |node| {
+            predicates.iter().all(|predicate| predicate.apply(node))
+        })))
+    }
+
+    fn count_nodes(&self, predicates: &[MetadataPredicate]) -> Result<u32, NodeRegistryError> {
+        Ok(self.list_nodes(predicates)?.count() as u32)
+    }
+
+    fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
+        let node = self.inner.fetch_node(identity)?;
+        self.check_node(node)?.ok_or_else(|| {
+            NodeRegistryError::NotFoundError(format!(
+                "Node {} does not have a valid signature",
+                identity
+            ))
+        })
+    }
+
+    /// Adds a subscriber to the inner registry, whose events have the policy applied. Under the
+    /// `Reject` policy, an entry that is inserted or updated without a valid signature is sent as
+    /// a deletion, as it can no longer be read.
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        self.inner.add_subscriber(Box::new(VerifyingSubscriber {
+            subscriber,
+            verifier: self.verifier.clone(),
+            policy: self.policy,
+        }))
+    }
+}
+
+impl<R> NodeRegistryWriter for VerifyingNodeRegistry<R>
+where
+    R: NodeRegistryWriter,
+{
+    fn insert_node(&self, mut node: Node) -> Result<(), NodeRegistryError> {
+        node.metadata.remove(SIGNATURE_STATUS_METADATA_KEY);
+
+        if self.policy == SignaturePolicy::Reject {
+            match self.verifier.verify(&node)? {
+                SignatureStatus::Valid => (),
+                SignatureStatus::Unsigned => {
+                    return Err(NodeRegistryError::InvalidNode(
+                        InvalidNodeError::InvalidSignature(
+                            node.identity,
+                            "node is unsigned".into(),
+                        ),
+                    ))
+                }
+                SignatureStatus::Invalid(msg) => {
+                    return Err(NodeRegistryError::InvalidNode(
+                        InvalidNodeError::InvalidSignature(node.identity, msg),
+                    ))
+                }
+            }
+        }
+
+        self.inner.insert_node(node)
+    }
+
+    fn delete_node(&self, identity: &str) -> Result<(), NodeRegistryError> {
+        self.inner.delete_node(identity)
+    }
+}
+
+impl<R> RwNodeRegistry for VerifyingNodeRegistry<R>
+where
+    R: RwNodeRegistry + Clone + 'static,
+{
+    fn clone_box(&self) -> Box<dyn RwNodeRegistry> {
+        Box::new(self.clone())
+    }
+}
+
+/// Applies a signature policy to the events of a registry before passing them to a subscriber.
+struct VerifyingSubscriber {
+    subscriber: Box<dyn NodeRegistrySubscriber>,
+    verifier: NodeSignatureVerifier,
+    policy: SignaturePolicy,
+}
+
+impl NodeRegistrySubscriber for VerifyingSubscriber {
+    fn handle_event(&self, event: &NodeRegistryEvent) -> Result<(), NodeRegistrySubscriberError> {
+        let (node, inserted) = match event {
+            NodeRegistryEvent::NodeInserted(node) => (node, true),
+            NodeRegistryEvent::NodeUpdated(node) => (node, false),
+            NodeRegistryEvent::NodeDeleted(_) => return self.subscriber.handle_event(event),
+        };
+
+        let checked_node = self
+            .verifier
+            .apply_policy(node.clone(), self.policy)
+            .map_err(|err| NodeRegistrySubscriberError::UnableToHandleEvent(err.to_string()))?;
+
+        let event = match checked_node {
+            Some(node) if inserted => NodeRegistryEvent::NodeInserted(node),
+            Some(node) => NodeRegistryEvent::NodeUpdated(node),
+            None => NodeRegistryEvent::NodeDeleted(node.identity.clone()),
+        };
+
+        self.subscriber.handle_event(&event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::keys::{storage::StorageKeyRegistry, KeyInfo};
+    use crate::node_registry::events::tests::EventCollector;
+    use crate::node_registry::NodeRegistrySubscribers;
+    use crate::signing::hash::{HashSigner, HashVerifier};
+
+    /// A simple in-memory node registry.
+    #[derive(Clone, Default)]
+    struct MemoryRegistry {
+        nodes: Arc<Mutex<HashMap<String, Node>>>,
+        subscribers: NodeRegistrySubscribers,
+    }
+
+    impl NodeRegistryReader for MemoryRegistry {
+        fn list_nodes<'a, 'b: 'a>(
+            &'b self,
+            predicates: &'a [MetadataPredicate],
+        ) -> Result<Box<dyn Iterator<Item = Node> + Send + 'a>, NodeRegistryError> {
+            let nodes = mutex_lock_unwrap!(self.nodes)
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            Ok(Box::new(nodes.into_iter().filter(move |node| {
+                predicates.iter().all(|predicate| predicate.apply(node))
+            })))
+        }
+
+        fn count_nodes(&self, predicates: &[MetadataPredicate]) -> Result<u32, NodeRegistryError> {
+            Ok(self.list_nodes(predicates)?.count() as u32)
+        }
+
+        fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
+            mutex_lock_unwrap!(self.nodes)
+                .get(identity)
+                .cloned()
+                .ok_or_else(|| NodeRegistryError::NotFoundError(identity.to_string()))
+        }
+
+        fn add_subscriber(
+            &self,
+            subscriber: Box<dyn NodeRegistrySubscriber>,
+        ) -> Result<(), NodeRegistryError> {
+            self.subscribers.add(subscriber);
+            Ok(())
+        }
+    }
+
+    impl NodeRegistryWriter for MemoryRegistry {
+        fn insert_node(&self, node: Node) -> Result<(), NodeRegistryError> {
+            let previous =
+                mutex_lock_unwrap!(self.nodes).insert(node.identity.clone(), node.clone());
+            self.subscribers.notify(&match previous {
+                Some(_) => NodeRegistryEvent::NodeUpdated(node),
+                None => NodeRegistryEvent::NodeInserted(node),
+            });
+            Ok(())
+        }
+
+        fn delete_node(&self, identity: &str) -> Result<(), NodeRegistryError> {
+            mutex_lock_unwrap!(self.nodes).remove(identity);
+            self.subscribers
+                .notify(&NodeRegistryEvent::NodeDeleted(identity.to_string()));
+            Ok(())
+        }
+    }
+
+    impl RwNodeRegistry for MemoryRegistry {
+        fn clone_box(&self) -> Box<dyn RwNodeRegistry> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Test that the verifier accepts an entry signed with the node's own key, and that it detects
+    /// unsigned entries, modified entries and entries signed with another node's key.
+    #[test]
+    fn test_verify() {
+        let verifier = create_verifier("node-a");
+
+        let mut node = create_node("node-a", "tcp://a:8044");
+        assert_eq!(SignatureStatus::Unsigned, verifier.verify(&node).unwrap());
+
+        sign_node(&mut node, &HashSigner).expect("Unable to sign node");
+        assert_eq!(SignatureStatus::Valid, verifier.verify(&node).unwrap());
+
+        let mut modified_node = node.clone();
+        modified_node.endpoint = "tcp://evil:8044".into();
+        assert!(match verifier.verify(&modified_node).unwrap() {
+            SignatureStatus::Invalid(_) => true,
+            _ => false,
+        });
+
+        let mut other_node = create_node("node-b", "tcp://b:8044");
+        sign_node(&mut other_node, &HashSigner).expect("Unable to sign node");
+        assert!(match verifier.verify(&other_node).unwrap() {
+            SignatureStatus::Invalid(_) => true,
+            _ => false,
+        });
+    }
+
+    /// Test that under the Reject policy, unsigned and invalid entries are not read, counted or
+    /// fetched, and that they cannot be inserted.
+    #[test]
+    fn test_reject_policy() {
+        let inner = MemoryRegistry::default();
+        let mut signed_node = create_node("node-a", "tcp://a:8044");
+        sign_node(&mut signed_node, &HashSigner).expect("Unable to sign node");
+        let unsigned_node = create_node("node-b", "tcp://b:8044");
+        inner.insert_node(signed_node.clone()).unwrap();
+        inner.insert_node(unsigned_node.clone()).unwrap();
+
+        let registry =
+            VerifyingNodeRegistry::new(inner, create_verifier("node-a"), SignaturePolicy::Reject);
+
+        let nodes = registry.list_nodes(&[]).unwrap().collect::<Vec<_>>();
+        assert_eq!(vec![signed_node.clone()], nodes);
+        assert_eq!(1, registry.count_nodes(&[]).unwrap());
+        assert_eq!(signed_node, registry.fetch_node("node-a").unwrap());
+        assert!(!registry.has_node("node-b").unwrap());
+
+        match registry.insert_node(unsigned_node) {
+            Err(NodeRegistryError::InvalidNode(InvalidNodeError::InvalidSignature(..))) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        registry
+            .insert_node(signed_node)
+            .expect("Unable to insert signed node");
+    }
+
+    /// Test that under the Flag policy, all entries are read with their signature status in their
+    /// metadata, and that the status can be used in predicates.
+    #[test]
+    fn test_flag_policy() {
+        let inner = MemoryRegistry::default();
+        let mut signed_node = create_node("node-a", "tcp://a:8044");
+        sign_node(&mut signed_node, &HashSigner).expect("Unable to sign node");
+        inner.insert_node(signed_node).unwrap();
+        inner
+            .insert_node(create_node("node-b", "tcp://b:8044"))
+            .unwrap();
+
+        let registry =
+            VerifyingNodeRegistry::new(inner, create_verifier("node-a"), SignaturePolicy::Flag);
+
+        let node = registry.fetch_node("node-a").unwrap();
+        assert_eq!(
+            Some(&"valid".to_string()),
+            node.metadata.get(SIGNATURE_STATUS_METADATA_KEY)
+        );
+        let node = registry.fetch_node("node-b").unwrap();
+        assert_eq!(
+            Some(&"unsigned".to_string()),
+            node.metadata.get(SIGNATURE_STATUS_METADATA_KEY)
+        );
+
+        assert_eq!(2, registry.count_nodes(&[]).unwrap());
+        let predicates = vec![MetadataPredicate::eq(
+            SIGNATURE_STATUS_METADATA_KEY,
+            "valid",
+        )];
+        let nodes = registry
+            .list_nodes(&predicates)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(1, nodes.len());
+        assert_eq!("node-a", nodes[0].identity);
+
+        // The flag is not written back to the inner registry
+        let node = registry.fetch_node("node-b").unwrap();
+        registry.insert_node(node).unwrap();
+        assert!(registry
+            .inner
+            .fetch_node("node-b")
+            .unwrap()
+            .metadata
+            .get(SIGNATURE_STATUS_METADATA_KEY)
+            .is_none());
+    }
+
+    /// Test that the events of the inner registry have the policy applied: under the Reject
+    /// policy, an entry that is updated to be unsigned is sent as a deletion, and under the Flag
+    /// policy, the entries in the events have their signature status.
+    #[test]
+    fn test_subscriber_events() {
+        let mut signed_node = create_node("node-a", "tcp://a:8044");
+        sign_node(&mut signed_node, &HashSigner).expect("Unable to sign node");
+        let unsigned_node = create_node("node-a", "tcp://a:8044");
+
+        let inner = MemoryRegistry::default();
+        let registry = VerifyingNodeRegistry::new(
+            inner.clone(),
+            create_verifier("node-a"),
+            SignaturePolicy::Reject,
+        );
+        let collector = EventCollector::default();
+        registry
+            .add_subscriber(Box::new(collector.clone()))
+            .expect("Unable to add subscriber");
+
+        inner.insert_node(signed_node.clone()).unwrap();
+        inner.insert_node(unsigned_node).unwrap();
+
+        assert_eq!(
+            vec![
+                NodeRegistryEvent::NodeInserted(signed_node.clone()),
+                NodeRegistryEvent::NodeDeleted("node-a".into()),
+            ],
+            collector.events()
+        );
+
+        let inner = MemoryRegistry::default();
+        let registry =
+            VerifyingNodeRegistry::new(inner, create_verifier("node-a"), SignaturePolicy::Flag);
+        let collector = EventCollector::default();
+        registry
+            .add_subscriber(Box::new(collector.clone()))
+            .expect("Unable to add subscriber");
+
+        registry.insert_node(signed_node.clone()).unwrap();
+
+        let mut flagged_node = signed_node;
+        flagged_node
+            .metadata
+            .insert(SIGNATURE_STATUS_METADATA_KEY.into(), "valid".into());
+        assert_eq!(
+            vec![NodeRegistryEvent::NodeInserted(flagged_node)],
+            collector.events()
+        );
+    }
+
+    /// Creates a verifier whose key registry has the hash signer's key belong to the given node.
+    fn create_verifier(node_id: &str) -> NodeSignatureVerifier {
+        let mut key_registry =
+            StorageKeyRegistry::new("memory".into()).expect("Unable to create registry");
+        key_registry
+            .save_key(KeyInfo::builder(HashSigner.public_key().to_vec(), node_id.into()).build())
+            .expect("Unable to save key");
+
+        NodeSignatureVerifier::new(Box::new(HashVerifier), Box::new(key_registry))
+    }
+
+    fn create_node(identity: &str, endpoint: &str) -> Node {
+        let mut node = Node::new(identity, endpoint);
+        node.display_name = format!("Node {}", identity);
+        node.metadata.insert("company".into(), "Bitwise IO".into());
+        node
+    }
+}
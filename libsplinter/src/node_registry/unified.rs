@@ -19,13 +19,18 @@
 //! local source into values from the read-only sources, allowing the user to replace values from
 //! the remove sources.
 //!
+//! Subscribers of the unified registry are notified of the changes to the local registry and to
+//! the read-only sources that can detect them, such as the files of `YamlNodeRegistry` sources.
+//!
 //! This module is behind the `"node-registry-unified"` feature, and is considered experimental.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::mutex_lock_unwrap;
 
 use super::{
-    MetadataPredicate, Node, NodeRegistryError, NodeRegistryReader, NodeRegistryWriter,
-    RwNodeRegistry,
+    MetadataPredicate, Node, NodeRegistryError, NodeRegistryEvent, NodeRegistryReader,
+    NodeRegistrySubscriber, NodeRegistrySubscriberError, NodeRegistryWriter, RwNodeRegistry,
 };
 
 /// Unifies a set of read-only node registries with a local, read-write node registry.
@@ -113,6 +118,131 @@ impl NodeRegistryReader for UnifiedNodeRegistry {
             .find(Result::is_ok)
             .unwrap_or_else(|| Err(NodeRegistryError::NotFoundError(identity.to_string())))
     }
+
+    /// Subscribes to the changes of the local registry and of the read-only sources. The events
+    /// describe the changes to the unified nodes: a change to an entry that is replaced by a
+    /// source of higher precedence is not sent, and the deletion of an entry that replaced one
+    /// from a source of lower precedence is sent as an update to that entry.
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        let subscriber = Arc::new(Mutex::new(Some(subscriber)));
+        let unified_subscriber = |source_index| {
+            Box::new(UnifiedSubscriber {
+                local_source: Arc::downgrade(&self.local_source),
+                readable_sources: self.readable_sources.iter().map(Arc::downgrade).collect(),
+                source_index,
+                subscriber: subscriber.clone(),
+            })
+        };
+
+        self.local_source.add_subscriber(unified_subscriber(0))?;
+        for (i, source) in self.readable_sources.iter().enumerate() {
+            source.add_subscriber(unified_subscriber(i + 1))?;
+        }
+        Ok(())
+    }
+}
+
+/// Translates the events of one of the sources of a unified registry into events of the unified
+/// registry. The sources are only referenced weakly, as the subscriber is owned by one of them.
+struct UnifiedSubscriber {
+    local_source: Weak<dyn RwNodeRegistry>,
+    readable_sources: Vec<Weak<dyn NodeRegistryReader>>,
+    // The precedence of the source the events are from; 0 is the local source
+    source_index: usize,
+    // Shared by the subscribers added to each source; `None` once it has unsubscribed
+    subscriber: Arc<Mutex<Option<Box<dyn NodeRegistrySubscriber>>>>,
+}
+
+impl UnifiedSubscriber {
+    /// Fetches a node from the source with the given precedence, returning `None` if the source
+    /// does not have it. Returns `Unsubscribe` if the unified registry has been dropped.
+    fn fetch_node(
+        &self,
+        source_index: usize,
+        identity: &str,
+    ) -> Result<Option<Node>, NodeRegistrySubscriberError> {
+        let res = if source_index == 0 {
+            self.local_source
+                .upgrade()
+                .ok_or(NodeRegistrySubscriberError::Unsubscribe)?
+                .fetch_node(identity)
+        } else {
+            self.readable_sources[source_index - 1]
+                .upgrade()
+                .ok_or(NodeRegistrySubscriberError::Unsubscribe)?
+                .fetch_node(identity)
+        };
+
+        match res {
+            Ok(node) => Ok(Some(node)),
+            Err(NodeRegistryError::NotFoundError(_)) => Ok(None),
+            Err(err) => Err(NodeRegistrySubscriberError::UnableToHandleEvent(format!(
+                "unable to read unified node registry source: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Returns the event of the unified registry that corresponds to an event of the source, if
+    /// the change is visible in the unified registry.
+    fn translate(
+        &self,
+        event: &NodeRegistryEvent,
+    ) -> Result<Option<NodeRegistryEvent>, NodeRegistrySubscriberError> {
+        let identity = match event {
+            NodeRegistryEvent::NodeInserted(node) | NodeRegistryEvent::NodeUpdated(node) => {
+                &node.identity
+            }
+            NodeRegistryEvent::NodeDeleted(identity) => identity,
+        };
+
+        for source_index in 0..self.source_index {
+            if self.fetch_node(source_index, identity)?.is_some() {
+                return Ok(None);
+            }
+        }
+
+        let mut replaced = None;
+        for source_index in self.source_index + 1..=self.readable_sources.len() {
+            replaced = self.fetch_node(source_index, identity)?;
+            if replaced.is_some() {
+                break;
+            }
+        }
+
+        Ok(Some(match (event, replaced) {
+            (NodeRegistryEvent::NodeInserted(node), Some(_)) => {
+                NodeRegistryEvent::NodeUpdated(node.clone())
+            }
+            (NodeRegistryEvent::NodeDeleted(_), Some(replaced)) => {
+                NodeRegistryEvent::NodeUpdated(replaced)
+            }
+            (event, _) => event.clone(),
+        }))
+    }
+}
+
+impl NodeRegistrySubscriber for UnifiedSubscriber {
+    fn handle_event(&self, event: &NodeRegistryEvent) -> Result<(), NodeRegistrySubscriberError> {
+        let event = match self.translate(event)? {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+
+        let mut subscriber = mutex_lock_unwrap!(self.subscriber);
+        let res = match subscriber.as_ref() {
+            Some(subscriber) => subscriber.handle_event(&event),
+            None => Err(NodeRegistrySubscriberError::Unsubscribe),
+        };
+        if let Err(NodeRegistrySubscriberError::Unsubscribe) = res {
+            // The subscribers added to the other sources unsubscribe on their next event
+            *subscriber = None;
+        }
+        res
+    }
 }
 
 impl NodeRegistryWriter for UnifiedNodeRegistry {
@@ -138,6 +268,9 @@ mod test {
 
     use super::*;
 
+    use crate::node_registry::events::tests::EventCollector;
+    use crate::node_registry::NodeRegistrySubscribers;
+
     /// Simple macro for creating nodes
     macro_rules! node {
         ($identity:expr, $($key:expr => $val:expr),*) => {
@@ -278,9 +411,61 @@ mod test {
         assert_eq!(None, iterator.next());
     }
 
+    /// This test ensures that the subscribers of the unified registry are notified of the changes
+    /// to every source, as they appear in the unified registry:
+    ///
+    /// 1. A node inserted into a read-only source is inserted
+    /// 2. A node inserted into the local source that replaces a read-only node is updated
+    /// 3. A change to a read-only node that is replaced by a local node is not sent
+    /// 4. Deleting a local node that replaced a read-only node updates it to the read-only node
+    /// 5. A node deleted from a read-only source is deleted
+    #[test]
+    fn test_unified_subscriber() {
+        let readable = MemRegistry::default();
+        readable
+            .insert_node(node!("node1", "meta_a" => "a value"))
+            .expect("Unable to insert node");
+
+        let writable = MemRegistry::default();
+
+        let unified =
+            UnifiedNodeRegistry::new(Box::new(writable.clone()), vec![Box::new(readable.clone())]);
+        let collector = EventCollector::default();
+        unified
+            .add_subscriber(Box::new(collector.clone()))
+            .expect("Unable to add subscriber");
+
+        readable
+            .insert_node(node!("node2", "meta_c" => "c value"))
+            .expect("Unable to insert node");
+        writable
+            .insert_node(node!("node1", "meta_b" => "b value"))
+            .expect("Unable to insert node");
+        readable
+            .insert_node(node!("node1", "meta_a" => "new a value"))
+            .expect("Unable to insert node");
+        writable
+            .delete_node("node1")
+            .expect("Unable to delete node");
+        readable
+            .delete_node("node2")
+            .expect("Unable to delete node");
+
+        assert_eq!(
+            vec![
+                NodeRegistryEvent::NodeInserted(node!("node2", "meta_c" => "c value")),
+                NodeRegistryEvent::NodeUpdated(node!("node1", "meta_b" => "b value")),
+                NodeRegistryEvent::NodeUpdated(node!("node1", "meta_a" => "new a value")),
+                NodeRegistryEvent::NodeDeleted("node2".into()),
+            ],
+            collector.events()
+        );
+    }
+
     #[derive(Clone, Default)]
     struct MemRegistry {
         nodes: Arc<Mutex<BTreeMap<String, Node>>>,
+        subscribers: NodeRegistrySubscribers,
     }
 
     impl NodeRegistryReader for MemRegistry {
@@ -313,14 +498,27 @@ mod test {
                 .cloned()
                 .ok_or_else(|| NodeRegistryError::NotFoundError(identity.to_string()))
         }
+
+        fn add_subscriber(
+            &self,
+            subscriber: Box<dyn NodeRegistrySubscriber>,
+        ) -> Result<(), NodeRegistryError> {
+            self.subscribers.add(subscriber);
+            Ok(())
+        }
     }
 
     impl NodeRegistryWriter for MemRegistry {
         fn insert_node(&self, node: Node) -> Result<(), NodeRegistryError> {
-            self.nodes
+            let previous = self
+                .nodes
                 .lock()
                 .expect("mem registry lock was poisoned")
-                .insert(node.identity.clone(), node);
+                .insert(node.identity.clone(), node.clone());
+            self.subscribers.notify(&match previous {
+                Some(_) => NodeRegistryEvent::NodeUpdated(node),
+                None => NodeRegistryEvent::NodeInserted(node),
+            });
             Ok(())
         }
 
@@ -329,6 +527,8 @@ mod test {
                 .lock()
                 .expect("mem registry lock was poisoned")
                 .remove(identity);
+            self.subscribers
+                .notify(&NodeRegistryEvent::NodeDeleted(identity.to_string()));
             Ok(())
         }
     }
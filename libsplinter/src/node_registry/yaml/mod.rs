@@ -15,65 +15,185 @@
 mod error;
 
 use std::fs::File;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::file_version::file_version;
 
 use super::{
-    InvalidNodeError, MetadataPredicate, Node, NodeRegistryError, NodeRegistryReader,
-    NodeRegistryWriter, RwNodeRegistry,
+    check_if_node_is_duplicate, check_node_required_fields_are_not_empty, diff_nodes,
+    MetadataPredicate, Node, NodeRegistryError, NodeRegistryEvent, NodeRegistryReader,
+    NodeRegistrySubscriber, NodeRegistrySubscribers, NodeRegistryWriter, RwNodeRegistry,
 };
 
 use error::YamlNodeRegistryError;
 
+/// How often the YAML file is checked for changes once the registry has subscribers.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A node registry backed by a YAML file.
+///
+/// The file is reloaded when it is modified by another process, which is detected by changes to
+/// its modified time or size. The file is checked before every read and, once the registry has
+/// subscribers, periodically by a watcher thread, so that subscribers are notified of changes to
+/// the file as well as of the changes made through the registry. A modified file that is not valid
+/// is ignored, and the previous nodes are kept.
 #[derive(Clone)]
 pub struct YamlNodeRegistry {
     file_internal: Arc<Mutex<FileInternal>>,
+    subscribers: NodeRegistrySubscribers,
 }
 
 pub struct FileInternal {
     pub file_path: String,
     pub cached_nodes: Vec<Node>,
+    version: Option<(SystemTime, u64)>,
+    watching: bool,
 }
 
 impl YamlNodeRegistry {
     pub fn new(file_path: &str) -> Result<YamlNodeRegistry, YamlNodeRegistryError> {
-        let file = File::open(file_path)?;
-        let cached_nodes: Vec<Node> = serde_yaml::from_reader(&file)?;
-
-        for (idx, node) in cached_nodes.iter().enumerate() {
-            check_node_required_fields_are_not_empty(node)?;
-            check_if_node_is_duplicate(node, &cached_nodes[idx + 1..])?;
-        }
+        let version = file_version(file_path);
+        let cached_nodes = load_nodes(file_path)?;
 
         let file_internal = FileInternal {
             file_path: file_path.into(),
             cached_nodes,
+            version,
+            watching: false,
         };
 
         Ok(YamlNodeRegistry {
             file_internal: Arc::new(Mutex::new(file_internal)),
+            subscribers: NodeRegistrySubscribers::default(),
         })
     }
 
     fn get_cached_nodes(&self) -> Result<Vec<Node>, YamlNodeRegistryError> {
-        let file_backend = self
-            .file_internal
-            .lock()
-            .map_err(|err| YamlNodeRegistryError::PoisonLockError(format!("{}", err)))?;
-        Ok(file_backend.cached_nodes.clone())
+        let (cached_nodes, events) = {
+            let mut file_backend = self
+                .file_internal
+                .lock()
+                .map_err(|err| YamlNodeRegistryError::PoisonLockError(format!("{}", err)))?;
+            let events = file_backend.reload_if_modified();
+            (file_backend.cached_nodes.clone(), events)
+        };
+
+        notify_all(&self.subscribers, &events);
+
+        Ok(cached_nodes)
     }
 
     fn write_nodes(&self, data: &[Node]) -> Result<(), YamlNodeRegistryError> {
+        let events = {
+            let mut file_backend = self
+                .file_internal
+                .lock()
+                .map_err(|err| YamlNodeRegistryError::PoisonLockError(format!("{}", err)))?;
+            let output = serde_yaml::to_vec(&data)?;
+            std::fs::write(&file_backend.file_path, &output)?;
+            let events = diff_nodes(&file_backend.cached_nodes, data);
+            file_backend.cached_nodes = data.to_vec();
+            file_backend.version = file_version(&file_backend.file_path);
+            events
+        };
+
+        notify_all(&self.subscribers, &events);
+
+        Ok(())
+    }
+
+    /// Starts the thread that checks the file for changes, unless it has already been started.
+    ///
+    /// The thread stops once the registry and all of its clones have been dropped.
+    fn start_watching(&self) -> Result<(), YamlNodeRegistryError> {
         let mut file_backend = self
             .file_internal
             .lock()
             .map_err(|err| YamlNodeRegistryError::PoisonLockError(format!("{}", err)))?;
-        let output = serde_yaml::to_vec(&data)?;
-        std::fs::write(&file_backend.file_path, &output)?;
-        file_backend.cached_nodes = data.to_vec();
+        if file_backend.watching {
+            return Ok(());
+        }
+
+        let file_internal = Arc::downgrade(&self.file_internal);
+        let subscribers = self.subscribers.clone();
+        thread::Builder::new()
+            .name("YamlNodeRegistryWatcher".into())
+            .spawn(move || watch_file(file_internal, subscribers))?;
+        file_backend.watching = true;
+
         Ok(())
     }
 }
 
+impl FileInternal {
+    /// Reloads the nodes if the file has been modified since it was last read or written,
+    /// returning the resulting changes.
+    fn reload_if_modified(&mut self) -> Vec<NodeRegistryEvent> {
+        let version = file_version(&self.file_path);
+        if version == self.version {
+            return vec![];
+        }
+
+        // Only remember the new version once the file has been loaded, so that a file that is
+        // being written is loaded again on the next check
+        match load_nodes(&self.file_path) {
+            Ok(nodes) => {
+                debug!("Reloaded node registry from {}", self.file_path);
+                let events = diff_nodes(&self.cached_nodes, &nodes);
+                self.cached_nodes = nodes;
+                self.version = version;
+                events
+            }
+            Err(err) => {
+                error!(
+                    "Unable to reload node registry from {}; keeping the previous nodes: {}",
+                    self.file_path, err
+                );
+                vec![]
+            }
+        }
+    }
+}
+
+fn watch_file(file_internal: Weak<Mutex<FileInternal>>, subscribers: NodeRegistrySubscribers) {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        let file_internal = match file_internal.upgrade() {
+            Some(file_internal) => file_internal,
+            None => break,
+        };
+
+        let events = match file_internal.lock() {
+            Ok(mut file_backend) => file_backend.reload_if_modified(),
+            Err(err) => {
+                error!("Node registry file watcher stopped: {}", err);
+                break;
+            }
+        };
+
+        notify_all(&subscribers, &events);
+    }
+}
+
+fn notify_all(subscribers: &NodeRegistrySubscribers, events: &[NodeRegistryEvent]) {
+    events.iter().for_each(|event| subscribers.notify(event))
+}
+
+fn load_nodes(file_path: &str) -> Result<Vec<Node>, YamlNodeRegistryError> {
+    let file = File::open(file_path)?;
+    let nodes: Vec<Node> = serde_yaml::from_reader(&file)?;
+
+    for (idx, node) in nodes.iter().enumerate() {
+        check_node_required_fields_are_not_empty(node)?;
+        check_if_node_is_duplicate(node, &nodes[idx + 1..])?;
+    }
+
+    Ok(nodes)
+}
+
 impl NodeRegistryReader for YamlNodeRegistry {
     fn fetch_node(&self, identity: &str) -> Result<Node, NodeRegistryError> {
         match self
@@ -113,6 +233,17 @@ impl NodeRegistryReader for YamlNodeRegistry {
             .filter(move |node| predicates.iter().all(|predicate| predicate.apply(node)))
             .count() as u32)
     }
+
+    /// Adds a subscriber, which is notified of the changes made through the registry and of the
+    /// changes made to the file by other processes, so a read-only registry can be watched too.
+    fn add_subscriber(
+        &self,
+        subscriber: Box<dyn NodeRegistrySubscriber>,
+    ) -> Result<(), NodeRegistryError> {
+        self.subscribers.add(subscriber);
+        self.start_watching()
+            .map_err(|err| NodeRegistryError::InternalError(Box::new(err)))
+    }
 }
 
 impl NodeRegistryWriter for YamlNodeRegistry {
@@ -166,42 +297,19 @@ impl RwNodeRegistry for YamlNodeRegistry {
     }
 }
 
-fn check_node_required_fields_are_not_empty(node: &Node) -> Result<(), InvalidNodeError> {
-    if node.identity.is_empty() {
-        Err(InvalidNodeError::EmptyIdentity)
-    } else if node.endpoint.is_empty() {
-        Err(InvalidNodeError::EmptyEndpoint)
-    } else if node.display_name.is_empty() {
-        Err(InvalidNodeError::EmptyDisplayName)
-    } else {
-        Ok(())
-    }
-}
-
-fn check_if_node_is_duplicate(
-    node: &Node,
-    existing_nodes: &[Node],
-) -> Result<(), InvalidNodeError> {
-    existing_nodes.iter().try_for_each(|existing_node| {
-        if existing_node.identity == node.identity {
-            Err(InvalidNodeError::DuplicateIdentity(node.identity.clone()))
-        } else if existing_node.endpoint == node.endpoint {
-            Err(InvalidNodeError::DuplicateEndpoint(node.endpoint.clone()))
-        } else {
-            Ok(())
-        }
-    })
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use crate::node_registry::events::tests::EventCollector;
+    use crate::node_registry::InvalidNodeError;
+
     use std::collections::HashMap;
     use std::env;
     use std::fs::{remove_file, File};
     use std::panic;
     use std::thread;
+    use std::time::Instant;
 
     ///
     /// Verifies that reading from a YAML file that contains two nodes with the same identity
@@ -712,6 +820,88 @@ mod test {
         })
     }
 
+    ///
+    /// Verifies that subscribers are notified of the nodes inserted, updated and deleted through
+    /// the registry.
+    ///
+    #[test]
+    fn test_subscriber_events() {
+        run_test(|test_yaml_file_path| {
+            write_to_file(&vec![], test_yaml_file_path);
+
+            let registry = YamlNodeRegistry::new(test_yaml_file_path)
+                .expect("Failed to create YamlNodeRegistry");
+            let collector = EventCollector::default();
+            registry
+                .add_subscriber(Box::new(collector.clone()))
+                .expect("Failed to add subscriber");
+
+            let mut node = get_node_1();
+            registry
+                .insert_node(node.clone())
+                .expect("Failed to insert node");
+            node.display_name = "Updated".to_string();
+            registry
+                .insert_node(node.clone())
+                .expect("Failed to update node");
+            registry
+                .delete_node(&node.identity)
+                .expect("Failed to delete node");
+
+            assert_eq!(
+                collector.events(),
+                vec![
+                    NodeRegistryEvent::NodeInserted(get_node_1()),
+                    NodeRegistryEvent::NodeUpdated(node.clone()),
+                    NodeRegistryEvent::NodeDeleted(node.identity),
+                ]
+            );
+        })
+    }
+
+    ///
+    /// Verifies that changes made to the YAML file by another process are picked up by the
+    /// registry and sent to its subscribers, and that an invalid file is ignored.
+    ///
+    #[test]
+    fn test_file_watch() {
+        run_test(|test_yaml_file_path| {
+            write_to_file(&vec![get_node_1()], test_yaml_file_path);
+
+            let registry = YamlNodeRegistry::new(test_yaml_file_path)
+                .expect("Failed to create YamlNodeRegistry");
+            let collector = EventCollector::default();
+            registry
+                .add_subscriber(Box::new(collector.clone()))
+                .expect("Failed to add subscriber");
+
+            write_to_file(&vec![get_node_2()], test_yaml_file_path);
+
+            // The watcher thread reports the change without the registry being read
+            let expected_events = vec![
+                NodeRegistryEvent::NodeInserted(get_node_2()),
+                NodeRegistryEvent::NodeDeleted(get_node_1().identity),
+            ];
+            let start = Instant::now();
+            while collector.events().len() < expected_events.len()
+                && start.elapsed() < Duration::from_secs(10)
+            {
+                thread::sleep(Duration::from_millis(100));
+            }
+            assert_eq!(collector.events(), expected_events);
+
+            std::fs::write(test_yaml_file_path, b"not: [a, list, of, nodes]")
+                .expect("Failed to write file");
+
+            let nodes = registry
+                .list_nodes(&[])
+                .expect("Failed to retrieve nodes")
+                .collect::<Vec<_>>();
+            assert_eq!(nodes, vec![get_node_2()]);
+            assert_eq!(collector.events(), expected_events);
+        })
+    }
+
     fn get_node_1() -> Node {
         let mut metadata = HashMap::new();
         metadata.insert("company".to_string(), "Bitwise IO".to_string());
@@ -721,6 +911,7 @@ mod test {
             endpoint: "tls://12.0.0.123:8431".to_string(),
             display_name: "Bitwise IO - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -733,6 +924,7 @@ mod test {
             endpoint: "tls://12.0.0.123:8434".to_string(),
             display_name: "Cargill - Node 1".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -745,6 +937,7 @@ mod test {
             endpoint: "tls://12.0.0.123:8435".to_string(),
             display_name: "Cargill - Node 2".to_string(),
             metadata,
+            signature: None,
         }
     }
 
@@ -28,6 +28,7 @@ use protobuf::Message;
 use crate::channel;
 use crate::mesh::{Envelope, Mesh, RecvTimeoutError as MeshRecvTimeoutError};
 use crate::network::reply::InboundRouter;
+use crate::network::trace::parse_trace_id;
 use crate::protos::authorization::{
     AuthorizationMessage, AuthorizationMessageType, ConnectRequest, ConnectRequest_HandshakeMode,
 };
@@ -468,6 +469,9 @@ pub fn run_incoming_loop(
                 }
             }
             NetworkMessageType::NETWORK_HEARTBEAT => trace!("Received network heartbeat"),
+            NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT => {
+                trace!("Received network route advertisement")
+            }
             _ => warn!("Received unimplemented message"),
         }
     }
@@ -515,6 +519,8 @@ fn run_inbound_loop(
                             sender: admin_direct_message.take_sender(),
                             circuit: admin_direct_message.take_circuit(),
                             correlation_id: admin_direct_message.take_correlation_id(),
+                            trace_id: parse_trace_id(admin_direct_message.get_trace_id())
+                                .map(String::from),
                         };
 
                         service
@@ -551,6 +557,8 @@ fn run_inbound_loop(
                             sender: circuit_direct_message.take_sender(),
                             circuit: circuit_direct_message.take_circuit(),
                             correlation_id: circuit_direct_message.take_correlation_id(),
+                            trace_id: parse_trace_id(circuit_direct_message.get_trace_id())
+                                .map(String::from),
                         };
 
                         service
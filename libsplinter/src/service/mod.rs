@@ -63,6 +63,9 @@ pub struct ServiceMessageContext {
     pub sender: String,
     pub circuit: String,
     pub correlation_id: String,
+    /// The trace id of the message, if it was sent with one. Replies to the message are sent with
+    /// the same trace id.
+    pub trace_id: Option<String>,
 }
 
 /// The ServiceNetworkRegistry trait provides functions to register and unregister the service on
@@ -82,6 +85,19 @@ pub trait ServiceNetworkSender: Send {
     /// Send the message bytes to the given recipient (another service)
     fn send(&self, recipient: &str, message: &[u8]) -> Result<(), ServiceSendError>;
 
+    /// Send the message bytes to the given recipient (another service) with the trace id of the
+    /// operation the message belongs to, so that the operation can be followed across nodes.
+    ///
+    /// By default, the message is sent without the trace id.
+    fn send_with_trace_id(
+        &self,
+        recipient: &str,
+        message: &[u8],
+        _trace_id: &str,
+    ) -> Result<(), ServiceSendError> {
+        self.send(recipient, message)
+    }
+
     /// Send the message bytes to the given recipient (another service) and await the reply.  This
     /// function blocks until the reply is returned.
     fn send_and_await(&self, recipient: &str, message: &[u8]) -> Result<Vec<u8>, ServiceSendError>;
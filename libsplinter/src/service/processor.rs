@@ -15,27 +15,38 @@
 use crossbeam_channel::{Receiver, Sender};
 use protobuf::Message;
 
+#[cfg(feature = "metrics")]
+use std::any::Any;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
 use crate::channel;
 use crate::mesh::{Envelope, Mesh, RecvTimeoutError as MeshRecvTimeoutError};
+#[cfg(feature = "metrics")]
+use crate::metrics::{Counter, Histogram, MetricsRegistry};
 use crate::network::reply::InboundRouter;
+use crate::network::trace::{parse_trace_id, TraceField};
 use crate::protos::authorization::{
     AuthorizationMessage, AuthorizationMessageType, ConnectRequest, ConnectRequest_HandshakeMode,
 };
 use crate::protos::circuit::{
-    AdminDirectMessage, CircuitDirectMessage, CircuitError, CircuitMessage, CircuitMessageType,
-    ServiceConnectResponse, ServiceDisconnectResponse,
+    AdminDirectMessage, CircuitDirectMessage, CircuitDirectMessageAck, CircuitError,
+    CircuitMessage, CircuitMessageType, ServiceConnectResponse, ServiceDisconnectResponse,
 };
 use crate::protos::network::{NetworkMessage, NetworkMessageType};
 use crate::service::error::ServiceProcessorError;
 use crate::service::registry::StandardServiceNetworkRegistry;
-use crate::service::sender::{ProcessorMessage, ServiceMessage};
+use crate::service::sender::{create_message, ProcessorMessage, ServiceMessage};
 use crate::service::{Service, ServiceMessageContext};
+#[cfg(feature = "metrics")]
+use crate::service::{
+    ServiceDestroyError, ServiceError, ServiceNetworkRegistry, ServiceStartError, ServiceStopError,
+};
 use crate::transport::Connection;
 use crate::{rwlock_read_unwrap, rwlock_write_unwrap};
 
@@ -82,6 +93,8 @@ pub struct ServiceProcessor {
     inbound_router: InboundRouter<CircuitMessageType>,
     inbound_receiver: Receiver<Result<(CircuitMessageType, Vec<u8>), channel::RecvError>>,
     channel_capacity: usize,
+    #[cfg(feature = "metrics")]
+    metrics: Option<MetricsRegistry>,
 }
 
 impl ServiceProcessor {
@@ -114,9 +127,19 @@ impl ServiceProcessor {
             inbound_router: InboundRouter::new(Box::new(inbound_sender)),
             inbound_receiver,
             channel_capacity,
+            #[cfg(feature = "metrics")]
+            metrics: None,
         })
     }
 
+    /// Record the number of messages handled by each service, the time taken to handle them and
+    /// the errors returned, in the given registry.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: MetricsRegistry) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// add_service takes a Service and sets up the thread that the service will run in.
     /// The service will be started, including registration and then messages are routed to the
     /// the services using a channel.
@@ -173,6 +196,11 @@ impl ServiceProcessor {
             let network_sender = self.network_sender.clone();
             let circuit = self.circuit.clone();
             let inbound_router = self.inbound_router.clone();
+            #[cfg(feature = "metrics")]
+            let service = match self.metrics.as_ref() {
+                Some(registry) => MeteredService::new(service, registry, &circuit),
+                None => service,
+            };
             let join_handle = thread::Builder::new()
                 .name(format!("Service {}", service_id))
                 .spawn(move || {
@@ -401,6 +429,10 @@ fn process_incoming_msg(
             }
         }
         NetworkMessageType::NETWORK_HEARTBEAT => trace!("Received network heartbeat"),
+        // Services do not route messages, so their node's route advertisements are ignored
+        NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT => {
+            trace!("Received network route advertisement")
+        }
         _ => warn!("Received unimplemented message"),
     }
 
@@ -475,6 +507,96 @@ impl ShutdownHandle {
     }
 }
 
+/// A service that records the number of messages it handles, the time taken to handle them and
+/// the errors returned.
+#[cfg(feature = "metrics")]
+struct MeteredService {
+    service: Box<dyn Service>,
+    messages: Counter,
+    errors: Counter,
+    duration: Histogram,
+}
+
+#[cfg(feature = "metrics")]
+impl MeteredService {
+    fn new(
+        service: Box<dyn Service>,
+        registry: &MetricsRegistry,
+        circuit: &str,
+    ) -> Box<dyn Service> {
+        let service_id = service.service_id().to_string();
+        let labels = [("circuit", circuit), ("service_id", service_id.as_str())];
+        Box::new(MeteredService {
+            messages: registry.counter(
+                "splinter_service_messages_total",
+                "Messages handled by a service",
+                &labels,
+            ),
+            errors: registry.counter(
+                "splinter_service_errors_total",
+                "Messages a service returned an error for",
+                &labels,
+            ),
+            duration: registry.histogram(
+                "splinter_service_handler_duration_seconds",
+                "Time taken by a service to handle messages",
+                &labels,
+            ),
+            service,
+        })
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Service for MeteredService {
+    fn service_id(&self) -> &str {
+        self.service.service_id()
+    }
+
+    fn service_type(&self) -> &str {
+        self.service.service_type()
+    }
+
+    fn start(
+        &mut self,
+        service_registry: &dyn ServiceNetworkRegistry,
+    ) -> Result<(), ServiceStartError> {
+        self.service.start(service_registry)
+    }
+
+    fn stop(
+        &mut self,
+        service_registry: &dyn ServiceNetworkRegistry,
+    ) -> Result<(), ServiceStopError> {
+        self.service.stop(service_registry)
+    }
+
+    fn destroy(self: Box<Self>) -> Result<(), ServiceDestroyError> {
+        self.service.destroy()
+    }
+
+    fn handle_message(
+        &self,
+        message_bytes: &[u8],
+        message_context: &ServiceMessageContext,
+    ) -> Result<(), ServiceError> {
+        let start = Instant::now();
+        let result = self.service.handle_message(message_bytes, message_context);
+
+        self.messages.inc();
+        self.duration.observe_duration(start.elapsed());
+        if result.is_err() {
+            self.errors.inc();
+        }
+
+        result
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self.service.as_any()
+    }
+}
+
 fn run_service_loop(
     circuit: String,
     mut service: Box<dyn Service>,
@@ -483,7 +605,8 @@ fn run_service_loop(
     inbound_router: InboundRouter<CircuitMessageType>,
 ) -> Result<(), ServiceProcessorError> {
     info!("Starting Service: {}", service.service_id());
-    let registry = StandardServiceNetworkRegistry::new(circuit, network_sender, inbound_router);
+    let registry =
+        StandardServiceNetworkRegistry::new(circuit, network_sender.clone(), inbound_router);
     service.start(&registry).map_err(to_process_err!(
         "unable to start service {}",
         service.service_id()
@@ -511,28 +634,74 @@ fn run_service_loop(
                     sender: admin_direct_message.take_sender(),
                     circuit: admin_direct_message.take_circuit(),
                     correlation_id: admin_direct_message.take_correlation_id(),
+                    trace_id: parse_trace_id(admin_direct_message.get_trace_id()).map(String::from),
                 };
+                debug!(
+                    "Handling admin direct message from {}{}",
+                    msg_context.sender,
+                    TraceField(msg_context.trace_id.as_deref())
+                );
 
                 service
                     .handle_message(admin_direct_message.get_payload(), &msg_context)
                     .map_err(to_process_err!("unable to handle admin direct message"))?;
             }
             ServiceMessage::CircuitDirectMessage(mut direct_message) => {
+                let message_id = direct_message.take_message_id();
                 let msg_context = ServiceMessageContext {
                     sender: direct_message.take_sender(),
                     circuit: direct_message.take_circuit(),
                     correlation_id: direct_message.take_correlation_id(),
+                    trace_id: parse_trace_id(direct_message.get_trace_id()).map(String::from),
                 };
+                debug!(
+                    "Handling circuit direct message from {}{}",
+                    msg_context.sender,
+                    TraceField(msg_context.trace_id.as_deref())
+                );
 
                 service
                     .handle_message(direct_message.get_payload(), &msg_context)
                     .map_err(to_process_err!("unable to handle circuit direct message"))?;
+
+                // Messages on circuits with store-and-forward durability are resent until the
+                // service acknowledges them
+                if !message_id.is_empty() {
+                    send_direct_message_ack(
+                        &network_sender,
+                        service.service_id(),
+                        msg_context,
+                        message_id,
+                    )?;
+                }
             }
         }
     }
     Ok(())
 }
 
+fn send_direct_message_ack(
+    network_sender: &Sender<Vec<u8>>,
+    service_id: &str,
+    msg_context: ServiceMessageContext,
+    message_id: String,
+) -> Result<(), ServiceProcessorError> {
+    let mut ack = CircuitDirectMessageAck::new();
+    ack.set_circuit(msg_context.circuit);
+    ack.set_sender(service_id.to_string());
+    ack.set_recipient(msg_context.sender);
+    ack.set_message_id(message_id);
+
+    let ack_bytes = ack
+        .write_to_bytes()
+        .map_err(to_process_err!("unable to serialize direct message ack"))?;
+    let msg = create_message(ack_bytes, CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK)
+        .map_err(to_process_err!("unable to create direct message ack"))?;
+    network_sender
+        .send(msg)
+        .map_err(to_process_err!("unable to send direct message ack"))
+}
+
 fn handle_circuit_direct_msg(
     direct_message: CircuitDirectMessage,
     shared_state: &Arc<RwLock<SharedState>>,
@@ -613,7 +782,6 @@ pub mod tests {
     use crate::service::error::{
         ServiceDestroyError, ServiceError, ServiceStartError, ServiceStopError,
     };
-    use crate::service::sender::create_message;
     use crate::service::{ServiceNetworkRegistry, ServiceNetworkSender};
     use crate::transport::inproc::InprocTransport;
     use crate::transport::Transport;
@@ -26,7 +26,10 @@ use crate::consensus::{
     ConsensusEngine, ConsensusMessage, ConsensusNetworkSender, PeerId, Proposal, ProposalId,
     ProposalManager, ProposalUpdate, StartupState,
 };
+use crate::network::trace::TraceField;
 use crate::protos::scabbard::{ProposedBatch, ScabbardMessage, ScabbardMessage_Type};
+use crate::protos::two_phase::TwoPhaseMessage;
+use crate::service::{ServiceNetworkSender, ServiceSendError};
 
 use super::error::{ScabbardConsensusManagerError, ScabbardError};
 use super::shared::ScabbardShared;
@@ -178,6 +181,15 @@ impl ProposalManager for ScabbardProposalManager {
             proposal.id = expected_hash.as_bytes().into();
             proposal.summary = expected_hash.as_bytes().into();
 
+            // The batch's header signature is used as the proposal's trace id, since it is known
+            // to the submitter of the batch and to every node that receives the proposal
+            let trace_id = batch.batch().header_signature().to_string();
+            debug!(
+                "Proposing batch as proposal {}{}",
+                proposal.id,
+                TraceField(Some(&trace_id))
+            );
+
             shared.add_proposed_batch(proposal.id.clone(), batch.clone());
 
             // Send the proposal to the other services
@@ -208,7 +220,7 @@ impl ProposalManager for ScabbardProposalManager {
 
             for service in shared.peer_services() {
                 sender
-                    .send(service, msg_bytes.as_slice())
+                    .send_with_trace_id(service, msg_bytes.as_slice(), &trace_id)
                     .map_err(|err| ProposalManagerError::Internal(Box::new(err)))?;
             }
 
@@ -263,7 +275,7 @@ impl ProposalManager for ScabbardProposalManager {
             .lock()
             .map_err(|_| ProposalManagerError::Internal(Box::new(ScabbardError::LockPoisoned)))?;
 
-        shared
+        let batch = shared
             .remove_proposed_batch(id)
             .ok_or_else(|| ProposalManagerError::UnknownProposal(id.clone()))?;
 
@@ -276,7 +288,11 @@ impl ProposalManager for ScabbardProposalManager {
         self.proposal_update_sender
             .send(ProposalUpdate::ProposalAccepted(id.clone()))?;
 
-        info!("Committed proposal {}", id);
+        info!(
+            "Committed proposal {}{}",
+            id,
+            TraceField(Some(batch.batch().header_signature()))
+        );
 
         Ok(())
     }
@@ -287,7 +303,7 @@ impl ProposalManager for ScabbardProposalManager {
             .lock()
             .map_err(|_| ProposalManagerError::Internal(Box::new(ScabbardError::LockPoisoned)))?;
 
-        shared
+        let batch = shared
             .remove_proposed_batch(id)
             .ok_or_else(|| ProposalManagerError::UnknownProposal(id.clone()))?;
 
@@ -297,7 +313,11 @@ impl ProposalManager for ScabbardProposalManager {
             .rollback()
             .map_err(|err| ProposalManagerError::Internal(Box::new(err)))?;
 
-        info!("Rolled back proposal {}", id);
+        info!(
+            "Rolled back proposal {}{}",
+            id,
+            TraceField(Some(batch.batch().header_signature()))
+        );
 
         Ok(())
     }
@@ -314,16 +334,32 @@ impl ScabbardConsensusNetworkSender {
     }
 }
 
+/// Get the trace id of the proposal a two-phase consensus message is about, which is the header
+/// signature of the proposed batch.
+fn proposal_trace_id(shared: &ScabbardShared, message: &[u8]) -> Option<String> {
+    let two_phase_msg: TwoPhaseMessage = protobuf::parse_from_bytes(message).ok()?;
+    shared
+        .get_proposed_batch(&two_phase_msg.get_proposal_id().into())
+        .map(|batch| batch.batch().header_signature().to_string())
+}
+
+fn send_with_optional_trace_id(
+    network_sender: &dyn ServiceNetworkSender,
+    recipient: &str,
+    message: &[u8],
+    trace_id: Option<&str>,
+) -> Result<(), ServiceSendError> {
+    match trace_id {
+        Some(trace_id) => network_sender.send_with_trace_id(recipient, message, trace_id),
+        None => network_sender.send(recipient, message),
+    }
+}
+
 impl ConsensusNetworkSender for ScabbardConsensusNetworkSender {
     fn send_to(&self, peer_id: &PeerId, message: Vec<u8>) -> Result<(), ConsensusSendError> {
         let peer_id_string = String::from_utf8(peer_id.clone().into())
             .map_err(|err| ConsensusSendError::Internal(Box::new(err)))?;
 
-        let consensus_message = ConsensusMessage::new(message, self.service_id.as_bytes().into());
-        let mut msg = ScabbardMessage::new();
-        msg.set_message_type(ScabbardMessage_Type::CONSENSUS_MESSAGE);
-        msg.set_consensus_message(consensus_message.try_into()?);
-
         let shared = self
             .shared
             .lock()
@@ -333,36 +369,53 @@ impl ConsensusNetworkSender for ScabbardConsensusNetworkSender {
             return Err(ConsensusSendError::UnknownPeer(peer_id.clone()));
         }
 
+        let trace_id = proposal_trace_id(&shared, &message);
+        let consensus_message = ConsensusMessage::new(message, self.service_id.as_bytes().into())
+            .with_trace_id(trace_id.clone());
+        let mut msg = ScabbardMessage::new();
+        msg.set_message_type(ScabbardMessage_Type::CONSENSUS_MESSAGE);
+        msg.set_consensus_message(consensus_message.try_into()?);
+
         let network_sender = shared
             .network_sender()
             .ok_or(ConsensusSendError::NotReady)?;
 
-        network_sender
-            .send(&peer_id_string, msg.write_to_bytes()?.as_slice())
-            .map_err(|err| ConsensusSendError::Internal(Box::new(err)))?;
+        send_with_optional_trace_id(
+            network_sender,
+            &peer_id_string,
+            msg.write_to_bytes()?.as_slice(),
+            trace_id.as_deref(),
+        )
+        .map_err(|err| ConsensusSendError::Internal(Box::new(err)))?;
 
         Ok(())
     }
 
     fn broadcast(&self, message: Vec<u8>) -> Result<(), ConsensusSendError> {
-        let consensus_message = ConsensusMessage::new(message, self.service_id.as_bytes().into());
-        let mut msg = ScabbardMessage::new();
-        msg.set_message_type(ScabbardMessage_Type::CONSENSUS_MESSAGE);
-        msg.set_consensus_message(consensus_message.try_into()?);
-
         let shared = self
             .shared
             .lock()
             .map_err(|_| ConsensusSendError::Internal(Box::new(ScabbardError::LockPoisoned)))?;
 
+        let trace_id = proposal_trace_id(&shared, &message);
+        let consensus_message = ConsensusMessage::new(message, self.service_id.as_bytes().into())
+            .with_trace_id(trace_id.clone());
+        let mut msg = ScabbardMessage::new();
+        msg.set_message_type(ScabbardMessage_Type::CONSENSUS_MESSAGE);
+        msg.set_consensus_message(consensus_message.try_into()?);
+
         let network_sender = shared
             .network_sender()
             .ok_or(ConsensusSendError::NotReady)?;
 
         for service in shared.peer_services() {
-            network_sender
-                .send(service, msg.write_to_bytes()?.as_slice())
-                .map_err(|err| ConsensusSendError::Internal(Box::new(err)))?;
+            send_with_optional_trace_id(
+                network_sender,
+                service,
+                msg.write_to_bytes()?.as_slice(),
+                trace_id.as_deref(),
+            )
+            .map_err(|err| ConsensusSendError::Internal(Box::new(err)))?;
         }
 
         Ok(())
@@ -421,6 +474,7 @@ mod tests {
                 .expect("failed to parse 1st consensus message");
         assert_eq!(consensus_message.message, vec![0]);
         assert_eq!(consensus_message.origin_id, "0".as_bytes().into());
+        assert_eq!(consensus_message.trace_id, None);
 
         // Test broadcast
         consensus_sender.broadcast(vec![1]).expect("failed to send");
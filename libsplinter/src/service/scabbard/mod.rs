@@ -38,6 +38,7 @@ use transact::protos::FromBytes;
 
 use crate::consensus::{Proposal, ProposalUpdate};
 use crate::hex::to_hex;
+use crate::network::trace::TraceField;
 use crate::protos::scabbard::{ScabbardMessage, ScabbardMessage_Type};
 use crate::signing::SignatureVerifier;
 
@@ -136,6 +137,10 @@ impl Scabbard {
                     .add_batch(&batch.batch().header_signature());
 
                 link.push_str(&format!("{},", batch.batch().header_signature()));
+                debug!(
+                    "Queued batch for proposal{}",
+                    TraceField(Some(batch.batch().header_signature()))
+                );
                 shared.add_batch_to_queue(batch);
             }
 
@@ -266,7 +271,7 @@ impl Service for Scabbard {
     fn handle_message(
         &self,
         message_bytes: &[u8],
-        _message_context: &ServiceMessageContext,
+        message_context: &ServiceMessageContext,
     ) -> Result<(), ServiceError> {
         let message: ScabbardMessage = protobuf::parse_from_bytes(message_bytes)?;
 
@@ -285,6 +290,12 @@ impl Service for Scabbard {
                 let proposal = Proposal::try_from(proposed_batch.get_proposal())?;
                 let batch = BatchPair::from_bytes(proposed_batch.get_batch())
                     .map_err(|err| ServiceError::UnableToHandleMessage(Box::new(err)))?;
+                debug!(
+                    "Received proposed batch for proposal {} from {}{}",
+                    proposal.id,
+                    message_context.sender,
+                    TraceField(message_context.trace_id.as_deref())
+                );
 
                 self.shared
                     .lock()
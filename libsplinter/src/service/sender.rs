@@ -59,28 +59,52 @@ impl AdminServiceNetworkSender {
     }
 }
 
-impl ServiceNetworkSender for AdminServiceNetworkSender {
-    /// the service will create the admin direct message themselves so it can set which circuit
-    /// this message should be sent over.
-    fn send(&self, recipient: &str, message: &[u8]) -> Result<(), ServiceSendError> {
+impl AdminServiceNetworkSender {
+    fn send_admin_direct_message(
+        &self,
+        recipient: &str,
+        message: &[u8],
+        trace_id: Option<&str>,
+    ) -> Result<(), ServiceSendError> {
         let mut admin_direct_message = AdminDirectMessage::new();
         admin_direct_message.set_circuit("admin".into());
         admin_direct_message.set_sender(self.message_sender.to_string());
         admin_direct_message.set_recipient(recipient.into());
         admin_direct_message.set_payload(message.to_vec());
+        if let Some(trace_id) = trace_id {
+            admin_direct_message.set_trace_id(trace_id.into());
+        }
 
         let bytes = admin_direct_message
             .write_to_bytes()
             .map_err(|err| ServiceSendError(Box::new(err)))?;
 
-        let msg = create_message(bytes, CircuitMessageType::ADMIN_DIRECT_MESSAGE)
-            .map_err(|err| ServiceSendError(Box::new(err)))?;
+        let msg =
+            create_message_with_trace_id(bytes, CircuitMessageType::ADMIN_DIRECT_MESSAGE, trace_id)
+                .map_err(|err| ServiceSendError(Box::new(err)))?;
 
         self.outgoing_sender
             .send(msg)
             .map_err(|err| ServiceSendError(Box::new(err)))?;
         Ok(())
     }
+}
+
+impl ServiceNetworkSender for AdminServiceNetworkSender {
+    /// the service will create the admin direct message themselves so it can set which circuit
+    /// this message should be sent over.
+    fn send(&self, recipient: &str, message: &[u8]) -> Result<(), ServiceSendError> {
+        self.send_admin_direct_message(recipient, message, None)
+    }
+
+    fn send_with_trace_id(
+        &self,
+        recipient: &str,
+        message: &[u8],
+        trace_id: &str,
+    ) -> Result<(), ServiceSendError> {
+        self.send_admin_direct_message(recipient, message, Some(trace_id))
+    }
 
     /// Send the message bytes to the given recipient (another admin service)
     /// and await the reply. This function blocks until the reply is
@@ -127,13 +151,18 @@ impl ServiceNetworkSender for AdminServiceNetworkSender {
         admin_direct_message.set_recipient(message_origin.sender.to_string());
         admin_direct_message.set_payload(message.to_vec());
         admin_direct_message.set_correlation_id(message_origin.correlation_id.to_string());
+        let trace_id = message_origin.trace_id.as_deref();
+        if let Some(trace_id) = trace_id {
+            admin_direct_message.set_trace_id(trace_id.into());
+        }
 
         let bytes = admin_direct_message
             .write_to_bytes()
             .map_err(|err| ServiceSendError(Box::new(err)))?;
 
-        let message = create_message(bytes, CircuitMessageType::ADMIN_DIRECT_MESSAGE)
-            .map_err(|err| ServiceSendError(Box::new(err)))?;
+        let message =
+            create_message_with_trace_id(bytes, CircuitMessageType::ADMIN_DIRECT_MESSAGE, trace_id)
+                .map_err(|err| ServiceSendError(Box::new(err)))?;
 
         self.outgoing_sender
             .send(message)
@@ -172,27 +201,55 @@ impl StandardServiceNetworkSender {
     }
 }
 
-impl ServiceNetworkSender for StandardServiceNetworkSender {
-    /// Send the message bytes to the given recipient (another service)
-    fn send(&self, recipient: &str, message: &[u8]) -> Result<(), ServiceSendError> {
+impl StandardServiceNetworkSender {
+    fn send_direct_message(
+        &self,
+        recipient: &str,
+        message: &[u8],
+        trace_id: Option<&str>,
+    ) -> Result<(), ServiceSendError> {
         let mut direct_message = CircuitDirectMessage::new();
         direct_message.set_circuit(self.circuit.to_string());
         direct_message.set_sender(self.message_sender.to_string());
         direct_message.set_recipient(recipient.to_string());
         direct_message.set_payload(message.to_vec());
+        if let Some(trace_id) = trace_id {
+            direct_message.set_trace_id(trace_id.to_string());
+        }
 
         let bytes = direct_message
             .write_to_bytes()
             .map_err(|err| ServiceSendError(Box::new(err)))?;
 
-        let message = create_message(bytes, CircuitMessageType::CIRCUIT_DIRECT_MESSAGE)
-            .map_err(|err| ServiceSendError(Box::new(err)))?;
+        let message = create_message_with_trace_id(
+            bytes,
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            trace_id,
+        )
+        .map_err(|err| ServiceSendError(Box::new(err)))?;
 
         self.outgoing_sender
             .send(message)
             .map_err(|err| ServiceSendError(Box::new(err)))?;
         Ok(())
     }
+}
+
+impl ServiceNetworkSender for StandardServiceNetworkSender {
+    /// Send the message bytes to the given recipient (another service)
+    fn send(&self, recipient: &str, message: &[u8]) -> Result<(), ServiceSendError> {
+        self.send_direct_message(recipient, message, None)
+    }
+
+    /// Send the message bytes to the given recipient (another service) with the given trace id
+    fn send_with_trace_id(
+        &self,
+        recipient: &str,
+        message: &[u8],
+        trace_id: &str,
+    ) -> Result<(), ServiceSendError> {
+        self.send_direct_message(recipient, message, Some(trace_id))
+    }
 
     /// Send the message bytes to the given recipient (another service)
     /// and await the reply.  This function blocks until the reply is
@@ -240,13 +297,21 @@ impl ServiceNetworkSender for StandardServiceNetworkSender {
         direct_message.set_recipient(message_origin.sender.to_string());
         direct_message.set_payload(message.to_vec());
         direct_message.set_correlation_id(message_origin.correlation_id.to_string());
+        let trace_id = message_origin.trace_id.as_deref();
+        if let Some(trace_id) = trace_id {
+            direct_message.set_trace_id(trace_id.to_string());
+        }
 
         let bytes = direct_message
             .write_to_bytes()
             .map_err(|err| ServiceSendError(Box::new(err)))?;
 
-        let message = create_message(bytes, CircuitMessageType::CIRCUIT_DIRECT_MESSAGE)
-            .map_err(|err| ServiceSendError(Box::new(err)))?;
+        let message = create_message_with_trace_id(
+            bytes,
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            trace_id,
+        )
+        .map_err(|err| ServiceSendError(Box::new(err)))?;
 
         self.outgoing_sender
             .send(message)
@@ -268,15 +333,37 @@ impl ServiceNetworkSender for StandardServiceNetworkSender {
 pub fn create_message(
     payload: Vec<u8>,
     circuit_message_type: CircuitMessageType,
+) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
+    create_message_with_trace_id(payload, circuit_message_type, None)
+}
+
+/// Helper function for creating a NetworkMessage with a Circuit message type and, if given, a
+/// trace id, which is set on both the Circuit message and the NetworkMessage
+///
+/// # Arguments
+///
+/// * `payload` - The payload in bytes that should be set in the Circuit message get_payload
+/// * `circuit_message_type` - The message type that should be set in the Circuit message
+/// * `trace_id` - The trace id of the operation the message belongs to, if any
+pub fn create_message_with_trace_id(
+    payload: Vec<u8>,
+    circuit_message_type: CircuitMessageType,
+    trace_id: Option<&str>,
 ) -> Result<Vec<u8>, protobuf::error::ProtobufError> {
     let mut circuit_msg = CircuitMessage::new();
     circuit_msg.set_message_type(circuit_message_type);
     circuit_msg.set_payload(payload);
+    if let Some(trace_id) = trace_id {
+        circuit_msg.set_trace_id(trace_id.to_string());
+    }
     let circuit_bytes = circuit_msg.write_to_bytes()?;
 
     let mut network_msg = NetworkMessage::new();
     network_msg.set_message_type(NetworkMessageType::CIRCUIT);
     network_msg.set_payload(circuit_bytes);
+    if let Some(trace_id) = trace_id {
+        network_msg.set_trace_id(trace_id.to_string());
+    }
     network_msg.write_to_bytes()
 }
 
@@ -324,6 +411,43 @@ pub mod tests {
         assert_eq!(direct_message.get_payload(), b"test_message");
     }
 
+    #[test]
+    // test that a StandardServiceNetworkSender sets the trace id on the direct message and on the
+    // circuit and network envelopes
+    fn test_standard_send_with_trace_id() {
+        let (outgoing_sender, outgoing_receiver) = crossbeam_channel::bounded(3);
+        let (internal_sender, _) = crossbeam_channel::bounded(3);
+        let inbound_router: InboundRouter<CircuitMessageType> =
+            InboundRouter::new(Box::new(internal_sender));
+        let network_sender = StandardServiceNetworkSender::new(
+            outgoing_sender,
+            "test_circuit".to_string(),
+            "service_a".to_string(),
+            inbound_router,
+        );
+
+        network_sender
+            .send_with_trace_id("service_b", b"test_message", "test_trace_id")
+            .unwrap();
+
+        let msg_bytes = match outgoing_receiver.recv() {
+            Ok(msg_bytes) => msg_bytes,
+            Err(err) => panic!("Received error: {}", err),
+        };
+
+        let network_msg: NetworkMessage = protobuf::parse_from_bytes(&msg_bytes).unwrap();
+        let circuit_msg: CircuitMessage =
+            protobuf::parse_from_bytes(network_msg.get_payload()).unwrap();
+        let direct_message: CircuitDirectMessage =
+            protobuf::parse_from_bytes(circuit_msg.get_payload()).unwrap();
+
+        assert_eq!(network_msg.get_trace_id(), "test_trace_id");
+        assert_eq!(circuit_msg.get_trace_id(), "test_trace_id");
+        assert_eq!(direct_message.get_trace_id(), "test_trace_id");
+        assert_eq!(direct_message.get_recipient(), "service_b");
+        assert_eq!(direct_message.get_payload(), b"test_message");
+    }
+
     #[test]
     // test that a StandardServiceNetworkSender properly send_and_awaits. Sends a message and
     // waits for a reply.
@@ -414,6 +538,7 @@ pub mod tests {
                     sender: "service_b".to_string(),
                     circuit: "test_circuit".to_string(),
                     correlation_id: "test_correlation_id".to_string(),
+                    trace_id: None,
                 };
                 network_sender.reply(&msg_context, b"test_message").unwrap();
             })
@@ -562,6 +687,7 @@ pub mod tests {
                     sender: "service_b".to_string(),
                     circuit: "admin".to_string(),
                     correlation_id: "test_correlation_id".to_string(),
+                    trace_id: None,
                 };
                 network_sender.reply(&msg_context, b"test_message").unwrap();
             })
@@ -0,0 +1,425 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Futures-based variants of the `Transport`, `Listener` and `Connection` traits.
+//!
+//! An `AsyncConnection` is a `Stream` of the messages received on the connection and a `Sink` for
+//! the messages sent on it, so that many connections can be driven by a shared executor instead of
+//! a thread per connection.
+//!
+//! The existing, blocking transports can be used through the `TransportAdapter` and
+//! `ConnectionAdapter`. A `ConnectionAdapter` registers the connection's `Evented` with the
+//! tokio reactor of the task that first polls it, and only receives once the connection is
+//! readable and sends once it is writable. Connecting and accepting are blocking operations for
+//! the existing transports, so the `TransportAdapter` connects on a short-lived thread with a
+//! transport of its own, and accepts on one thread per listener. Once a listener is dropped, its
+//! accept thread is woken with a connection of its own and stops.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures::sync::{mpsc, oneshot};
+use futures::{future, try_ready, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use mio::{Evented, PollOpt, Ready, Token};
+use tokio::reactor::PollEvented2;
+
+use super::{
+    AcceptError, ConnectError, Connection, DisconnectError, ListenError, RecvError, SendError,
+    Transport,
+};
+
+/// A future that resolves to a new connection.
+pub type ConnectFuture =
+    Box<dyn Future<Item = Box<dyn AsyncConnection>, Error = ConnectError> + Send>;
+
+/// A bi-directional connection between two nodes.
+///
+/// The connection is a `Stream` of the messages received on it, which ends once the connection
+/// has been disconnected, and a `Sink` for the messages to send on it.
+pub trait AsyncConnection:
+    Stream<Item = Vec<u8>, Error = RecvError> + Sink<SinkItem = Vec<u8>, SinkError = SendError> + Send
+{
+    /// Return the remote endpoint address for this connection.
+    fn remote_endpoint(&self) -> String;
+
+    /// Return the local endpoint address for this connection.
+    fn local_endpoint(&self) -> String;
+
+    /// Shut down the connection.
+    ///
+    /// After the connection has been disconnected, messages cannot be sent or received.
+    fn disconnect(&mut self) -> Result<(), DisconnectError>;
+}
+
+/// A `Stream` of the connections accepted by a listener.
+pub trait AsyncListener:
+    Stream<Item = Box<dyn AsyncConnection>, Error = AcceptError> + Send
+{
+    fn endpoint(&self) -> String;
+}
+
+/// Factory-pattern based type for creating connections asynchronously.
+pub trait AsyncTransport: Send {
+    /// Indicates whether or not a given address can be used to create a connection or listener.
+    fn accepts(&self, address: &str) -> bool;
+    fn connect(&mut self, endpoint: &str) -> ConnectFuture;
+    fn listen(&mut self, bind: &str) -> Result<Box<dyn AsyncListener>, ListenError>;
+}
+
+type TransportFactory = Arc<dyn Fn() -> Box<dyn Transport> + Send + Sync>;
+
+/// Adapts a `Transport` to an `AsyncTransport`.
+pub struct TransportAdapter {
+    transport: Arc<Mutex<Box<dyn Transport>>>,
+    new_transport: TransportFactory,
+}
+
+impl TransportAdapter {
+    /// Adapts the transports created by `new_transport`: one is used for listening, and each
+    /// connection is made with a new one, so that connecting never blocks on another connection
+    /// or on the listening transport.
+    pub fn new<T, F>(new_transport: F) -> Self
+    where
+        T: Transport + 'static,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        TransportAdapter {
+            transport: Arc::new(Mutex::new(Box::new(new_transport()))),
+            new_transport: Arc::new(move || Box::new(new_transport()) as Box<dyn Transport>),
+        }
+    }
+}
+
+impl AsyncTransport for TransportAdapter {
+    fn accepts(&self, address: &str) -> bool {
+        mutex_lock_unwrap!(self.transport).accepts(address)
+    }
+
+    /// Connects on a new thread, with a new transport, as connecting with a `Transport` blocks
+    /// until the connection has been established.
+    fn connect(&mut self, endpoint: &str) -> ConnectFuture {
+        let (result_tx, result_rx) = oneshot::channel();
+        let new_transport = self.new_transport.clone();
+        let thread_endpoint = endpoint.to_string();
+
+        let spawned = thread::Builder::new()
+            .name(format!("Connect-{}", endpoint))
+            .spawn(move || {
+                let result = new_transport().connect(&thread_endpoint);
+                // The future may have been dropped, in which case the connection is dropped too
+                let _ = result_tx.send(result);
+            });
+
+        if let Err(err) = spawned {
+            return Box::new(future::err(ConnectError::IoError(err)));
+        }
+
+        Box::new(result_rx.then(|result| match result {
+            Ok(connect_result) => connect_result.map(|connection| {
+                Box::new(ConnectionAdapter::new(connection)) as Box<dyn AsyncConnection>
+            }),
+            Err(oneshot::Canceled) => Err(ConnectError::ProtocolError(
+                "connect thread exited before connecting".into(),
+            )),
+        }))
+    }
+
+    /// Listens with the adapted transport, and accepts the listener's connections on a new thread.
+    fn listen(&mut self, bind: &str) -> Result<Box<dyn AsyncListener>, ListenError> {
+        let listener = mutex_lock_unwrap!(self.transport).listen(bind)?;
+        let endpoint = listener.endpoint();
+        let (accepted_tx, accepted_rx) = mpsc::unbounded();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_shutdown = shutdown.clone();
+        thread::Builder::new()
+            .name(format!("Accept-{}", endpoint))
+            .spawn(move || accept_loop(listener, accepted_tx, thread_shutdown))
+            .map_err(ListenError::IoError)?;
+
+        Ok(Box::new(ListenerAdapter {
+            endpoint,
+            accepted: accepted_rx,
+            shutdown,
+            new_transport: self.new_transport.clone(),
+        }))
+    }
+}
+
+/// Accepts connections until the listener fails or the `ListenerAdapter` has been dropped.
+fn accept_loop(
+    mut listener: Box<dyn super::Listener>,
+    accepted_tx: mpsc::UnboundedSender<Result<Box<dyn Connection>, AcceptError>>,
+    shutdown: Arc<AtomicBool>,
+) {
+    loop {
+        let result = listener.accept();
+        // The connection that woke the thread, or any other accepted after the drop, is dropped
+        if shutdown.load(Ordering::SeqCst) {
+            debug!(
+                "Listener for {} dropped; no longer accepting connections",
+                listener.endpoint()
+            );
+            break;
+        }
+        let failed = result.is_err();
+        if accepted_tx.unbounded_send(result).is_err() {
+            debug!(
+                "Listener for {} dropped; no longer accepting connections",
+                listener.endpoint()
+            );
+            break;
+        }
+        if failed {
+            break;
+        }
+    }
+}
+
+/// A `Stream` of the connections accepted by a `Listener` on its accept thread.
+struct ListenerAdapter {
+    endpoint: String,
+    accepted: mpsc::UnboundedReceiver<Result<Box<dyn Connection>, AcceptError>>,
+    shutdown: Arc<AtomicBool>,
+    new_transport: TransportFactory,
+}
+
+impl Drop for ListenerAdapter {
+    /// Stops the accept thread. The thread is blocked accepting, so once it has been told to stop,
+    /// it is woken by connecting to the listener; the connection is made on a short-lived thread,
+    /// as connecting blocks.
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        let new_transport = self.new_transport.clone();
+        let endpoint = self.endpoint.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("StopAccept-{}", endpoint))
+            .spawn(move || {
+                // The accept thread may already have stopped if its listener failed
+                if let Err(err) = new_transport().connect(&endpoint) {
+                    debug!("Unable to wake accept thread for {}: {}", endpoint, err);
+                }
+            });
+        if let Err(err) = spawned {
+            warn!(
+                "Unable to stop accept thread for {}; it stops once it accepts another \
+                 connection: {}",
+                self.endpoint, err
+            );
+        }
+    }
+}
+
+impl Stream for ListenerAdapter {
+    type Item = Box<dyn AsyncConnection>;
+    type Error = AcceptError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // Receiving from an unbounded receiver cannot fail
+        match self.accepted.poll() {
+            Ok(Async::Ready(Some(Ok(connection)))) => {
+                let connection: Box<dyn AsyncConnection> =
+                    Box::new(ConnectionAdapter::new(connection));
+                Ok(Async::Ready(Some(connection)))
+            }
+            Ok(Async::Ready(Some(Err(err)))) => Err(err),
+            Ok(Async::Ready(None)) | Err(()) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl AsyncListener for ListenerAdapter {
+    fn endpoint(&self) -> String {
+        self.endpoint.clone()
+    }
+}
+
+/// Adapts a `Connection` to an `AsyncConnection`.
+pub struct ConnectionAdapter {
+    io: PollEvented2<EventedConnection>,
+}
+
+impl ConnectionAdapter {
+    pub fn new(connection: Box<dyn Connection>) -> Self {
+        ConnectionAdapter {
+            io: PollEvented2::new(EventedConnection(connection)),
+        }
+    }
+
+    fn connection(&mut self) -> &mut dyn Connection {
+        &mut *self.io.get_mut().0
+    }
+}
+
+impl Stream for ConnectionAdapter {
+    type Item = Vec<u8>;
+    type Error = RecvError;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, RecvError> {
+        try_ready!(self.io.poll_read_ready(Ready::readable()));
+
+        match self.connection().recv() {
+            Ok(message) => Ok(Async::Ready(Some(message))),
+            Err(RecvError::WouldBlock) => {
+                // Readiness is only cleared once the connection has no more messages, so that
+                // the task is notified when the connection is readable again
+                self.io.clear_read_ready(Ready::readable())?;
+                Ok(Async::NotReady)
+            }
+            Err(RecvError::Disconnected) => Ok(Async::Ready(None)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Sink for ConnectionAdapter {
+    type SinkItem = Vec<u8>;
+    type SinkError = SendError;
+
+    fn start_send(&mut self, message: Vec<u8>) -> StartSend<Vec<u8>, SendError> {
+        if let Async::NotReady = self.io.poll_write_ready()? {
+            return Ok(AsyncSink::NotReady(message));
+        }
+
+        match self.connection().send(&message) {
+            Ok(()) => Ok(AsyncSink::Ready),
+            Err(SendError::WouldBlock) => {
+                // The task is notified once the connection is writable again
+                self.io.clear_write_ready()?;
+                Ok(AsyncSink::NotReady(message))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), SendError> {
+        // Messages are written to the connection as they are sent
+        Ok(Async::Ready(()))
+    }
+}
+
+impl AsyncConnection for ConnectionAdapter {
+    fn remote_endpoint(&self) -> String {
+        self.io.get_ref().0.remote_endpoint()
+    }
+
+    fn local_endpoint(&self) -> String {
+        self.io.get_ref().0.local_endpoint()
+    }
+
+    fn disconnect(&mut self) -> Result<(), DisconnectError> {
+        self.connection().disconnect()
+    }
+}
+
+/// Registers a connection's `Evented` with the reactor.
+struct EventedConnection(Box<dyn Connection>);
+
+impl Evented for EventedConnection {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        self.0.evented().register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        self.0.evented().reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        self.0.evented().deregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::runtime::Runtime;
+
+    use crate::transport::raw::RawTransport;
+
+    /// Test that connections made and accepted through a `TransportAdapter` can send and receive
+    /// messages on a tokio runtime.
+    #[test]
+    fn test_transport_adapter() {
+        let mut runtime = Runtime::new().expect("Unable to create runtime");
+        let mut transport = TransportAdapter::new(RawTransport::default);
+
+        assert!(transport.accepts("127.0.0.1:0"));
+
+        let listener = transport.listen("127.0.0.1:0").expect("Unable to listen");
+        let endpoint = listener.endpoint();
+
+        let client = runtime
+            .block_on(transport.connect(&endpoint))
+            .expect("Unable to connect");
+        assert_eq!(endpoint, client.remote_endpoint());
+
+        let (server, _listener) = runtime
+            .block_on(listener.into_future().map_err(|(err, _)| err))
+            .expect("Unable to accept");
+        let server = server.expect("Listener ended without accepting");
+
+        let client = runtime
+            .block_on(client.send(b"hello".to_vec()))
+            .expect("Unable to send");
+        let (message, server) = runtime
+            .block_on(server.into_future().map_err(|(err, _)| err))
+            .expect("Unable to receive");
+        assert_eq!(Some(b"hello".to_vec()), message);
+
+        let _server = runtime
+            .block_on(server.send(b"world".to_vec()))
+            .expect("Unable to send");
+        let (message, _client) = runtime
+            .block_on(client.into_future().map_err(|(err, _)| err))
+            .expect("Unable to receive");
+        assert_eq!(Some(b"world".to_vec()), message);
+    }
+
+    /// Test that dropping a listener stops its accept thread, which releases the listener's
+    /// address.
+    #[test]
+    fn test_listener_drop_stops_accepting() {
+        let mut transport = TransportAdapter::new(RawTransport::default);
+        let listener = transport.listen("127.0.0.1:0").expect("Unable to listen");
+        let endpoint = listener.endpoint();
+
+        drop(listener);
+
+        let mut raw_transport = RawTransport::default();
+        for _ in 0..50 {
+            if raw_transport.listen(&endpoint).is_ok() {
+                return;
+            }
+            thread::sleep(std::time::Duration::from_millis(100));
+        }
+        panic!("Listener address {} was not released", endpoint);
+    }
+}
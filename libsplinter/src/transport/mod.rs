@@ -12,11 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "async-transport")]
+pub mod async_transport;
 pub mod inproc;
 pub mod multi;
+#[cfg(feature = "quic-transport")]
+pub mod quic;
 pub mod raw;
 mod rw;
 pub mod tls;
+#[cfg(feature = "unix-transport")]
+pub mod unix;
+#[cfg(feature = "ws-transport")]
+pub mod ws;
 #[cfg(feature = "zmq-transport")]
 pub mod zmq;
 
@@ -106,6 +106,36 @@ mod tests {
         tests::test_transport(transport, "127.0.0.1:0");
     }
 
+    /// Test MultiTransport using a WebSocket transport for an explicit `ws://` listening endpoint,
+    /// with the standard transport tests.
+    #[cfg(feature = "ws-transport")]
+    #[test]
+    fn test_transport_ws_listener() {
+        let raw_transport = Box::new(raw::RawTransport::default());
+        let tls_transport = Box::new(create_test_tls_transport(true));
+        let ws_transport = Box::new(crate::transport::ws::WsTransport::default());
+
+        let transport = MultiTransport::new(vec![raw_transport, tls_transport, ws_transport]);
+        assert!(transport.accepts("ws://127.0.0.1:0"));
+        tests::test_transport(transport, "ws://127.0.0.1:0");
+    }
+
+    /// Test MultiTransport using a Unix domain socket transport for a `unix://` listening
+    /// endpoint, alongside a raw transport accepting endpoints without a protocol.
+    #[cfg(feature = "unix-transport")]
+    #[test]
+    fn test_transport_unix_listener() {
+        let temp_dir = tempdir::TempDir::new("multi-transport-test").unwrap();
+        let endpoint = format!("unix://{}", temp_dir.path().join("test.sock").display());
+
+        let raw_transport = Box::new(raw::RawTransport::default());
+        let unix_transport = Box::new(crate::transport::unix::UnixTransport::default());
+
+        let transport = MultiTransport::new(vec![raw_transport, unix_transport]);
+        assert!(transport.accepts(&endpoint));
+        tests::test_transport(transport, &endpoint);
+    }
+
     /// Create a transport with tcp and tls transports and attempt to create an unknown protocol.
     /// Expect that a protocol error should be returned.
     #[test]
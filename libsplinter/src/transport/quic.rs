@@ -0,0 +1,1217 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A transport that carries messages over QUIC connections.
+//!
+//! Messages are sent on one of several unidirectional QUIC streams, chosen by the priority of the
+//! message's type, so that a large message only delays the messages sent after it on the same
+//! stream. Heartbeats and authorization messages are sent on the highest priority stream, circuit
+//! management and admin messages on the next, and service messages on the lowest. Messages of the
+//! same priority are received in the order they were sent.
+//!
+//! Each endpoint's UDP socket is serviced by a background thread, which receives packets, handles
+//! the connection timers, and updates the readiness of the connections. The thread waits for
+//! packets until the earliest of the connections' timers is due.
+//!
+//! A listener only creates a connection once the client has proven that it can receive packets
+//! at its address: the first Initial packet from a client is answered with a Retry packet
+//! carrying an address validation token, which the client must send back.
+
+use byteorder::{BigEndian, ByteOrder};
+use mio::{Evented, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use protobuf::ProtobufEnum;
+use uuid::Uuid;
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::protos::circuit::CircuitMessageType;
+use crate::protos::network::NetworkMessageType;
+use crate::transport::tls::{endpoint_to_dns_name, TlsInitError};
+use crate::transport::{
+    AcceptError, ConnectError, Connection, DisconnectError, ListenError, Listener, RecvError,
+    SendError, Transport,
+};
+
+const PROTOCOL_PREFIX: &str = "quic://";
+const APPLICATION_PROTOCOL: &[u8] = b"\x08splinter";
+
+const CONNECTION_ID_LEN: usize = 16;
+const MAX_DATAGRAM_SIZE: usize = 1350;
+const MAX_IDLE_TIMEOUT_MS: u64 = 60_000;
+const MAX_CONNECTION_DATA: u64 = 16 * 1024 * 1024;
+const MAX_STREAM_DATA: u64 = 4 * 1024 * 1024;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest the socket thread waits for a packet, so it notices when the listener is dropped
+const MAX_SOCKET_READ_TIMEOUT: Duration = Duration::from_millis(500);
+const MIN_SOCKET_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// The maximum number of messages of each priority that may be waiting to be written to a
+/// connection's streams
+const MAX_QUEUED_MESSAGES: usize = 1024;
+/// The maximum number of received messages that may be waiting to be read from a connection;
+/// streams are not read while this many are waiting, so the peer is held back by flow control
+const MAX_RECEIVED_MESSAGES: usize = 1024;
+/// The largest message a peer may send; the connection is closed if a larger one is received
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// The application error code a connection is closed with when a message is too large
+const MESSAGE_TOO_LARGE_ERROR: u64 = 0x1;
+
+/// How long an address validation token sent in a Retry packet is accepted for, in seconds
+const TOKEN_LIFETIME_SECS: u64 = 10;
+const TOKEN_KEY_LEN: usize = 32;
+const TOKEN_MAC_LEN: usize = 32;
+
+/// Priorities of the streams messages are sent on, from highest to lowest.
+const HIGH_PRIORITY: usize = 0;
+const MEDIUM_PRIORITY: usize = 1;
+const LOW_PRIORITY: usize = 2;
+const PRIORITIES: usize = 3;
+
+type SharedState = Arc<Mutex<ConnectionState>>;
+
+pub struct QuicTransport {
+    ca_cert: Option<String>,
+    client_key: String,
+    client_cert: String,
+    server_key: String,
+    server_cert: String,
+}
+
+impl QuicTransport {
+    pub fn new(
+        ca_cert: Option<String>,
+        client_key: String,
+        client_cert: String,
+        server_key: String,
+        server_cert: String,
+    ) -> Result<Self, TlsInitError> {
+        let transport = QuicTransport {
+            ca_cert,
+            client_key,
+            client_cert,
+            server_key,
+            server_cert,
+        };
+
+        // Check that the keys and certificates can be loaded
+        transport.build_config(false)?;
+        transport.build_config(true)?;
+
+        Ok(transport)
+    }
+
+    fn build_config(&self, server: bool) -> Result<quiche::Config, TlsInitError> {
+        let (key, cert) = if server {
+            (&self.server_key, &self.server_cert)
+        } else {
+            (&self.client_key, &self.client_cert)
+        };
+
+        let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION)?;
+        config.load_cert_chain_from_pem_file(cert)?;
+        config.load_priv_key_from_pem_file(key)?;
+        // if ca_cert is provided verify the peer, otherwise do not verify it
+        if let Some(ca_cert) = &self.ca_cert {
+            config.load_verify_locations_from_file(ca_cert)?;
+            config.verify_peer(true);
+        } else {
+            config.verify_peer(false);
+        }
+        config.set_application_protos(APPLICATION_PROTOCOL)?;
+        config.set_max_idle_timeout(MAX_IDLE_TIMEOUT_MS);
+        config.set_initial_max_data(MAX_CONNECTION_DATA);
+        config.set_initial_max_stream_data_uni(MAX_STREAM_DATA);
+        config.set_initial_max_streams_uni(PRIORITIES as u64);
+        config.set_initial_max_streams_bidi(0);
+
+        Ok(config)
+    }
+}
+
+impl Transport for QuicTransport {
+    fn accepts(&self, address: &str) -> bool {
+        address.starts_with(PROTOCOL_PREFIX) || !address.contains("://")
+    }
+
+    fn connect(&mut self, endpoint: &str) -> Result<Box<dyn Connection>, ConnectError> {
+        if !self.accepts(endpoint) {
+            return Err(ConnectError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                endpoint
+            )));
+        }
+
+        let address = if endpoint.starts_with(PROTOCOL_PREFIX) {
+            &endpoint[PROTOCOL_PREFIX.len()..]
+        } else {
+            endpoint
+        };
+
+        let peer_addr = address.to_socket_addrs()?.next().ok_or_else(|| {
+            ConnectError::ParseError(format!("Unable to resolve address \"{}\"", address))
+        })?;
+        let socket = if peer_addr.is_ipv4() {
+            UdpSocket::bind("0.0.0.0:0")?
+        } else {
+            UdpSocket::bind("[::]:0")?
+        };
+        let socket = Arc::new(socket);
+
+        let dns_name = endpoint_to_dns_name(address)?;
+        let mut config = self
+            .build_config(false)
+            .map_err(|err| ConnectError::ProtocolError(err.to_string()))?;
+        let scid = new_connection_id();
+        let conn = quiche::connect(Some(&dns_name), &scid, &mut config)?;
+
+        let (registration, readiness) = Registration::new2();
+        let state = Arc::new(Mutex::new(ConnectionState::new(
+            conn, peer_addr, false, readiness,
+        )));
+        mutex_lock_unwrap!(state).process(&socket)?;
+
+        let mut connections = HashMap::new();
+        connections.insert(scid, state.clone());
+        Driver {
+            socket: socket.clone(),
+            connections,
+            server: None,
+        }
+        .start()?;
+
+        // Block until the handshake has completed
+        let start = Instant::now();
+        loop {
+            {
+                let state = mutex_lock_unwrap!(state);
+                if state.conn.is_established() {
+                    break;
+                }
+                if state.conn.is_closed() {
+                    return Err(ConnectError::ProtocolError(format!(
+                        "QUIC handshake with {} failed",
+                        endpoint
+                    )));
+                }
+            }
+            if start.elapsed() > HANDSHAKE_TIMEOUT {
+                mutex_lock_unwrap!(state).close();
+                return Err(ConnectError::IoError(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "QUIC handshake timed out",
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(Box::new(QuicConnection {
+            state,
+            socket,
+            registration,
+        }))
+    }
+
+    fn listen(&mut self, bind: &str) -> Result<Box<dyn Listener>, ListenError> {
+        if !self.accepts(bind) {
+            return Err(ListenError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                bind
+            )));
+        }
+
+        let address = if bind.starts_with(PROTOCOL_PREFIX) {
+            &bind[PROTOCOL_PREFIX.len()..]
+        } else {
+            bind
+        };
+
+        let config = self
+            .build_config(true)
+            .map_err(|err| ListenError::ProtocolError(err.to_string()))?;
+        let token_key = new_token_key().map_err(|err| {
+            ListenError::ProtocolError(format!("Unable to create token key: {}", err))
+        })?;
+        let socket = Arc::new(UdpSocket::bind(address)?);
+        let listening = Arc::new(AtomicBool::new(true));
+        let (accepted_tx, accepted_rx) = channel();
+
+        Driver {
+            socket: socket.clone(),
+            connections: HashMap::new(),
+            server: Some(Server {
+                config,
+                token_key,
+                accepted: accepted_tx,
+                listening: listening.clone(),
+            }),
+        }
+        .start()?;
+
+        Ok(Box::new(QuicListener {
+            socket,
+            accepted: accepted_rx,
+            listening,
+        }))
+    }
+}
+
+pub struct QuicListener {
+    socket: Arc<UdpSocket>,
+    accepted: Receiver<(SharedState, Registration)>,
+    listening: Arc<AtomicBool>,
+}
+
+impl Listener for QuicListener {
+    fn accept(&mut self) -> Result<Box<dyn Connection>, AcceptError> {
+        let (state, registration) = self
+            .accepted
+            .recv()
+            .map_err(|_| AcceptError::ProtocolError("QUIC listener is no longer running".into()))?;
+
+        Ok(Box::new(QuicConnection {
+            state,
+            socket: self.socket.clone(),
+            registration,
+        }))
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}{}", PROTOCOL_PREFIX, self.socket.local_addr().unwrap())
+    }
+}
+
+impl Drop for QuicListener {
+    fn drop(&mut self) {
+        self.listening.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct QuicConnection {
+    state: SharedState,
+    socket: Arc<UdpSocket>,
+    registration: Registration,
+}
+
+impl Connection for QuicConnection {
+    fn send(&mut self, message: &[u8]) -> Result<(), SendError> {
+        let mut state = mutex_lock_unwrap!(self.state);
+        if state.conn.is_closed() {
+            return Err(SendError::Disconnected);
+        }
+        if message.len() > MAX_MESSAGE_SIZE {
+            return Err(SendError::ProtocolError(format!(
+                "Message of {} bytes is larger than the maximum of {} bytes",
+                message.len(),
+                MAX_MESSAGE_SIZE
+            )));
+        }
+        let priority = message_priority(message);
+        if state.is_full(priority) {
+            return Err(SendError::WouldBlock);
+        }
+
+        state.queue(priority, message);
+        state
+            .process(&self.socket)
+            .map_err(|err| SendError::ProtocolError(format!("QUIC Err: {}", err)))
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, RecvError> {
+        let mut state = mutex_lock_unwrap!(self.state);
+        let was_full = state.incoming.len() >= MAX_RECEIVED_MESSAGES;
+        let message = state.incoming.pop_front();
+        if was_full {
+            // Resume reading the streams, which also lets the peer send more data
+            if let Err(err) = state.process(&self.socket) {
+                debug!("Unable to process QUIC connection: {}", err);
+            }
+        } else {
+            state.update_readiness();
+        }
+
+        match message {
+            Some(message) => Ok(message),
+            None if state.conn.is_closed() => Err(RecvError::Disconnected),
+            None => Err(RecvError::WouldBlock),
+        }
+    }
+
+    fn remote_endpoint(&self) -> String {
+        format!(
+            "{}{}",
+            PROTOCOL_PREFIX,
+            mutex_lock_unwrap!(self.state).peer_addr
+        )
+    }
+
+    fn local_endpoint(&self) -> String {
+        format!("{}{}", PROTOCOL_PREFIX, self.socket.local_addr().unwrap())
+    }
+
+    fn disconnect(&mut self) -> Result<(), DisconnectError> {
+        let mut state = mutex_lock_unwrap!(self.state);
+        state.close();
+        state
+            .process(&self.socket)
+            .map_err(|err| DisconnectError::ProtocolError(format!("QUIC Err: {}", err)))
+    }
+
+    fn evented(&self) -> &dyn Evented {
+        self
+    }
+}
+
+impl Drop for QuicConnection {
+    fn drop(&mut self) {
+        let mut state = mutex_lock_unwrap!(self.state);
+        state.close();
+        if let Err(err) = state.process(&self.socket) {
+            debug!("Unable to close QUIC connection: {}", err);
+        }
+    }
+}
+
+impl Evented for QuicConnection {
+    fn register(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        self.registration.register(poll, token, interest, opts)?;
+        mutex_lock_unwrap!(self.state).update_readiness();
+        Ok(())
+    }
+
+    fn reregister(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        self.registration.reregister(poll, token, interest, opts)?;
+        mutex_lock_unwrap!(self.state).update_readiness();
+        Ok(())
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        poll.deregister(&self.registration)
+    }
+}
+
+/// The state of a QUIC connection, shared between the `QuicConnection` and the thread servicing
+/// its socket.
+struct ConnectionState {
+    conn: Pin<Box<quiche::Connection>>,
+    peer_addr: SocketAddr,
+    server: bool,
+    // Length-prefixed messages waiting to be written to the stream of each priority, and how much
+    // of the first message has been written
+    outgoing: Vec<VecDeque<Vec<u8>>>,
+    outgoing_offsets: Vec<usize>,
+    priorities_set: bool,
+    // Bytes received on each stream that do not yet make up a complete message
+    partial: HashMap<u64, Vec<u8>>,
+    incoming: VecDeque<Vec<u8>>,
+    readiness: SetReadiness,
+}
+
+impl ConnectionState {
+    fn new(
+        conn: Pin<Box<quiche::Connection>>,
+        peer_addr: SocketAddr,
+        server: bool,
+        readiness: SetReadiness,
+    ) -> Self {
+        ConnectionState {
+            conn,
+            peer_addr,
+            server,
+            outgoing: (0..PRIORITIES).map(|_| VecDeque::new()).collect(),
+            outgoing_offsets: vec![0; PRIORITIES],
+            priorities_set: false,
+            partial: HashMap::new(),
+            incoming: VecDeque::new(),
+            readiness,
+        }
+    }
+
+    /// Returns the id of the unidirectional stream this end sends messages of the given priority
+    /// on. Lower stream ids are used for higher priorities.
+    fn stream_id(&self, priority: usize) -> u64 {
+        let initiator = if self.server { 3 } else { 2 };
+        initiator + 4 * priority as u64
+    }
+
+    fn queue(&mut self, priority: usize, message: &[u8]) {
+        let mut framed = vec![0; 4];
+        BigEndian::write_u32(&mut framed, message.len() as u32);
+        framed.extend_from_slice(message);
+        self.outgoing[priority].push_back(framed);
+    }
+
+    /// Returns whether the maximum number of messages of the given priority are waiting to be
+    /// written to its stream. Each priority is bounded separately, so that a backlog of low
+    /// priority messages does not hold back higher priority ones.
+    fn is_full(&self, priority: usize) -> bool {
+        self.outgoing[priority].len() >= MAX_QUEUED_MESSAGES
+    }
+
+    fn close(&mut self) {
+        match self.conn.close(true, 0x0, b"") {
+            Ok(()) | Err(quiche::Error::Done) => (),
+            Err(err) => debug!("Unable to close QUIC connection: {}", err),
+        }
+    }
+
+    /// Writes queued messages to their streams, reads received messages from the streams, sends
+    /// any resulting packets, and updates the readiness of the connection.
+    fn process(&mut self, socket: &UdpSocket) -> Result<(), quiche::Error> {
+        if self.conn.is_established() {
+            self.write_streams()?;
+            self.read_streams()?;
+        }
+        self.send_packets(socket);
+        self.update_readiness();
+        Ok(())
+    }
+
+    fn write_streams(&mut self) -> Result<(), quiche::Error> {
+        if !self.priorities_set {
+            // Data on higher priority streams is always sent before data on lower priority ones
+            for priority in 0..PRIORITIES {
+                let stream_id = self.stream_id(priority);
+                self.conn
+                    .stream_priority(stream_id, priority as u8, false)?;
+            }
+            self.priorities_set = true;
+        }
+
+        for priority in 0..PRIORITIES {
+            let stream_id = self.stream_id(priority);
+            while let Some(message) = self.outgoing[priority].front() {
+                let offset = self.outgoing_offsets[priority];
+                let written = match self.conn.stream_send(stream_id, &message[offset..], false) {
+                    Ok(written) => written,
+                    Err(quiche::Error::Done) => 0,
+                    Err(err) => return Err(err),
+                };
+
+                if offset + written < message.len() {
+                    // Out of flow control credit; lower priorities must wait for this message
+                    self.outgoing_offsets[priority] = offset + written;
+                    return Ok(());
+                }
+
+                self.outgoing[priority].pop_front();
+                self.outgoing_offsets[priority] = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads received messages from the streams, unless the maximum number of received messages
+    /// are waiting to be read. Data that is not read is left to QUIC flow control, which stops the
+    /// peer from sending more than the streams' windows.
+    fn read_streams(&mut self) -> Result<(), quiche::Error> {
+        let mut buf = [0; 65535];
+        // Streams may hold complete messages that were not read because too many were waiting
+        let mut stream_ids: Vec<u64> = self.conn.readable().collect();
+        for (stream_id, received) in &self.partial {
+            if !received.is_empty() && !stream_ids.contains(stream_id) {
+                stream_ids.push(*stream_id);
+            }
+        }
+        for stream_id in stream_ids {
+            if self.incoming.len() >= MAX_RECEIVED_MESSAGES {
+                break;
+            }
+
+            let received = self.partial.entry(stream_id).or_insert_with(Vec::new);
+            loop {
+                match self.conn.stream_recv(stream_id, &mut buf) {
+                    Ok((read, _)) => received.extend_from_slice(&buf[..read]),
+                    Err(quiche::Error::Done) => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            // Split the received bytes into complete messages
+            let mut start = 0;
+            while received.len() - start >= 4 && self.incoming.len() < MAX_RECEIVED_MESSAGES {
+                let len = BigEndian::read_u32(&received[start..start + 4]) as usize;
+                if len > MAX_MESSAGE_SIZE {
+                    error!(
+                        "Closing QUIC connection to {}: received a message of {} bytes, larger \
+                         than the maximum of {} bytes",
+                        self.peer_addr, len, MAX_MESSAGE_SIZE
+                    );
+                    received.clear();
+                    match self
+                        .conn
+                        .close(true, MESSAGE_TOO_LARGE_ERROR, b"message too large")
+                    {
+                        Ok(()) | Err(quiche::Error::Done) => (),
+                        Err(err) => debug!("Unable to close QUIC connection: {}", err),
+                    }
+                    return Ok(());
+                }
+                if received.len() - start - 4 < len {
+                    break;
+                }
+                self.incoming
+                    .push_back(received[start + 4..start + 4 + len].to_vec());
+                start += 4 + len;
+            }
+            received.drain(..start);
+        }
+
+        Ok(())
+    }
+
+    fn send_packets(&mut self, socket: &UdpSocket) {
+        let mut out = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let len = match self.conn.send(&mut out) {
+                Ok(len) => len,
+                Err(quiche::Error::Done) => break,
+                Err(err) => {
+                    error!(
+                        "Unable to create QUIC packet for {}: {}",
+                        self.peer_addr, err
+                    );
+                    self.close();
+                    break;
+                }
+            };
+
+            if let Err(err) = socket.send_to(&out[..len], self.peer_addr) {
+                // The packet is treated as lost and will be retransmitted
+                debug!("Unable to send QUIC packet to {}: {}", self.peer_addr, err);
+                break;
+            }
+        }
+    }
+
+    fn update_readiness(&self) {
+        let mut readiness = Ready::empty();
+        if !self.incoming.is_empty() || self.conn.is_closed() {
+            readiness |= Ready::readable();
+        }
+        if !self.conn.is_closed() && !(0..PRIORITIES).any(|priority| self.is_full(priority)) {
+            readiness |= Ready::writable();
+        }
+        if let Err(err) = self.readiness.set_readiness(readiness) {
+            error!("Unable to set readiness of QUIC connection: {}", err);
+        }
+    }
+}
+
+struct Server {
+    config: quiche::Config,
+    /// The key address validation tokens are authenticated with
+    token_key: PKey<Private>,
+    accepted: Sender<(SharedState, Registration)>,
+    listening: Arc<AtomicBool>,
+}
+
+/// Services a UDP socket: receives packets for the connections on the socket, accepts new
+/// connections if listening, and handles the connections' timers.
+struct Driver {
+    socket: Arc<UdpSocket>,
+    // Connections by connection id
+    connections: HashMap<Vec<u8>, SharedState>,
+    server: Option<Server>,
+}
+
+impl Driver {
+    fn start(mut self) -> Result<(), io::Error> {
+        thread::Builder::new()
+            .name(format!("QuicDriver-{}", self.socket.local_addr()?))
+            .spawn(move || self.run())?;
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        // pending connections accepted by a server that have not completed the handshake
+        let mut pending: Vec<(SharedState, Registration)> = vec![];
+        let mut buf = [0; 65535];
+
+        loop {
+            if let Err(err) = self.socket.set_read_timeout(Some(self.read_timeout())) {
+                error!("Unable to set QUIC socket read timeout: {}", err);
+                break;
+            }
+
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    if let Some(accepted) = self.handle_packet(&mut buf[..len], from) {
+                        pending.push(accepted);
+                    }
+                }
+                Err(ref err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut => {}
+                Err(err) => {
+                    error!("Unable to receive QUIC packet: {}", err);
+                    break;
+                }
+            }
+
+            for state in self.connections.values() {
+                let mut state = mutex_lock_unwrap!(state);
+                if state.conn.timeout() == Some(Duration::from_secs(0)) {
+                    state.conn.on_timeout();
+                }
+                if let Err(err) = state.process(&self.socket) {
+                    error!("QUIC connection to {} failed: {}", state.peer_addr, err);
+                    state.close();
+                }
+            }
+
+            if let Some(server) = self.server.as_ref() {
+                let (established, waiting): (Vec<_>, Vec<_>) = pending
+                    .into_iter()
+                    .partition(|(state, _)| mutex_lock_unwrap!(state).conn.is_established());
+                pending = waiting;
+
+                for accepted in established {
+                    if let Err(mpsc::SendError((state, _))) = server.accepted.send(accepted) {
+                        // The listener has been dropped
+                        mutex_lock_unwrap!(state).close();
+                    }
+                }
+            }
+
+            self.connections
+                .retain(|_, state| !mutex_lock_unwrap!(state).conn.is_closed());
+            pending.retain(|(state, _)| !mutex_lock_unwrap!(state).conn.is_closed());
+
+            let listening = self
+                .server
+                .as_ref()
+                .map(|server| server.listening.load(Ordering::SeqCst))
+                .unwrap_or(false);
+            if !listening && self.connections.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Returns how long to wait for a packet: until the earliest of the connections' timers is
+    /// due, within bounds.
+    fn read_timeout(&self) -> Duration {
+        let timeout = self
+            .connections
+            .values()
+            .filter_map(|state| mutex_lock_unwrap!(state).conn.timeout())
+            .min()
+            .unwrap_or(MAX_SOCKET_READ_TIMEOUT);
+        if timeout < MIN_SOCKET_READ_TIMEOUT {
+            MIN_SOCKET_READ_TIMEOUT
+        } else if timeout > MAX_SOCKET_READ_TIMEOUT {
+            MAX_SOCKET_READ_TIMEOUT
+        } else {
+            timeout
+        }
+    }
+
+    /// Passes a received packet to its connection. Returns the state and registration of a new
+    /// connection if the packet started one.
+    fn handle_packet(
+        &mut self,
+        packet: &mut [u8],
+        from: SocketAddr,
+    ) -> Option<(SharedState, Registration)> {
+        let header = match quiche::Header::from_slice(packet, CONNECTION_ID_LEN) {
+            Ok(header) => header,
+            Err(err) => {
+                debug!("Dropping invalid QUIC packet from {}: {}", from, err);
+                return None;
+            }
+        };
+
+        if let Some(state) = self.connections.get(&header.dcid) {
+            let mut state = mutex_lock_unwrap!(state);
+            if let Err(err) = state.conn.recv(packet) {
+                debug!("Unable to process QUIC packet from {}: {}", from, err);
+            }
+            return None;
+        }
+
+        let server = match self.server.as_mut() {
+            Some(server) if header.ty == quiche::Type::Initial => server,
+            _ => return None,
+        };
+        if !server.listening.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        if !quiche::version_is_supported(header.version) {
+            let mut out = [0; MAX_DATAGRAM_SIZE];
+            match quiche::negotiate_version(&header.scid, &header.dcid, &mut out) {
+                Ok(len) => {
+                    if let Err(err) = self.socket.send_to(&out[..len], from) {
+                        debug!("Unable to send QUIC version negotiation: {}", err);
+                    }
+                }
+                Err(err) => debug!("Unable to create QUIC version negotiation: {}", err),
+            }
+            return None;
+        }
+
+        let token = header.token.as_ref().map(Vec::as_slice).unwrap_or(&[]);
+        if token.is_empty() {
+            // No state is kept until the client returns the token sent in the Retry packet
+            let scid = new_connection_id();
+            let mut out = [0; MAX_DATAGRAM_SIZE];
+            let retry = mint_token(&server.token_key, &from, &header.dcid).and_then(|token| {
+                quiche::retry(
+                    &header.scid,
+                    &header.dcid,
+                    &scid,
+                    &token,
+                    header.version,
+                    &mut out,
+                )
+                .map_err(|err| err.to_string())
+            });
+            match retry {
+                Ok(len) => {
+                    if let Err(err) = self.socket.send_to(&out[..len], from) {
+                        debug!("Unable to send QUIC retry to {}: {}", from, err);
+                    }
+                }
+                Err(err) => error!("Unable to create QUIC retry for {}: {}", from, err),
+            }
+            return None;
+        }
+
+        let odcid = match validate_token(&server.token_key, &from, token) {
+            Some(odcid) => odcid,
+            None => {
+                debug!(
+                    "Dropping QUIC packet from {} with an invalid address validation token",
+                    from
+                );
+                return None;
+            }
+        };
+        if header.dcid.len() != CONNECTION_ID_LEN {
+            debug!(
+                "Dropping QUIC packet from {} with an invalid connection id",
+                from
+            );
+            return None;
+        }
+
+        // The client has switched to the connection id sent in the Retry packet
+        let scid = header.dcid.to_vec();
+        let conn = match quiche::accept(&scid, Some(odcid.as_slice()), &mut server.config) {
+            Ok(conn) => conn,
+            Err(err) => {
+                error!("Unable to accept QUIC connection from {}: {}", from, err);
+                return None;
+            }
+        };
+
+        let (registration, readiness) = Registration::new2();
+        let state = Arc::new(Mutex::new(ConnectionState::new(
+            conn, from, true, readiness,
+        )));
+        if let Err(err) = mutex_lock_unwrap!(state).conn.recv(packet) {
+            debug!("Unable to process QUIC packet from {}: {}", from, err);
+        }
+
+        self.connections.insert(scid, state.clone());
+
+        Some((state, registration))
+    }
+}
+
+fn new_connection_id() -> Vec<u8> {
+    Uuid::new_v4().as_bytes().to_vec()
+}
+
+fn new_token_key() -> Result<PKey<Private>, ErrorStack> {
+    let mut key = [0; TOKEN_KEY_LEN];
+    openssl::rand::rand_bytes(&mut key)?;
+    PKey::hmac(&key)
+}
+
+/// Creates an address validation token for a client at the given address, which contains the
+/// connection id the client originally chose, the time the token was created and a MAC of both
+/// and the address.
+fn mint_token(key: &PKey<Private>, from: &SocketAddr, odcid: &[u8]) -> Result<Vec<u8>, String> {
+    let mut token = vec![0; 8];
+    BigEndian::write_u64(&mut token, now_secs());
+    token.extend_from_slice(odcid);
+
+    let mac = token_mac(key, from, &token).map_err(|err| err.to_string())?;
+    token.extend_from_slice(&mac);
+    Ok(token)
+}
+
+/// Checks an address validation token returned by a client at the given address, returning the
+/// connection id the client originally chose if the token is valid and has not expired.
+fn validate_token(key: &PKey<Private>, from: &SocketAddr, token: &[u8]) -> Option<Vec<u8>> {
+    if token.len() < 8 + TOKEN_MAC_LEN {
+        return None;
+    }
+    let (contents, mac) = token.split_at(token.len() - TOKEN_MAC_LEN);
+    let expected_mac = token_mac(key, from, contents).ok()?;
+    if !openssl::memcmp::eq(&expected_mac, mac) {
+        return None;
+    }
+
+    let created = BigEndian::read_u64(&contents[..8]);
+    let now = now_secs();
+    if created > now || now - created > TOKEN_LIFETIME_SECS {
+        return None;
+    }
+
+    Some(contents[8..].to_vec())
+}
+
+fn token_mac(
+    key: &PKey<Private>,
+    from: &SocketAddr,
+    contents: &[u8],
+) -> Result<Vec<u8>, ErrorStack> {
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    signer.update(from.to_string().as_bytes())?;
+    signer.update(contents)?;
+    signer.sign_to_vec()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the priority of a network message: heartbeats and other network-level messages have
+/// the highest priority, followed by circuit management and admin messages, with service messages
+/// having the lowest priority. Compressed messages, which are only created for large messages, and
+/// messages that cannot be read have the lowest priority.
+fn message_priority(message: &[u8]) -> usize {
+    match message_fields(message) {
+        Some((message_type, payload)) if message_type == NetworkMessageType::CIRCUIT.value() => {
+            match message_fields(payload) {
+                Some((circuit_type, _))
+                    if circuit_type == CircuitMessageType::CIRCUIT_DIRECT_MESSAGE.value()
+                        || circuit_type == CircuitMessageType::CIRCUIT_ROUTED_MESSAGE.value() =>
+                {
+                    LOW_PRIORITY
+                }
+                _ => MEDIUM_PRIORITY,
+            }
+        }
+        Some((message_type, _))
+            if message_type == NetworkMessageType::NETWORK_COMPRESSED_MESSAGE.value() =>
+        {
+            LOW_PRIORITY
+        }
+        Some(_) => HIGH_PRIORITY,
+        None => LOW_PRIORITY,
+    }
+}
+
+/// Reads the `message_type` (field 1) and `payload` (field 2) of an encoded message envelope,
+/// such as a `NetworkMessage` or `CircuitMessage`, without copying the payload.
+fn message_fields(mut bytes: &[u8]) -> Option<(i32, &[u8])> {
+    let mut message_type = 0;
+    let mut payload: &[u8] = &[];
+
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes)?;
+        let field_number = key >> 3;
+        match key & 0x7 {
+            // varint
+            0 => {
+                let value = read_varint(&mut bytes)?;
+                if field_number == 1 {
+                    message_type = value as i32;
+                }
+            }
+            // 64-bit
+            1 if bytes.len() >= 8 => bytes = &bytes[8..],
+            // length-delimited
+            2 => {
+                let len = read_varint(&mut bytes)? as usize;
+                if len > bytes.len() {
+                    return None;
+                }
+                let (value, rest) = bytes.split_at(len);
+                if field_number == 2 {
+                    payload = value;
+                }
+                bytes = rest;
+            }
+            // 32-bit
+            5 if bytes.len() >= 4 => bytes = &bytes[4..],
+            _ => return None,
+        }
+    }
+
+    Some((message_type, payload))
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+impl From<quiche::Error> for TlsInitError {
+    fn from(error: quiche::Error) -> Self {
+        TlsInitError::ProtocolError(format!("QUIC Error: {}", error))
+    }
+}
+
+impl From<quiche::Error> for ConnectError {
+    fn from(error: quiche::Error) -> Self {
+        ConnectError::ProtocolError(format!("QUIC Error: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::circuit::CircuitMessage;
+    use crate::protos::network::NetworkMessage;
+    use crate::transport::tests;
+    use crate::transport::tls::tests::with_test_tls_files;
+
+    use protobuf::Message;
+
+    fn create_test_quic_transport() -> QuicTransport {
+        with_test_tls_files(
+            true,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                QuicTransport::new(ca_cert, client_key, client_cert, server_key, server_cert)
+                    .unwrap()
+            },
+        )
+    }
+
+    fn network_message(message_type: NetworkMessageType, payload: Vec<u8>) -> Vec<u8> {
+        let mut network_msg = NetworkMessage::new();
+        network_msg.set_message_type(message_type);
+        network_msg.set_payload(payload);
+        network_msg.write_to_bytes().unwrap()
+    }
+
+    fn circuit_message(message_type: CircuitMessageType, payload: Vec<u8>) -> Vec<u8> {
+        let mut circuit_msg = CircuitMessage::new();
+        circuit_msg.set_message_type(message_type);
+        circuit_msg.set_payload(payload);
+        network_message(
+            NetworkMessageType::CIRCUIT,
+            circuit_msg.write_to_bytes().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_accepts() {
+        let transport = create_test_quic_transport();
+        assert!(transport.accepts("127.0.0.1:0"));
+        assert!(transport.accepts("quic://127.0.0.1:0"));
+        assert!(transport.accepts("quic://somewhere.example.com:4000"));
+
+        assert!(!transport.accepts("tls://somewhere.example.com:4000"));
+    }
+
+    #[test]
+    fn test_transport() {
+        let transport = create_test_quic_transport();
+        tests::test_transport(transport, "127.0.0.1:0");
+    }
+
+    #[test]
+    fn test_transport_explicit_protocol() {
+        let transport = create_test_quic_transport();
+        tests::test_transport(transport, "quic://127.0.0.1:0");
+    }
+
+    /// Test that an address validation token is only valid for the address it was created for,
+    /// and only if it has not been modified.
+    #[test]
+    fn test_address_validation_token() {
+        let key = new_token_key().unwrap();
+        let from: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let odcid = new_connection_id();
+
+        let token = mint_token(&key, &from, &odcid).unwrap();
+        assert_eq!(Some(odcid), validate_token(&key, &from, &token));
+
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        assert_eq!(None, validate_token(&key, &other, &token));
+
+        let mut modified = token.clone();
+        modified[8] ^= 0xff;
+        assert_eq!(None, validate_token(&key, &from, &modified));
+
+        assert_eq!(
+            None,
+            validate_token(&new_token_key().unwrap(), &from, &token)
+        );
+        assert_eq!(None, validate_token(&key, &from, &token[..8]));
+    }
+
+    /// Test that messages are prioritized by their message type.
+    #[test]
+    fn test_message_priority() {
+        assert_eq!(
+            HIGH_PRIORITY,
+            message_priority(&network_message(
+                NetworkMessageType::NETWORK_HEARTBEAT,
+                vec![]
+            ))
+        );
+        assert_eq!(
+            HIGH_PRIORITY,
+            message_priority(&network_message(
+                NetworkMessageType::AUTHORIZATION,
+                vec![1, 2, 3]
+            ))
+        );
+        assert_eq!(
+            MEDIUM_PRIORITY,
+            message_priority(&circuit_message(
+                CircuitMessageType::ADMIN_DIRECT_MESSAGE,
+                vec![1, 2, 3]
+            ))
+        );
+        assert_eq!(
+            LOW_PRIORITY,
+            message_priority(&circuit_message(
+                CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+                vec![1, 2, 3]
+            ))
+        );
+        assert_eq!(
+            LOW_PRIORITY,
+            message_priority(&network_message(
+                NetworkMessageType::NETWORK_COMPRESSED_MESSAGE,
+                vec![1, 2, 3]
+            ))
+        );
+        assert_eq!(LOW_PRIORITY, message_priority(&[0xff, 0xff]));
+    }
+
+    /// Test that a message larger than the maximum message size is refused without closing the
+    /// connection.
+    #[test]
+    fn test_message_too_large() {
+        let mut transport = create_test_quic_transport();
+        let mut listener = transport.listen("quic://127.0.0.1:0").unwrap();
+        let endpoint = listener.endpoint();
+
+        let heartbeat = network_message(NetworkMessageType::NETWORK_HEARTBEAT, vec![]);
+        let heartbeat_clone = heartbeat.clone();
+        let (done_tx, done_rx) = channel();
+        let handle = thread::spawn(move || {
+            let mut client = transport.connect(&endpoint).unwrap();
+            match client.send(&vec![0; MAX_MESSAGE_SIZE + 1]) {
+                Err(SendError::ProtocolError(_)) => (),
+                res => panic!("Expected protocol error, got {:?}", res),
+            }
+            client.send(&heartbeat_clone).unwrap();
+            done_rx.recv().unwrap();
+        });
+
+        let mut server = listener.accept().unwrap();
+        let message = loop {
+            match server.recv() {
+                Ok(message) => break message,
+                Err(RecvError::WouldBlock) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => panic!("Unexpected error {:?}", err),
+            }
+        };
+        assert_eq!(heartbeat, message);
+
+        done_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    /// Test that a heartbeat sent after a large service message is not blocked behind it, and
+    /// that messages of the same priority are received in order.
+    #[test]
+    fn test_no_head_of_line_blocking() {
+        let mut transport = create_test_quic_transport();
+        let mut listener = transport.listen("quic://127.0.0.1:0").unwrap();
+        let endpoint = listener.endpoint();
+
+        let large = circuit_message(
+            CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
+            vec![7; 2 * MAX_STREAM_DATA as usize],
+        );
+        let small = circuit_message(CircuitMessageType::CIRCUIT_DIRECT_MESSAGE, vec![1]);
+        let heartbeat = network_message(NetworkMessageType::NETWORK_HEARTBEAT, vec![]);
+
+        let (done_tx, done_rx) = channel();
+        let (large_clone, small_clone, heartbeat_clone) =
+            (large.clone(), small.clone(), heartbeat.clone());
+        let handle = thread::spawn(move || {
+            let mut client = transport.connect(&endpoint).unwrap();
+            client.send(&large_clone).unwrap();
+            client.send(&small_clone).unwrap();
+            client.send(&heartbeat_clone).unwrap();
+            // keep the connection open until the messages have been received
+            done_rx.recv().unwrap();
+        });
+
+        let mut server = listener.accept().unwrap();
+        let mut received = vec![];
+        while received.len() < 3 {
+            match server.recv() {
+                Ok(message) => received.push(message),
+                Err(RecvError::WouldBlock) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => panic!("Unexpected error {:?}", err),
+            }
+        }
+
+        assert_eq!(heartbeat, received[0]);
+        assert_eq!(large, received[1]);
+        assert_eq!(small, received[2]);
+
+        done_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -61,8 +61,12 @@ pub fn read<T: Read>(reader: &mut T) -> Result<Vec<u8>, RecvError> {
     }
 }
 
+/// Writes the buffer as a length-prefixed frame. If the writer cannot accept any of the frame,
+/// `SendError::WouldBlock` is returned so the caller can retry later; once part of the frame has
+/// been written, the rest is written before returning.
 pub fn write<T: Write>(writer: &mut T, buffer: &[u8]) -> Result<(), SendError> {
-    let mut packed = &pack(buffer)?[..];
+    let packed_buffer = pack(buffer)?;
+    let mut packed = &packed_buffer[..];
     while !packed.is_empty() {
         match writer.write(packed) {
             Ok(0) => {
@@ -74,6 +78,9 @@ pub fn write<T: Write>(writer: &mut T, buffer: &[u8]) -> Result<(), SendError> {
             Ok(n) => packed = &packed[n..],
             Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if packed.len() == packed_buffer.len() {
+                    return Err(SendError::WouldBlock);
+                }
                 thread::sleep(Duration::from_millis(100));
             }
             Err(e) => return Err(SendError::IoError(e)),
@@ -13,31 +13,41 @@
 // limitations under the License.
 
 use mio::{unix::EventedFd, Evented, Poll, PollOpt, Ready, Token};
+use openssl::asn1::Asn1Time;
 use openssl::error::ErrorStack;
 use openssl::ssl::{
     Error as OpensslError, HandshakeError, SslAcceptor, SslConnector, SslFiletype, SslMethod,
     SslStream, SslVerifyMode,
 };
+use openssl::x509::verify::X509VerifyFlags;
+use openssl::x509::{X509Ref, X509};
 use url::{ParseError, Url};
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::fs;
 use std::io;
 use std::net::{Ipv4Addr, Ipv6Addr, TcpListener, TcpStream};
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
 use crate::transport::rw::{read, write};
 use crate::transport::{
     AcceptError, ConnectError, Connection, DisconnectError, ListenError, Listener, RecvError,
     SendError, Transport,
 };
+use crate::{rwlock_read_unwrap, rwlock_write_unwrap};
 
 const PROTOCOL_PREFIX: &str = "tls://";
 
+pub(super) type SharedTlsConfig = Arc<RwLock<(SslAcceptor, SslConnector)>>;
+
 pub struct TlsTransport {
-    connector: SslConnector,
-    acceptor: SslAcceptor,
+    config: SharedTlsConfig,
+    files: TlsFiles,
 }
 
 impl TlsTransport {
@@ -48,49 +58,223 @@ impl TlsTransport {
         server_key: String,
         server_cert: String,
     ) -> Result<Self, TlsInitError> {
-        let client_cert_path = Path::new(&client_cert);
-        let client_key_path = Path::new(&client_key);
-        let server_cert_path = Path::new(&server_cert);
-        let server_key_path = Path::new(&server_key);
-
-        // Build TLS Connector
-        let mut connector = SslConnector::builder(SslMethod::tls())?;
-        connector.set_private_key_file(&client_key_path, SslFiletype::PEM)?;
-        connector.set_certificate_chain_file(client_cert_path)?;
-        connector.check_private_key()?;
-
-        // Build TLS Acceptor
-        let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
-        acceptor.set_private_key_file(server_key_path, SslFiletype::PEM)?;
-        acceptor.set_certificate_chain_file(&server_cert_path)?;
-        acceptor.check_private_key()?;
-
-        // if ca_cert is provided set as accept cert, otherwise set verify to none
-        let (acceptor, connector) = {
-            if let Some(ca_cert) = ca_cert {
-                let ca_cert_path = Path::new(&ca_cert);
-                acceptor.set_ca_file(ca_cert_path)?;
-                connector.set_ca_file(ca_cert_path)?;
-                let connector = connector.build();
-                let acceptor = acceptor.build();
-                (acceptor, connector)
-            } else {
-                connector.set_verify(SslVerifyMode::NONE);
-                acceptor.set_verify(SslVerifyMode::NONE);
-                let connector = connector.build();
-                let acceptor = acceptor.build();
-                (acceptor, connector)
-            }
+        let files = TlsFiles {
+            ca_cert,
+            client_key,
+            client_cert,
+            server_key,
+            server_cert,
+            crl: None,
         };
+        let config = files.build()?;
 
         Ok(TlsTransport {
-            connector,
-            acceptor,
+            config: Arc::new(RwLock::new(config)),
+            files,
         })
     }
+
+    /// Checks the certificates of peers against the certificate revocation lists (CRLs) in the
+    /// given PEM file, rejecting revoked certificates. The file must contain a CRL issued by the
+    /// CA of each peer's certificate, and a CA certificate must have been provided.
+    pub fn with_crl_file(mut self, crl_file: String) -> Result<Self, TlsInitError> {
+        self.files.crl = Some(crl_file);
+        *rwlock_write_unwrap!(self.config) = self.files.build()?;
+        Ok(self)
+    }
+
+    /// Returns the TLS configuration used by this transport, which is replaced when the files are
+    /// reloaded.
+    pub(super) fn shared_config(&self) -> SharedTlsConfig {
+        self.config.clone()
+    }
+
+    /// Returns a reloader that replaces the certificates, keys and CRLs used by this transport
+    /// with the current contents of their files.
+    pub fn reloader(&self) -> TlsReloader {
+        TlsReloader {
+            config: self.config.clone(),
+            modified: self.files.modified(),
+            files: self.files.clone(),
+        }
+    }
+}
+
+/// Reloads the certificates, keys and CRLs of a `TlsTransport` from their files.
+///
+/// Connections made or accepted after a reload use the reloaded files; existing connections are
+/// not affected.
+#[derive(Clone)]
+pub struct TlsReloader {
+    config: SharedTlsConfig,
+    files: TlsFiles,
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl TlsReloader {
+    /// Reloads the files. If any of the files cannot be loaded, the transport continues to use
+    /// the previously loaded files.
+    pub fn reload(&mut self) -> Result<(), TlsInitError> {
+        self.modified = self.files.modified();
+        *rwlock_write_unwrap!(self.config) = self.files.build()?;
+        Ok(())
+    }
+
+    /// Reloads the files if any of them have been modified since they were last loaded, or since
+    /// the last attempt to load them failed. Returns whether the files were reloaded.
+    pub fn reload_if_changed(&mut self) -> Result<bool, TlsInitError> {
+        if self.files.modified() == self.modified {
+            return Ok(false);
+        }
+
+        self.reload()?;
+        Ok(true)
+    }
+}
+
+/// The files the TLS configuration of a transport is loaded from.
+#[derive(Clone)]
+struct TlsFiles {
+    ca_cert: Option<String>,
+    client_key: String,
+    client_cert: String,
+    server_key: String,
+    server_cert: String,
+    crl: Option<String>,
+}
+
+impl TlsFiles {
+    fn build(&self) -> Result<(SslAcceptor, SslConnector), TlsInitError> {
+        build_tls_config_with_crl(
+            self.ca_cert.clone(),
+            self.client_key.clone(),
+            self.client_cert.clone(),
+            self.server_key.clone(),
+            self.server_cert.clone(),
+            self.crl.as_deref(),
+        )
+    }
+
+    /// Returns the modification time of each file, if it can be read.
+    fn modified(&self) -> HashMap<String, Option<SystemTime>> {
+        self.ca_cert
+            .iter()
+            .chain(self.crl.iter())
+            .chain(
+                [
+                    &self.client_key,
+                    &self.client_cert,
+                    &self.server_key,
+                    &self.server_cert,
+                ]
+                .iter()
+                .cloned(),
+            )
+            .map(|file| {
+                let modified = fs::metadata(file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (file.clone(), modified)
+            })
+            .collect()
+    }
 }
 
-fn endpoint_to_dns_name(endpoint: &str) -> Result<String, ParseError> {
+/// Returns the number of whole days until the certificate in the given PEM file expires, or a
+/// negative number if it has already expired.
+pub fn days_until_expiry(cert_file: &str) -> Result<i32, TlsInitError> {
+    let pem = fs::read(cert_file).map_err(|err| {
+        TlsInitError::ProtocolError(format!("Unable to read {}: {}", cert_file, err))
+    })?;
+    let cert = X509::from_pem(&pem)?;
+    cert_days_until_expiry(&cert).map_err(TlsInitError::from)
+}
+
+/// Returns the number of whole days until the given certificate expires, or a negative number if
+/// it has already expired.
+pub fn cert_days_until_expiry(cert: &X509Ref) -> Result<i32, ErrorStack> {
+    let now = Asn1Time::days_from_now(0)?;
+    Ok(now.diff(cert.not_after())?.days)
+}
+
+/// Builds the TLS acceptor and connector used by transports that secure their connections with
+/// TLS. If no CA certificate is provided, peer certificates are not verified.
+pub(super) fn build_tls_config(
+    ca_cert: Option<String>,
+    client_key: String,
+    client_cert: String,
+    server_key: String,
+    server_cert: String,
+) -> Result<(SslAcceptor, SslConnector), TlsInitError> {
+    build_tls_config_with_crl(
+        ca_cert,
+        client_key,
+        client_cert,
+        server_key,
+        server_cert,
+        None,
+    )
+}
+
+fn build_tls_config_with_crl(
+    ca_cert: Option<String>,
+    client_key: String,
+    client_cert: String,
+    server_key: String,
+    server_cert: String,
+    crl_file: Option<&str>,
+) -> Result<(SslAcceptor, SslConnector), TlsInitError> {
+    let client_cert_path = Path::new(&client_cert);
+    let client_key_path = Path::new(&client_key);
+    let server_cert_path = Path::new(&server_cert);
+    let server_key_path = Path::new(&server_key);
+
+    // Build TLS Connector
+    let mut connector = SslConnector::builder(SslMethod::tls())?;
+    connector.set_private_key_file(&client_key_path, SslFiletype::PEM)?;
+    connector.set_certificate_chain_file(client_cert_path)?;
+    connector.check_private_key()?;
+
+    // Build TLS Acceptor
+    let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
+    acceptor.set_private_key_file(server_key_path, SslFiletype::PEM)?;
+    acceptor.set_certificate_chain_file(&server_cert_path)?;
+    acceptor.check_private_key()?;
+
+    // if ca_cert is provided set as accept cert, otherwise set verify to none
+    let ca_cert_provided = ca_cert.is_some();
+    if let Some(ca_cert) = ca_cert {
+        let ca_cert_path = Path::new(&ca_cert);
+        acceptor.set_ca_file(ca_cert_path)?;
+        connector.set_ca_file(ca_cert_path)?;
+    } else {
+        connector.set_verify(SslVerifyMode::NONE);
+        acceptor.set_verify(SslVerifyMode::NONE);
+    }
+
+    if let Some(crl_file) = crl_file {
+        if !ca_cert_provided {
+            return Err(TlsInitError::ProtocolError(
+                "a CA certificate is required to check certificate revocation".into(),
+            ));
+        }
+
+        // Loading the file into the trusted store adds the CRLs it contains
+        let crl_path = Path::new(crl_file);
+        acceptor.set_ca_file(crl_path)?;
+        connector.set_ca_file(crl_path)?;
+        acceptor
+            .verify_param_mut()
+            .set_flags(X509VerifyFlags::CRL_CHECK)?;
+        connector
+            .verify_param_mut()
+            .set_flags(X509VerifyFlags::CRL_CHECK)?;
+    }
+
+    Ok((acceptor.build(), connector.build()))
+}
+
+pub(super) fn endpoint_to_dns_name(endpoint: &str) -> Result<String, ParseError> {
     let mut address = String::from("tcp://");
     address.push_str(endpoint);
     let url = Url::parse(&address)?;
@@ -125,7 +309,8 @@ impl Transport for TlsTransport {
         let dns_name = endpoint_to_dns_name(address)?;
 
         let stream = TcpStream::connect(address)?;
-        let tls_stream = self.connector.connect(&dns_name, stream)?;
+        let connector = rwlock_read_unwrap!(self.config).1.clone();
+        let tls_stream = connector.connect(&dns_name, stream)?;
 
         tls_stream.get_ref().set_nonblocking(true)?;
         let connection = TlsConnection { stream: tls_stream };
@@ -148,20 +333,21 @@ impl Transport for TlsTransport {
 
         Ok(Box::new(TlsListener {
             listener: TcpListener::bind(address)?,
-            acceptor: self.acceptor.clone(),
+            config: self.config.clone(),
         }))
     }
 }
 
 pub struct TlsListener {
     listener: TcpListener,
-    acceptor: SslAcceptor,
+    config: SharedTlsConfig,
 }
 
 impl Listener for TlsListener {
     fn accept(&mut self) -> Result<Box<dyn Connection>, AcceptError> {
         let (stream, _) = self.listener.accept()?;
-        let tls_stream = self.acceptor.accept(stream)?;
+        let acceptor = rwlock_read_unwrap!(self.config).0.clone();
+        let tls_stream = acceptor.accept(stream)?;
         tls_stream.get_ref().set_nonblocking(true)?;
         let connection = TlsConnection { stream: tls_stream };
         Ok(Box::new(connection))
@@ -296,6 +482,7 @@ pub(crate) mod tests {
     use openssl::hash::MessageDigest;
     use openssl::pkey::{PKey, PKeyRef, Private};
     use openssl::rsa::Rsa;
+    use openssl::sign::Signer;
     use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, KeyUsage};
     use openssl::x509::{X509NameBuilder, X509Ref, X509};
     use std::fs::File;
@@ -327,7 +514,7 @@ pub(crate) mod tests {
             .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
             .unwrap();
         cert_builder
-            .append_extension(KeyUsage::new().key_cert_sign().build().unwrap())
+            .append_extension(KeyUsage::new().key_cert_sign().crl_sign().build().unwrap())
             .unwrap();
 
         cert_builder
@@ -395,7 +582,102 @@ pub(crate) mod tests {
         path
     }
 
+    /// Encodes a DER element with the given tag and contents.
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        let len = contents.len();
+        if len < 0x80 {
+            element.push(len as u8);
+        } else if len < 0x100 {
+            element.extend_from_slice(&[0x81, len as u8]);
+        } else {
+            element.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]);
+        }
+        element.extend_from_slice(contents);
+        element
+    }
+
+    /// Writes a CRL, signed by the CA whose certificate is in the given file, that revokes the
+    /// certificates in the given files, and returns the path to the CRL file.
+    fn write_test_crl(ca_cert_file: &str, revoked_cert_files: &[&str]) -> String {
+        const SEQUENCE: u8 = 0x30;
+        const INTEGER: u8 = 0x02;
+        const UTC_TIME: u8 = 0x17;
+        const BIT_STRING: u8 = 0x03;
+        // sha256WithRSAEncryption
+        const SIGNATURE_ALGORITHM: &[u8] = &[
+            0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b, 0x05,
+            0x00,
+        ];
+
+        let dir = Path::new(ca_cert_file).parent().unwrap().to_path_buf();
+        let ca_cert = X509::from_pem(&fs::read(ca_cert_file).unwrap()).unwrap();
+        let ca_key = PKey::private_key_from_pem(&fs::read(dir.join("ca.key")).unwrap()).unwrap();
+
+        let revoked = revoked_cert_files
+            .iter()
+            .map(|cert_file| {
+                let cert = X509::from_pem(&fs::read(cert_file).unwrap()).unwrap();
+                let mut serial = cert.serial_number().to_bn().unwrap().to_vec();
+                if serial.first().map(|byte| byte & 0x80 != 0).unwrap_or(true) {
+                    serial.insert(0, 0);
+                }
+                let mut entry = der(INTEGER, &serial);
+                entry.extend(der(UTC_TIME, b"200101000000Z"));
+                der(SEQUENCE, &entry)
+            })
+            .collect::<Vec<_>>()
+            .concat();
+
+        let mut tbs = der(INTEGER, &[1]);
+        tbs.extend_from_slice(SIGNATURE_ALGORITHM);
+        tbs.extend(ca_cert.subject_name().to_der().unwrap());
+        tbs.extend(der(UTC_TIME, b"200101000000Z"));
+        tbs.extend(der(UTC_TIME, b"491231235959Z"));
+        if !revoked.is_empty() {
+            tbs.extend(der(SEQUENCE, &revoked));
+        }
+        let tbs = der(SEQUENCE, &tbs);
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &ca_key).unwrap();
+        signer.update(&tbs).unwrap();
+        let mut signature = vec![0];
+        signature.extend(signer.sign_to_vec().unwrap());
+
+        let mut crl = tbs;
+        crl.extend_from_slice(SIGNATURE_ALGORITHM);
+        crl.extend(der(BIT_STRING, &signature));
+        let crl = der(SEQUENCE, &crl);
+
+        let encoded = openssl::base64::encode_block(&crl);
+        let mut pem = String::from("-----BEGIN X509 CRL-----\n");
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).unwrap());
+            pem.push('\n');
+        }
+        pem.push_str("-----END X509 CRL-----\n");
+
+        write_file(dir, "crl.pem", pem.as_bytes())
+    }
+
     pub fn create_test_tls_transport(insecure: bool) -> TlsTransport {
+        with_test_tls_files(
+            insecure,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                TlsTransport::new(ca_cert, client_key, client_cert, server_key, server_cert)
+                    .unwrap()
+            },
+        )
+    }
+
+    /// Generates a CA and CA-signed client and server keys and certificates in a temporary
+    /// directory, and calls the given function with the paths to the files. Unless `insecure` is
+    /// set, the CA's key is written to `ca.key` next to its certificate. The files are removed
+    /// once the function returns.
+    pub fn with_test_tls_files<F, T>(insecure: bool, f: F) -> T
+    where
+        F: FnOnce(Option<String>, String, String, String, String) -> T,
+    {
         // Genearte Certificat Authority keys and certificate
         let (ca_key, ca_cert) = make_ca_cert();
 
@@ -411,6 +693,12 @@ pub(crate) mod tests {
                     "ca.cert",
                     &ca_cert.to_pem().unwrap(),
                 );
+                // kept next to the CA certificate so tests can sign CRLs with it
+                write_file(
+                    temp_dir_path.to_path_buf(),
+                    "ca.key",
+                    &ca_key.private_key_to_pem_pkcs8().unwrap(),
+                );
                 Some(ca_path_file)
             }
         };
@@ -443,15 +731,13 @@ pub(crate) mod tests {
             &server_key.private_key_to_pem_pkcs8().unwrap(),
         );
 
-        // Create TLsTransport
-        TlsTransport::new(
+        f(
             ca_path_file,
             client_key_file,
             client_cert_file,
             server_key_file,
             server_cert_file,
         )
-        .unwrap()
     }
 
     #[test]
@@ -493,4 +779,141 @@ pub(crate) mod tests {
             Ready::readable() | Ready::writable(),
         );
     }
+
+    /// Test that reloading the files of a transport keeps its existing connections open, and that
+    /// files are only reloaded by `reload_if_changed` once they have been modified.
+    #[test]
+    fn test_reload() {
+        with_test_tls_files(
+            true,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                let mut transport = TlsTransport::new(
+                    ca_cert,
+                    client_key,
+                    client_cert.clone(),
+                    server_key,
+                    server_cert,
+                )
+                .unwrap();
+                let mut reloader = transport.reloader();
+                assert!(!reloader.reload_if_changed().unwrap());
+
+                let mut listener = transport.listen("127.0.0.1:0").unwrap();
+                let endpoint = listener.endpoint();
+
+                let handle = std::thread::spawn(move || {
+                    let mut first = listener.accept().unwrap();
+                    let mut second = listener.accept().unwrap();
+                    assert_eq!(b"first".to_vec(), first.recv().unwrap());
+                    assert_eq!(b"second".to_vec(), second.recv().unwrap());
+                });
+
+                let mut first = transport.connect(&endpoint).unwrap();
+
+                // Rewrite the client certificate, as rotating it would; wait first so the
+                // modification time changes
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                let cert = fs::read(&client_cert).unwrap();
+                fs::write(&client_cert, cert).unwrap();
+
+                assert!(reloader.reload_if_changed().unwrap());
+                assert!(!reloader.reload_if_changed().unwrap());
+
+                let mut second = transport.connect(&endpoint).unwrap();
+                first.send(b"first").unwrap();
+                second.send(b"second").unwrap();
+
+                handle.join().unwrap();
+            },
+        )
+    }
+
+    /// Test that the days until a certificate expires are read from its file.
+    #[test]
+    fn test_days_until_expiry() {
+        with_test_tls_files(true, |_, _, client_cert, _, _| {
+            let days = days_until_expiry(&client_cert).unwrap();
+            assert!(days == 364 || days == 365, "unexpected days: {}", days);
+
+            assert!(days_until_expiry("/nonexistent/cert.pem").is_err());
+        })
+    }
+
+    /// Test that checking certificate revocation requires a CA certificate.
+    #[test]
+    fn test_crl_requires_ca() {
+        let transport = create_test_tls_transport(true);
+        match transport.with_crl_file("/nonexistent/crl.pem".into()) {
+            Err(TlsInitError::ProtocolError(_)) => (),
+            Err(err) => panic!("Unexpected error {}", err),
+            Ok(_) => panic!("Unexpected successful result"),
+        }
+    }
+
+    /// Test that a peer whose certificate is revoked by the CRL file is refused.
+    #[test]
+    fn test_crl_revoked_cert() {
+        with_test_tls_files(
+            false,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                let crl_file = write_test_crl(ca_cert.as_ref().unwrap(), &[&server_cert]);
+                let mut transport =
+                    TlsTransport::new(ca_cert, client_key, client_cert, server_key, server_cert)
+                        .unwrap()
+                        .with_crl_file(crl_file)
+                        .unwrap();
+
+                let mut listener = transport.listen("127.0.0.1:0").unwrap();
+                let endpoint = listener.endpoint();
+                let handle = std::thread::spawn(move || {
+                    let _ = listener.accept();
+                });
+
+                assert!(transport.connect(&endpoint).is_err());
+                handle.join().unwrap();
+            },
+        )
+    }
+
+    /// Test that reloading picks up a changed CRL file: a peer that was accepted before the reload
+    /// is refused once the reloaded CRL revokes its certificate.
+    #[test]
+    fn test_reload_crl() {
+        with_test_tls_files(
+            false,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                let ca_cert_file = ca_cert.clone().unwrap();
+                let crl_file = write_test_crl(&ca_cert_file, &[]);
+                let mut transport = TlsTransport::new(
+                    ca_cert,
+                    client_key,
+                    client_cert,
+                    server_key,
+                    server_cert.clone(),
+                )
+                .unwrap()
+                .with_crl_file(crl_file)
+                .unwrap();
+                let mut reloader = transport.reloader();
+
+                let mut listener = transport.listen("127.0.0.1:0").unwrap();
+                let endpoint = listener.endpoint();
+                let handle = std::thread::spawn(move || {
+                    let first = listener.accept().unwrap();
+                    let _ = listener.accept();
+                    drop(first);
+                });
+
+                let _first = transport.connect(&endpoint).unwrap();
+
+                // Wait first so the modification time changes
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                write_test_crl(&ca_cert_file, &[&server_cert]);
+                assert!(reloader.reload_if_changed().unwrap());
+
+                assert!(transport.connect(&endpoint).is_err());
+                handle.join().unwrap();
+            },
+        )
+    }
 }
@@ -0,0 +1,345 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A transport over Unix domain sockets, for processes running on the same host.
+//!
+//! Endpoints use the `unix://` scheme followed by the path of the socket file, for example
+//! `unix:///var/run/splinter/service.sock`. Access to a listening socket is controlled by the
+//! permissions of its socket file.
+
+use mio::{unix::EventedFd, Evented, Poll, PollOpt, Ready, Token};
+
+use std::fs;
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener as StdUnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::transport::rw::{read, write};
+use crate::transport::{
+    AcceptError, ConnectError, Connection, DisconnectError, ListenError, Listener, RecvError,
+    SendError, Transport,
+};
+
+const PROTOCOL_PREFIX: &str = "unix://";
+
+/// The default permissions of socket files created by a `UnixTransport`, which allow connections
+/// from processes running as the owner or group of the socket file.
+pub const DEFAULT_SOCKET_PERMISSIONS: u32 = 0o660;
+
+pub struct UnixTransport {
+    permissions: u32,
+}
+
+impl UnixTransport {
+    /// Sets the permissions of the socket files created when listening. Only processes allowed to
+    /// write to a socket file may connect to it.
+    pub fn with_permissions(mut self, permissions: u32) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+impl Default for UnixTransport {
+    fn default() -> Self {
+        UnixTransport {
+            permissions: DEFAULT_SOCKET_PERMISSIONS,
+        }
+    }
+}
+
+impl Transport for UnixTransport {
+    fn accepts(&self, address: &str) -> bool {
+        address.starts_with(PROTOCOL_PREFIX)
+    }
+
+    fn connect(&mut self, endpoint: &str) -> Result<Box<dyn Connection>, ConnectError> {
+        if !self.accepts(endpoint) {
+            return Err(ConnectError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                endpoint
+            )));
+        }
+
+        let path = PathBuf::from(&endpoint[PROTOCOL_PREFIX.len()..]);
+        let stream = UnixStream::connect(&path)?;
+        stream.set_nonblocking(true)?;
+        Ok(Box::new(UnixConnection { stream, path }))
+    }
+
+    fn listen(&mut self, bind: &str) -> Result<Box<dyn Listener>, ListenError> {
+        if !self.accepts(bind) {
+            return Err(ListenError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                bind
+            )));
+        }
+
+        let path = PathBuf::from(&bind[PROTOCOL_PREFIX.len()..]);
+        remove_stale_socket(&path)?;
+
+        let listener = bind_with_permissions(&path, self.permissions)?;
+
+        Ok(Box::new(UnixListener { listener, path }))
+    }
+}
+
+/// Binds a socket file at the given path with the given permissions.
+///
+/// A newly bound socket file gets its permissions from the process umask, so it may briefly be
+/// open to any process before its permissions are set. To avoid this, the socket is bound in a
+/// new directory that only the current user can access, and linked to the given path once its
+/// permissions are set. Nothing already at the given path is replaced.
+fn bind_with_permissions(path: &Path, permissions: u32) -> Result<StdUnixListener, ListenError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        ListenError::ProtocolError(format!("Invalid socket path {}", path.display()))
+    })?;
+    let bind_dir = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    let bind_path = bind_dir.join("socket");
+
+    // Fails if the directory exists, as it may have been created by another user
+    fs::DirBuilder::new().mode(0o700).create(&bind_dir)?;
+
+    // The socket file is linked into place rather than renamed, as linking fails instead of
+    // replacing whatever is already at the path
+    let result = StdUnixListener::bind(&bind_path).and_then(|listener| {
+        fs::set_permissions(&bind_path, fs::Permissions::from_mode(permissions))?;
+        fs::hard_link(&bind_path, path)?;
+        Ok(listener)
+    });
+
+    let _ = fs::remove_file(&bind_path);
+    fs::remove_dir(&bind_dir)?;
+
+    result.map_err(|err| match err.kind() {
+        io::ErrorKind::AlreadyExists => ListenError::ProtocolError(format!(
+            "Unable to bind {}: the path already exists",
+            path.display()
+        )),
+        _ => ListenError::from(err),
+    })
+}
+
+/// Removes a socket file left behind by a listener that was not shut down cleanly. A socket file
+/// that still has a listener, or a path that is not a socket file, is left in place so binding to
+/// it fails.
+fn remove_stale_socket(path: &Path) -> Result<(), ListenError> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if UnixStream::connect(path).is_err() {
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(ListenError::from(err)),
+    }
+}
+
+pub struct UnixListener {
+    listener: StdUnixListener,
+    path: PathBuf,
+}
+
+impl Listener for UnixListener {
+    fn accept(&mut self) -> Result<Box<dyn Connection>, AcceptError> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nonblocking(true)?;
+        let connection = UnixConnection {
+            stream,
+            path: self.path.clone(),
+        };
+        Ok(Box::new(connection))
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}{}", PROTOCOL_PREFIX, self.path.display())
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            debug!(
+                "Unable to remove socket file {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// A connection over a Unix domain socket. Both ends of the connection report the path of the
+/// listening socket file as their endpoints, as the connecting end is not bound to a path.
+pub struct UnixConnection {
+    stream: UnixStream,
+    path: PathBuf,
+}
+
+impl Connection for UnixConnection {
+    fn send(&mut self, message: &[u8]) -> Result<(), SendError> {
+        write(&mut self.stream, message)
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, RecvError> {
+        read(&mut self.stream)
+    }
+
+    fn remote_endpoint(&self) -> String {
+        format!("{}{}", PROTOCOL_PREFIX, self.path.display())
+    }
+
+    fn local_endpoint(&self) -> String {
+        format!("{}{}", PROTOCOL_PREFIX, self.path.display())
+    }
+
+    fn disconnect(&mut self) -> Result<(), DisconnectError> {
+        self.stream
+            .shutdown(Shutdown::Both)
+            .map_err(DisconnectError::from)
+    }
+
+    fn evented(&self) -> &dyn Evented {
+        self
+    }
+}
+
+impl AsRawFd for UnixConnection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl Evented for UnixConnection {
+    fn register(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).deregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::tests;
+
+    use tempdir::TempDir;
+
+    fn socket_endpoint(temp_dir: &TempDir, name: &str) -> String {
+        format!(
+            "{}{}",
+            PROTOCOL_PREFIX,
+            temp_dir.path().join(name).display()
+        )
+    }
+
+    #[test]
+    fn test_accepts() {
+        let transport = UnixTransport::default();
+        assert!(transport.accepts("unix:///var/run/splinter/service.sock"));
+
+        assert!(!transport.accepts("127.0.0.1:0"));
+        assert!(!transport.accepts("tcp://127.0.0.1:0"));
+    }
+
+    #[test]
+    fn test_transport() {
+        let temp_dir = TempDir::new("unix-transport-test").unwrap();
+        let transport = UnixTransport::default();
+
+        tests::test_transport(transport, &socket_endpoint(&temp_dir, "transport.sock"));
+    }
+
+    #[test]
+    fn test_poll() {
+        let temp_dir = TempDir::new("unix-transport-test").unwrap();
+        let transport = UnixTransport::default();
+        tests::test_poll(
+            transport,
+            &socket_endpoint(&temp_dir, "poll.sock"),
+            Ready::readable() | Ready::writable(),
+        );
+    }
+
+    /// Test that the socket file is created with the configured permissions, without leaving the
+    /// directory it was bound in behind, that a stale socket file is replaced when listening, and
+    /// that the socket file is removed with the listener.
+    #[test]
+    fn test_socket_file() {
+        let temp_dir = TempDir::new("unix-transport-test").unwrap();
+        let path = temp_dir.path().join("listener.sock");
+        let endpoint = socket_endpoint(&temp_dir, "listener.sock");
+
+        // leave a socket file behind, as a listener that exited uncleanly would
+        let stale = StdUnixListener::bind(&path).unwrap();
+        drop(stale);
+        assert!(path.exists());
+
+        let mut transport = UnixTransport::default().with_permissions(0o600);
+        let listener = transport.listen(&endpoint).unwrap();
+        assert_eq!(endpoint, listener.endpoint());
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+
+        // only the socket file is left in the directory
+        assert_eq!(1, fs::read_dir(temp_dir.path()).unwrap().count());
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    /// Test that listening on a path that exists, and is not a socket file, fails and leaves the
+    /// existing file in place.
+    #[test]
+    fn test_existing_path() {
+        let temp_dir = TempDir::new("unix-transport-test").unwrap();
+        let path = temp_dir.path().join("listener.sock");
+        fs::write(&path, b"not a socket").unwrap();
+
+        let mut transport = UnixTransport::default();
+        assert!(transport
+            .listen(&socket_endpoint(&temp_dir, "listener.sock"))
+            .is_err());
+
+        assert_eq!(b"not a socket".to_vec(), fs::read(&path).unwrap());
+        assert_eq!(1, fs::read_dir(temp_dir.path()).unwrap().count());
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A transport that carries messages over WebSocket connections.
+//!
+//! Each message is sent as a single binary WebSocket message. Endpoints use the `ws://` scheme
+//! for plain connections, or the `wss://` scheme for connections secured with TLS, which allows
+//! nodes to connect to each other through networks that only allow HTTP(S) traffic.
+
+use mio::{unix::EventedFd, Evented, Poll, PollOpt, Ready, Token};
+use openssl::ssl::SslStream;
+use tungstenite::{Error as WsError, Message, WebSocket};
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::rwlock_read_unwrap;
+use crate::transport::tls::{
+    build_tls_config, endpoint_to_dns_name, SharedTlsConfig, TlsInitError, TlsTransport,
+};
+use crate::transport::{
+    AcceptError, ConnectError, Connection, DisconnectError, ListenError, Listener, RecvError,
+    SendError, Transport,
+};
+
+const WS_PROTOCOL_PREFIX: &str = "ws://";
+const WSS_PROTOCOL_PREFIX: &str = "wss://";
+
+/// A transport for `ws://` and, if configured with TLS, `wss://` endpoints.
+///
+/// Endpoints and bind strings provided without a protocol use `wss://` if the transport is
+/// configured with TLS, and `ws://` otherwise.
+#[derive(Default)]
+pub struct WsTransport {
+    tls: Option<SharedTlsConfig>,
+}
+
+impl WsTransport {
+    /// Construct a new WsTransport that accepts both `ws://` and `wss://` endpoints.
+    pub fn new_secure(
+        ca_cert: Option<String>,
+        client_key: String,
+        client_cert: String,
+        server_key: String,
+        server_cert: String,
+    ) -> Result<Self, TlsInitError> {
+        let tls = build_tls_config(ca_cert, client_key, client_cert, server_key, server_cert)?;
+
+        Ok(WsTransport {
+            tls: Some(Arc::new(RwLock::new(tls))),
+        })
+    }
+
+    /// Construct a new WsTransport that accepts both `ws://` and `wss://` endpoints, securing
+    /// `wss://` connections with the TLS configuration of the given `TlsTransport`. The CRLs
+    /// checked by the TLS transport are checked by this transport as well, and files reloaded
+    /// with the TLS transport's reloader are used by both transports.
+    pub fn from_tls_transport(tls_transport: &TlsTransport) -> Self {
+        WsTransport {
+            tls: Some(tls_transport.shared_config()),
+        }
+    }
+
+    /// Splits the given endpoint into whether it is secure and the address portion of the
+    /// endpoint.
+    fn parse_endpoint<'a>(&self, endpoint: &'a str) -> (bool, &'a str) {
+        if endpoint.starts_with(WS_PROTOCOL_PREFIX) {
+            (false, &endpoint[WS_PROTOCOL_PREFIX.len()..])
+        } else if endpoint.starts_with(WSS_PROTOCOL_PREFIX) {
+            (true, &endpoint[WSS_PROTOCOL_PREFIX.len()..])
+        } else {
+            (self.tls.is_some(), endpoint)
+        }
+    }
+}
+
+impl Transport for WsTransport {
+    fn accepts(&self, address: &str) -> bool {
+        address.starts_with(WS_PROTOCOL_PREFIX)
+            || (address.starts_with(WSS_PROTOCOL_PREFIX) && self.tls.is_some())
+            || !address.contains("://")
+    }
+
+    fn connect(&mut self, endpoint: &str) -> Result<Box<dyn Connection>, ConnectError> {
+        if !self.accepts(endpoint) {
+            return Err(ConnectError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                endpoint
+            )));
+        }
+
+        let (secure, address) = self.parse_endpoint(endpoint);
+        // The path, if any, is only part of the handshake request
+        let socket_address = address.splitn(2, '/').next().unwrap_or(address);
+
+        // Connect and perform the handshakes in blocking mode
+        let stream = TcpStream::connect(socket_address)?;
+        let (stream, url) = match (secure, self.tls.as_ref()) {
+            (true, Some(config)) => {
+                let dns_name = endpoint_to_dns_name(socket_address)?;
+                let connector = rwlock_read_unwrap!(config).1.clone();
+                let tls_stream = connector.connect(&dns_name, stream)?;
+                (WsStream::Tls(tls_stream), format!("wss://{}", address))
+            }
+            _ => (WsStream::Plain(stream), format!("ws://{}", address)),
+        };
+
+        let (websocket, _) = tungstenite::client(url.as_str(), stream).map_err(|err| {
+            ConnectError::ProtocolError(format!("WebSocket Handshake Err: {}", err))
+        })?;
+
+        let connection = WsConnection::new(websocket, secure)?;
+        Ok(Box::new(connection))
+    }
+
+    fn listen(&mut self, bind: &str) -> Result<Box<dyn Listener>, ListenError> {
+        if !self.accepts(bind) {
+            return Err(ListenError::ProtocolError(format!(
+                "Invalid protocol \"{}\"",
+                bind
+            )));
+        }
+
+        let (secure, address) = self.parse_endpoint(bind);
+        let socket_address = address.splitn(2, '/').next().unwrap_or(address);
+        let tls = if secure { self.tls.clone() } else { None };
+
+        Ok(Box::new(WsListener {
+            listener: TcpListener::bind(socket_address)?,
+            tls,
+        }))
+    }
+}
+
+pub struct WsListener {
+    listener: TcpListener,
+    tls: Option<SharedTlsConfig>,
+}
+
+impl Listener for WsListener {
+    fn accept(&mut self) -> Result<Box<dyn Connection>, AcceptError> {
+        let (stream, _) = self.listener.accept()?;
+        let stream = match self.tls.as_ref() {
+            Some(config) => {
+                let acceptor = rwlock_read_unwrap!(config).0.clone();
+                WsStream::Tls(acceptor.accept(stream)?)
+            }
+            None => WsStream::Plain(stream),
+        };
+
+        let websocket = tungstenite::accept(stream).map_err(|err| {
+            AcceptError::ProtocolError(format!("WebSocket Handshake Err: {}", err))
+        })?;
+
+        let connection = WsConnection::new(websocket, self.tls.is_some())?;
+        Ok(Box::new(connection))
+    }
+
+    fn endpoint(&self) -> String {
+        endpoint(self.tls.is_some(), self.listener.local_addr().unwrap())
+    }
+}
+
+pub struct WsConnection {
+    websocket: WebSocket<WsStream>,
+    secure: bool,
+}
+
+impl WsConnection {
+    fn new(websocket: WebSocket<WsStream>, secure: bool) -> Result<Self, io::Error> {
+        websocket.get_ref().tcp_stream().set_nonblocking(true)?;
+        Ok(WsConnection { websocket, secure })
+    }
+}
+
+impl Connection for WsConnection {
+    fn send(&mut self, message: &[u8]) -> Result<(), SendError> {
+        let mut result = self
+            .websocket
+            .write_message(Message::Binary(message.to_vec()));
+        loop {
+            match result {
+                Ok(()) => return Ok(()),
+                // The message has been queued; keep flushing until it has been written
+                Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(100));
+                    result = self.websocket.write_pending();
+                }
+                Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::Interrupted => {
+                    result = self.websocket.write_pending();
+                }
+                Err(err) => return Err(SendError::from(err)),
+            }
+        }
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, RecvError> {
+        loop {
+            match self.websocket.read_message() {
+                Ok(Message::Binary(bytes)) => return Ok(bytes),
+                Ok(Message::Close(_)) => return Err(RecvError::Disconnected),
+                // Pings are answered by the websocket itself
+                Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => continue,
+                Ok(Message::Text(_)) => {
+                    return Err(RecvError::ProtocolError(
+                        "Received unexpected text message".into(),
+                    ))
+                }
+                Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(RecvError::from(err)),
+            }
+        }
+    }
+
+    fn remote_endpoint(&self) -> String {
+        endpoint(
+            self.secure,
+            self.websocket.get_ref().tcp_stream().peer_addr().unwrap(),
+        )
+    }
+
+    fn local_endpoint(&self) -> String {
+        endpoint(
+            self.secure,
+            self.websocket.get_ref().tcp_stream().local_addr().unwrap(),
+        )
+    }
+
+    fn disconnect(&mut self) -> Result<(), DisconnectError> {
+        match self.websocket.close(None) {
+            Ok(()) | Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => (),
+            Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(err) => return Err(DisconnectError::from(err)),
+        }
+
+        self.websocket
+            .get_ref()
+            .tcp_stream()
+            .shutdown(Shutdown::Both)
+            .map_err(DisconnectError::from)
+    }
+
+    fn evented(&self) -> &dyn Evented {
+        self
+    }
+}
+
+impl AsRawFd for WsConnection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.websocket.get_ref().tcp_stream().as_raw_fd()
+    }
+}
+
+impl Evented for WsConnection {
+    fn register(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.as_raw_fd()).deregister(poll)
+    }
+}
+
+fn endpoint(secure: bool, address: SocketAddr) -> String {
+    if secure {
+        format!("{}{}", WSS_PROTOCOL_PREFIX, address)
+    } else {
+        format!("{}{}", WS_PROTOCOL_PREFIX, address)
+    }
+}
+
+/// The stream underlying a websocket, which is either a plain TCP stream or a TLS stream.
+enum WsStream {
+    Plain(TcpStream),
+    Tls(SslStream<TcpStream>),
+}
+
+impl WsStream {
+    fn tcp_stream(&self) -> &TcpStream {
+        match self {
+            WsStream::Plain(stream) => stream,
+            WsStream::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for WsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            WsStream::Plain(stream) => stream.read(buf),
+            WsStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for WsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            WsStream::Plain(stream) => stream.write(buf),
+            WsStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            WsStream::Plain(stream) => stream.flush(),
+            WsStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl From<WsError> for SendError {
+    fn from(err: WsError) -> Self {
+        match err {
+            WsError::Io(err) => SendError::IoError(err),
+            WsError::ConnectionClosed | WsError::AlreadyClosed => SendError::Disconnected,
+            err => SendError::ProtocolError(format!("WebSocket Err: {}", err)),
+        }
+    }
+}
+
+impl From<WsError> for RecvError {
+    fn from(err: WsError) -> Self {
+        match err {
+            WsError::Io(err) => RecvError::IoError(err),
+            WsError::ConnectionClosed | WsError::AlreadyClosed => RecvError::Disconnected,
+            err => RecvError::ProtocolError(format!("WebSocket Err: {}", err)),
+        }
+    }
+}
+
+impl From<WsError> for DisconnectError {
+    fn from(err: WsError) -> Self {
+        match err {
+            WsError::Io(err) => DisconnectError::IoError(err),
+            err => DisconnectError::ProtocolError(format!("WebSocket Err: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::tests;
+    use crate::transport::tls::tests::{create_test_tls_transport, with_test_tls_files};
+
+    fn create_test_wss_transport() -> WsTransport {
+        with_test_tls_files(
+            true,
+            |ca_cert, client_key, client_cert, server_key, server_cert| {
+                WsTransport::new_secure(ca_cert, client_key, client_cert, server_key, server_cert)
+                    .unwrap()
+            },
+        )
+    }
+
+    #[test]
+    fn test_accepts() {
+        let transport = WsTransport::default();
+        assert!(transport.accepts("127.0.0.1:0"));
+        assert!(transport.accepts("ws://127.0.0.1:0"));
+        assert!(transport.accepts("ws://somewhere.example.com:4000/splinter"));
+
+        assert!(!transport.accepts("wss://somewhere.example.com:4000"));
+        assert!(!transport.accepts("tls://somewhere.example.com:4000"));
+
+        let transport = create_test_wss_transport();
+        assert!(transport.accepts("ws://127.0.0.1:0"));
+        assert!(transport.accepts("wss://somewhere.example.com:4000"));
+        assert!(!transport.accepts("tcp://somewhere.example.com:4000"));
+    }
+
+    #[test]
+    fn test_transport() {
+        let transport = WsTransport::default();
+        tests::test_transport(transport, "127.0.0.1:0");
+    }
+
+    #[test]
+    fn test_transport_explicit_protocol() {
+        let transport = WsTransport::default();
+        tests::test_transport(transport, "ws://127.0.0.1:0");
+    }
+
+    #[test]
+    fn test_poll() {
+        let transport = WsTransport::default();
+        tests::test_poll(
+            transport,
+            "127.0.0.1:0",
+            Ready::readable() | Ready::writable(),
+        );
+    }
+
+    #[test]
+    fn test_secure_transport() {
+        let transport = create_test_wss_transport();
+        tests::test_transport(transport, "wss://127.0.0.1:0");
+    }
+
+    #[test]
+    fn test_secure_transport_plain_endpoint() {
+        let transport = create_test_wss_transport();
+        tests::test_transport(transport, "ws://127.0.0.1:0");
+    }
+
+    #[test]
+    fn test_secure_transport_from_tls_transport() {
+        let transport = WsTransport::from_tls_transport(&create_test_tls_transport(true));
+        tests::test_transport(transport, "wss://127.0.0.1:0");
+    }
+}
@@ -19,6 +19,7 @@ pub struct ConfigBuilder {
     transport: Option<String>,
     cert_dir: Option<String>,
     ca_certs: Option<String>,
+    crl_file: Option<String>,
     client_cert: Option<String>,
     client_key: Option<String>,
     server_cert: Option<String>,
@@ -32,7 +33,27 @@ pub struct ConfigBuilder {
     database: Option<String>,
     registry_backend: Option<String>,
     registry_file: Option<String>,
+    #[cfg(feature = "node-registry-remote")]
+    remote_registries: Option<Vec<String>>,
+    #[cfg(feature = "node-registry-remote")]
+    registry_refresh_interval: Option<u64>,
+    #[cfg(feature = "node-registry-signing")]
+    registry_signature_policy: Option<String>,
+    #[cfg(feature = "database")]
+    key_registry_backend: Option<String>,
+    key_permissions: Option<String>,
+    key_permissions_file: Option<String>,
     heartbeat_interval: Option<u64>,
+    send_queue_high_watermark: Option<usize>,
+    send_queue_low_watermark: Option<usize>,
+    slow_peer_policy: Option<String>,
+    slow_peer_timeout: Option<u64>,
+    peer_rate_limit: Option<f64>,
+    peer_rate_limit_burst: Option<u32>,
+    rate_limit_max_violations: Option<u32>,
+    rate_limit_violation_window: Option<u64>,
+    #[cfg(feature = "unix-transport")]
+    unix_socket_permissions: Option<String>,
 }
 
 impl ConfigBuilder {
@@ -42,6 +63,7 @@ impl ConfigBuilder {
             transport: None,
             cert_dir: None,
             ca_certs: None,
+            crl_file: None,
             client_cert: None,
             client_key: None,
             server_cert: None,
@@ -55,7 +77,27 @@ impl ConfigBuilder {
             database: None,
             registry_backend: None,
             registry_file: None,
+            #[cfg(feature = "node-registry-remote")]
+            remote_registries: None,
+            #[cfg(feature = "node-registry-remote")]
+            registry_refresh_interval: None,
+            #[cfg(feature = "node-registry-signing")]
+            registry_signature_policy: None,
+            #[cfg(feature = "database")]
+            key_registry_backend: None,
+            key_permissions: None,
+            key_permissions_file: None,
             heartbeat_interval: None,
+            send_queue_high_watermark: None,
+            send_queue_low_watermark: None,
+            slow_peer_policy: None,
+            slow_peer_timeout: None,
+            peer_rate_limit: None,
+            peer_rate_limit_burst: None,
+            rate_limit_max_violations: None,
+            rate_limit_violation_window: None,
+            #[cfg(feature = "unix-transport")]
+            unix_socket_permissions: None,
         }
     }
 
@@ -79,6 +121,11 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_crl_file(mut self, crl_file: String) -> Self {
+        self.crl_file = Some(crl_file);
+        self
+    }
+
     pub fn with_client_cert(mut self, client_cert: String) -> Self {
         self.client_cert = Some(client_cert);
         self
@@ -140,17 +187,98 @@ impl ConfigBuilder {
         self
     }
 
+    #[cfg(feature = "node-registry-remote")]
+    pub fn with_remote_registries(mut self, remote_registries: Vec<String>) -> Self {
+        self.remote_registries = Some(remote_registries);
+        self
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    pub fn with_registry_refresh_interval(mut self, registry_refresh_interval: u64) -> Self {
+        self.registry_refresh_interval = Some(registry_refresh_interval);
+        self
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    pub fn with_registry_signature_policy(mut self, registry_signature_policy: String) -> Self {
+        self.registry_signature_policy = Some(registry_signature_policy);
+        self
+    }
+
+    #[cfg(feature = "database")]
+    pub fn with_key_registry_backend(mut self, key_registry_backend: String) -> Self {
+        self.key_registry_backend = Some(key_registry_backend);
+        self
+    }
+
+    pub fn with_key_permissions(mut self, key_permissions: String) -> Self {
+        self.key_permissions = Some(key_permissions);
+        self
+    }
+
+    pub fn with_key_permissions_file(mut self, key_permissions_file: String) -> Self {
+        self.key_permissions_file = Some(key_permissions_file);
+        self
+    }
+
     pub fn with_heartbeat_interval(mut self, heartbeat_interval: u64) -> Self {
         self.heartbeat_interval = Some(heartbeat_interval);
         self
     }
 
+    pub fn with_send_queue_high_watermark(mut self, send_queue_high_watermark: usize) -> Self {
+        self.send_queue_high_watermark = Some(send_queue_high_watermark);
+        self
+    }
+
+    pub fn with_send_queue_low_watermark(mut self, send_queue_low_watermark: usize) -> Self {
+        self.send_queue_low_watermark = Some(send_queue_low_watermark);
+        self
+    }
+
+    pub fn with_slow_peer_policy(mut self, slow_peer_policy: String) -> Self {
+        self.slow_peer_policy = Some(slow_peer_policy);
+        self
+    }
+
+    pub fn with_slow_peer_timeout(mut self, slow_peer_timeout: u64) -> Self {
+        self.slow_peer_timeout = Some(slow_peer_timeout);
+        self
+    }
+
+    pub fn with_peer_rate_limit(mut self, peer_rate_limit: f64) -> Self {
+        self.peer_rate_limit = Some(peer_rate_limit);
+        self
+    }
+
+    pub fn with_peer_rate_limit_burst(mut self, peer_rate_limit_burst: u32) -> Self {
+        self.peer_rate_limit_burst = Some(peer_rate_limit_burst);
+        self
+    }
+
+    pub fn with_rate_limit_max_violations(mut self, rate_limit_max_violations: u32) -> Self {
+        self.rate_limit_max_violations = Some(rate_limit_max_violations);
+        self
+    }
+
+    pub fn with_rate_limit_violation_window(mut self, rate_limit_violation_window: u64) -> Self {
+        self.rate_limit_violation_window = Some(rate_limit_violation_window);
+        self
+    }
+
+    #[cfg(feature = "unix-transport")]
+    pub fn with_unix_socket_permissions(mut self, unix_socket_permissions: String) -> Self {
+        self.unix_socket_permissions = Some(unix_socket_permissions);
+        self
+    }
+
     pub fn build(self) -> Config {
         Config {
             storage: self.storage,
             transport: self.transport,
             cert_dir: self.cert_dir,
             ca_certs: self.ca_certs,
+            crl_file: self.crl_file,
             client_cert: self.client_cert,
             client_key: self.client_key,
             server_cert: self.server_cert,
@@ -164,7 +292,27 @@ impl ConfigBuilder {
             database: self.database,
             registry_backend: self.registry_backend,
             registry_file: self.registry_file,
+            #[cfg(feature = "node-registry-remote")]
+            remote_registries: self.remote_registries,
+            #[cfg(feature = "node-registry-remote")]
+            registry_refresh_interval: self.registry_refresh_interval,
+            #[cfg(feature = "node-registry-signing")]
+            registry_signature_policy: self.registry_signature_policy,
+            #[cfg(feature = "database")]
+            key_registry_backend: self.key_registry_backend,
+            key_permissions: self.key_permissions,
+            key_permissions_file: self.key_permissions_file,
             heartbeat_interval: self.heartbeat_interval,
+            send_queue_high_watermark: self.send_queue_high_watermark,
+            send_queue_low_watermark: self.send_queue_low_watermark,
+            slow_peer_policy: self.slow_peer_policy,
+            slow_peer_timeout: self.slow_peer_timeout,
+            peer_rate_limit: self.peer_rate_limit,
+            peer_rate_limit_burst: self.peer_rate_limit_burst,
+            rate_limit_max_violations: self.rate_limit_max_violations,
+            rate_limit_violation_window: self.rate_limit_violation_window,
+            #[cfg(feature = "unix-transport")]
+            unix_socket_permissions: self.unix_socket_permissions,
         }
     }
 }
@@ -40,6 +40,7 @@ pub struct Config {
     transport: Option<String>,
     cert_dir: Option<String>,
     ca_certs: Option<String>,
+    crl_file: Option<String>,
     client_cert: Option<String>,
     client_key: Option<String>,
     server_cert: Option<String>,
@@ -53,7 +54,27 @@ pub struct Config {
     database: Option<String>,
     registry_backend: Option<String>,
     registry_file: Option<String>,
+    #[cfg(feature = "node-registry-remote")]
+    remote_registries: Option<Vec<String>>,
+    #[cfg(feature = "node-registry-remote")]
+    registry_refresh_interval: Option<u64>,
+    #[cfg(feature = "node-registry-signing")]
+    registry_signature_policy: Option<String>,
+    #[cfg(feature = "database")]
+    key_registry_backend: Option<String>,
+    key_permissions: Option<String>,
+    key_permissions_file: Option<String>,
     heartbeat_interval: Option<u64>,
+    send_queue_high_watermark: Option<usize>,
+    send_queue_low_watermark: Option<usize>,
+    slow_peer_policy: Option<String>,
+    slow_peer_timeout: Option<u64>,
+    peer_rate_limit: Option<f64>,
+    peer_rate_limit_burst: Option<u32>,
+    rate_limit_max_violations: Option<u32>,
+    rate_limit_violation_window: Option<u64>,
+    #[cfg(feature = "unix-transport")]
+    unix_socket_permissions: Option<String>,
 }
 
 impl Config {
@@ -81,6 +102,10 @@ impl Config {
         self.ca_certs.clone()
     }
 
+    pub fn crl_file(&self) -> Option<String> {
+        self.crl_file.clone()
+    }
+
     pub fn client_cert(&self) -> Option<String> {
         self.client_cert.clone()
     }
@@ -130,7 +155,72 @@ impl Config {
         self.registry_file.clone()
     }
 
+    #[cfg(feature = "node-registry-remote")]
+    pub fn remote_registries(&self) -> Option<Vec<String>> {
+        self.remote_registries.clone()
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    pub fn registry_refresh_interval(&self) -> Option<u64> {
+        self.registry_refresh_interval
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    pub fn registry_signature_policy(&self) -> Option<String> {
+        self.registry_signature_policy.clone()
+    }
+
+    #[cfg(feature = "database")]
+    pub fn key_registry_backend(&self) -> Option<String> {
+        self.key_registry_backend.clone()
+    }
+
+    pub fn key_permissions(&self) -> Option<String> {
+        self.key_permissions.clone()
+    }
+
+    pub fn key_permissions_file(&self) -> Option<String> {
+        self.key_permissions_file.clone()
+    }
+
     pub fn heartbeat_interval(&self) -> Option<u64> {
         self.heartbeat_interval
     }
+
+    pub fn send_queue_high_watermark(&self) -> Option<usize> {
+        self.send_queue_high_watermark
+    }
+
+    pub fn send_queue_low_watermark(&self) -> Option<usize> {
+        self.send_queue_low_watermark
+    }
+
+    pub fn slow_peer_policy(&self) -> Option<String> {
+        self.slow_peer_policy.clone()
+    }
+
+    pub fn slow_peer_timeout(&self) -> Option<u64> {
+        self.slow_peer_timeout
+    }
+
+    pub fn peer_rate_limit(&self) -> Option<f64> {
+        self.peer_rate_limit
+    }
+
+    pub fn peer_rate_limit_burst(&self) -> Option<u32> {
+        self.peer_rate_limit_burst
+    }
+
+    pub fn rate_limit_max_violations(&self) -> Option<u32> {
+        self.rate_limit_max_violations
+    }
+
+    pub fn rate_limit_violation_window(&self) -> Option<u64> {
+        self.rate_limit_violation_window
+    }
+
+    #[cfg(feature = "unix-transport")]
+    pub fn unix_socket_permissions(&self) -> Option<String> {
+        self.unix_socket_permissions.clone()
+    }
 }
@@ -25,6 +25,7 @@ pub struct TomlConfig {
     transport: Option<String>,
     cert_dir: Option<String>,
     ca_certs: Option<String>,
+    crl_file: Option<String>,
     client_cert: Option<String>,
     client_key: Option<String>,
     server_cert: Option<String>,
@@ -38,7 +39,27 @@ pub struct TomlConfig {
     database: Option<String>,
     registry_backend: Option<String>,
     registry_file: Option<String>,
+    #[cfg(feature = "node-registry-remote")]
+    remote_registries: Option<Vec<String>>,
+    #[cfg(feature = "node-registry-remote")]
+    registry_refresh_interval: Option<u64>,
+    #[cfg(feature = "node-registry-signing")]
+    registry_signature_policy: Option<String>,
+    #[cfg(feature = "database")]
+    key_registry_backend: Option<String>,
+    key_permissions: Option<String>,
+    key_permissions_file: Option<String>,
     heartbeat_interval: Option<u64>,
+    send_queue_high_watermark: Option<usize>,
+    send_queue_low_watermark: Option<usize>,
+    slow_peer_policy: Option<String>,
+    slow_peer_timeout: Option<u64>,
+    peer_rate_limit: Option<f64>,
+    peer_rate_limit_burst: Option<u32>,
+    rate_limit_max_violations: Option<u32>,
+    rate_limit_violation_window: Option<u64>,
+    #[cfg(feature = "unix-transport")]
+    unix_socket_permissions: Option<String>,
 }
 
 impl TomlConfig {
@@ -62,6 +83,10 @@ impl TomlConfig {
         self.ca_certs.take()
     }
 
+    pub fn take_crl_file(&mut self) -> Option<String> {
+        self.crl_file.take()
+    }
+
     pub fn take_client_cert(&mut self) -> Option<String> {
         self.client_cert.take()
     }
@@ -111,10 +136,75 @@ impl TomlConfig {
         self.registry_file.take()
     }
 
+    #[cfg(feature = "node-registry-remote")]
+    pub fn take_remote_registries(&mut self) -> Option<Vec<String>> {
+        self.remote_registries.take()
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    pub fn take_registry_refresh_interval(&mut self) -> Option<u64> {
+        self.registry_refresh_interval.take()
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    pub fn take_registry_signature_policy(&mut self) -> Option<String> {
+        self.registry_signature_policy.take()
+    }
+
+    #[cfg(feature = "database")]
+    pub fn take_key_registry_backend(&mut self) -> Option<String> {
+        self.key_registry_backend.take()
+    }
+
+    pub fn take_key_permissions(&mut self) -> Option<String> {
+        self.key_permissions.take()
+    }
+
+    pub fn take_key_permissions_file(&mut self) -> Option<String> {
+        self.key_permissions_file.take()
+    }
+
     pub fn take_heartbeat_interval(&mut self) -> Option<u64> {
         self.heartbeat_interval.take()
     }
 
+    pub fn take_send_queue_high_watermark(&mut self) -> Option<usize> {
+        self.send_queue_high_watermark.take()
+    }
+
+    pub fn take_send_queue_low_watermark(&mut self) -> Option<usize> {
+        self.send_queue_low_watermark.take()
+    }
+
+    pub fn take_slow_peer_policy(&mut self) -> Option<String> {
+        self.slow_peer_policy.take()
+    }
+
+    pub fn take_slow_peer_timeout(&mut self) -> Option<u64> {
+        self.slow_peer_timeout.take()
+    }
+
+    pub fn take_peer_rate_limit(&mut self) -> Option<f64> {
+        self.peer_rate_limit.take()
+    }
+
+    pub fn take_peer_rate_limit_burst(&mut self) -> Option<u32> {
+        self.peer_rate_limit_burst.take()
+    }
+
+    pub fn take_rate_limit_max_violations(&mut self) -> Option<u32> {
+        self.rate_limit_max_violations.take()
+    }
+
+    pub fn take_rate_limit_violation_window(&mut self) -> Option<u64> {
+        self.rate_limit_violation_window.take()
+    }
+
+    #[cfg(feature = "unix-transport")]
+    pub fn take_unix_socket_permissions(&mut self) -> Option<String> {
+        self.unix_socket_permissions.take()
+    }
+
     pub fn apply_to_builder(mut self, mut builder: ConfigBuilder) -> ConfigBuilder {
         if let Some(x) = self.take_storage() {
             builder = builder.with_storage(x);
@@ -128,6 +218,9 @@ impl TomlConfig {
         if let Some(x) = self.take_ca_certs() {
             builder = builder.with_ca_certs(x);
         }
+        if let Some(x) = self.take_crl_file() {
+            builder = builder.with_crl_file(x);
+        }
         if let Some(x) = self.take_client_cert() {
             builder = builder.with_client_cert(x);
         }
@@ -167,9 +260,66 @@ impl TomlConfig {
         if let Some(x) = self.take_registry_file() {
             builder = builder.with_registry_file(x);
         }
+        #[cfg(feature = "node-registry-remote")]
+        {
+            if let Some(x) = self.take_remote_registries() {
+                builder = builder.with_remote_registries(x);
+            }
+            if let Some(x) = self.take_registry_refresh_interval() {
+                builder = builder.with_registry_refresh_interval(x);
+            }
+        }
+        #[cfg(feature = "node-registry-signing")]
+        {
+            if let Some(x) = self.take_registry_signature_policy() {
+                builder = builder.with_registry_signature_policy(x);
+            }
+        }
+        #[cfg(feature = "database")]
+        {
+            if let Some(x) = self.take_key_registry_backend() {
+                builder = builder.with_key_registry_backend(x);
+            }
+        }
+        if let Some(x) = self.take_key_permissions() {
+            builder = builder.with_key_permissions(x);
+        }
+        if let Some(x) = self.take_key_permissions_file() {
+            builder = builder.with_key_permissions_file(x);
+        }
         if let Some(x) = self.take_heartbeat_interval() {
             builder = builder.with_heartbeat_interval(x);
         }
+        if let Some(x) = self.take_send_queue_high_watermark() {
+            builder = builder.with_send_queue_high_watermark(x);
+        }
+        if let Some(x) = self.take_send_queue_low_watermark() {
+            builder = builder.with_send_queue_low_watermark(x);
+        }
+        if let Some(x) = self.take_slow_peer_policy() {
+            builder = builder.with_slow_peer_policy(x);
+        }
+        if let Some(x) = self.take_slow_peer_timeout() {
+            builder = builder.with_slow_peer_timeout(x);
+        }
+        if let Some(x) = self.take_peer_rate_limit() {
+            builder = builder.with_peer_rate_limit(x);
+        }
+        if let Some(x) = self.take_peer_rate_limit_burst() {
+            builder = builder.with_peer_rate_limit_burst(x);
+        }
+        if let Some(x) = self.take_rate_limit_max_violations() {
+            builder = builder.with_rate_limit_max_violations(x);
+        }
+        if let Some(x) = self.take_rate_limit_violation_window() {
+            builder = builder.with_rate_limit_violation_window(x);
+        }
+        #[cfg(feature = "unix-transport")]
+        {
+            if let Some(x) = self.take_unix_socket_permissions() {
+                builder = builder.with_unix_socket_permissions(x);
+            }
+        }
 
         builder
     }
@@ -13,6 +13,8 @@
 // limitations under the License.
 use std::error::Error;
 use std::fmt;
+#[cfg(feature = "circuit-store-lmdb")]
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -22,38 +24,67 @@ use crossbeam_channel;
 
 #[cfg(feature = "health")]
 use health::HealthService;
-use splinter::admin::service::{admin_service_id, AdminService};
+use splinter::admin::service::{admin_service_id, AdminService, PROPOSER_ROLE, VOTER_ROLE};
 #[cfg(feature = "biome")]
 use splinter::biome::rest_api::{BiomeRestResourceManager, BiomeRestResourceManagerBuilder};
-use splinter::circuit::directory::CircuitDirectory;
+use splinter::circuit::durable::{DurableMessageResender, DurableMessageStore};
 use splinter::circuit::handlers::{
-    AdminDirectMessageHandler, CircuitDirectMessageHandler, CircuitErrorHandler,
-    CircuitMessageHandler, ServiceConnectRequestHandler, ServiceDisconnectRequestHandler,
+    AdminDirectMessageHandler, CircuitDirectMessageAckHandler, CircuitDirectMessageHandler,
+    CircuitErrorHandler, CircuitMessageHandler, CircuitRoutedMessageHandler,
+    ServiceConnectRequestHandler, ServiceDisconnectRequestHandler,
 };
 #[cfg(feature = "circuit-read")]
 use splinter::circuit::rest_api::CircuitResourceProvider;
+#[cfg(feature = "circuit-store-lmdb")]
+use splinter::circuit::store::lmdb::{LmdbCircuitStore, DEFAULT_CIRCUIT_STORE_SIZE};
+use splinter::circuit::store::{CircuitStore, StorageCircuitStore};
 use splinter::circuit::SplinterState;
 #[cfg(feature = "biome")]
 use splinter::database::{self, ConnectionPool};
+#[cfg(feature = "database")]
+use splinter::keys::database::DatabaseKeyRegistry;
 use splinter::keys::{
-    insecure::AllowAllKeyPermissionManager, rest_api::KeyRegistryManager,
+    insecure::AllowAllKeyPermissionManager,
+    rest_api::{KeyRegistryManager, KEY_REGISTRY_ADMIN_ROLE, KEY_REGISTRY_OTHER_NODES_ROLE},
+    roles::{KeyRegistryPermissionManager, PolicyFileKeyPermissionManager},
     storage::StorageKeyRegistry,
+    KeyPermissionManager, KeyRegistry,
 };
-use splinter::mesh::Mesh;
+use splinter::mesh::{FlowControl, Mesh};
+#[cfg(feature = "metrics")]
+use splinter::metrics::MetricsRegistry;
 use splinter::network::auth::handlers::{
     create_authorization_dispatcher, AuthorizationMessageHandler, NetworkAuthGuardHandler,
 };
-use splinter::network::auth::AuthorizationManager;
+use splinter::network::auth::{AuthorizationManager, AuthorizationState};
+#[cfg(feature = "connection-manager")]
+use splinter::network::connection_manager::{
+    ConnectionManager, ConnectionManagerNotification, NotificationIter,
+};
 use splinter::network::dispatch::{DispatchLoop, DispatchMessage, Dispatcher};
-use splinter::network::handlers::{NetworkEchoHandler, NetworkHeartbeatHandler};
+use splinter::network::handlers::{
+    NetworkEchoHandler, NetworkHeartbeatHandler, NetworkRouteAdvertisementHandler,
+};
 use splinter::network::peer::PeerConnector;
+use splinter::network::rate_limit::{RateLimit, RateLimiter};
+use splinter::network::routing::RoutingTable;
 use splinter::network::sender::{NetworkMessageSender, SendRequest};
+use splinter::network::trace::parse_trace_id;
 use splinter::network::{ConnectionError, Network, PeerUpdateError, RecvTimeoutError, SendError};
+#[cfg(feature = "node-registry-signing")]
+use splinter::node_registry::signing::{
+    NodeSignatureVerifier, SignaturePolicy, VerifyingNodeRegistry,
+};
 use splinter::node_registry::{
     self,
-    rest_api::{make_nodes_identity_resource, make_nodes_resource},
+    rest_api::{make_nodes_identity_resource, make_nodes_resource, make_nodes_subscribe_resource},
     RwNodeRegistry,
 };
+#[cfg(feature = "node-registry-remote")]
+use splinter::node_registry::{
+    remote::{RemoteYamlNodeRegistry, DEFAULT_REFRESH_INTERVAL},
+    NodeRegistryReader, UnifiedNodeRegistry,
+};
 use splinter::orchestrator::{NewOrchestratorError, ServiceOrchestrator};
 use splinter::protos::authorization::AuthorizationMessageType;
 use splinter::protos::circuit::CircuitMessageType;
@@ -65,17 +96,25 @@ use splinter::rwlock_read_unwrap;
 use splinter::service::scabbard::ScabbardFactory;
 use splinter::service::{self, ServiceProcessor, ShutdownHandle};
 use splinter::signing::sawtooth::SawtoothSecp256k1SignatureVerifier;
-use splinter::storage::get_storage;
+#[cfg(feature = "unix-transport")]
+use splinter::transport::unix::{UnixTransport, DEFAULT_SOCKET_PERMISSIONS};
 use splinter::transport::{
     inproc::InprocTransport, multi::MultiTransport, AcceptError, ConnectError, Incoming,
     ListenError, Listener, Transport,
 };
 
+use crate::key_permissions_config::{
+    KeyPermissionsConfig, KeyPermissionsConfigBuilder, KeyPermissionsConfigError,
+};
 use crate::registry_config::{RegistryConfig, RegistryConfigBuilder, RegistryConfigError};
 use crate::routes;
 
 // Recv timeout in secs
 const TIMEOUT_SEC: u64 = 2;
+const ROUTE_ADVERTISEMENT_INTERVAL_SEC: u64 = 10;
+// The number of advertisement intervals after which the routes through a silent node are removed
+const ROUTE_EXPIRY_INTERVALS: u32 = 3;
+const DURABLE_RESEND_INTERVAL_SEC: u64 = 1;
 const ADMIN_SERVICE_ADDRESS: &str = "inproc://admin-service";
 
 const ORCHESTRATOR_INCOMING_CAPACITY: usize = 8;
@@ -98,6 +137,7 @@ type ServiceJoinHandle = service::JoinHandles<Result<(), service::error::Service
 pub struct SplinterDaemon {
     storage_location: String,
     key_registry_location: String,
+    durable_store_location: String,
     service_endpoint: String,
     network_endpoint: String,
     initial_peers: Vec<String>,
@@ -106,10 +146,27 @@ pub struct SplinterDaemon {
     rest_api_endpoint: String,
     #[cfg(feature = "database")]
     db_url: Option<String>,
+    #[cfg(feature = "database")]
+    key_registry_db_url: Option<String>,
     #[cfg(feature = "biome")]
     biome_enabled: bool,
     registry_config: RegistryConfig,
+    #[cfg(feature = "node-registry-remote")]
+    remote_registries: Vec<String>,
+    #[cfg(feature = "node-registry-remote")]
+    registry_refresh_interval: Duration,
+    #[cfg(feature = "node-registry-signing")]
+    registry_signature_policy: Option<SignaturePolicy>,
+    key_permissions_config: KeyPermissionsConfig,
     storage_type: String,
+    #[cfg(feature = "connection-manager")]
+    heartbeat_interval: u64,
+    peer_rate_limit: Option<RateLimit>,
+    rate_limit_disconnect_threshold: Option<(u32, Duration)>,
+    #[cfg(feature = "unix-transport")]
+    unix_socket_permissions: u32,
+    #[cfg(feature = "metrics")]
+    metrics: MetricsRegistry,
 }
 
 impl SplinterDaemon {
@@ -117,6 +174,12 @@ impl SplinterDaemon {
         let mut inproc_transport = InprocTransport::default();
         let mut transports = vec![transport, Box::new(inproc_transport.clone())];
 
+        // Allow services on the same host to connect over a Unix domain socket
+        #[cfg(feature = "unix-transport")]
+        transports.push(Box::new(
+            UnixTransport::default().with_permissions(self.unix_socket_permissions),
+        ));
+
         // Allowing unused_variable because health_inproc must be available later if feature
         // health is enabled
         #[allow(unused_variables)]
@@ -135,14 +198,10 @@ impl SplinterDaemon {
 
         // Load initial state from the configured storage location and create the new
         // SplinterState from the retrieved circuit directory
-        let storage = get_storage(&self.storage_location, CircuitDirectory::new)
-            .map_err(StartError::StorageError)?;
-
-        let circuit_directory = storage.read().clone();
-        let state = Arc::new(RwLock::new(SplinterState::new(
-            self.storage_location.to_string(),
-            circuit_directory,
-        )));
+        let state = Arc::new(RwLock::new(
+            SplinterState::from_store(self.create_circuit_store()?)
+                .map_err(|err| StartError::StorageError(err.to_string()))?,
+        ));
 
         // set up the listeners on the transport
         let mut network_listener = transport.listen(&self.network_endpoint)?;
@@ -168,7 +227,39 @@ impl SplinterDaemon {
             service_listener,
         );
 
+        #[cfg(not(feature = "connection-manager"))]
         let peer_connector = PeerConnector::new(self.network.clone(), Box::new(transport));
+
+        // Peer connections are opened through the connection manager, which maintains them via
+        // heartbeats and reconnects to peers whose heartbeats fail.
+        #[cfg(feature = "connection-manager")]
+        let (connection_manager, peer_connector) = {
+            let mut connection_manager = ConnectionManager::new(
+                self.network.get_life_cycle(),
+                self.network.get_sender(),
+                Box::new(transport),
+            );
+            if self.heartbeat_interval != 0 {
+                connection_manager =
+                    connection_manager.with_heartbeat_interval(self.heartbeat_interval);
+            }
+            let connector = connection_manager.start().map_err(|err| {
+                StartError::NetworkError(format!("unable to start connection manager: {}", err))
+            })?;
+            let notifications = connector.subscribe().map_err(|err| {
+                StartError::NetworkError(format!(
+                    "unable to subscribe to connection manager notifications: {}",
+                    err
+                ))
+            })?;
+            let peer_connector = PeerConnector::with_connector(self.network.clone(), connector);
+            Self::handle_connection_notifications(notifications, peer_connector.clone())?;
+
+            (connection_manager, peer_connector)
+        };
+        #[cfg(feature = "connection-manager")]
+        let connection_manager_shutdown_handle = connection_manager.shutdown_handle();
+
         let auth_manager = AuthorizationManager::new(self.network.clone(), self.node_id.clone());
 
         info!("Starting SpinterNode with ID {}", self.node_id);
@@ -181,14 +272,25 @@ impl SplinterDaemon {
             network_sender.run()
         });
 
+        // Direct messages on circuits with store-and-forward durability are queued here until
+        // they are acknowledged
+        let durable_store = DurableMessageStore::new(&self.durable_store_location)
+            .map_err(|err| StartError::StorageError(err.to_string()))?;
+
         // Set up the Circuit dispatcher
+        let routing_table = RoutingTable::new(self.node_id.clone());
         let (circuit_dispatch_send, circuit_dispatch_recv) = crossbeam_channel::bounded(5);
         let circuit_dispatcher = set_up_circuit_dispatcher(
             send.clone(),
             &self.node_id,
             &self.network_endpoint,
             state.clone(),
+            routing_table.clone(),
+            durable_store.clone(),
+            circuit_dispatch_send.clone(),
         );
+        #[cfg(feature = "metrics")]
+        let circuit_dispatcher = circuit_dispatcher.with_metrics(self.metrics.clone());
         let circuit_dispatch_loop = DispatchLoop::new(
             Box::new(circuit_dispatch_recv),
             circuit_dispatcher,
@@ -200,6 +302,8 @@ impl SplinterDaemon {
         let (auth_dispatch_send, auth_dispatch_recv) = crossbeam_channel::bounded(5);
         let auth_dispatcher =
             create_authorization_dispatcher(auth_manager.clone(), Box::new(send.clone()));
+        #[cfg(feature = "metrics")]
+        let auth_dispatcher = auth_dispatcher.with_metrics(self.metrics.clone());
         let auth_dispatch_loop = DispatchLoop::new(
             Box::new(auth_dispatch_recv),
             auth_dispatcher,
@@ -207,22 +311,55 @@ impl SplinterDaemon {
         );
         let auth_dispatcher_thread = thread::spawn(move || auth_dispatch_loop.run());
 
+        let durable_message_resender = DurableMessageResender::new(
+            self.node_id.clone(),
+            durable_store,
+            state.clone(),
+            Box::new(send.clone()),
+        )
+        .with_routing_table(routing_table.clone());
+        Self::resend_durable_messages(durable_message_resender, running.clone())?;
+
         // Set up the Network dispatcher
         let (network_dispatch_send, network_dispatch_recv) = crossbeam_channel::bounded(5);
         let network_dispatcher = set_up_network_dispatcher(
             send,
             &self.node_id,
+            self.network.clone(),
             auth_manager.clone(),
+            routing_table.clone(),
             circuit_dispatch_send,
             auth_dispatch_send,
         );
+        #[cfg(feature = "metrics")]
+        let network_dispatcher = network_dispatcher.with_metrics(self.metrics.clone());
         let network_dispatch_loop = DispatchLoop::new(
             Box::new(network_dispatch_recv),
             network_dispatcher,
             running.clone(),
         );
+        // Every message from a peer passes through the network dispatch loop, so peers are rate
+        // limited there, before their circuit and authorization messages are dispatched further
+        let network_dispatch_loop = match self.peer_rate_limit {
+            Some(ref peer_rate_limit) => {
+                let mut rate_limiter = RateLimiter::new(peer_rate_limit.clone());
+                if let Some((max_violations, window)) = self.rate_limit_disconnect_threshold {
+                    rate_limiter = rate_limiter.with_disconnect_threshold(max_violations, window);
+                }
+                network_dispatch_loop
+                    .with_rate_limiter(rate_limiter, Box::new(self.network.clone()))
+            }
+            None => network_dispatch_loop,
+        };
         let network_dispatcher_thread = thread::spawn(move || network_dispatch_loop.run());
 
+        Self::advertise_routes(
+            self.network.clone(),
+            auth_manager.clone(),
+            routing_table,
+            running.clone(),
+        )?;
+
         // setup a thread to listen on the network port and add incoming connection to the network
         let network_clone = self.network.clone();
 
@@ -286,11 +423,13 @@ impl SplinterDaemon {
                         Ok(message) => {
                             let mut msg: NetworkMessage =
                                 protobuf::parse_from_bytes(message.payload()).unwrap();
+                            let trace_id = parse_trace_id(msg.get_trace_id()).map(String::from);
                             let dispatch_msg = DispatchMessage::new(
                                 msg.get_message_type(),
                                 msg.take_payload(),
                                 message.peer_id().to_string(),
-                            );
+                            )
+                            .with_trace_id(trace_id);
                             trace!("Received Message from {}: {:?}", message.peer_id(), msg);
                             match network_dispatch_send.send(dispatch_msg) {
                                 Ok(()) => (),
@@ -338,11 +477,68 @@ impl SplinterDaemon {
 
         let signature_verifier = SawtoothSecp256k1SignatureVerifier::new();
 
-        let key_registry = Box::new(
-            StorageKeyRegistry::new(self.key_registry_location.clone())
-                .map_err(|err| StartError::StorageError(format!("{}", err)))?,
+        let key_registry = self.create_key_registry()?;
+
+        let key_permission_manager =
+            create_key_permission_manager(&self.key_permissions_config, key_registry.clone())?;
+
+        #[cfg(feature = "node-registry-signing")]
+        let node_signature_verifier = NodeSignatureVerifier::new(
+            Box::new(SawtoothSecp256k1SignatureVerifier::new()),
+            key_registry.clone(),
         );
 
+        // Each registry is verified on its own, so that a rejected local entry does not hide a
+        // valid remote entry for the same node
+        let node_registry = create_node_registry(&self.registry_config)?;
+        #[cfg(feature = "node-registry-signing")]
+        let node_registry: Box<dyn RwNodeRegistry> = match self.registry_signature_policy {
+            Some(policy) => Box::new(VerifyingNodeRegistry::new(
+                node_registry,
+                node_signature_verifier.clone(),
+                policy,
+            )),
+            None => node_registry,
+        };
+
+        #[cfg(feature = "node-registry-remote")]
+        let node_registry = {
+            let remote_registries = self
+                .remote_registries
+                .iter()
+                .map(|url| {
+                    RemoteYamlNodeRegistry::new(url, self.registry_refresh_interval)
+                        .map(|registry| {
+                            let registry: Box<dyn NodeRegistryReader> = Box::new(registry);
+                            registry
+                        })
+                        .map_err(|err| StartError::ThreadError(err.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            #[cfg(feature = "node-registry-signing")]
+            let remote_registries = remote_registries
+                .into_iter()
+                .map(|registry| {
+                    let registry: Box<dyn NodeRegistryReader> = match self.registry_signature_policy
+                    {
+                        Some(policy) => Box::new(VerifyingNodeRegistry::new(
+                            registry,
+                            node_signature_verifier.clone(),
+                            policy,
+                        )),
+                        None => registry,
+                    };
+                    registry
+                })
+                .collect();
+            add_remote_node_registries(node_registry, remote_registries)
+        };
+
+        let peers_network = self.network.clone();
+        let peers_auth_manager = auth_manager.clone();
+        let peer_network = self.network.clone();
+        let peer_auth_manager = auth_manager.clone();
+
         let admin_service = AdminService::new(
             &self.node_id,
             orchestrator,
@@ -354,15 +550,37 @@ impl SplinterDaemon {
             state.clone(),
             Box::new(signature_verifier),
             key_registry.clone(),
-            Box::new(AllowAllKeyPermissionManager),
+            key_permission_manager.clone(),
+            Box::new(node_registry.clone()),
             &self.storage_type,
         )
         .map_err(|err| {
             StartError::AdminServiceError(format!("unable to create admin service: {}", err))
         })?;
-        let key_registry_manager = KeyRegistryManager::new(key_registry);
-
-        let node_registry = create_node_registry(&self.registry_config)?;
+        let mut key_registry_manager = KeyRegistryManager::new(key_registry)
+            .with_key_permission_manager(
+                key_permission_manager.clone(),
+                vec![
+                    PROPOSER_ROLE.to_string(),
+                    VOTER_ROLE.to_string(),
+                    KEY_REGISTRY_ADMIN_ROLE.to_string(),
+                    KEY_REGISTRY_OTHER_NODES_ROLE.to_string(),
+                ],
+            );
+        // When every key is allowed every role, any key of this node could change the key
+        // registry, so it may only be changed through the REST API if key permissions are set
+        match self.key_permissions_config {
+            KeyPermissionsConfig::AllowAll => {
+                debug!("Key registry changes through the REST API are disabled by allow-all")
+            }
+            _ => {
+                key_registry_manager = key_registry_manager.with_write_authorization(
+                    self.node_id.clone(),
+                    Box::new(SawtoothSecp256k1SignatureVerifier::new()),
+                    key_permission_manager,
+                )
+            }
+        }
 
         let node_id = self.node_id.clone();
         let service_endpoint = self.service_endpoint.clone();
@@ -380,8 +598,19 @@ impl SplinterDaemon {
                     routes::get_status(node_id.clone(), service_endpoint.clone())
                 }),
             )
+            .add_resource(
+                Resource::build("/peers").add_method(Method::Get, move |_, _| {
+                    routes::list_peers(&peers_network, &peers_auth_manager)
+                }),
+            )
+            .add_resource(
+                Resource::build("/peers/{peer_id}").add_method(Method::Get, move |request, _| {
+                    routes::fetch_peer(request, &peer_network, &peer_auth_manager)
+                }),
+            )
             .add_resource(make_nodes_identity_resource(node_registry.clone()))
             .add_resource(make_nodes_resource(node_registry.clone()))
+            .add_resource(make_nodes_subscribe_resource(node_registry.clone()))
             .add_resources(key_registry_manager.resources())
             .add_resources(admin_service.resources())
             .add_resources(orchestrator_resources);
@@ -392,6 +621,15 @@ impl SplinterDaemon {
             rest_api_builder = rest_api_builder.add_resources(circuit_resource.resources());
         }
 
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = self.metrics.clone();
+            rest_api_builder = rest_api_builder.add_resource(
+                Resource::build("/metrics")
+                    .add_method(Method::Get, move |_, _| routes::get_metrics(&metrics)),
+            );
+        }
+
         #[cfg(feature = "biome")]
         {
             if self.biome_enabled {
@@ -408,7 +646,7 @@ impl SplinterDaemon {
         let (rest_api_shutdown_handle, rest_api_join_handle) = rest_api_builder.build()?.run()?;
 
         let (admin_shutdown_handle, service_processor_join_handle) =
-            Self::start_admin_service(inproc_transport, admin_service, Arc::clone(&running))?;
+            self.start_admin_service(inproc_transport, admin_service, Arc::clone(&running))?;
 
         // Allowing possibly redundant clone of `running` since it will be needed again if the
         // `health` feature is enabled
@@ -425,6 +663,13 @@ impl SplinterDaemon {
             if let Err(err) = rest_api_shutdown_handle.shutdown() {
                 error!("Unable to cleanly shut down REST API server: {}", err);
             }
+
+            #[cfg(feature = "connection-manager")]
+            {
+                if let Some(ref shutdown_handle) = connection_manager_shutdown_handle {
+                    shutdown_handle.shutdown();
+                }
+            }
         })
         .expect("Error setting Ctrl-C handler");
 
@@ -435,8 +680,11 @@ impl SplinterDaemon {
         #[cfg(feature = "health")]
         {
             let health_service = HealthService::new(&self.node_id);
-            let health_service_processor_join_handle =
-                start_health_service(health_inproc.unwrap(), health_service, Arc::clone(&running))?;
+            let health_service_processor_join_handle = self.start_health_service(
+                health_inproc.unwrap(),
+                health_service,
+                Arc::clone(&running),
+            )?;
 
             let _ = health_service_processor_join_handle.join_all();
         }
@@ -449,6 +697,176 @@ impl SplinterDaemon {
         let _ = rest_api_join_handle.join();
         let _ = service_processor_join_handle.join_all();
 
+        #[cfg(feature = "connection-manager")]
+        connection_manager.await_shutdown();
+
+        Ok(())
+    }
+
+    /// Creates the key registry, which is stored in the database if the key registry backend is
+    /// DATABASE, and with the node's other storage otherwise.
+    fn create_key_registry(&self) -> Result<Box<dyn KeyRegistry>, StartError> {
+        #[cfg(feature = "database")]
+        {
+            if let Some(db_url) = &self.key_registry_db_url {
+                let connection_pool =
+                    splinter::database::create_connection_pool(db_url).map_err(|err| {
+                        StartError::StorageError(format!(
+                            "Unable to connect to the key registry database: {}",
+                            err
+                        ))
+                    })?;
+                return Ok(Box::new(DatabaseKeyRegistry::new(connection_pool)));
+            }
+        }
+
+        Ok(Box::new(
+            StorageKeyRegistry::new(self.key_registry_location.clone())
+                .map_err(|err| StartError::StorageError(format!("{}", err)))?,
+        ))
+    }
+
+    fn create_circuit_store(&self) -> Result<Box<dyn CircuitStore>, StartError> {
+        #[cfg(feature = "circuit-store-lmdb")]
+        {
+            if self.storage_type == "lmdb" {
+                let mut store = LmdbCircuitStore::new(
+                    Path::new(&self.storage_location),
+                    DEFAULT_CIRCUIT_STORE_SIZE,
+                )
+                .map_err(|err| StartError::StorageError(err.to_string()))?;
+
+                // The circuit state of a node that used YAML storage is moved into LMDB the first
+                // time it starts with it
+                let yaml_path = Path::new(&self.storage_location).with_extension("yaml");
+                store
+                    .migrate_from_yaml(&yaml_path)
+                    .map_err(|err| StartError::StorageError(err.to_string()))?;
+
+                return Ok(Box::new(store));
+            }
+        }
+
+        Ok(Box::new(StorageCircuitStore::new(
+            self.storage_location.clone(),
+        )))
+    }
+
+    /// Periodically advertises this node's authorized peers to those peers, so that nodes without
+    /// a direct connection can route circuit messages through this node.
+    fn advertise_routes(
+        network: Network,
+        auth_manager: AuthorizationManager,
+        routing_table: RoutingTable,
+        running: Arc<AtomicBool>,
+    ) -> Result<(), StartError> {
+        let _ = thread::Builder::new()
+            .name("RouteAdvertisement".into())
+            .spawn(move || {
+                let interval = Duration::from_secs(ROUTE_ADVERTISEMENT_INTERVAL_SEC);
+                while running.load(Ordering::SeqCst) {
+                    let peers = network
+                        .peer_ids()
+                        .into_iter()
+                        .filter(|peer_id| {
+                            auth_manager.authorization_state(peer_id)
+                                == AuthorizationState::Authorized
+                        })
+                        .collect::<Vec<_>>();
+
+                    match routing_table.create_advertisement(peers.clone()) {
+                        Ok(advertisement) => {
+                            for peer_id in peers {
+                                if let Err(err) = network.send(&peer_id, &advertisement) {
+                                    warn!(
+                                        "Unable to send route advertisement to {}: {:?}",
+                                        peer_id, err
+                                    );
+                                }
+                            }
+                        }
+                        Err(err) => error!("Unable to create route advertisement: {}", err),
+                    }
+
+                    for node_id in routing_table.expire_nodes(interval * ROUTE_EXPIRY_INTERVALS) {
+                        debug!("Removed the expired routes advertised by {}", node_id);
+                    }
+
+                    thread::sleep(interval);
+                }
+            })
+            .map_err(|_| {
+                StartError::ThreadError("Unable to spawn route advertisement thread".into())
+            })?;
+
+        Ok(())
+    }
+
+    /// Periodically resends queued durable messages that have not been acknowledged, such as
+    /// messages for services that were disconnected when they were first sent.
+    fn resend_durable_messages(
+        resender: DurableMessageResender,
+        running: Arc<AtomicBool>,
+    ) -> Result<(), StartError> {
+        let _ = thread::Builder::new()
+            .name("DurableMessageResender".into())
+            .spawn(move || {
+                let interval = Duration::from_secs(DURABLE_RESEND_INTERVAL_SEC);
+                while running.load(Ordering::SeqCst) {
+                    if let Err(err) = resender.resend() {
+                        error!("Unable to resend durable messages: {}", err);
+                    }
+
+                    thread::sleep(interval);
+                }
+            })
+            .map_err(|_| {
+                StartError::ThreadError("Unable to spawn durable message resend thread".into())
+            })?;
+
+        Ok(())
+    }
+
+    /// Reacts to the connection manager's notifications in a background thread.
+    ///
+    /// A heartbeat failure causes the connection manager to replace the connection, which removes
+    /// the old peer from the network (and, as a result, from authorization and circuit routing).
+    /// Once the connection has been reestablished, the new connection must be authorized again.
+    #[cfg(feature = "connection-manager")]
+    fn handle_connection_notifications(
+        notifications: NotificationIter,
+        peer_connector: PeerConnector,
+    ) -> Result<(), StartError> {
+        // this thread will exit when the connection manager shuts down
+        let _ = thread::Builder::new()
+            .name("ConnectionNotifications".into())
+            .spawn(move || {
+                for notification in notifications {
+                    match notification {
+                        ConnectionManagerNotification::HeartbeatSendFail { endpoint, message } => {
+                            warn!("Heartbeat to {} failed: {}", endpoint, message)
+                        }
+                        ConnectionManagerNotification::ReconnectAttemptSuccess { endpoint } => {
+                            debug!("Reconnected to {}; reauthorizing", endpoint);
+                            if let Err(err) = peer_connector.reauthorize_peer(&endpoint) {
+                                error!("Unable to reauthorize {}: {}", endpoint, err);
+                            }
+                        }
+                        ConnectionManagerNotification::ReconnectAttemptFailed {
+                            endpoint,
+                            message,
+                        } => warn!("Unable to reconnect to {}: {}", endpoint, message),
+                        ConnectionManagerNotification::FatalError { error, message } => {
+                            error!("Connection manager error: {}: {}", message, error)
+                        }
+                        ConnectionManagerNotification::HeartbeatSent { .. } => (),
+                    }
+                }
+            })
+            .map_err(|_| {
+                StartError::ThreadError("Unable to spawn connection notification thread".into())
+            })?;
+
         Ok(())
     }
 
@@ -501,10 +919,13 @@ impl SplinterDaemon {
     }
 
     fn start_admin_service(
+        &self,
         transport: InprocTransport,
         admin_service: AdminService,
         running: Arc<AtomicBool>,
     ) -> Result<(ShutdownHandle, ServiceJoinHandle), StartError> {
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.clone();
         let start_admin: std::thread::JoinHandle<
             Result<(ShutdownHandle, ServiceJoinHandle), StartError>,
         > = thread::spawn(move || {
@@ -531,6 +952,10 @@ impl SplinterDaemon {
                     err
                 ))
             })?;
+            #[cfg(feature = "metrics")]
+            {
+                admin_service_processor = admin_service_processor.with_metrics(metrics);
+            }
 
             admin_service_processor
                 .add_service(Box::new(admin_service))
@@ -552,66 +977,77 @@ impl SplinterDaemon {
             )
         })?
     }
-}
 
-#[cfg(feature = "health")]
-fn start_health_service(
-    mut transport: InprocTransport,
-    health_service: HealthService,
-    running: Arc<AtomicBool>,
-) -> Result<service::JoinHandles<Result<(), service::error::ServiceProcessorError>>, StartError> {
-    let start_health_service: std::thread::JoinHandle<
-        Result<service::JoinHandles<Result<(), service::error::ServiceProcessorError>>, StartError>,
-    > = thread::spawn(move || {
-        // use a match statement here, to inform
-        let connection = transport
-            .connect("inproc://health-service")
+    #[cfg(feature = "health")]
+    fn start_health_service(
+        &self,
+        mut transport: InprocTransport,
+        health_service: HealthService,
+        running: Arc<AtomicBool>,
+    ) -> Result<service::JoinHandles<Result<(), service::error::ServiceProcessorError>>, StartError>
+    {
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.clone();
+        let start_health_service: std::thread::JoinHandle<
+            Result<
+                service::JoinHandles<Result<(), service::error::ServiceProcessorError>>,
+                StartError,
+            >,
+        > = thread::spawn(move || {
+            // use a match statement here, to inform
+            let connection = transport
+                .connect("inproc://health-service")
+                .map_err(|err| {
+                    StartError::HealthServiceError(format!(
+                        "unable to initiate health service connection: {:?}",
+                        err
+                    ))
+                })?;
+            let mut health_service_processor = ServiceProcessor::new(
+                connection,
+                "health".into(),
+                HEALTH_SERVICE_PROCESSOR_INCOMING_CAPACITY,
+                HEALTH_SERVICE_PROCESSOR_OUTGOING_CAPACITY,
+                HEALTH_SERVICE_PROCESSOR_CHANNEL_CAPACITY,
+                running,
+            )
             .map_err(|err| {
                 StartError::HealthServiceError(format!(
-                    "unable to initiate health service connection: {:?}",
+                    "unable to create health service processor: {}",
                     err
                 ))
             })?;
-        let mut health_service_processor = ServiceProcessor::new(
-            connection,
-            "health".into(),
-            HEALTH_SERVICE_PROCESSOR_INCOMING_CAPACITY,
-            HEALTH_SERVICE_PROCESSOR_OUTGOING_CAPACITY,
-            HEALTH_SERVICE_PROCESSOR_CHANNEL_CAPACITY,
-            running,
-        )
-        .map_err(|err| {
-            StartError::HealthServiceError(format!(
-                "unable to create health service processor: {}",
-                err
-            ))
-        })?;
+            #[cfg(feature = "metrics")]
+            {
+                health_service_processor = health_service_processor.with_metrics(metrics);
+            }
 
-        health_service_processor
-            .add_service(Box::new(health_service))
-            .map_err(|err| {
-                StartError::HealthServiceError(format!(
-                    "unable to add health service to processor: {}",
-                    err
-                ))
-            })?;
+            health_service_processor
+                .add_service(Box::new(health_service))
+                .map_err(|err| {
+                    StartError::HealthServiceError(format!(
+                        "unable to add health service to processor: {}",
+                        err
+                    ))
+                })?;
 
-        health_service_processor
-            .start()
-            .map(|(_, join_handles)| join_handles)
-            .map_err(|err| {
-                StartError::HealthServiceError(format!(
-                    "unable to health service processor: {}",
-                    err
-                ))
-            })
-    });
+            health_service_processor
+                .start()
+                .map(|(_, join_handles)| join_handles)
+                .map_err(|err| {
+                    StartError::HealthServiceError(format!(
+                        "unable to health service processor: {}",
+                        err
+                    ))
+                })
+        });
 
-    start_health_service.join().map_err(|_| {
-        StartError::HealthServiceError(
-            "unable to start health service, due to thread join error".into(),
-        )
-    })?
+        start_health_service.join().map_err(|_| {
+            StartError::HealthServiceError(
+                "unable to start health service, due to thread join error".into(),
+            )
+        })?
+    }
 }
 
 #[cfg(feature = "biome")]
@@ -643,6 +1079,7 @@ fn build_biome_routes(db_url: &str) -> Result<BiomeRestResourceManager, StartErr
 pub struct SplinterDaemonBuilder {
     storage_location: Option<String>,
     key_registry_location: Option<String>,
+    durable_store_location: Option<String>,
     service_endpoint: Option<String>,
     network_endpoint: Option<String>,
     initial_peers: Option<Vec<String>>,
@@ -650,12 +1087,29 @@ pub struct SplinterDaemonBuilder {
     rest_api_endpoint: Option<String>,
     #[cfg(feature = "database")]
     db_url: Option<String>,
+    #[cfg(feature = "database")]
+    key_registry_backend: Option<String>,
     #[cfg(feature = "biome")]
     biome_enabled: bool,
     registry_backend: Option<String>,
     registry_file: Option<String>,
+    #[cfg(feature = "node-registry-remote")]
+    remote_registries: Vec<String>,
+    #[cfg(feature = "node-registry-remote")]
+    registry_refresh_interval: Option<u64>,
+    #[cfg(feature = "node-registry-signing")]
+    registry_signature_policy: Option<String>,
+    key_permissions: Option<String>,
+    key_permissions_file: Option<String>,
     storage_type: Option<String>,
     heartbeat_interval: Option<u64>,
+    flow_control: Option<FlowControl>,
+    peer_rate_limit: Option<RateLimit>,
+    rate_limit_disconnect_threshold: Option<(u32, Duration)>,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    #[cfg(feature = "unix-transport")]
+    unix_socket_permissions: Option<u32>,
 }
 
 impl SplinterDaemonBuilder {
@@ -673,6 +1127,11 @@ impl SplinterDaemonBuilder {
         self
     }
 
+    pub fn with_durable_store_location(mut self, value: String) -> Self {
+        self.durable_store_location = Some(value);
+        self
+    }
+
     pub fn with_service_endpoint(mut self, value: String) -> Self {
         self.service_endpoint = Some(value);
         self
@@ -704,6 +1163,12 @@ impl SplinterDaemonBuilder {
         self
     }
 
+    #[cfg(feature = "database")]
+    pub fn with_key_registry_backend(mut self, value: String) -> Self {
+        self.key_registry_backend = Some(value);
+        self
+    }
+
     #[cfg(feature = "biome")]
     pub fn enable_biome(mut self, enabled: bool) -> Self {
         self.biome_enabled = enabled;
@@ -720,6 +1185,34 @@ impl SplinterDaemonBuilder {
         self
     }
 
+    #[cfg(feature = "node-registry-remote")]
+    pub fn with_remote_registries(mut self, value: Vec<String>) -> Self {
+        self.remote_registries = value;
+        self
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    pub fn with_registry_refresh_interval(mut self, value: u64) -> Self {
+        self.registry_refresh_interval = Some(value);
+        self
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    pub fn with_registry_signature_policy(mut self, value: String) -> Self {
+        self.registry_signature_policy = Some(value);
+        self
+    }
+
+    pub fn with_key_permissions(mut self, value: String) -> Self {
+        self.key_permissions = Some(value);
+        self
+    }
+
+    pub fn with_key_permissions_file(mut self, value: String) -> Self {
+        self.key_permissions_file = Some(value);
+        self
+    }
+
     pub fn with_storage_type(mut self, value: String) -> Self {
         self.storage_type = Some(value);
         self
@@ -730,15 +1223,60 @@ impl SplinterDaemonBuilder {
         self
     }
 
+    pub fn with_flow_control(mut self, value: FlowControl) -> Self {
+        self.flow_control = Some(value);
+        self
+    }
+
+    pub fn with_peer_rate_limit(mut self, value: RateLimit) -> Self {
+        self.peer_rate_limit = Some(value);
+        self
+    }
+
+    pub fn with_rate_limit_disconnect_threshold(
+        mut self,
+        max_violations: u32,
+        window: Duration,
+    ) -> Self {
+        self.rate_limit_disconnect_threshold = Some((max_violations, window));
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn with_compression_threshold(mut self, value: usize) -> Self {
+        self.compression_threshold = Some(value);
+        self
+    }
+
+    #[cfg(feature = "unix-transport")]
+    pub fn with_unix_socket_permissions(mut self, value: u32) -> Self {
+        self.unix_socket_permissions = Some(value);
+        self
+    }
+
     pub fn build(self) -> Result<SplinterDaemon, CreateError> {
         let heartbeat_interval = self.heartbeat_interval.ok_or_else(|| {
             CreateError::MissingRequiredField("Missing field: heartbeat_interval".to_string())
         })?;
 
-        let mesh = Mesh::new(512, 128);
+        #[cfg(feature = "metrics")]
+        let metrics = MetricsRegistry::new();
+
+        let mesh = match self.flow_control {
+            Some(flow_control) => Mesh::with_flow_control(512, flow_control),
+            None => Mesh::new(512, 128),
+        };
+        #[cfg(feature = "metrics")]
+        let mesh = mesh.with_metrics(metrics.clone());
         let network = Network::new(mesh, heartbeat_interval)
             .map_err(|err| CreateError::NetworkError(err.to_string()))?;
 
+        #[cfg(feature = "compression")]
+        let network = match self.compression_threshold {
+            Some(threshold) => network.with_compression_threshold(threshold),
+            None => network,
+        };
+
         let storage_location = self.storage_location.ok_or_else(|| {
             CreateError::MissingRequiredField("Missing field: storage_location".to_string())
         })?;
@@ -747,6 +1285,10 @@ impl SplinterDaemonBuilder {
             CreateError::MissingRequiredField("Missing field: key_registry_location".to_string())
         })?;
 
+        let durable_store_location = self.durable_store_location.ok_or_else(|| {
+            CreateError::MissingRequiredField("Missing field: durable_store_location".to_string())
+        })?;
+
         let service_endpoint = self.service_endpoint.ok_or_else(|| {
             CreateError::MissingRequiredField("Missing field: service_location".to_string())
         })?;
@@ -791,8 +1333,57 @@ impl SplinterDaemonBuilder {
             registry_config_builder = registry_config_builder.with_registry_file(value);
         }
 
+        #[cfg(feature = "database")]
+        {
+            if let Some(value) = db_url.clone() {
+                registry_config_builder = registry_config_builder.with_db_url(value);
+            }
+        }
+
         let registry_config = registry_config_builder.build()?;
 
+        let mut key_permissions_config_builder = KeyPermissionsConfigBuilder::default();
+
+        if let Some(value) = self.key_permissions {
+            key_permissions_config_builder =
+                key_permissions_config_builder.with_key_permissions(value);
+        }
+
+        if let Some(value) = self.key_permissions_file {
+            key_permissions_config_builder = key_permissions_config_builder.with_policy_file(value);
+        }
+
+        let key_permissions_config = key_permissions_config_builder.build()?;
+
+        #[cfg(feature = "database")]
+        let key_registry_db_url = match self.key_registry_backend.as_deref() {
+            None | Some("STORAGE") => None,
+            Some("DATABASE") => Some(db_url.clone().ok_or_else(|| {
+                CreateError::MissingRequiredField(
+                    "db_url is required for a key registry backend of type DATABASE".to_string(),
+                )
+            })?),
+            Some(backend) => {
+                return Err(CreateError::KeyRegistryError(format!(
+                    "invalid key registry backend: {}",
+                    backend
+                )))
+            }
+        };
+
+        #[cfg(feature = "node-registry-signing")]
+        let registry_signature_policy = match self.registry_signature_policy.as_deref() {
+            None | Some("ignore") => None,
+            Some("flag") => Some(SignaturePolicy::Flag),
+            Some("reject") => Some(SignaturePolicy::Reject),
+            Some(policy) => {
+                return Err(CreateError::NodeRegistryError(format!(
+                    "invalid registry signature policy: {}",
+                    policy
+                )))
+            }
+        };
+
         Ok(SplinterDaemon {
             storage_location,
             service_endpoint,
@@ -803,11 +1394,34 @@ impl SplinterDaemonBuilder {
             rest_api_endpoint,
             #[cfg(feature = "database")]
             db_url,
+            #[cfg(feature = "database")]
+            key_registry_db_url,
             #[cfg(feature = "biome")]
             biome_enabled: self.biome_enabled,
             registry_config,
+            #[cfg(feature = "node-registry-remote")]
+            remote_registries: self.remote_registries,
+            #[cfg(feature = "node-registry-remote")]
+            registry_refresh_interval: self
+                .registry_refresh_interval
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL),
+            #[cfg(feature = "node-registry-signing")]
+            registry_signature_policy,
+            key_permissions_config,
             key_registry_location,
+            durable_store_location,
             storage_type,
+            #[cfg(feature = "connection-manager")]
+            heartbeat_interval,
+            peer_rate_limit: self.peer_rate_limit,
+            rate_limit_disconnect_threshold: self.rate_limit_disconnect_threshold,
+            #[cfg(feature = "unix-transport")]
+            unix_socket_permissions: self
+                .unix_socket_permissions
+                .unwrap_or(DEFAULT_SOCKET_PERMISSIONS),
+            #[cfg(feature = "metrics")]
+            metrics,
         })
     }
 }
@@ -815,7 +1429,9 @@ impl SplinterDaemonBuilder {
 fn set_up_network_dispatcher(
     send: crossbeam_channel::Sender<SendRequest>,
     node_id: &str,
+    network: Network,
     auth_manager: AuthorizationManager,
+    routing_table: RoutingTable,
     circuit_sender: crossbeam_channel::Sender<DispatchMessage<CircuitMessageType>>,
     auth_sender: crossbeam_channel::Sender<DispatchMessage<AuthorizationMessageType>>,
 ) -> Dispatcher<NetworkMessageType> {
@@ -830,13 +1446,22 @@ fn set_up_network_dispatcher(
         )),
     );
 
-    let network_heartbeat_handler = NetworkHeartbeatHandler::new();
+    let network_heartbeat_handler = NetworkHeartbeatHandler::with_network(network);
     // do not add auth guard
     dispatcher.set_handler(
         NetworkMessageType::NETWORK_HEARTBEAT,
         Box::new(network_heartbeat_handler),
     );
 
+    let route_advertisement_handler = NetworkRouteAdvertisementHandler::new(routing_table);
+    dispatcher.set_handler(
+        NetworkMessageType::NETWORK_ROUTE_ADVERTISEMENT,
+        Box::new(NetworkAuthGuardHandler::new(
+            auth_manager.clone(),
+            Box::new(route_advertisement_handler),
+        )),
+    );
+
     let circuit_message_handler = CircuitMessageHandler::new(Box::new(circuit_sender));
     dispatcher.set_handler(
         NetworkMessageType::CIRCUIT,
@@ -860,6 +1485,9 @@ fn set_up_circuit_dispatcher(
     node_id: &str,
     endpoint: &str,
     state: Arc<RwLock<SplinterState>>,
+    routing_table: RoutingTable,
+    durable_store: DurableMessageStore,
+    circuit_sender: crossbeam_channel::Sender<DispatchMessage<CircuitMessageType>>,
 ) -> Dispatcher<CircuitMessageType> {
     let mut dispatcher = Dispatcher::<CircuitMessageType>::new(Box::new(send));
 
@@ -877,18 +1505,37 @@ fn set_up_circuit_dispatcher(
     );
 
     let direct_message_handler =
-        CircuitDirectMessageHandler::new(node_id.to_string(), state.clone());
+        CircuitDirectMessageHandler::new(node_id.to_string(), state.clone())
+            .with_routing_table(routing_table.clone())
+            .with_durable_store(durable_store.clone());
     dispatcher.set_handler(
         CircuitMessageType::CIRCUIT_DIRECT_MESSAGE,
         Box::new(direct_message_handler),
     );
 
-    let circuit_error_handler = CircuitErrorHandler::new(node_id.to_string(), state.clone());
+    let direct_message_ack_handler =
+        CircuitDirectMessageAckHandler::new(node_id.to_string(), state.clone())
+            .with_routing_table(routing_table.clone())
+            .with_durable_store(durable_store);
+    dispatcher.set_handler(
+        CircuitMessageType::CIRCUIT_DIRECT_MESSAGE_ACK,
+        Box::new(direct_message_ack_handler),
+    );
+
+    let circuit_error_handler = CircuitErrorHandler::new(node_id.to_string(), state.clone())
+        .with_routing_table(routing_table.clone());
     dispatcher.set_handler(
         CircuitMessageType::CIRCUIT_ERROR_MESSAGE,
         Box::new(circuit_error_handler),
     );
 
+    let routed_message_handler =
+        CircuitRoutedMessageHandler::new(routing_table, state.clone(), Box::new(circuit_sender));
+    dispatcher.set_handler(
+        CircuitMessageType::CIRCUIT_ROUTED_MESSAGE,
+        Box::new(routed_message_handler),
+    );
+
     // Circuit Admin handlers
     let admin_direct_message_handler = AdminDirectMessageHandler::new(node_id.to_string(), state);
     dispatcher.set_handler(
@@ -911,14 +1558,62 @@ fn create_node_registry(
                 ))
             })?,
         )),
+        #[cfg(feature = "database")]
+        RegistryConfig::Database { db_url } => {
+            let connection_pool =
+                splinter::database::create_connection_pool(db_url).map_err(|err| {
+                    RestApiServerError::StartUpError(format!(
+                        "Failed to connect to the node registry database: {}",
+                        err
+                    ))
+                })?;
+            Ok(Box::new(
+                node_registry::database::DatabaseNodeRegistry::new(connection_pool),
+            ))
+        }
         RegistryConfig::NoOp => Ok(Box::new(node_registry::noop::NoOpNodeRegistry)),
     }
 }
 
+/// Combines the local node registry with the given remote registries, if there are any.
+#[cfg(feature = "node-registry-remote")]
+fn add_remote_node_registries(
+    local_registry: Box<dyn RwNodeRegistry>,
+    remote_registries: Vec<Box<dyn NodeRegistryReader>>,
+) -> Box<dyn RwNodeRegistry> {
+    if remote_registries.is_empty() {
+        return local_registry;
+    }
+
+    Box::new(UnifiedNodeRegistry::new(local_registry, remote_registries))
+}
+
+fn create_key_permission_manager(
+    key_permissions_config: &KeyPermissionsConfig,
+    key_registry: Box<dyn KeyRegistry>,
+) -> Result<Box<dyn KeyPermissionManager>, StartError> {
+    match key_permissions_config {
+        KeyPermissionsConfig::AllowAll => Ok(Box::new(AllowAllKeyPermissionManager)),
+        KeyPermissionsConfig::KeyRegistry => {
+            Ok(Box::new(KeyRegistryPermissionManager::new(key_registry)))
+        }
+        KeyPermissionsConfig::PolicyFile { policy_file } => Ok(Box::new(
+            PolicyFileKeyPermissionManager::new(policy_file).map_err(|err| {
+                StartError::AdminServiceError(format!(
+                    "unable to load key permission policy: {}",
+                    err
+                ))
+            })?,
+        )),
+    }
+}
+
 #[derive(Debug)]
 pub enum CreateError {
     MissingRequiredField(String),
     NodeRegistryError(String),
+    KeyRegistryError(String),
+    KeyPermissionsError(String),
     NetworkError(String),
 }
 
@@ -931,6 +1626,12 @@ impl fmt::Display for CreateError {
             CreateError::NodeRegistryError(msg) => {
                 write!(f, "node registry raised an error: {}", msg)
             }
+            CreateError::KeyRegistryError(msg) => {
+                write!(f, "unable to configure the key registry: {}", msg)
+            }
+            CreateError::KeyPermissionsError(msg) => {
+                write!(f, "unable to configure key permissions: {}", msg)
+            }
             CreateError::NetworkError(msg) => write!(f, "network raised an error: {}", msg),
         }
     }
@@ -942,6 +1643,12 @@ impl From<RegistryConfigError> for CreateError {
     }
 }
 
+impl From<KeyPermissionsConfigError> for CreateError {
+    fn from(err: KeyPermissionsConfigError) -> Self {
+        CreateError::KeyPermissionsError(err.to_string())
+    }
+}
+
 #[derive(Debug)]
 pub enum StartError {
     TransportError(String),
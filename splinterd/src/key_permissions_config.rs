@@ -0,0 +1,92 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum KeyPermissionsConfig {
+    AllowAll,
+    KeyRegistry,
+    PolicyFile { policy_file: String },
+}
+
+pub struct KeyPermissionsConfigBuilder {
+    key_permissions: Option<String>,
+    policy_file: Option<String>,
+}
+
+impl Default for KeyPermissionsConfigBuilder {
+    fn default() -> Self {
+        Self {
+            key_permissions: Some("allow-all".to_owned()),
+            policy_file: None,
+        }
+    }
+}
+
+impl KeyPermissionsConfigBuilder {
+    pub fn with_key_permissions(mut self, value: String) -> Self {
+        self.key_permissions = Some(value);
+        self
+    }
+
+    pub fn with_policy_file(mut self, value: String) -> Self {
+        self.policy_file = Some(value);
+        self
+    }
+
+    pub fn build(self) -> Result<KeyPermissionsConfig, KeyPermissionsConfigError> {
+        match self.key_permissions.as_ref().map(String::as_str) {
+            Some("allow-all") | None => Ok(KeyPermissionsConfig::AllowAll),
+            Some("key-registry") => Ok(KeyPermissionsConfig::KeyRegistry),
+            Some("policy-file") => self
+                .policy_file
+                .map(|policy_file| KeyPermissionsConfig::PolicyFile { policy_file })
+                .ok_or_else(|| {
+                    KeyPermissionsConfigError::MissingValue(
+                        "For key_permissions of type 'policy-file' a path to the file must be \
+                         provided."
+                            .to_string(),
+                    )
+                }),
+            Some(key_permissions) => Err(KeyPermissionsConfigError::InvalidType(
+                key_permissions.to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyPermissionsConfigError {
+    MissingValue(String),
+    InvalidType(String),
+}
+
+impl Error for KeyPermissionsConfigError {}
+
+impl fmt::Display for KeyPermissionsConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyPermissionsConfigError::MissingValue(config_field_name) => {
+                write!(f, "Missing configuration for {}", config_field_name)
+            }
+            KeyPermissionsConfigError::InvalidType(key_permissions) => write!(
+                f,
+                "Key permissions of type {} are not supported",
+                key_permissions
+            ),
+        }
+    }
+}
@@ -23,6 +23,7 @@ extern crate clap;
 mod certs;
 mod config;
 mod daemon;
+mod key_permissions_config;
 mod registry_config;
 mod routes;
 
@@ -39,8 +40,20 @@ use clap::{clap_app, crate_version};
 use clap::{Arg, ArgMatches};
 #[cfg(feature = "generate-certs")]
 use openssl::error::ErrorStack;
+use splinter::mesh::{FlowControl, SlowPeerPolicy, DEFAULT_SLOW_PEER_TIMEOUT};
+#[cfg(feature = "compression")]
+use splinter::network::compression::DEFAULT_COMPRESSION_THRESHOLD;
+use splinter::network::rate_limit::{RateLimit, DEFAULT_VIOLATION_WINDOW};
+#[cfg(feature = "node-registry-remote")]
+use splinter::node_registry::remote::DEFAULT_REFRESH_INTERVAL;
+#[cfg(feature = "ws-transport")]
+use splinter::transport::multi::MultiTransport;
+#[cfg(feature = "quic-transport")]
+use splinter::transport::quic::QuicTransport;
 use splinter::transport::raw::RawTransport;
-use splinter::transport::tls::{TlsInitError, TlsTransport};
+use splinter::transport::tls::{days_until_expiry, TlsInitError, TlsReloader, TlsTransport};
+#[cfg(feature = "ws-transport")]
+use splinter::transport::ws::WsTransport;
 use splinter::transport::Transport;
 #[cfg(feature = "generate-certs")]
 use tempdir::TempDir;
@@ -53,7 +66,10 @@ use std::fs;
 use std::fs::File;
 use std::io;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 const DEFAULT_STATE_DIR: &str = "/var/lib/splinter/";
 const STATE_DIR_ENV: &str = "SPLINTER_STATE_DIR";
@@ -67,6 +83,10 @@ const SERVER_CERT: &str = "server.crt";
 const SERVER_KEY: &str = "private/server.key";
 const CA_PEM: &str = "ca.pem";
 
+const TLS_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const CERT_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const CERT_EXPIRY_WARNING_DAYS: i32 = 30;
+
 const HEARTBEAT_DEFAULT: u64 = 30;
 
 #[cfg(not(feature = "config-toml"))]
@@ -138,17 +158,19 @@ fn main() {
         (@arg node_id: --("node-id") +takes_value
           "Unique ID for the node ")
         (@arg storage: --("storage") +takes_value
-          "Storage type used for the node; defaults to yaml")
+          "Storage type used for the node, either yaml, memory or lmdb; defaults to yaml")
         (@arg transport: --("transport") +takes_value
-          "Transport type for sockets, either raw or tls")
+          "Transport type for sockets, either raw, tls, ws, wss or quic")
         (@arg network_endpoint: -n --("network-endpoint") +takes_value
           "Endpoint to connect to the network, tcp://ip:port")
         (@arg service_endpoint: --("service-endpoint") +takes_value
-          "Endpoint that service will connect to, tcp://ip:port")
+          "Endpoint that service will connect to, tcp://ip:port or unix://path")
         (@arg peers: --peer +takes_value +multiple
           "Endpoint that service will connect to, ip:port")
         (@arg ca_file: --("ca-file") +takes_value
           "File path to the trusted CA certificate")
+        (@arg crl_file: --("crl-file") +takes_value
+          "File path to the certificate revocation lists used to reject revoked peer certificates")
         (@arg cert_dir: --("cert-dir") +takes_value
           "Path to the directory where the certificates and keys are")
         (@arg client_cert: --("client-cert") +takes_value
@@ -164,9 +186,13 @@ fn main() {
         (@arg bind: --("bind") +takes_value
           "Connection endpoint for REST API")
         (@arg registry_backend: --("registry-backend") +takes_value
-          "Backend type for the node registry. Possible values: FILE.")
+          "Backend type for the node registry. Possible values: FILE, DATABASE.")
         (@arg registry_file: --("registry-file") +takes_value
           "File path to the node registry file if registry-backend is FILE.")
+        (@arg key_permissions: --("key-permissions") +takes_value
+          "How key roles are checked. Possible values: allow-all, key-registry, policy-file.")
+        (@arg key_permissions_file: --("key-permissions-file") +takes_value
+          "File path to the key permission policy if key-permissions is policy-file.")
         (@arg verbose: -v --verbose +multiple
           "Increase output verbosity"));
 
@@ -180,6 +206,105 @@ fn main() {
             .takes_value(true),
     );
 
+    let app = app
+        .arg(
+            Arg::with_name("send_queue_high_watermark")
+                .long("send-queue-high-watermark")
+                .long_help(
+                    "Number of messages queued for a peer at which sends to it fail until the \
+                     queue drains to the low watermark; the send queues are not limited by \
+                     watermarks if unset",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("send_queue_low_watermark")
+                .long("send-queue-low-watermark")
+                .long_help(
+                    "Number of messages queued for a peer at which sends to it succeed again; \
+                     defaults to half the high watermark",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("slow_peer_policy")
+                .long("slow-peer-policy")
+                .long_help(
+                    "How a peer whose send queue stays full is handled: \"backpressure\" (the \
+                     default), \"drop-messages\" or \"disconnect\"",
+                )
+                .takes_value(true)
+                .possible_values(&["backpressure", "drop-messages", "disconnect"]),
+        )
+        .arg(
+            Arg::with_name("slow_peer_timeout")
+                .long("slow-peer-timeout")
+                .long_help(
+                    "How long a peer's send queue may stay full before the slow peer policy is \
+                     applied, in seconds; defaults to 30",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("peer_rate_limit")
+                .long("peer-rate-limit")
+                .long_help(
+                    "Number of messages per second each peer may send; messages above the limit \
+                     are dropped. Peers are not rate limited if unset",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("peer_rate_limit_burst")
+                .long("peer-rate-limit-burst")
+                .long_help(
+                    "Number of messages a peer may send at once, above its rate limit; defaults \
+                     to the rate limit",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rate_limit_max_violations")
+                .long("rate-limit-max-violations")
+                .long_help(
+                    "Number of a peer's messages that may be dropped for exceeding its rate \
+                     limit within the violation window before the peer is disconnected; peers \
+                     are not disconnected if unset",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rate_limit_violation_window")
+                .long("rate-limit-violation-window")
+                .long_help(
+                    "Time within which a peer's dropped messages count towards disconnecting it, \
+                     in seconds; defaults to 10",
+                )
+                .takes_value(true),
+        );
+
+    #[cfg(feature = "compression")]
+    let app = app.arg(
+        Arg::with_name("compression_threshold")
+            .long("compression-threshold")
+            .long_help(
+                "Size in bytes above which messages to peers that support compression are \
+                 compressed; defaults to 4096",
+            )
+            .takes_value(true),
+    );
+
+    #[cfg(feature = "unix-transport")]
+    let app = app.arg(
+        Arg::with_name("unix_socket_permissions")
+            .long("unix-socket-permissions")
+            .long_help(
+                "Permissions of the socket files of unix:// endpoints, in octal; defaults to \
+                 660, which allows connections from the owner and group of the socket file",
+            )
+            .takes_value(true),
+    );
+
     #[cfg(feature = "database")]
     let app = app.arg(
         Arg::with_name("database")
@@ -188,6 +313,52 @@ fn main() {
             .takes_value(true),
     );
 
+    #[cfg(feature = "database")]
+    let app = app.arg(
+        Arg::with_name("key_registry_backend")
+            .long("key-registry-backend")
+            .long_help(
+                "Backend type for the key registry. Possible values: STORAGE (the default), to \
+                 store keys with the node's other state, or DATABASE",
+            )
+            .takes_value(true)
+            .possible_values(&["STORAGE", "DATABASE"]),
+    );
+
+    #[cfg(feature = "node-registry-remote")]
+    let app = app
+        .arg(
+            Arg::with_name("remote_registries")
+                .long("remote-registry")
+                .long_help(
+                    "URL of a read-only node registry, in the YAML format of the registry file, \
+                     to combine with the local node registry",
+                )
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("registry_refresh_interval")
+                .long("registry-refresh-interval")
+                .long_help(
+                    "How often the remote node registries are fetched, in seconds; defaults to \
+                     600",
+                )
+                .takes_value(true),
+        );
+
+    #[cfg(feature = "node-registry-signing")]
+    let app = app.arg(
+        Arg::with_name("registry_signature_policy")
+            .long("registry-signature-policy")
+            .long_help(
+                "How node registry entries that are unsigned or have an invalid signature are \
+                 handled: \"ignore\" (the default), \"flag\" or \"reject\"",
+            )
+            .takes_value(true)
+            .possible_values(&["ignore", "flag", "reject"]),
+    );
+
     #[cfg(feature = "biome")]
     let app = app.arg(
         Arg::with_name("biome_enabled")
@@ -291,6 +462,10 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
 
     let (transport, transport_log) = get_transport(&transport_type, &matches, &config)?;
 
+    let flow_control = get_flow_control(&matches, &config)?;
+
+    let rate_limit = get_rate_limit(&matches, &config)?;
+
     let location = {
         if let Ok(s) = env::var(STATE_DIR_ENV) {
             s
@@ -301,6 +476,8 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
 
     let storage_location = match &storage_type as &str {
         "yaml" => format!("{}{}", location, "circuits.yaml"),
+        #[cfg(feature = "circuit-store-lmdb")]
+        "lmdb" => format!("{}{}", location, "circuits.lmdb"),
         "memory" => "memory".to_string(),
         _ => {
             return Err(UserError::InvalidArgument(format!(
@@ -312,6 +489,22 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
 
     let key_registry_location = match &storage_type as &str {
         "yaml" => format!("{}{}", location, "keys.yaml"),
+        // Only the circuit state is stored in LMDB
+        #[cfg(feature = "circuit-store-lmdb")]
+        "lmdb" => format!("{}{}", location, "keys.yaml"),
+        "memory" => "memory".to_string(),
+        _ => {
+            return Err(UserError::InvalidArgument(format!(
+                "storage type is not supported: {}",
+                storage_type
+            )))
+        }
+    };
+
+    let durable_store_location = match &storage_type as &str {
+        "yaml" => format!("{}{}", location, "durable_messages.log"),
+        #[cfg(feature = "circuit-store-lmdb")]
+        "lmdb" => format!("{}{}", location, "durable_messages.log"),
         "memory" => "memory".to_string(),
         _ => {
             return Err(UserError::InvalidArgument(format!(
@@ -333,9 +526,52 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
         .map(String::from)
         .or_else(|| config.database());
 
+    #[cfg(feature = "database")]
+    let key_registry_backend = matches
+        .value_of("key_registry_backend")
+        .map(String::from)
+        .or_else(|| config.key_registry_backend());
+
+    #[cfg(feature = "node-registry-remote")]
+    let remote_registries = matches
+        .values_of("remote_registries")
+        .map(|values| values.map(String::from).collect::<Vec<String>>())
+        .or_else(|| config.remote_registries())
+        .unwrap_or_default();
+
+    #[cfg(feature = "node-registry-remote")]
+    let registry_refresh_interval = value_t!(matches.value_of("registry_refresh_interval"), u64)
+        .unwrap_or_else(|_| {
+            config
+                .registry_refresh_interval()
+                .unwrap_or_else(|| DEFAULT_REFRESH_INTERVAL.as_secs())
+        });
+
+    #[cfg(feature = "node-registry-signing")]
+    let registry_signature_policy = matches
+        .value_of("registry_signature_policy")
+        .map(String::from)
+        .or_else(|| config.registry_signature_policy());
+
     #[cfg(feature = "biome")]
     let biome_enabled: bool = matches.is_present("biome_enabled");
 
+    #[cfg(feature = "compression")]
+    let compression_threshold = match matches.value_of("compression_threshold") {
+        Some(value) => parse_arg::<usize>("compression_threshold", value)?,
+        None => DEFAULT_COMPRESSION_THRESHOLD,
+    };
+
+    #[cfg(feature = "unix-transport")]
+    let unix_socket_permissions = match matches
+        .value_of("unix_socket_permissions")
+        .map(String::from)
+        .or_else(|| config.unix_socket_permissions())
+    {
+        Some(value) => Some(parse_permissions("unix_socket_permissions", &value)?),
+        None => None,
+    };
+
     let registry_backend = matches
         .value_of("registry_backend")
         .map(String::from)
@@ -346,13 +582,42 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
         .map(String::from)
         .or_else(|| config.registry_file());
 
+    let key_permissions = matches
+        .value_of("key_permissions")
+        .map(String::from)
+        .or_else(|| config.key_permissions());
+
+    let key_permissions_file = matches
+        .value_of("key_permissions_file")
+        .map(String::from)
+        .or_else(|| config.key_permissions_file());
+
     // Allow unused mut for experimental features
     #[allow(unused_mut)]
     let mut feature_fields = "".to_string();
 
     #[cfg(feature = "database")]
     {
-        feature_fields = format!("{}, db_url: {:?}", feature_fields, db_url);
+        feature_fields = format!(
+            "{}, db_url: {:?}, key_registry_backend: {:?}",
+            feature_fields, db_url, key_registry_backend
+        );
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    {
+        feature_fields = format!(
+            "{}, remote_registries: {:?}, registry_refresh_interval: {}",
+            feature_fields, remote_registries, registry_refresh_interval
+        );
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    {
+        feature_fields = format!(
+            "{}, registry_signature_policy: {:?}",
+            feature_fields, registry_signature_policy
+        );
     }
 
     #[cfg(feature = "biome")]
@@ -360,14 +625,33 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
         feature_fields = format!("{}, biome_enabled: {}", feature_fields, biome_enabled);
     }
 
+    #[cfg(feature = "compression")]
+    {
+        feature_fields = format!(
+            "{}, compression_threshold: {}",
+            feature_fields, compression_threshold
+        );
+    }
+
+    #[cfg(feature = "unix-transport")]
+    {
+        feature_fields = format!(
+            "{}, unix_socket_permissions: {:?}",
+            feature_fields,
+            unix_socket_permissions.map(|permissions| format!("{:o}", permissions))
+        );
+    }
+
     debug!(
-        "Configuration: {{ storage_type: {}, storage_location: {}, key_registry_location: {}, {}, \
-         service_endpoint: {}, network_endpoint: {}, initial_peers: {:?}, node_id: {}, \
-         rest_api_endpoint: {}, registry_backend: {:?}, registry_file: {:?}, \
-         heartbeat_interval: {}{} }}",
+        "Configuration: {{ storage_type: {}, storage_location: {}, key_registry_location: {}, \
+         durable_store_location: {}, {}, service_endpoint: {}, network_endpoint: {}, \
+         initial_peers: {:?}, node_id: {}, rest_api_endpoint: {}, registry_backend: {:?}, \
+         registry_file: {:?}, key_permissions: {:?}, key_permissions_file: {:?}, \
+         heartbeat_interval: {}, flow_control: {:?}, rate_limit: {:?}{} }}",
         storage_type,
         storage_location,
         key_registry_location,
+        durable_store_location,
         transport_log,
         service_endpoint,
         network_endpoint,
@@ -376,13 +660,18 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
         rest_api_endpoint,
         registry_backend,
         registry_file,
+        key_permissions,
+        key_permissions_file,
         heartbeat_interval,
+        flow_control,
+        rate_limit,
         feature_fields,
     );
 
     let mut daemon_builder = SplinterDaemonBuilder::new()
         .with_storage_location(storage_location)
         .with_key_registry_location(key_registry_location)
+        .with_durable_store_location(durable_store_location)
         .with_network_endpoint(network_endpoint)
         .with_service_endpoint(service_endpoint)
         .with_initial_peers(initial_peers)
@@ -395,6 +684,25 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
     #[cfg(feature = "database")]
     {
         daemon_builder = daemon_builder.with_db_url(db_url);
+
+        if let Some(key_registry_backend) = key_registry_backend {
+            daemon_builder = daemon_builder.with_key_registry_backend(key_registry_backend);
+        }
+    }
+
+    #[cfg(feature = "node-registry-remote")]
+    {
+        daemon_builder = daemon_builder
+            .with_remote_registries(remote_registries)
+            .with_registry_refresh_interval(registry_refresh_interval);
+    }
+
+    #[cfg(feature = "node-registry-signing")]
+    {
+        if let Some(registry_signature_policy) = registry_signature_policy {
+            daemon_builder =
+                daemon_builder.with_registry_signature_policy(registry_signature_policy);
+        }
     }
 
     #[cfg(feature = "biome")]
@@ -402,10 +710,42 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
         daemon_builder = daemon_builder.enable_biome(biome_enabled);
     }
 
+    #[cfg(feature = "compression")]
+    {
+        daemon_builder = daemon_builder.with_compression_threshold(compression_threshold);
+    }
+
+    #[cfg(feature = "unix-transport")]
+    {
+        if let Some(unix_socket_permissions) = unix_socket_permissions {
+            daemon_builder = daemon_builder.with_unix_socket_permissions(unix_socket_permissions);
+        }
+    }
+
     if let Some(registry_file) = registry_file {
         daemon_builder = daemon_builder.with_registry_file(registry_file);
     }
 
+    if let Some(flow_control) = flow_control {
+        daemon_builder = daemon_builder.with_flow_control(flow_control);
+    }
+
+    if let Some((peer_rate_limit, disconnect_threshold)) = rate_limit {
+        daemon_builder = daemon_builder.with_peer_rate_limit(peer_rate_limit);
+        if let Some((max_violations, window)) = disconnect_threshold {
+            daemon_builder =
+                daemon_builder.with_rate_limit_disconnect_threshold(max_violations, window);
+        }
+    }
+
+    if let Some(key_permissions) = key_permissions {
+        daemon_builder = daemon_builder.with_key_permissions(key_permissions);
+    }
+
+    if let Some(key_permissions_file) = key_permissions_file {
+        daemon_builder = daemon_builder.with_key_permissions_file(key_permissions_file);
+    }
+
     let mut node = daemon_builder.build().map_err(|err| {
         UserError::daemon_err_with_source("unable to build the Splinter daemon", Box::new(err))
     })?;
@@ -413,13 +753,135 @@ fn start_daemon(matches: ArgMatches) -> Result<(), UserError> {
     Ok(())
 }
 
+/// Returns the flow control configuration for the send queues to peers, if a high watermark is
+/// configured.
+fn get_flow_control(
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<Option<FlowControl>, UserError> {
+    let high_watermark = match matches.value_of("send_queue_high_watermark") {
+        Some(value) => Some(parse_arg::<usize>("send_queue_high_watermark", value)?),
+        None => config.send_queue_high_watermark(),
+    };
+    let high_watermark = match high_watermark {
+        Some(high_watermark) => high_watermark,
+        None => return Ok(None),
+    };
+
+    let low_watermark = match matches.value_of("send_queue_low_watermark") {
+        Some(value) => Some(parse_arg::<usize>("send_queue_low_watermark", value)?),
+        None => config.send_queue_low_watermark(),
+    }
+    .unwrap_or(high_watermark / 2);
+
+    let slow_peer_policy = match matches
+        .value_of("slow_peer_policy")
+        .map(String::from)
+        .or_else(|| config.slow_peer_policy())
+        .as_ref()
+        .map(String::as_str)
+    {
+        None | Some("backpressure") => SlowPeerPolicy::Backpressure,
+        Some("drop-messages") => SlowPeerPolicy::DropMessages,
+        Some("disconnect") => SlowPeerPolicy::Disconnect,
+        Some(policy) => {
+            return Err(UserError::InvalidArgument(format!(
+                "slow peer policy is not supported: {}",
+                policy
+            )))
+        }
+    };
+
+    let slow_peer_timeout = match matches.value_of("slow_peer_timeout") {
+        Some(value) => Duration::from_secs(parse_arg::<u64>("slow_peer_timeout", value)?),
+        None => config
+            .slow_peer_timeout()
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SLOW_PEER_TIMEOUT),
+    };
+
+    Ok(Some(
+        FlowControl::new(high_watermark, low_watermark)
+            .with_slow_peer_policy(slow_peer_policy, slow_peer_timeout),
+    ))
+}
+
+/// Returns the rate limit for the messages of each peer, and the threshold at which peers that
+/// exceed it are disconnected, if a rate limit is configured.
+fn get_rate_limit(
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<Option<(RateLimit, Option<(u32, Duration)>)>, UserError> {
+    let per_second = match matches.value_of("peer_rate_limit") {
+        Some(value) => Some(parse_arg::<f64>("peer_rate_limit", value)?),
+        None => config.peer_rate_limit(),
+    };
+    let per_second = match per_second {
+        Some(per_second) if per_second.is_finite() && per_second > 0.0 => per_second,
+        Some(per_second) => {
+            return Err(UserError::InvalidArgument(format!(
+                "invalid value for peer_rate_limit: {}; must be greater than 0",
+                per_second
+            )))
+        }
+        None => return Ok(None),
+    };
+
+    let burst = match matches.value_of("peer_rate_limit_burst") {
+        Some(value) => Some(parse_arg::<u32>("peer_rate_limit_burst", value)?),
+        None => config.peer_rate_limit_burst(),
+    }
+    .unwrap_or_else(|| per_second.ceil().max(1.0) as u32);
+    if burst == 0 {
+        return Err(UserError::InvalidArgument(
+            "invalid value for peer_rate_limit_burst: 0; must be at least 1".into(),
+        ));
+    }
+
+    let max_violations = match matches.value_of("rate_limit_max_violations") {
+        Some(value) => Some(parse_arg::<u32>("rate_limit_max_violations", value)?),
+        None => config.rate_limit_max_violations(),
+    };
+
+    let violation_window = match matches.value_of("rate_limit_violation_window") {
+        Some(value) => Duration::from_secs(parse_arg::<u64>("rate_limit_violation_window", value)?),
+        None => config
+            .rate_limit_violation_window()
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_VIOLATION_WINDOW),
+    };
+
+    let rate_limit = RateLimit::new(burst, per_second)
+        .map_err(|err| UserError::InvalidArgument(err.to_string()))?;
+
+    Ok(Some((
+        rate_limit,
+        max_violations.map(|max_violations| (max_violations, violation_window)),
+    )))
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, UserError> {
+    value
+        .parse()
+        .map_err(|_| UserError::InvalidArgument(format!("invalid value for {}: {}", name, value)))
+}
+
+/// Parses file permissions given in octal, such as `660` or `0660`.
+#[cfg(feature = "unix-transport")]
+fn parse_permissions(name: &str, value: &str) -> Result<u32, UserError> {
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|permissions| *permissions <= 0o777)
+        .ok_or_else(|| UserError::InvalidArgument(format!("invalid value for {}: {}", name, value)))
+}
+
 fn get_transport(
     transport_type: &str,
     matches: &clap::ArgMatches,
     config: &Config,
 ) -> Result<(Box<dyn Transport + Send>, String), GetTransportError> {
     match transport_type {
-        "tls" => {
+        "tls" | "wss" | "quic" => {
             #[cfg(feature = "generate-certs")]
             {
                 if matches.is_present("generate_certs") {
@@ -475,15 +937,18 @@ fn get_transport(
 
                     // Start transport in insecure mode, do not verify the certs if auto generated,
                     // as the ca will not match
-                    let transport = TlsTransport::new(
+                    let transport = new_tls_transport(
+                        transport_type,
                         None,
                         client_key_file,
                         client_cert,
                         server_key_file,
                         server_cert,
+                        None,
+                        false,
                     )?;
 
-                    return Ok((Box::new(transport), log_value));
+                    return Ok((transport, log_value));
                 }
             }
 
@@ -602,6 +1067,11 @@ fn get_transport(
                 }
             };
 
+            let crl_file = matches
+                .value_of("crl_file")
+                .map(String::from)
+                .or_else(|| config.crl_file());
+
             let ca_file_log = {
                 if let Some(ca_file) = &ca_file {
                     match fs::canonicalize(&ca_file)?.to_str() {
@@ -618,29 +1088,39 @@ fn get_transport(
             };
 
             let log_value = format!(
-                "transport_type: tls, ca_certs: {:?}, client_cert: {:?}, \
-                 client_key: {:?}, server_cert: {:?}, server_key: {:?}",
+                "transport_type: {}, ca_certs: {:?}, client_cert: {:?}, \
+                 client_key: {:?}, server_cert: {:?}, server_key: {:?}, crl_file: {:?}",
+                transport_type,
                 ca_file_log,
                 fs::canonicalize(client_cert.clone())?,
                 fs::canonicalize(client_key_file.clone())?,
                 fs::canonicalize(server_cert.clone())?,
                 fs::canonicalize(server_key_file.clone())?,
+                crl_file,
             );
 
-            let transport = TlsTransport::new(
+            let transport = new_tls_transport(
+                transport_type,
                 ca_file,
                 client_key_file,
                 client_cert,
                 server_key_file,
                 server_cert,
+                crl_file,
+                true,
             )?;
 
-            Ok((Box::new(transport), log_value))
+            Ok((transport, log_value))
         }
         "raw" => Ok((
             Box::new(RawTransport::default()),
             "transport_type: raw".to_string(),
         )),
+        #[cfg(feature = "ws-transport")]
+        "ws" => Ok((
+            Box::new(WsTransport::default()),
+            "transport_type: ws".to_string(),
+        )),
         _ => Err(GetTransportError::NotSupportedError(format!(
             "Transport type {} is not supported",
             transport_type
@@ -648,6 +1128,167 @@ fn get_transport(
     }
 }
 
+/// Creates a transport of the given type that secures its connections with TLS.
+///
+/// The wss transport also accepts TLS connections. For the tls and wss transports, peer
+/// certificates of TLS connections are checked against the given CRL file, and if `watch_files` is
+/// set the certificates, keys and CRLs are reloaded when they change or splinterd receives SIGHUP.
+#[allow(clippy::too_many_arguments)]
+fn new_tls_transport(
+    transport_type: &str,
+    ca_file: Option<String>,
+    client_key_file: String,
+    client_cert: String,
+    server_key_file: String,
+    server_cert: String,
+    crl_file: Option<String>,
+    watch_files: bool,
+) -> Result<Box<dyn Transport + Send>, GetTransportError> {
+    if transport_type == "quic" && crl_file.is_some() {
+        return Err(GetTransportError::NotSupportedError(format!(
+            "CRL files are only supported by the tls and wss transports, not {}",
+            transport_type
+        )));
+    }
+
+    if watch_files {
+        warn_if_expiring(&[&client_cert, &server_cert]);
+    }
+
+    match transport_type {
+        "tls" => Ok(Box::new(new_watched_tls_transport(
+            ca_file,
+            client_key_file,
+            client_cert,
+            server_key_file,
+            server_cert,
+            crl_file,
+            watch_files,
+        )?)),
+        // Secure websockets are served alongside TLS, so that peers that can open TLS
+        // connections keep using them; addresses without a protocol are handled by TLS.
+        #[cfg(feature = "ws-transport")]
+        "wss" => {
+            let tls_transport = new_watched_tls_transport(
+                ca_file,
+                client_key_file,
+                client_cert,
+                server_key_file,
+                server_cert,
+                crl_file,
+                watch_files,
+            )?;
+            // The websocket transport shares the TLS transport's configuration, so that it
+            // checks the same CRLs and picks up reloaded files
+            let ws_transport = WsTransport::from_tls_transport(&tls_transport);
+            Ok(Box::new(MultiTransport::new(vec![
+                Box::new(tls_transport),
+                Box::new(ws_transport),
+            ])))
+        }
+        #[cfg(feature = "quic-transport")]
+        "quic" => Ok(Box::new(QuicTransport::new(
+            ca_file,
+            client_key_file,
+            client_cert,
+            server_key_file,
+            server_cert,
+        )?)),
+        _ => Err(GetTransportError::NotSupportedError(format!(
+            "Transport type {} is not supported",
+            transport_type
+        ))),
+    }
+}
+
+/// Creates a `TlsTransport` that checks peer certificates against the given CRL file, and that
+/// reloads its files when they change if `watch_files` is set.
+fn new_watched_tls_transport(
+    ca_file: Option<String>,
+    client_key_file: String,
+    client_cert: String,
+    server_key_file: String,
+    server_cert: String,
+    crl_file: Option<String>,
+    watch_files: bool,
+) -> Result<TlsTransport, GetTransportError> {
+    let cert_files = vec![client_cert.clone(), server_cert.clone()];
+    let mut transport = TlsTransport::new(
+        ca_file,
+        client_key_file,
+        client_cert,
+        server_key_file,
+        server_cert,
+    )?;
+    if let Some(crl_file) = crl_file {
+        transport = transport.with_crl_file(crl_file)?;
+    }
+    if watch_files {
+        watch_tls_files(transport.reloader(), cert_files)?;
+    }
+    Ok(transport)
+}
+
+/// Starts a thread that reloads the TLS certificates, keys and CRLs when splinterd receives SIGHUP
+/// or when any of their files change. Connections that are already open are not affected. The
+/// thread also checks the expiry of the given certificates daily and after each reload.
+fn watch_tls_files(
+    mut reloader: TlsReloader,
+    cert_files: Vec<String>,
+) -> Result<(), GetTransportError> {
+    let reload_requested = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::SIGHUP, reload_requested.clone())?;
+
+    thread::Builder::new()
+        .name("TlsReloader".into())
+        .spawn(move || {
+            let mut last_expiry_check = Instant::now();
+            loop {
+                thread::sleep(TLS_FILE_CHECK_INTERVAL);
+
+                let result = if reload_requested.swap(false, Ordering::SeqCst) {
+                    info!("Received SIGHUP, reloading TLS certificates and keys");
+                    reloader.reload().map(|_| true)
+                } else {
+                    reloader.reload_if_changed()
+                };
+
+                let reloaded = match result {
+                    Ok(reloaded) => reloaded,
+                    Err(err) => {
+                        error!("Unable to reload TLS certificates and keys: {}", err);
+                        false
+                    }
+                };
+                if reloaded {
+                    info!("Reloaded TLS certificates and keys");
+                }
+
+                if reloaded || last_expiry_check.elapsed() >= CERT_EXPIRY_CHECK_INTERVAL {
+                    warn_if_expiring(&cert_files);
+                    last_expiry_check = Instant::now();
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Logs a warning for each certificate that expires within `CERT_EXPIRY_WARNING_DAYS`, and an
+/// error for each certificate that has expired.
+fn warn_if_expiring<S: AsRef<str>>(cert_files: &[S]) {
+    for cert_file in cert_files.iter().map(AsRef::as_ref) {
+        match days_until_expiry(cert_file) {
+            Ok(days) if days < 0 => error!("Certificate {} has expired", cert_file),
+            Ok(days) if days <= CERT_EXPIRY_WARNING_DAYS => {
+                warn!("Certificate {} expires in {} days", cert_file, days)
+            }
+            Ok(_) => (),
+            Err(err) => warn!("Unable to check the expiry of {}: {}", cert_file, err),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum UserError {
     TransportError(GetTransportError),
@@ -17,13 +17,21 @@ use std::fmt;
 
 #[derive(Debug)]
 pub enum RegistryConfig {
-    File { registry_file: String },
+    File {
+        registry_file: String,
+    },
+    #[cfg(feature = "database")]
+    Database {
+        db_url: String,
+    },
     NoOp,
 }
 
 pub struct RegistryConfigBuilder {
     registry_backend: Option<String>,
     registry_file: Option<String>,
+    #[cfg(feature = "database")]
+    db_url: Option<String>,
 }
 
 impl Default for RegistryConfigBuilder {
@@ -31,6 +39,8 @@ impl Default for RegistryConfigBuilder {
         Self {
             registry_backend: Some("FILE".to_owned()),
             registry_file: None,
+            #[cfg(feature = "database")]
+            db_url: None,
         }
     }
 }
@@ -46,6 +56,12 @@ impl RegistryConfigBuilder {
         self
     }
 
+    #[cfg(feature = "database")]
+    pub fn with_db_url(mut self, value: String) -> Self {
+        self.db_url = Some(value);
+        self
+    }
+
     pub fn build(self) -> Result<RegistryConfig, RegistryConfigError> {
         match self.registry_backend {
             Some(ref registry_type) if registry_type == "FILE" => self
@@ -57,6 +73,16 @@ impl RegistryConfigBuilder {
                             .to_string(),
                     )
                 }),
+            #[cfg(feature = "database")]
+            Some(ref registry_type) if registry_type == "DATABASE" => self
+                .db_url
+                .map(|db_url| RegistryConfig::Database { db_url })
+                .ok_or_else(|| {
+                    RegistryConfigError::MissingValue(
+                        "For registry_backend of type 'DATABASE' a database URL must be provided."
+                            .to_string(),
+                    )
+                }),
             None => Ok(RegistryConfig::NoOp),
             _ => Err(RegistryConfigError::InvalidType(
                 "NodeRegistry type is not supported".to_string(),
@@ -13,6 +13,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "metrics")]
+mod metrics;
+mod peers;
 mod status;
 
+#[cfg(feature = "metrics")]
+pub use metrics::*;
+pub use peers::*;
 pub use status::*;
@@ -0,0 +1,107 @@
+// Copyright 2018-2020 Cargill Incorporated
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::UNIX_EPOCH;
+
+use splinter::actix_web::{Error, HttpRequest, HttpResponse};
+use splinter::futures::{Future, IntoFuture};
+use splinter::network::auth::AuthorizationManager;
+use splinter::network::Network;
+use splinter::rest_api::ErrorResponse;
+
+#[derive(Debug, Serialize)]
+struct PeerResponse<T: serde::Serialize> {
+    data: T,
+}
+
+#[derive(Debug, Serialize)]
+struct Peer {
+    peer_id: String,
+    endpoint: Option<String>,
+    authorization_state: String,
+    /// Seconds since the UNIX epoch
+    last_heartbeat: Option<u64>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    messages_sent: u64,
+    messages_received: u64,
+}
+
+fn peer_status(
+    peer_id: &str,
+    network: &Network,
+    auth_manager: &AuthorizationManager,
+) -> Option<Peer> {
+    let stats = network.get_peer_stats(peer_id)?;
+
+    Some(Peer {
+        peer_id: peer_id.to_string(),
+        endpoint: network.get_peer_endpoint(peer_id),
+        authorization_state: auth_manager.authorization_state(peer_id).to_string(),
+        last_heartbeat: stats.last_heartbeat.and_then(|time| {
+            time.duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_secs())
+        }),
+        bytes_sent: stats.bytes_sent,
+        bytes_received: stats.bytes_received,
+        messages_sent: stats.messages_sent,
+        messages_received: stats.messages_received,
+    })
+}
+
+pub fn list_peers(
+    network: &Network,
+    auth_manager: &AuthorizationManager,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let mut peer_ids = network.peer_ids();
+    peer_ids.sort();
+
+    // A peer may be removed between listing the ids and reading its stats, so skip any that are
+    // no longer present.
+    let peers = peer_ids
+        .iter()
+        .filter_map(|peer_id| peer_status(peer_id, network, auth_manager))
+        .collect::<Vec<_>>();
+
+    Box::new(
+        HttpResponse::Ok()
+            .json(PeerResponse { data: peers })
+            .into_future(),
+    )
+}
+
+pub fn fetch_peer(
+    request: HttpRequest,
+    network: &Network,
+    auth_manager: &AuthorizationManager,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let peer_id = request.match_info().get("peer_id").unwrap_or("");
+
+    match peer_status(peer_id, network, auth_manager) {
+        Some(peer) => Box::new(
+            HttpResponse::Ok()
+                .json(PeerResponse { data: peer })
+                .into_future(),
+        ),
+        None => Box::new(
+            HttpResponse::NotFound()
+                .json(ErrorResponse::not_found(&format!(
+                    "Unable to find peer: {}",
+                    peer_id
+                )))
+                .into_future(),
+        ),
+    }
+}